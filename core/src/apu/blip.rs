@@ -0,0 +1,165 @@
+use crate::{apu::math, prelude::*};
+
+/// Kernel phases between two output samples, rows are interpolated in between
+const PHASE_BITS: u32 = 5;
+const PHASE_COUNT: usize = 1 << PHASE_BITS;
+const HALF_WIDTH: usize = 8;
+const KERNEL_WIDTH: usize = HALF_WIDTH * 2;
+
+/// Every kernel row adds up to `DELTA_UNIT`, so a delta settles to exactly its value
+const DELTA_BITS: u32 = 15;
+const DELTA_UNIT: i32 = 1 << DELTA_BITS;
+
+/// Fractional bits of the fixed point output sample positions
+const FRAC_BITS: u32 = 32;
+
+/// Fraction of the output Nyquist frequency kept by the kernel,
+/// leaving room for the window transition before frequencies start folding back
+const CUTOFF: f64 = 0.9;
+
+/// # Band-limited step buffer
+/// Same approach as blargg's blip_buf: the emulated output is a sum of steps, and instead of point sampling it
+/// every amplitude change is added as a delta shaped by a windowed sinc at its exact clock time.
+/// Reading integrates the deltas back into samples without the aliasing of hard square edges
+#[derive(Debug)]
+pub struct BlipBuf {
+    /// Output samples per clock, as a fixed point with `FRAC_BITS`
+    factor: u64,
+    /// Position where the current frame starts, its integer part is the number of samples ready to be read
+    offset: u64,
+    integrator: i32,
+    buffer: Vec<i32>,
+    kernel: Vec<[i16; KERNEL_WIDTH]>,
+}
+
+impl BlipBuf {
+    /// Buffer for `sample_rate` samples per second of a `clock_rate` clock,
+    /// with room for frames of up to `max_frame_clocks` clocks
+    pub fn new(clock_rate: u32, sample_rate: u32, max_frame_clocks: u32) -> Self {
+        let mut blip = Self {
+            factor: 0,
+            offset: 0,
+            integrator: 0,
+            buffer: Vec::new(),
+            kernel: (0..=PHASE_COUNT).map(kernel_row).collect(),
+        };
+        blip.set_rates(clock_rate, sample_rate as f64, max_frame_clocks);
+        blip
+    }
+
+    /// Change the output rate keeping the samples already added, meant to be called between frames.
+    /// Fractional rates allow resampling by slightly more or less than the nominal rate
+    pub fn set_rates(&mut self, clock_rate: u32, sample_rate: f64, max_frame_clocks: u32) {
+        // rounding up can't make a frame come out shorter than expected
+        self.factor = math::ceil(sample_rate * (1u64 << FRAC_BITS) as f64 / clock_rate as f64) as u64;
+
+        let capacity = ((max_frame_clocks as u64 * self.factor) >> FRAC_BITS) as usize + 1;
+        if self.buffer.len() < capacity + KERNEL_WIDTH {
+            self.buffer.resize(capacity + KERNEL_WIDTH, 0);
+        }
+    }
+
+    /// Add an amplitude change happening `time` clocks after the start of the current frame
+    #[inline]
+    pub fn add_delta(&mut self, time: u32, delta: i32) {
+        if delta == 0 {
+            return;
+        }
+
+        let position = time as u64 * self.factor + self.offset;
+        let start = (position >> FRAC_BITS) as usize;
+        let fraction = position as u32;
+
+        let phase = (fraction >> (FRAC_BITS - PHASE_BITS)) as usize;
+        let interp = ((fraction >> (FRAC_BITS - PHASE_BITS - DELTA_BITS)) as i32) & (DELTA_UNIT - 1);
+        let next_delta = (delta * interp) >> DELTA_BITS;
+        let delta = delta - next_delta;
+
+        let (row, next_row) = (&self.kernel[phase], &self.kernel[phase + 1]);
+        let out = &mut self.buffer[start..start + KERNEL_WIDTH];
+        for (i, sample) in out.iter_mut().enumerate() {
+            *sample += row[i] as i32 * delta + next_row[i] as i32 * next_delta;
+        }
+    }
+
+    /// Close the current frame after `duration` clocks, making its samples ready to be read
+    pub fn end_frame(&mut self, duration: u32) {
+        self.offset += duration as u64 * self.factor;
+        debug_assert!(self.samples_avail() + KERNEL_WIDTH <= self.buffer.len());
+    }
+
+    #[inline(always)]
+    pub fn samples_avail(&self) -> usize { (self.offset >> FRAC_BITS) as usize }
+
+    /// Read up to `out.len()` samples in amplitude units, returning how many were read
+    pub fn read_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = out.len().min(self.samples_avail());
+
+        for (sample, delta) in out.iter_mut().zip(&self.buffer[..count]) {
+            self.integrator += delta;
+            *sample = self.integrator as f32 / DELTA_UNIT as f32;
+        }
+
+        // keep the tails of the kernels already added past the read samples
+        let kept = self.samples_avail() - count + KERNEL_WIDTH;
+        self.buffer.copy_within(count..count + kept, 0);
+        self.buffer[kept..kept + count].fill(0);
+        self.offset -= (count as u64) << FRAC_BITS;
+
+        count
+    }
+}
+
+/// Band-limited impulse for a delta `phase / PHASE_COUNT` samples after the start of the kernel,
+/// a Blackman windowed sinc normalized to add up to `DELTA_UNIT`
+fn kernel_row(phase: usize) -> [i16; KERNEL_WIDTH] {
+    let shift = phase as f64 / PHASE_COUNT as f64;
+    let taps: [f64; KERNEL_WIDTH] = core::array::from_fn(|i| {
+        let x = i as f64 - (HALF_WIDTH - 1) as f64 - shift;
+        let sinc = match x * CUTOFF {
+            0.0 => 1.0,
+            x => math::sin(core::f64::consts::PI * x) / (core::f64::consts::PI * x),
+        };
+        let window = match x.abs() / HALF_WIDTH as f64 {
+            w if w >= 1.0 => 0.0,
+            w => {
+                let angle = core::f64::consts::PI * w;
+                0.42 + 0.5 * math::cos(angle) + 0.08 * math::cos(2.0 * angle)
+            }
+        };
+        sinc * window
+    });
+
+    let total: f64 = taps.iter().sum();
+    let mut row = taps.map(|tap| math::round(tap * DELTA_UNIT as f64 / total) as i16);
+
+    // rounding leftovers go to the center tap so the row adds up to exactly one unit
+    let error = DELTA_UNIT - row.iter().map(|&tap| tap as i32).sum::<i32>();
+    let center = if shift < 0.5 { HALF_WIDTH - 1 } else { HALF_WIDTH };
+    row[center] += error as i16;
+
+    row
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_step_settles_to_delta() {
+        let mut blip = BlipBuf::new(4_194_304, 44_100, 8192);
+        blip.add_delta(100, 10);
+        blip.add_delta(3000, -4);
+        blip.end_frame(8192);
+
+        let mut samples = [0.0; 128];
+        let read = blip.read_samples(&mut samples);
+
+        assert_eq!(read, 86);
+        // a square edge rings a little around the step but never far from it
+        assert!(samples.iter().all(|&sample| (-1.0..=11.0).contains(&sample)));
+        assert_eq!(samples[20], 10.0);
+        assert_eq!(samples[read - 1], 6.0);
+        assert_eq!(blip.samples_avail(), 0);
+    }
+}
@@ -1,5 +1,6 @@
 use super::{Envelope, LengthCounter, handle_control_write};
 use crate::apu::*;
+use crate::state::impl_state;
 
 #[derive(Debug, Default)]
 pub struct Noise {
@@ -31,6 +32,18 @@ pub struct Noise {
     pub lfsr: u16,
 }
 
+impl_state!(Noise {
+    length_timer,
+    envelope,
+    frequency,
+    control,
+    enabled,
+    timer,
+    envelope_state,
+    length,
+    lfsr,
+});
+
 impl Noise {
     pub fn new() -> Self {
         Self {
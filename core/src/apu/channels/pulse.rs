@@ -1,5 +1,6 @@
 use super::{DUTY_TABLE, Envelope, LengthCounter, handle_control_write};
 use crate::apu::*;
+use crate::state::impl_state;
 
 #[derive(Debug, Default)]
 pub struct Pulse {
@@ -32,6 +33,19 @@ pub struct Pulse {
     pub length: LengthCounter,
 }
 
+impl_state!(Pulse {
+    wave_duty,
+    length_timer,
+    envelope,
+    period_low,
+    period_high,
+    enabled,
+    timer,
+    duty_step,
+    envelope_state,
+    length,
+});
+
 impl Pulse {
     pub fn new() -> Self {
         Self {
@@ -1,5 +1,6 @@
 use super::{DUTY_TABLE, Envelope, LengthCounter, handle_control_write};
 use crate::apu::*;
+use crate::state::impl_state;
 
 #[derive(Debug, Default)]
 pub struct SweepPulse {
@@ -42,6 +43,24 @@ pub struct SweepPulse {
     pub sweep_negate_used: bool,
 }
 
+impl_state!(SweepPulse {
+    sweep,
+    wave_duty,
+    length_timer,
+    envelope,
+    period_low,
+    period_high,
+    enabled,
+    timer,
+    duty_step,
+    envelope_state,
+    length,
+    sweep_timer,
+    sweep_enabled,
+    shadow_period,
+    sweep_negate_used,
+});
+
 impl SweepPulse {
     pub fn new() -> Self {
         Self {
@@ -1,6 +1,7 @@
 use super::{LengthCounter, handle_control_write};
 use crate::apu::*;
 use crate::prelude::*;
+use crate::state::impl_state;
 
 mem_range!(WAVE_RAM, 0xFF30, 0xFF3F);
 
@@ -38,6 +39,20 @@ pub struct Wave {
     pub length: LengthCounter,
 }
 
+impl_state!(Wave {
+    dac_enable,
+    length_timer,
+    output_level,
+    period_low,
+    period_high,
+    wave_ram,
+    enabled,
+    timer,
+    sample_idx,
+    wave_ram_accessible,
+    length,
+});
+
 impl Wave {
     pub fn new() -> Self {
         Self {
@@ -1,9 +1,13 @@
+use crate::state::impl_state;
+
 #[derive(Debug, Default, Clone)]
 pub struct Envelope {
     pub volume: u8,
     pub timer: u8,
 }
 
+impl_state!(Envelope { volume, timer });
+
 impl Envelope {
     pub fn tick(&mut self, envelope_register: u8, enabled: bool) {
         if !enabled {
@@ -1,9 +1,13 @@
+use crate::state::impl_state;
+
 #[derive(Debug, Clone)]
 pub struct LengthCounter {
     pub counter: u16,
     pub max: u16,
 }
 
+impl_state!(LengthCounter { counter, max });
+
 impl Default for LengthCounter {
     fn default() -> Self { Self { counter: 64, max: 64 } }
 }
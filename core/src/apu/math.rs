@@ -0,0 +1,31 @@
+//! Float functions `core` doesn't have, from `std` when it's available and from libm without it
+
+#[cfg(feature = "std")]
+pub fn ceil(x: f64) -> f64 { x.ceil() }
+
+#[cfg(not(feature = "std"))]
+pub fn ceil(x: f64) -> f64 { libm::ceil(x) }
+
+#[cfg(feature = "std")]
+pub fn sin(x: f64) -> f64 { x.sin() }
+
+#[cfg(not(feature = "std"))]
+pub fn sin(x: f64) -> f64 { libm::sin(x) }
+
+#[cfg(feature = "std")]
+pub fn cos(x: f64) -> f64 { x.cos() }
+
+#[cfg(not(feature = "std"))]
+pub fn cos(x: f64) -> f64 { libm::cos(x) }
+
+#[cfg(feature = "std")]
+pub fn powf(x: f64, n: f64) -> f64 { x.powf(n) }
+
+#[cfg(not(feature = "std"))]
+pub fn powf(x: f64, n: f64) -> f64 { libm::pow(x, n) }
+
+#[cfg(feature = "std")]
+pub fn round(x: f64) -> f64 { x.round() }
+
+#[cfg(not(feature = "std"))]
+pub fn round(x: f64) -> f64 { libm::round(x) }
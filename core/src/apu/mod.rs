@@ -1,14 +1,17 @@
+mod blip;
 mod channels;
 mod envelope;
 mod length_counter;
+mod math;
 mod player;
 
+use blip::BlipBuf;
 use channels::*;
 use envelope::Envelope;
 use length_counter::LengthCounter;
 pub use player::*;
 
-use crate::{cpu, prelude::*};
+use crate::{cpu, prelude::*, state::impl_state};
 
 mem_range!(APU_REGISTER, 0xFF10, 0xFF3F);
 
@@ -50,10 +53,42 @@ const CH3_ON_FLAG: u8 = 0x04;
 const CH2_ON_FLAG: u8 = 0x02;
 const CH1_ON_FLAG: u8 = 0x01;
 
+/// Output rate until a frontend picks another one with `Apu::set_sample_rate`
 pub const SAMPLE_RATE: u32 = 44100;
 pub const BUFFER_SIZE: usize = 4096;
 pub const STEREO_BUFFER_SIZE: usize = BUFFER_SIZE * 2;
 
+/// The four sound channels, in the same order as their NR52 status bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudioChannel {
+    /// Channel 1, pulse with period sweep
+    SweepPulse,
+    /// Channel 2, pulse
+    Pulse,
+    /// Channel 3, wave RAM playback
+    Wave,
+    /// Channel 4, noise
+    Noise,
+}
+
+impl AudioChannel {
+    pub const ALL: [AudioChannel; 4] = [Self::SweepPulse, Self::Pulse, Self::Wave, Self::Noise];
+
+    #[inline(always)]
+    fn mask(self) -> u8 { 1 << self as u8 }
+}
+
+/// Clocks between two reads of the band-limited buffers, bounding the audio latency they add
+const BLIP_FRAME_CYCLES: u32 = 2048;
+
+/// Furthest `Apu::set_rate_adjustment` can move the output rate away from the nominal one
+const MAX_RATE_ADJUSTMENT: f32 = 0.005;
+
+/// raw sum is 0..=60 per side; after master volume (1..=8) and the
+/// high-pass filter the signal sits around 0 with peaks near +-240,
+/// so ~130x fills i16 without routinely clipping
+const OUTPUT_GAIN: f32 = 130.0;
+
 /// The base rate for the filter, this is used to calculate the
 /// filter rate based on the clock frequency and the sampling rate.
 const FILTER_RATE_BASE: f64 = 0.999958;
@@ -89,13 +124,70 @@ pub struct Apu {
     frame_sequencer: u8,
     cycles: u32,
 
-    sample_counter: u32,
+    sample_rate: u32,
+    /// Output rate multiplier set by the frontend, applied on the next read of the band-limited buffers
+    rate_adjustment: f32,
+    rate_adjustment_changed: bool,
+    /// Band-limited left and right outputs
+    blip: [BlipBuf; 2],
+    /// Clocks since the band-limited buffers were last read
+    blip_time: u32,
+    /// Left and right amplitudes last added to `blip`
+    amplitudes: [i32; 2],
+    /// Band-limited output of every channel on its own, only synthesized when enabled
+    isolated: Option<Box<IsolatedOutput>>,
+
+    /// Channels kept out of the mix (bits 3-0: ch4-ch1).
+    /// Not a hardware register, so powering the APU off through NR52 leaves it untouched
+    muted: u8,
+    /// When any bit is set, only those channels are mixed (bits 3-0: ch4-ch1)
+    soloed: u8,
+    /// Last sample of every channel before mixing, as the 0..=15 DAC input
+    channel_samples: [i16; 4],
 
     /// Per-channel state for the DC-blocking high-pass filter ([left, right]).
     filter_diff: [f32; 2],
     filter_rate: f32,
 }
 
+// the band-limited buffers and the frontend settings, like the sample rate and the muted channels, stay
+impl_state!(
+    Apu {
+        sweep_pulse,
+        pulse,
+        wave,
+        noise,
+        master_volume,
+        sound_panning,
+        master_control,
+        frame_sequencer,
+        cycles,
+        channel_samples,
+    },
+    after_load: restart_output
+);
+
+#[derive(Debug)]
+struct IsolatedOutput {
+    blip: [BlipBuf; 4],
+    filter_diff: [f32; 4],
+}
+
+impl IsolatedOutput {
+    /// Buffers starting from the current channel levels, since only their changes get added from now on
+    fn new(sample_rate: u32, levels: [i16; 4]) -> Self {
+        let mut blip = [(); 4].map(|_| BlipBuf::new(cpu::FREQUENCY, sample_rate, BLIP_FRAME_CYCLES));
+        for (blip, level) in blip.iter_mut().zip(levels) {
+            blip.add_delta(0, level as i32);
+        }
+
+        Self {
+            blip,
+            filter_diff: [0.0; 4],
+        }
+    }
+}
+
 impl Default for Apu {
     fn default() -> Self { Self::new() }
 }
@@ -116,36 +208,133 @@ impl Apu {
             frame_sequencer: 0,
             cycles: 0,
 
-            sample_counter: 0,
+            sample_rate: SAMPLE_RATE,
+            rate_adjustment: 1.0,
+            rate_adjustment_changed: false,
+            blip: [(); 2].map(|_| BlipBuf::new(cpu::FREQUENCY, SAMPLE_RATE, BLIP_FRAME_CYCLES)),
+            blip_time: 0,
+            amplitudes: [0; 2],
+            isolated: None,
+
+            muted: 0,
+            soloed: 0,
+            channel_samples: [0; 4],
 
             filter_diff: [0.0; 2],
-            filter_rate: FILTER_RATE_BASE.powf(cpu::FREQUENCY as f64 / SAMPLE_RATE as f64) as f32,
+            filter_rate: filter_rate(SAMPLE_RATE),
         }
     }
 
+    pub fn sample_rate(&self) -> u32 { self.sample_rate }
+
+    /// Change the rate samples are pushed to the `AudioPlayer` at, usually 22050, 32000, 44100 or 48000.
+    /// Samples still waiting in the band-limited buffers are dropped
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        let sample_rate = sample_rate.max(1);
+
+        self.sample_rate = sample_rate;
+        self.blip = [(); 2].map(|_| BlipBuf::new(cpu::FREQUENCY, sample_rate, BLIP_FRAME_CYCLES));
+        self.rate_adjustment_changed = self.rate_adjustment != 1.0;
+        self.blip_time = 0;
+        // the new buffers start silent, the next update adds the current levels back
+        self.amplitudes = [0; 2];
+        self.filter_rate = filter_rate(sample_rate);
+        if self.isolated.is_some() {
+            self.isolated = Some(Box::new(IsolatedOutput::new(sample_rate, self.channel_samples)));
+        }
+    }
+
+    /// Start the band-limited buffers over from the loaded channel levels, dropping what the
+    /// previous state left in them
+    fn restart_output(&mut self) { self.set_sample_rate(self.sample_rate); }
+
     #[inline(always)]
     pub fn is_active(&self) -> bool { self.master_control & AUDIO_ON_OFF != 0 }
 
     bit_accessors!(target: master_control; AUDIO_ON_OFF, CH1_ON_FLAG, CH2_ON_FLAG, CH3_ON_FLAG, CH4_ON_FLAG);
     bit_accessors!(target: sound_panning; CH1_LEFT, CH2_LEFT, CH3_LEFT, CH4_LEFT, CH1_RIGHT, CH2_RIGHT, CH3_RIGHT, CH4_RIGHT);
 
+    pub fn is_muted(&self, channel: AudioChannel) -> bool { self.muted & channel.mask() != 0 }
+
+    pub fn set_muted(&mut self, channel: AudioChannel, muted: bool) {
+        self.muted = if muted {
+            self.muted | channel.mask()
+        } else {
+            self.muted & !channel.mask()
+        };
+    }
+
+    pub fn is_soloed(&self, channel: AudioChannel) -> bool { self.soloed & channel.mask() != 0 }
+
+    /// Soloing any channel silences every channel that isn't soloed too
+    pub fn set_soloed(&mut self, channel: AudioChannel, soloed: bool) {
+        self.soloed = if soloed {
+            self.soloed | channel.mask()
+        } else {
+            self.soloed & !channel.mask()
+        };
+    }
+
+    /// Whether the channel reaches the mix after applying mute and solo.
+    /// A channel both soloed and muted stays silent
+    pub fn is_audible(&self, channel: AudioChannel) -> bool { self.audible() & channel.mask() != 0 }
+
+    #[inline(always)]
+    fn audible(&self) -> u8 {
+        let soloed = if self.soloed != 0 { self.soloed } else { 0x0F };
+        soloed & !self.muted
+    }
+
+    /// Last sample produced by the channel before panning, master volume, mute and solo, in the 0..=15 range
+    pub fn channel_sample(&self, channel: AudioChannel) -> i16 { self.channel_samples[channel as usize] }
+
+    pub fn rate_adjustment(&self) -> f32 { self.rate_adjustment }
+
+    /// Produce `ratio` times the samples of the nominal rate, resampling the output by a few tenths of a percent
+    /// lets frontends keep their audio queue from running dry or overflowing. Limited to +-0.5%
+    pub fn set_rate_adjustment(&mut self, ratio: f32) {
+        let ratio = ratio.clamp(1.0 - MAX_RATE_ADJUSTMENT, 1.0 + MAX_RATE_ADJUSTMENT);
+        if ratio != self.rate_adjustment {
+            self.rate_adjustment = ratio;
+            self.rate_adjustment_changed = true;
+        }
+    }
+
+    pub fn isolated_output(&self) -> bool { self.isolated.is_some() }
+
+    /// Synthesize every channel on its own too, pushing them through `AudioPlayer::push_channel_samples`.
+    /// Off by default since it takes four more band-limited buffers
+    pub fn set_isolated_output(&mut self, enabled: bool) {
+        self.isolated =
+            enabled.then(|| Box::new(IsolatedOutput::new(self.sample_rate, self.channel_samples)));
+        self.rate_adjustment_changed |= self.rate_adjustment != 1.0;
+    }
+
     #[inline(never)]
     pub fn step<P: AudioPlayer>(&mut self, player: &mut P, delta: usize) {
         if !self.is_active() {
+            self.step_powered_off(player, delta as u32);
             return;
         }
 
         let mut remaining = delta as u32;
 
-        // process the requested cycles in chunks bounded by the next sample emission or the next frame sequencer tick.
-        // this avoids running the per-cycle inner loop
-        while remaining > 0 {
-            // cycles until next sample emission (sample_counter += SAMPLE_RATE per cycle, emits when >= FREQUENCY)
-            let cycles_to_sample = (cpu::FREQUENCY - self.sample_counter).div_ceil(SAMPLE_RATE);
+        // process the requested cycles in chunks bounded by the next channel output change, the next frame
+        // sequencer tick or the next read of the band-limited buffers.
+        // channels only change their output on those boundaries, so every change lands on the exact cycle it happens
+        loop {
+            // register writes since the last step show up here too
+            self.update_amplitudes();
+            if remaining == 0 {
+                break;
+            }
 
-            // cycles until next frame sequencer step
             let cycles_to_fs = 8192 - self.cycles;
-            let chunk = remaining.min(cycles_to_sample).min(cycles_to_fs);
+            let cycles_to_read = BLIP_FRAME_CYCLES - self.blip_time;
+            let chunk = remaining
+                .min(cycles_to_fs)
+                .min(cycles_to_read)
+                .min(self.cycles_to_next_edge());
 
             // new ticks implementation is equivalent to running the old tick() `chunk` times
             // but more efficient for large `chunk` sizes since it avoids branching and other per tick overhead.
@@ -156,24 +345,57 @@ impl Apu {
             self.wave.tick(chunk);
             self.noise.tick(chunk);
 
-            self.sample_counter += chunk * SAMPLE_RATE;
             self.cycles += chunk;
+            self.blip_time += chunk;
             remaining -= chunk;
 
-            if self.sample_counter >= cpu::FREQUENCY {
-                self.sample_counter -= cpu::FREQUENCY;
-                let (left, right) = self.mix();
-                player.push_sample(left, right);
-            }
-
             if self.cycles >= 8192 {
                 self.cycles -= 8192;
                 self.frame_sequencer = (self.frame_sequencer + 1) & 7;
                 self.tick_frame_sequencer();
             }
+
+            if self.blip_time >= BLIP_FRAME_CYCLES {
+                self.update_amplitudes();
+                self.read_samples(player);
+            }
         }
     }
 
+    /// Powered off nothing is clocked, but silence is still output at the usual rate so frontends
+    /// pacing themselves on the samples they get keep getting them
+    fn step_powered_off<P: AudioPlayer>(&mut self, player: &mut P, mut remaining: u32) {
+        while remaining > 0 {
+            let chunk = remaining.min(BLIP_FRAME_CYCLES - self.blip_time);
+            self.blip_time += chunk;
+            remaining -= chunk;
+
+            if self.blip_time >= BLIP_FRAME_CYCLES {
+                // the drop to silence when it was just turned off
+                self.update_amplitudes();
+                self.read_samples(player);
+            }
+        }
+    }
+
+    /// Cycles until the first enabled channel steps its waveform, the only time its output can change by itself
+    fn cycles_to_next_edge(&self) -> u32 {
+        let timers = [
+            (self.sweep_pulse.enabled, self.sweep_pulse.timer as u32),
+            (self.pulse.enabled, self.pulse.timer as u32),
+            (self.wave.enabled, self.wave.timer as u32),
+            (self.noise.enabled, self.noise.timer),
+        ];
+
+        // a timer at 0 reloads and steps on the next cycle
+        timers
+            .into_iter()
+            .filter(|&(enabled, _)| enabled)
+            .map(|(_, timer)| timer.max(1))
+            .min()
+            .unwrap_or(u32::MAX)
+    }
+
     /// ticks the frame sequencer, which controls the frequency of sound updates
     fn tick_frame_sequencer(&mut self) {
         match self.frame_sequencer {
@@ -221,58 +443,117 @@ impl Apu {
             .tick(self.noise.envelope, self.noise.enabled);
     }
 
-    /// applies the DC-blocking high-pass filter to a single channel sample
-    /// removes the DC offset that the unsigned DAC outputs would otherwise leave
-    #[inline(always)]
-    fn high_pass(&mut self, channel: usize, sample: f32) -> f32 {
-        let diff = self.filter_diff[channel];
-        let output = sample - diff;
-        self.filter_diff[channel] = sample - output * self.filter_rate;
-        output
+    /// Add the output changes since the last call to the band-limited buffers, at the current cycle
+    #[inline]
+    fn update_amplitudes(&mut self) {
+        let samples = [
+            self.sweep_pulse
+                .get_sample(self.sweep_pulse.envelope_state.volume),
+            self.pulse.get_sample(self.pulse.envelope_state.volume),
+            self.wave.get_sample(),
+            self.noise.get_sample(self.noise.envelope_state.volume),
+        ];
+
+        if let Some(isolated) = &mut self.isolated {
+            for ((blip, sample), previous) in isolated.blip.iter_mut().zip(samples).zip(self.channel_samples)
+            {
+                blip.add_delta(self.blip_time, (sample - previous) as i32);
+            }
+        }
+        self.channel_samples = samples;
+
+        let amplitudes = self.mix();
+        for (side, amplitude) in amplitudes.into_iter().enumerate() {
+            self.blip[side].add_delta(self.blip_time, amplitude - self.amplitudes[side]);
+        }
+        self.amplitudes = amplitudes;
     }
 
+    /// Left and right amplitudes of the current channel samples, after panning and master volume
     #[inline]
-    fn mix(&mut self) -> (i16, i16) {
-        let ch1_vol = self
-            .sweep_pulse
-            .get_sample(self.sweep_pulse.envelope_state.volume) as i32;
-        let ch2_vol = self.pulse.get_sample(self.pulse.envelope_state.volume) as i32;
-        let ch3_vol = self.wave.get_sample() as i32;
-        let ch4_vol = self.noise.get_sample(self.noise.envelope_state.volume) as i32;
+    fn mix(&self) -> [i32; 2] {
+        let [ch1_vol, ch2_vol, ch3_vol, ch4_vol] = self.channel_samples.map(|sample| sample as i32);
+
+        // mute and solo work as panning both sides off
+        let audible = self.audible() as i32;
+        let audible = audible | (audible << 4);
 
         // per channel masks to select whether the channel contributes to the left and right output respectively
         // changing branching to a masked sum operation
-        let pan = self.sound_panning as i32;
-        let l1 = -(pan & 1);
-        let l2 = -((pan >> 1) & 1);
-        let l3 = -((pan >> 2) & 1);
-        let l4 = -((pan >> 3) & 1);
-        let r1 = -((pan >> 4) & 1);
-        let r2 = -((pan >> 5) & 1);
-        let r3 = -((pan >> 6) & 1);
-        let r4 = -((pan >> 7) & 1);
+        let pan = self.sound_panning as i32 & audible;
+        let l1 = -((pan >> 4) & 1);
+        let l2 = -((pan >> 5) & 1);
+        let l3 = -((pan >> 6) & 1);
+        let l4 = -((pan >> 7) & 1);
+        let r1 = -(pan & 1);
+        let r2 = -((pan >> 1) & 1);
+        let r3 = -((pan >> 2) & 1);
+        let r4 = -((pan >> 3) & 1);
 
         let left = (ch1_vol & l1) + (ch2_vol & l2) + (ch3_vol & l3) + (ch4_vol & l4);
         let right = (ch1_vol & r1) + (ch2_vol & r2) + (ch3_vol & r3) + (ch4_vol & r4);
 
-        // raw sum is 0..=60 per side; after master volume (1..=8) and the
-        // high-pass filter the signal sits around 0 with peaks near +-240,
-        // so ~130x fills i16 without routinely clipping
-        const OUTPUT_GAIN: f32 = 130.0;
-
         let mv_left = ((self.master_volume >> 4) & 0x07) as i32 + 1;
         let mv_right = (self.master_volume & 0x07) as i32 + 1;
 
-        let left = (left * mv_left) as f32;
-        let right = (right * mv_right) as f32;
+        [left * mv_left, right * mv_right]
+    }
 
-        let left = self.high_pass(0, left) * OUTPUT_GAIN;
-        let right = self.high_pass(1, right) * OUTPUT_GAIN;
+    /// Push every sample the band-limited buffers have ready, filtered and scaled to i16
+    fn read_samples<P: AudioPlayer>(&mut self, player: &mut P) {
+        const CHUNK: usize = 64;
 
-        let left = left.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
-        let right = right.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        for blip in &mut self.blip {
+            blip.end_frame(self.blip_time);
+        }
+        if let Some(isolated) = &mut self.isolated {
+            for blip in &mut isolated.blip {
+                blip.end_frame(self.blip_time);
+            }
+        }
+        self.blip_time = 0;
+
+        let mut mixed = [[0.0; CHUNK]; 2];
+        let mut channels = [[0.0; CHUNK]; 4];
+        while self.blip[0].samples_avail() > 0 {
+            let count = self.blip[0].read_samples(&mut mixed[0]);
+            self.blip[1].read_samples(&mut mixed[1][..count]);
+
+            if let Some(isolated) = &mut self.isolated {
+                for (blip, samples) in isolated.blip.iter_mut().zip(&mut channels) {
+                    blip.read_samples(&mut samples[..count]);
+                }
+            }
 
-        (left, right)
+            for i in 0..count {
+                if let Some(isolated) = &mut self.isolated {
+                    // every channel as if it was alone, centered and at full master volume
+                    let samples: [i16; 4] = core::array::from_fn(|channel| {
+                        let sample = channels[channel][i] * 8.0;
+                        let sample = high_pass(&mut isolated.filter_diff[channel], self.filter_rate, sample);
+                        to_i16(sample * OUTPUT_GAIN)
+                    });
+                    player.push_channel_samples(samples);
+                }
+
+                let left = high_pass(&mut self.filter_diff[0], self.filter_rate, mixed[0][i]);
+                let right = high_pass(&mut self.filter_diff[1], self.filter_rate, mixed[1][i]);
+                player.push_sample(to_i16(left * OUTPUT_GAIN), to_i16(right * OUTPUT_GAIN));
+            }
+        }
+
+        // between frames, so no delta is placed with a different rate than the rest of its frame
+        if self.rate_adjustment_changed {
+            self.rate_adjustment_changed = false;
+            let rate = self.sample_rate as f64 * self.rate_adjustment as f64;
+            let isolated = self
+                .isolated
+                .iter_mut()
+                .flat_map(|isolated| isolated.blip.iter_mut());
+            for blip in self.blip.iter_mut().chain(isolated) {
+                blip.set_rates(cpu::FREQUENCY, rate, BLIP_FRAME_CYCLES);
+            }
+        }
     }
 
     fn sync_envelope(&mut self) {
@@ -282,6 +563,23 @@ impl Apu {
     }
 }
 
+/// Per sample decay of the DC-blocking filter at the given output rate
+fn filter_rate(sample_rate: u32) -> f32 {
+    math::powf(FILTER_RATE_BASE, cpu::FREQUENCY as f64 / sample_rate as f64) as f32
+}
+
+/// applies the DC-blocking high-pass filter to a single channel sample
+/// removes the DC offset that the unsigned DAC outputs would otherwise leave
+#[inline(always)]
+fn high_pass(diff: &mut f32, rate: f32, sample: f32) -> f32 {
+    let output = sample - *diff;
+    *diff = sample - output * rate;
+    output
+}
+
+#[inline(always)]
+fn to_i16(sample: f32) -> i16 { sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16 }
+
 impl Accessible<u16> for Apu {
     fn read(&self, address: u16) -> u8 {
         match address {
@@ -363,3 +661,197 @@ impl Accessible<u16> for Apu {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingPlayer {
+        mixed: Vec<(i16, i16)>,
+        channels: Vec<[i16; 4]>,
+    }
+
+    impl AudioPlayer for RecordingPlayer {
+        fn playing_stereo(&self) -> bool { true }
+        fn push_sample(&mut self, left: i16, right: i16) { self.mixed.push((left, right)); }
+        fn push_channel_samples(&mut self, samples: [i16; 4]) { self.channels.push(samples); }
+    }
+
+    /// Channel 1 at full volume with a 50% duty
+    fn play_square(apu: &mut Apu, panning: u8) -> RecordingPlayer {
+        apu.write(NR52, AUDIO_ON_OFF);
+        apu.write(NR50, 0x77);
+        apu.write(NR51, panning);
+        apu.write(NR11, 0x80);
+        apu.write(NR12, 0xF0);
+        apu.write(NR13, 0x00);
+        apu.write(NR14, TRIGGER | 0x07);
+
+        let mut player = RecordingPlayer::default();
+        apu.step(&mut player, 20_000);
+        player
+    }
+
+    #[test]
+    fn test_muted_channel_keeps_isolated_output() {
+        let mut apu = Apu::new();
+        apu.set_muted(AudioChannel::SweepPulse, true);
+        apu.set_isolated_output(true);
+
+        let player = play_square(&mut apu, 0xFF);
+
+        assert!(player.mixed.iter().all(|&sample| sample == (0, 0)));
+        assert!(player.channels.iter().any(|samples| samples[0] != 0));
+        assert!(player.channels.iter().all(|samples| samples[1..] == [0, 0, 0]));
+        assert_eq!(player.channels.len(), player.mixed.len());
+        assert_eq!(apu.channel_sample(AudioChannel::Pulse), 0);
+
+        apu.set_muted(AudioChannel::SweepPulse, false);
+        let player = play_square(&mut apu, 0xFF);
+        assert!(player.mixed.iter().any(|&sample| sample != (0, 0)));
+    }
+
+    #[test]
+    fn test_solo_silences_other_channels() {
+        let mut apu = Apu::new();
+        apu.set_soloed(AudioChannel::Wave, true);
+
+        assert!(apu.is_audible(AudioChannel::Wave));
+        assert!(!apu.is_audible(AudioChannel::SweepPulse));
+        let player = play_square(&mut apu, 0xFF);
+        assert!(player.mixed.iter().all(|&sample| sample == (0, 0)));
+
+        apu.set_muted(AudioChannel::Wave, true);
+        assert!(!apu.is_audible(AudioChannel::Wave));
+    }
+
+    #[test]
+    fn test_panning_follows_nr51() {
+        let mut apu = Apu::new();
+        let player = play_square(&mut apu, CH1_LEFT);
+
+        assert!(player.mixed.iter().any(|&(left, _)| left != 0));
+        assert!(player.mixed.iter().all(|&(_, right)| right == 0));
+    }
+
+    #[test]
+    fn test_samples_follow_sample_rate() {
+        for sample_rate in [22050, 32000, 44100, 48000] {
+            let mut apu = Apu::new();
+            apu.set_sample_rate(sample_rate);
+            apu.write(NR52, AUDIO_ON_OFF);
+
+            let mut player = RecordingPlayer::default();
+            // a quarter of a second, in several steps like the cpu does
+            for _ in 0..cpu::FREQUENCY / 4 / 16 {
+                apu.step(&mut player, 16);
+            }
+
+            let expected = sample_rate as usize / 4;
+            assert!(
+                player.mixed.len().abs_diff(expected) <= 1,
+                "{sample_rate}: {}",
+                player.mixed.len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_powered_off_outputs_silence() {
+        let mut apu = Apu::new();
+        let mut player = play_square(&mut apu, 0xFF);
+        apu.write(NR52, 0x00);
+
+        player.mixed.clear();
+        for _ in 0..cpu::FREQUENCY / 4 / 16 {
+            apu.step(&mut player, 16);
+        }
+
+        assert!(
+            player.mixed.len().abs_diff(44100 / 4) <= 1,
+            "{}",
+            player.mixed.len()
+        );
+        // the DC-blocking filter settles back to silence once the square wave stops
+        assert!(
+            player.mixed[player.mixed.len() - 100..]
+                .iter()
+                .all(|&(left, right)| left.abs() <= 1 && right.abs() <= 1)
+        );
+    }
+
+    /// The game turns the APU off and spins, and the frontend runs frames until its queue holds enough samples
+    #[test]
+    fn test_audio_sync_with_apu_off() {
+        use crate::serial::DefaultSerialListener;
+        use crate::DefaultRenderer;
+
+        controller!(
+            SyncController,
+            DefaultSerialListener,
+            DefaultRenderer,
+            RecordingPlayer
+        );
+
+        let mut rom = vec![0; 0x8000];
+        #[rustfmt::skip]
+        let program = [
+            0xAF,       // xor a
+            0xE0, 0x26, // ldh [$26], a
+            0x18, 0xFE, // jr -2
+        ];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+        let mut gb = Dmg::new(Cartridge::new(&rom, None).unwrap(), None);
+
+        let mut controller = SyncController {
+            listener: DefaultSerialListener::new(),
+            renderer: DefaultRenderer::new(),
+            audio_player: RecordingPlayer::default(),
+        };
+        // a tenth of a second is 6 frames, one more for the samples still in the band-limited buffers.
+        // the frontends give up at 4 or 5 times the frames they should run
+        let mut frames = 0;
+        while controller.audio_player.mixed.len() < 44100 / 10 && frames < 30 {
+            gb.run(&mut controller).unwrap();
+            frames += 1;
+        }
+
+        assert!(!gb.apu.is_active());
+        assert!(frames <= 7, "{frames}");
+    }
+
+    #[test]
+    fn test_rate_adjustment_resamples_output() {
+        let mut apu = Apu::new();
+        apu.write(NR52, AUDIO_ON_OFF);
+        apu.set_rate_adjustment(1.005);
+
+        let mut player = RecordingPlayer::default();
+        for _ in 0..cpu::FREQUENCY / 16 {
+            apu.step(&mut player, 16);
+        }
+
+        // the first read still happens at the nominal rate
+        assert!(player.mixed.len().abs_diff(44321) <= 2, "{}", player.mixed.len());
+
+        apu.set_rate_adjustment(2.0);
+        assert_eq!(apu.rate_adjustment(), 1.0 + MAX_RATE_ADJUSTMENT);
+        apu.set_rate_adjustment(0.5);
+        assert_eq!(apu.rate_adjustment(), 1.0 - MAX_RATE_ADJUSTMENT);
+    }
+
+    #[test]
+    fn test_mix_settings_survive_power_off() {
+        let mut apu = Apu::new();
+        apu.set_muted(AudioChannel::Noise, true);
+        apu.set_soloed(AudioChannel::Pulse, true);
+
+        apu.write(NR52, 0x00);
+        apu.write(NR52, AUDIO_ON_OFF);
+
+        assert!(apu.is_muted(AudioChannel::Noise));
+        assert!(apu.is_soloed(AudioChannel::Pulse));
+        assert!(!apu.is_soloed(AudioChannel::Wave));
+    }
+}
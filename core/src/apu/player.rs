@@ -3,6 +3,10 @@ use crate::{apu::STEREO_BUFFER_SIZE, prelude::*};
 pub trait AudioPlayer {
     fn playing_stereo(&self) -> bool;
     fn push_sample(&mut self, left: i16, right: i16);
+    /// Receives every channel on its own, in `AudioChannel` order, right before the mixed sample is pushed.
+    /// Only called with `Apu::set_isolated_output` enabled, each channel is centered at full master volume
+    /// ignoring panning, mute and solo
+    fn push_channel_samples(&mut self, _samples: [i16; 4]) {}
     fn flush_buffer(&mut self) {}
 }
 
@@ -1,3 +1,5 @@
+use crate::state::{State, StateReader, StateResult, StateWriter};
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum SelectedRTCRegister {
     #[default]
@@ -30,6 +32,57 @@ pub struct Rtc {
     pub latched_day_high: u8,
 }
 
+impl State for Rtc {
+    fn save(&self, state: &mut StateWriter) {
+        self.enabled.save(state);
+        // saved as the value written to select it, 0 when none is
+        self.selected_register
+            .map_or(0, |register| register as u8)
+            .save(state);
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ]
+        .save(state);
+        [
+            self.latched_seconds,
+            self.latched_minutes,
+            self.latched_hours,
+            self.latched_day_low,
+            self.latched_day_high,
+        ]
+        .save(state);
+    }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        let (mut selected, mut registers, mut latched) = (0u8, [0u8; 5], [0u8; 5]);
+        self.enabled.load(state)?;
+        selected.load(state)?;
+        registers.load(state)?;
+        latched.load(state)?;
+
+        self.select_register(selected);
+        [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.day_low,
+            self.day_high,
+        ] = registers;
+        [
+            self.latched_seconds,
+            self.latched_minutes,
+            self.latched_hours,
+            self.latched_day_low,
+            self.latched_day_high,
+        ] = latched;
+        Ok(())
+    }
+}
+
 impl Rtc {
     pub fn new() -> Self { Self::default() }
 
@@ -1,8 +1,12 @@
+use crate::state::impl_state;
+
 #[derive(Debug, Default, Clone)]
 pub struct Rumble {
     pub enabled: bool,
 }
 
+impl_state!(Rumble { enabled });
+
 impl Rumble {
     pub fn new() -> Self { Self { enabled: false } }
 }
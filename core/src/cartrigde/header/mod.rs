@@ -3,9 +3,12 @@ mod license;
 mod ram;
 mod rom;
 
-use crate::{cartrigde::CartridgeResult, mem_range};
+use crate::{
+    cartrigde::{CartridgeError, CartridgeResult},
+    mem_range,
+};
 pub use ram::RamSize;
-pub use rom::RomSize;
+pub use rom::{MAX_ROM_SIZE, RomSize};
 use {super::mbc::CartridgeType, license::get_license};
 
 mem_range!(TITLE, 0x0134, 0x0143);
@@ -75,6 +78,13 @@ pub struct CartridgeHeader {
 
 impl CartridgeHeader {
     pub fn new(raw_rom: &[u8]) -> CartridgeResult<Self> {
+        if raw_rom.len() <= GLOBAL_CHECKSUM_END as usize {
+            return Err(CartridgeError::InvalidRomSize(
+                None,
+                "ROM is too small to have a header",
+            ));
+        }
+
         let rom_size = RomSize::new(raw_rom[ROM_SIZE_ADDRESS])?;
         let ram_size = RamSize::new(raw_rom[RAM_SIZE_ADDRESS])?;
 
@@ -1,5 +1,8 @@
 use crate::cartrigde::{CartridgeError, CartridgeResult};
 
+/// Size of the largest ROM a header can describe, the 8 MiB of the biggest MBC5 cartridges
+pub const MAX_ROM_SIZE: usize = 8 * 1024 * 1024;
+
 /// # Rom Size
 /// Other formats are listed in unofficial docs, but they're not found in real cartridges
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
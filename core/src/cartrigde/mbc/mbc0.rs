@@ -6,6 +6,7 @@ use crate::{
     cartrigde::{
         CartridgeError, CartridgeResult, RamSize, features::CartridgeFeatures, header::CartridgeHeader,
     },
+    state::impl_state,
 };
 
 use super::MemoryBankController;
@@ -22,6 +23,8 @@ pub struct Mbc0 {
     ram: Option<Box<[u8; MBC0_RAM_SIZE]>>,
 }
 
+impl_state!(Mbc0 { ram });
+
 impl MemoryBankController for Mbc0 {
     fn new(
         raw_rom: &[u8],
@@ -79,12 +82,19 @@ impl MemoryBankController for Mbc0 {
         }
     }
 
+    fn get_rom(&self) -> &[u8] { self.rom.as_slice() }
     fn get_ram(&self) -> Option<&[u8]> {
         match &self.ram {
             Some(ram) => Some(ram.as_slice()),
             None => None,
         }
     }
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.ram {
+            Some(ram) => Some(ram.as_mut_slice()),
+            None => None,
+        }
+    }
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) {
         let rom_slice = &mut self.rom[BOOT_ROM_START as usize..=BOOT_ROM_END as usize];
         let boot_rom_slice = &mut boot_rom[..=(BOOT_ROM_END - BOOT_ROM_START) as usize];
@@ -7,6 +7,7 @@ use crate::{
         features::CartridgeFeatures, header::CartridgeHeader,
     },
     mem_range,
+    state::{State, StateError, StateReader, StateResult, StateWriter, impl_state},
 };
 
 use super::MemoryBankController;
@@ -23,6 +24,20 @@ enum BankingMode {
     Advanced,
 }
 
+impl State for BankingMode {
+    fn save(&self, state: &mut StateWriter) { (*self == BankingMode::Advanced).save(state); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        let mut advanced = false;
+        advanced.load(state)?;
+        *self = match advanced {
+            true => BankingMode::Advanced,
+            false => BankingMode::Simple,
+        };
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct Mbc1 {
     mode: BankingMode,
@@ -38,6 +53,24 @@ pub struct Mbc1 {
     ram_size: RamSize,
 }
 
+impl_state!(Mbc1 {
+    mode,
+    primary_bank,
+    secondary_bank,
+    ram_enabled,
+    ram,
+}, validate: check_loaded);
+
+impl Mbc1 {
+    /// Bank registers only keep the bits written to them
+    fn check_loaded(&self) -> StateResult<()> {
+        match self.primary_bank <= 0x1F && self.secondary_bank <= 0x03 {
+            true => Ok(()),
+            false => Err(StateError::InvalidValue("MBC1 bank")),
+        }
+    }
+}
+
 /// Checks for Nintendo logo in the various ROMs of the multicart
 /// Tested against [this test](https://github.com/Gekkio/mooneye-test-suite/blob/main/emulator-only/mbc1/multicart_rom_8Mb.s)
 fn check_mbc1m_multicart(raw_rom: &[u8], header: &CartridgeHeader) -> bool {
@@ -210,7 +243,9 @@ impl MemoryBankController for Mbc1 {
         }
     }
 
+    fn get_rom(&self) -> &[u8] { &self.rom }
     fn get_ram(&self) -> Option<&[u8]> { self.ram.as_deref() }
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]> { self.ram.as_deref_mut() }
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) {
         let rom_slice = &mut self.rom[BOOT_ROM_START as usize..=BOOT_ROM_END as usize];
         let boot_rom_slice = &mut boot_rom[..=(BOOT_ROM_END - BOOT_ROM_START) as usize];
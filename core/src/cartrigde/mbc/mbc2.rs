@@ -5,6 +5,7 @@ use crate::{
     cartrigde::{
         CartridgeError, CartridgeResult, RomSize, features::CartridgeFeatures, header::CartridgeHeader,
     },
+    state::{StateError, StateResult, impl_state},
 };
 
 use super::MemoryBankController;
@@ -21,6 +22,22 @@ pub struct Mbc2 {
     ram_enabled: bool,
 }
 
+impl_state!(Mbc2 {
+    rom_selected_bank,
+    ram,
+    ram_enabled,
+}, validate: check_loaded);
+
+impl Mbc2 {
+    /// Bank registers only keep the bits written to them
+    fn check_loaded(&self) -> StateResult<()> {
+        match self.rom_selected_bank <= 0x0F {
+            true => Ok(()),
+            false => Err(StateError::InvalidValue("MBC2 bank")),
+        }
+    }
+}
+
 impl Default for Mbc2 {
     fn default() -> Self {
         Self {
@@ -36,8 +53,8 @@ impl Default for Mbc2 {
 impl MemoryBankController for Mbc2 {
     fn new(
         raw_rom: &[u8],
-        _: Option<Vec<u8>>,
-        _: &CartridgeFeatures,
+        save: Option<Vec<u8>>,
+        features: &CartridgeFeatures,
         header: &CartridgeHeader,
     ) -> CartridgeResult<Self> {
         let rom = if raw_rom.len() == header.rom_size.get_size() as usize {
@@ -49,12 +66,20 @@ impl MemoryBankController for Mbc2 {
             ));
         };
 
+        // only the lower half of every byte exists
+        let mut ram = Box::new([0; MBC2_RAM_SIZE]);
+        if let Some(save) = save.filter(|s| features.has_battery && s.len() == MBC2_RAM_SIZE) {
+            for (nibble, byte) in ram.iter_mut().zip(save) {
+                *nibble = byte & 0x0F;
+            }
+        }
+
         Ok(Self {
             rom,
             rom_size: header.rom_size,
 
             rom_selected_bank: 1,
-            ram: Box::new([0; MBC2_RAM_SIZE]),
+            ram,
             ram_enabled: false,
         })
     }
@@ -109,7 +134,9 @@ impl MemoryBankController for Mbc2 {
         self.ram[offset] = value & 0x0F;
     }
 
+    fn get_rom(&self) -> &[u8] { &self.rom }
     fn get_ram(&self) -> Option<&[u8]> { Some(self.ram.as_slice()) }
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]> { Some(self.ram.as_mut_slice()) }
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) {
         let rom_slice = &mut self.rom[BOOT_ROM_START as usize..=BOOT_ROM_END as usize];
         let boot_rom_slice = &mut boot_rom[..=(BOOT_ROM_END - BOOT_ROM_START) as usize];
@@ -8,6 +8,7 @@ use crate::{
         header::CartridgeHeader,
     },
     prelude::*,
+    state::{StateError, StateResult, impl_state},
 };
 
 use super::MemoryBankController;
@@ -32,6 +33,26 @@ pub struct Mbc3 {
     rtc_latch_state: u8,
 }
 
+impl_state!(Mbc3 {
+    rom_selected_bank,
+    ram,
+    ram_enabled,
+    ram_selected_bank,
+    timer,
+    rtc_selected,
+    rtc_latch_state,
+}, validate: check_loaded);
+
+impl Mbc3 {
+    /// Bank registers only keep the bits written to them
+    fn check_loaded(&self) -> StateResult<()> {
+        match self.rom_selected_bank <= 0x7F && self.ram_selected_bank <= 0x03 {
+            true => Ok(()),
+            false => Err(StateError::InvalidValue("MBC3 bank")),
+        }
+    }
+}
+
 impl MemoryBankController for Mbc3 {
     fn new(
         raw_rom: &[u8],
@@ -184,10 +205,15 @@ impl MemoryBankController for Mbc3 {
         }
     }
 
+    fn get_rom(&self) -> &[u8] { &self.rom }
     fn get_ram(&self) -> Option<&[u8]> { self.ram.as_deref() }
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]> { self.ram.as_deref_mut() }
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) {
         let rom_slice = &mut self.rom[BOOT_ROM_START as usize..=BOOT_ROM_END as usize];
         let boot_rom_slice = &mut boot_rom[..=(BOOT_ROM_END - BOOT_ROM_START) as usize];
         rom_slice.swap_with_slice(boot_rom_slice);
     }
+
+    fn get_rtc(&self) -> Option<&Rtc> { self.timer.as_ref() }
+    fn get_rtc_mut(&mut self) -> Option<&mut Rtc> { self.timer.as_mut() }
 }
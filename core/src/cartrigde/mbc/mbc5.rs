@@ -8,6 +8,7 @@ use crate::{
         header::CartridgeHeader,
     },
     prelude::*,
+    state::{StateError, StateResult, impl_state},
 };
 
 use super::MemoryBankController;
@@ -31,6 +32,24 @@ pub struct Mbc5 {
     ram_selected_bank: u8,
 }
 
+impl_state!(Mbc5 {
+    rumble,
+    rom_selected_bank,
+    ram,
+    ram_enabled,
+    ram_selected_bank,
+}, validate: check_loaded);
+
+impl Mbc5 {
+    /// Bank registers only keep the bits written to them
+    fn check_loaded(&self) -> StateResult<()> {
+        match self.rom_selected_bank <= 0x1FF && self.ram_selected_bank <= 0x0F {
+            true => Ok(()),
+            false => Err(StateError::InvalidValue("MBC5 bank")),
+        }
+    }
+}
+
 impl MemoryBankController for Mbc5 {
     fn new(
         raw_rom: &[u8],
@@ -138,7 +157,9 @@ impl MemoryBankController for Mbc5 {
         }
     }
 
+    fn get_rom(&self) -> &[u8] { &self.rom }
     fn get_ram(&self) -> Option<&[u8]> { self.ram.as_deref() }
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]> { self.ram.as_deref_mut() }
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) {
         let rom_slice = &mut self.rom[BOOT_ROM_START as usize..=BOOT_ROM_END as usize];
         let boot_rom_slice = &mut boot_rom[..=(BOOT_ROM_END - BOOT_ROM_START) as usize];
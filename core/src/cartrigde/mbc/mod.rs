@@ -7,9 +7,10 @@ mod mbc5;
 
 use crate::cartrigde::{
     CartridgeError, CartridgeResult, RomSize,
-    features::CartridgeFeatures,
+    features::{CartridgeFeatures, Rtc},
     header::{CARTRIDGE_TYPE, CartridgeHeader, DESTINATION_CODE},
 };
+use crate::state::State;
 
 use mbc0::Mbc0;
 use mbc1::Mbc1;
@@ -122,6 +123,7 @@ impl CartridgeType {
             self,
             Mbc1RamBattery
                 | RomRamBattery
+                | Mbc2Battery
                 | Mbc3TimerBattery
                 | Mbc3TimerRamBattery
                 | Mbc3RamBattery
@@ -148,7 +150,8 @@ impl CartridgeType {
     pub fn has_sensor(&self) -> bool { matches!(self, CartridgeType::Mbc7SensorRumbleRamBattery) }
 }
 
-pub trait MemoryBankController {
+/// Only the banking registers and the RAM are part of the `State`, the ROM never changes
+pub trait MemoryBankController: State + Send + Sync {
     fn new(
         raw_rom: &[u8],
         save: Option<Vec<u8>>,
@@ -163,8 +166,13 @@ pub trait MemoryBankController {
     fn read_ram(&self, address: u16) -> u8;
     fn write_ram(&mut self, address: u16, value: u8);
 
+    fn get_rom(&self) -> &[u8];
     fn get_ram(&self) -> Option<&[u8]>;
+    fn get_ram_mut(&mut self) -> Option<&mut [u8]>;
     fn swap_boot_rom(&mut self, boot_rom: &mut [u8]);
+
+    fn get_rtc(&self) -> Option<&Rtc> { None }
+    fn get_rtc_mut(&mut self) -> Option<&mut Rtc> { None }
 }
 
 pub fn _check_multicart(raw_rom: &[u8], header: &CartridgeHeader) -> bool {
@@ -4,12 +4,14 @@ mod mbc;
 
 use crate::{
     EXTERNAL_RAM_END, EXTERNAL_RAM_START, ROM_BANK00_SIZE, ROM_BANK00_START, ROM_BANKNN_END, ROM_BANKNN_SIZE,
+    RtcFooter,
     prelude::*,
+    state::{State, StateReader, StateResult, StateWriter},
 };
 
 use features::CartridgeFeatures;
 use header::CartridgeHeader;
-pub use header::{RamSize, RomSize};
+pub use header::{MAX_ROM_SIZE, RamSize, RomSize};
 use mbc::{CartridgeType, MemoryBankController, select_mbc};
 
 /// Used for MBC1M multicart cartridge detection
@@ -75,6 +77,8 @@ pub struct Cartridge {
     pub header: CartridgeHeader,
     pub features: CartridgeFeatures,
     mbc: Box<dyn MemoryBankController>,
+    /// External RAM changed since the save was last written
+    ram_dirty: bool,
 }
 
 impl core::fmt::Debug for Cartridge {
@@ -107,6 +111,7 @@ impl Cartridge {
             header,
             features,
             mbc,
+            ram_dirty: false,
         })
     }
 
@@ -122,6 +127,70 @@ impl Cartridge {
         }
     }
 
+    /// Raw ROM data, with every bank regardless of the one currently mapped
+    #[inline(always)]
+    pub fn rom(&self) -> &[u8] { self.mbc.get_rom() }
+
+    /// Raw external RAM, with every bank regardless of the one currently mapped
+    #[inline(always)]
+    pub fn ram(&self) -> Option<&[u8]> { self.mbc.get_ram() }
+
+    /// Anything could be written through it, so the RAM is considered changed
+    #[inline(always)]
+    pub fn ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.ram_dirty = true;
+        self.mbc.get_ram_mut()
+    }
+
+    /// # Save tracking
+    /// Whether the battery backed RAM changed since the save was last written, so frontends can
+    /// write it often without writing the same data over and over
+    #[inline(always)]
+    pub fn save_changed(&self) -> bool { self.ram_dirty && self.supports_saves() }
+
+    /// Called once `save_game` was written somewhere it will survive a power loss
+    #[inline(always)]
+    pub fn mark_saved(&mut self) { self.ram_dirty = false; }
+
+    /// # Clock
+    /// MBC3 clock registers, for frontends keeping them apart from the RAM like RetroArch does.
+    /// The timestamp is left at 0, as the cartridge doesn't know the time
+    pub fn rtc(&self) -> Option<RtcFooter> {
+        let rtc = self.mbc.get_rtc()?;
+        Some(RtcFooter {
+            registers: [rtc.seconds, rtc.minutes, rtc.hours, rtc.day_low, rtc.day_high],
+            latched: [
+                rtc.latched_seconds,
+                rtc.latched_minutes,
+                rtc.latched_hours,
+                rtc.latched_day_low,
+                rtc.latched_day_high,
+            ],
+            timestamp: 0,
+        })
+    }
+
+    /// Set the clock registers of cartridges with one, masked like the game writing them would be
+    pub fn set_rtc(&mut self, footer: &RtcFooter) {
+        let Some(rtc) = self.mbc.get_rtc_mut() else {
+            return;
+        };
+
+        let [seconds, minutes, hours, day_low, day_high] = footer.registers;
+        rtc.seconds = seconds & 0x3F;
+        rtc.minutes = minutes & 0x3F;
+        rtc.hours = hours & 0x1F;
+        rtc.day_low = day_low;
+        rtc.day_high = day_high & 0xC1;
+
+        let [seconds, minutes, hours, day_low, day_high] = footer.latched;
+        rtc.latched_seconds = seconds & 0x3F;
+        rtc.latched_minutes = minutes & 0x3F;
+        rtc.latched_hours = hours & 0x1F;
+        rtc.latched_day_low = day_low;
+        rtc.latched_day_high = day_high & 0xC1;
+    }
+
     /// Used to not need to check if the read/write is for the boot ROM or the cartridge ROM in the MBCs
     pub fn swap_boot_rom(&mut self, boot_rom: &mut [u8]) { self.mbc.swap_boot_rom(boot_rom); }
 
@@ -177,7 +246,12 @@ impl Accessible<u16> for Cartridge {
     fn write(&mut self, address: u16, value: u8) {
         match address {
             ROM_BANK00_START..=ROM_BANKNN_END => self.mbc.write_rom(address, value),
-            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => self.mbc.write_ram(address, value),
+            EXTERNAL_RAM_START..=EXTERNAL_RAM_END => {
+                // writes with the RAM disabled or of the value already there don't change the save
+                let previous = self.mbc.read_ram(address);
+                self.mbc.write_ram(address, value);
+                self.ram_dirty |= self.mbc.read_ram(address) != previous;
+            }
 
             _ => unreachable!(
                 "Cartrigde: write of address {address:04X} should have been handled by other components"
@@ -186,6 +260,64 @@ impl Accessible<u16> for Cartridge {
     }
 }
 
+impl State for Cartridge {
+    fn save(&self, state: &mut StateWriter) { self.mbc.save(state); }
+
+    /// The loaded RAM is likely not the one last saved, so the save is written again
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.mbc.load(state)?;
+        self.ram_dirty = true;
+        Ok(())
+    }
+}
+
 impl core::fmt::Display for Cartridge {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result { writeln!(f, "{}", self.header) }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// MBC1 with 8 KB of battery backed RAM
+    fn battery_cartridge() -> Cartridge {
+        let mut rom = vec![0; (ROM_BANK00_SIZE + ROM_BANKNN_SIZE) as usize];
+        rom[header::CARTRIDGE_TYPE] = 0x03;
+        rom[header::RAM_SIZE_ADDRESS] = 0x02;
+        Cartridge::new(&rom, None).unwrap()
+    }
+
+    #[test]
+    fn test_mbc2_save() {
+        let mut rom = vec![0; (ROM_BANK00_SIZE + ROM_BANKNN_SIZE) as usize];
+        rom[header::CARTRIDGE_TYPE] = 0x06;
+
+        let cartridge = Cartridge::new(&rom, Some(vec![0xF5; 512])).unwrap();
+        assert_eq!(cartridge.save_game(), Some([0x05; 512].as_slice()));
+    }
+
+    #[test]
+    fn test_rom_without_header() {
+        assert!(matches!(
+            Cartridge::new(&[0; 0x100], None),
+            Err(CartridgeError::InvalidRomSize(None, _))
+        ));
+    }
+
+    #[test]
+    fn test_save_changed() {
+        let mut cartridge = battery_cartridge();
+        assert!(!cartridge.save_changed());
+
+        cartridge.write(EXTERNAL_RAM_START, 0x42);
+        assert!(!cartridge.save_changed(), "RAM is disabled");
+
+        cartridge.write(0x0000, 0x0A);
+        cartridge.write(EXTERNAL_RAM_START, 0x42);
+        assert!(cartridge.save_changed());
+
+        cartridge.mark_saved();
+        cartridge.write(EXTERNAL_RAM_START, 0x42);
+        assert!(!cartridge.save_changed(), "same value written again");
+    }
+}
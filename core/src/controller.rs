@@ -21,6 +21,18 @@ macro_rules! controller {
             }
 
             fn update_screen(&mut self, ppu: &Ppu) { self.renderer.update_screen(ppu) }
+
+            fn write_layer_pixel(
+                &mut self,
+                x: usize,
+                y: usize,
+                layer: $crate::PixelLayer,
+                palette: u8,
+                color_id: u8,
+            ) {
+                self.renderer
+                    .write_layer_pixel(x, y, layer, palette, color_id)
+            }
         }
 
         impl SerialListener for $name {
@@ -30,6 +42,9 @@ macro_rules! controller {
         impl AudioPlayer for $name {
             fn playing_stereo(&self) -> bool { self.audio_player.playing_stereo() }
             fn push_sample(&mut self, left: i16, right: i16) { self.audio_player.push_sample(left, right) }
+            fn push_channel_samples(&mut self, samples: [i16; 4]) {
+                self.audio_player.push_channel_samples(samples)
+            }
             fn flush_buffer(&mut self) { self.audio_player.flush_buffer() }
         }
 
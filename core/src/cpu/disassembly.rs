@@ -0,0 +1,133 @@
+use crate::{cpu::Cpu, dmg::Dmg, prelude::*, utils::to_u16};
+
+/// Longest instruction in the instruction set, opcode plus a 16 bits immediate
+pub const MAX_INSTRUCTION_LEN: usize = 3;
+
+/// # Disassembly
+/// Instruction decoded from memory without executing it.
+/// Unused opcodes are decoded as a single data byte, so a listing can always advance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disassembly {
+    pub address: u16,
+    pub bytes: [u8; MAX_INSTRUCTION_LEN],
+    pub len: u8,
+    /// M-cycles taken by the instruction, when conditional using the current flags
+    pub cycles: u8,
+    pub text: String,
+    /// Address this instruction may transfer control to, if it is known without executing it
+    pub target: Option<u16>,
+}
+
+impl Disassembly {
+    pub fn bytes(&self) -> &[u8] { &self.bytes[..self.len as usize] }
+    pub fn next_address(&self) -> u16 { self.address.wrapping_add(self.len as u16) }
+}
+
+impl Cpu {
+    /// Decode the instruction at `address` using the currently mapped memory,
+    /// so banked ROM areas are decoded from the selected bank
+    pub fn disassemble(gb: &Dmg, address: u16) -> Disassembly {
+        let opcode = gb.read(address);
+        let (cycles, len, text) = match Cpu::fetch_at(gb, address, opcode) {
+            Ok(instruction) => {
+                let (cycles, len) = instruction.info();
+                (cycles, len, instruction.disassembly())
+            }
+            Err(_) => (0, 1, format!("db ${opcode:02X}")),
+        };
+
+        let mut bytes = [0; MAX_INSTRUCTION_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate().take(len as usize) {
+            *byte = gb.read(address.wrapping_add(i as u16));
+        }
+
+        Disassembly {
+            address,
+            bytes,
+            len,
+            cycles,
+            text,
+            target: jump_target(address, &bytes),
+        }
+    }
+
+    /// Decode `count` consecutive instructions starting at `address`
+    pub fn disassemble_range(gb: &Dmg, address: u16, count: usize) -> Vec<Disassembly> {
+        let mut address = address;
+        let mut listing = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let line = Cpu::disassemble(gb, address);
+            address = line.next_address();
+            listing.push(line);
+        }
+
+        listing
+    }
+}
+
+/// Destination of jp, jr, call and rst instructions.
+/// `jp hl`, `ret` and `reti` depend on runtime state, so they have no static target
+fn jump_target(address: u16, bytes: &[u8; MAX_INSTRUCTION_LEN]) -> Option<u16> {
+    match bytes[0] {
+        // jp n16, call n16 and its conditional versions
+        0xC2 | 0xC3 | 0xCA | 0xD2 | 0xDA | 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC => {
+            Some(to_u16(bytes[1], bytes[2]))
+        }
+        // jr e8, relative to the next instruction
+        0x18 | 0x20 | 0x28 | 0x30 | 0x38 => Some(address.wrapping_add(2).wrapping_add(bytes[1] as i8 as u16)),
+        // rst vec
+        opcode if opcode & 0xC7 == 0xC7 => Some((opcode & 0x38) as u16),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disassemble_jp() {
+        let mut gb = Dmg::default();
+        gb.write(0xC000, 0xC3);
+        gb.write(0xC001, 0x50);
+        gb.write(0xC002, 0x01);
+
+        let line = Cpu::disassemble(&gb, 0xC000);
+
+        assert_eq!(line.bytes(), &[0xC3, 0x50, 0x01]);
+        assert_eq!(line.text, "jp $0150");
+        assert_eq!(line.cycles, 4);
+        assert_eq!(line.target, Some(0x0150));
+        assert_eq!(line.next_address(), 0xC003);
+        // decoding must not touch the cpu
+        assert_eq!(gb.cpu.pc, Dmg::default().cpu.pc);
+    }
+
+    #[test]
+    fn test_disassemble_jr_backwards() {
+        let mut gb = Dmg::default();
+        gb.write(0xC010, 0x18);
+        gb.write(0xC011, 0xFE);
+
+        let line = Cpu::disassemble(&gb, 0xC010);
+
+        assert_eq!(line.len, 2);
+        assert_eq!(line.target, Some(0xC010));
+    }
+
+    #[test]
+    fn test_disassemble_unused_opcode() {
+        let mut gb = Dmg::default();
+        gb.write(0xC000, 0xD3);
+        gb.write(0xC001, 0xFF);
+
+        let listing = Cpu::disassemble_range(&gb, 0xC000, 2);
+
+        assert_eq!(listing[0].text, "db $D3");
+        assert_eq!(listing[0].len, 1);
+        assert_eq!(listing[1].address, 0xC001);
+        assert_eq!(listing[1].text, "rst $38");
+        assert_eq!(listing[1].target, Some(0x0038));
+    }
+}
@@ -1,3 +1,4 @@
+mod disassembly;
 mod flags;
 mod instructions;
 mod registers;
@@ -7,9 +8,11 @@ use crate::{
     dmg::Dmg,
     interrupts::{JOYPAD_INTERRUPT, LCD_STAT_INTERRUPT, SERIAL_INTERRUPT, TIMER_INTERRUPT, VBLANK_INTERRUPT},
     prelude::*,
+    state::impl_state,
     utils::{from_u16, high, low, to_u16},
 };
 
+pub use disassembly::{Disassembly, MAX_INSTRUCTION_LEN};
 // TODO: not expose individual instructions
 pub use instructions::{Instruction, InstructionError, Len, Nop};
 use instructions::{JumpCondition as JC, *};
@@ -60,6 +63,22 @@ pub struct Cpu {
     pub halted: bool,
 }
 
+impl_state!(Cpu {
+    a,
+    f,
+    b,
+    c,
+    d,
+    e,
+    h,
+    l,
+    pc,
+    sp,
+    cycles,
+    ime,
+    halted,
+});
+
 impl Cpu {
     pub fn new(start_at_boot: bool) -> Cpu {
         if start_at_boot {
@@ -173,73 +192,77 @@ impl Cpu {
 
     /// Execute instruction based on the opcode.
     /// Return a result with the effect of the instruction or an instruction error (e.g unused opcode)
-    pub fn fetch(gb: &mut Dmg, opcode: u8) -> FetchResult {
+    pub fn fetch(gb: &Dmg, opcode: u8) -> FetchResult { Cpu::fetch_at(gb, gb.cpu.pc, opcode) }
+
+    /// Decode the instruction with the given opcode as if it were located at `pc`.
+    /// Only reads memory, so it can be used to inspect code without executing it
+    pub fn fetch_at(gb: &Dmg, pc: u16, opcode: u8) -> FetchResult {
         let cpu = &gb.cpu;
 
         let instruction: InstructionBox = match opcode {
             0x00 => Nop::new().into(),
-            0x01 => LdR16Imm16::new(R16::BC, gb.load(cpu.pc.wrapping_add(1))).into(),
+            0x01 => LdR16Imm16::new(R16::BC, gb.load(pc.wrapping_add(1))).into(),
             0x02 => LdPointedByR16A::new(R16::BC).into(),
             0x03 => IncR16::new(R16::BC).into(),
             0x04 => IncR8::new(R8::B).into(),
             0x05 => DecR8::new(R8::B).into(),
-            0x06 => LdR8Imm8::new(R8::B, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x06 => LdR8Imm8::new(R8::B, gb.read(pc.wrapping_add(1))).into(),
             0x07 => Rlca::new().into(),
-            0x08 => LdImm16SP::new(gb.load(cpu.pc.wrapping_add(1))).into(),
+            0x08 => LdImm16SP::new(gb.load(pc.wrapping_add(1))).into(),
             0x09 => AddR16::new(R16::BC).into(),
             0x0A => LdAPointedByR16::new(R16::BC).into(),
             0x0B => DecR16::new(R16::BC).into(),
             0x0C => IncR8::new(R8::C).into(),
             0x0D => DecR8::new(R8::C).into(),
-            0x0E => LdR8Imm8::new(R8::C, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x0E => LdR8Imm8::new(R8::C, gb.read(pc.wrapping_add(1))).into(),
             0x0F => Rrca::new().into(),
             0x10 => Stop::new().into(),
-            0x11 => LdR16Imm16::new(R16::DE, gb.load(cpu.pc.wrapping_add(1))).into(),
+            0x11 => LdR16Imm16::new(R16::DE, gb.load(pc.wrapping_add(1))).into(),
             0x12 => LdPointedByR16A::new(R16::DE).into(),
             0x13 => IncR16::new(R16::DE).into(),
             0x14 => IncR8::new(R8::D).into(),
             0x15 => DecR8::new(R8::D).into(),
-            0x16 => LdR8Imm8::new(R8::D, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x16 => LdR8Imm8::new(R8::D, gb.read(pc.wrapping_add(1))).into(),
             0x17 => Rla::new(cpu.carry()).into(),
-            0x18 => Jr::new(JC::None, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x18 => Jr::new(JC::None, gb.read(pc.wrapping_add(1))).into(),
             0x19 => AddR16::new(R16::DE).into(),
             0x1A => LdAPointedByR16::new(R16::DE).into(),
             0x1B => DecR16::new(R16::DE).into(),
             0x1C => IncR8::new(R8::E).into(),
             0x1D => DecR8::new(R8::E).into(),
-            0x1E => LdR8Imm8::new(R8::E, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x1E => LdR8Imm8::new(R8::E, gb.read(pc.wrapping_add(1))).into(),
             0x1F => Rra::new(cpu.carry()).into(),
-            0x20 => Jr::new(JC::NotZero(cpu.not_zero()), gb.read(cpu.pc.wrapping_add(1))).into(),
-            0x21 => LdR16Imm16::new(R16::HL, gb.load(cpu.pc.wrapping_add(1))).into(),
+            0x20 => Jr::new(JC::NotZero(cpu.not_zero()), gb.read(pc.wrapping_add(1))).into(),
+            0x21 => LdR16Imm16::new(R16::HL, gb.load(pc.wrapping_add(1))).into(),
             0x22 => LdPointedByHLIncA::new().into(),
             0x23 => IncR16::new(R16::HL).into(),
             0x24 => IncR8::new(R8::H).into(),
             0x25 => DecR8::new(R8::H).into(),
-            0x26 => LdR8Imm8::new(R8::H, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x26 => LdR8Imm8::new(R8::H, gb.read(pc.wrapping_add(1))).into(),
             0x27 => Daa::new().into(),
-            0x28 => Jr::new(JC::Zero(cpu.zero()), gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x28 => Jr::new(JC::Zero(cpu.zero()), gb.read(pc.wrapping_add(1))).into(),
             0x29 => AddR16::new(R16::HL).into(),
             0x2A => LdAPointedByHLInc::new().into(),
             0x2b => DecR16::new(R16::HL).into(),
             0x2C => IncR8::new(R8::L).into(),
             0x2D => DecR8::new(R8::L).into(),
-            0x2E => LdR8Imm8::new(R8::L, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x2E => LdR8Imm8::new(R8::L, gb.read(pc.wrapping_add(1))).into(),
             0x2F => Cpl::new().into(),
-            0x30 => Jr::new(JC::NotCarry(cpu.not_carry()), gb.read(cpu.pc.wrapping_add(1))).into(),
-            0x31 => LdSPImm16::new(gb.load(cpu.pc.wrapping_add(1))).into(),
+            0x30 => Jr::new(JC::NotCarry(cpu.not_carry()), gb.read(pc.wrapping_add(1))).into(),
+            0x31 => LdSPImm16::new(gb.load(pc.wrapping_add(1))).into(),
             0x32 => LdPointedByHLDecA::new().into(),
             0x33 => IncStackPointer::new().into(),
             0x34 => IncPointedByHL::new().into(),
             0x35 => DecPointedByHL::new().into(),
-            0x36 => LdPointedByHLImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x36 => LdPointedByHLImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0x37 => Scf::new().into(),
-            0x38 => Jr::new(JC::Carry(cpu.carry()), gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x38 => Jr::new(JC::Carry(cpu.carry()), gb.read(pc.wrapping_add(1))).into(),
             0x39 => AddHLSP::new().into(),
             0x3A => LdAPointedByHLDec::new().into(),
             0x3B => DecStackPointer::new().into(),
             0x3C => IncR8::new(R8::A).into(),
             0x3D => DecR8::new(R8::A).into(),
-            0x3E => LdR8Imm8::new(R8::A, gb.read(cpu.pc.wrapping_add(1))).into(),
+            0x3E => LdR8Imm8::new(R8::A, gb.read(pc.wrapping_add(1))).into(),
             0x3F => Ccf::new(cpu.carry()).into(),
             0x40 => LdR8R8::new(R8::B, R8::B).into(),
             0x41 => LdR8R8::new(R8::B, R8::C).into(),
@@ -371,80 +394,79 @@ impl Cpu {
             0xBF => CpR8::new(R8::A).into(),
             0xC0 => Ret::new(JC::NotZero(cpu.not_zero())).into(),
             0xC1 => Pop::new(R16::BC).into(),
-            0xC2 => JpToImm16::new(JC::NotZero(cpu.not_zero()), gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xC3 => JpToImm16::new(JC::None, gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xC4 => Call::new(JC::NotZero(cpu.not_zero()), gb.load(cpu.pc.wrapping_add(1))).into(),
+            0xC2 => JpToImm16::new(JC::NotZero(cpu.not_zero()), gb.load(pc.wrapping_add(1))).into(),
+            0xC3 => JpToImm16::new(JC::None, gb.load(pc.wrapping_add(1))).into(),
+            0xC4 => Call::new(JC::NotZero(cpu.not_zero()), gb.load(pc.wrapping_add(1))).into(),
             0xC5 => Push::new(R16::BC).into(),
-            0xC6 => AddImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xC6 => AddImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xC7 => Rst::new(0x00).into(),
             0xC8 => Ret::new(JC::Zero(cpu.zero())).into(),
             0xC9 => Ret::new(JC::None).into(),
-            0xCA => JpToImm16::new(JC::Zero(cpu.zero()), gb.load(cpu.pc.wrapping_add(1))).into(),
+            0xCA => JpToImm16::new(JC::Zero(cpu.zero()), gb.load(pc.wrapping_add(1))).into(),
             0xCB => {
-                let cb_opcode = gb.read(cpu.pc.wrapping_add(1));
-                Cpu::fetch_cb(gb, cb_opcode)?
+                let cb_opcode = gb.read(pc.wrapping_add(1));
+                Cpu::fetch_cb(pc, cb_opcode)?
             }
-            0xCC => Call::new(JC::Zero(cpu.zero()), gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xCD => Call::new(JC::None, gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xCE => AdcImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xCC => Call::new(JC::Zero(cpu.zero()), gb.load(pc.wrapping_add(1))).into(),
+            0xCD => Call::new(JC::None, gb.load(pc.wrapping_add(1))).into(),
+            0xCE => AdcImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xCF => Rst::new(0x08).into(),
             0xD0 => Ret::new(JC::NotCarry(cpu.not_carry())).into(),
             0xD1 => Pop::new(R16::DE).into(),
-            0xD2 => JpToImm16::new(JC::NotCarry(cpu.not_carry()), gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xD3 => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xD4 => Call::new(JC::NotCarry(cpu.not_carry()), gb.load(cpu.pc.wrapping_add(1))).into(),
+            0xD2 => JpToImm16::new(JC::NotCarry(cpu.not_carry()), gb.load(pc.wrapping_add(1))).into(),
+            0xD3 => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xD4 => Call::new(JC::NotCarry(cpu.not_carry()), gb.load(pc.wrapping_add(1))).into(),
             0xD5 => Push::new(R16::DE).into(),
-            0xD6 => SubImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xD6 => SubImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xD7 => Rst::new(0x10).into(),
             0xD8 => Ret::new(JC::Carry(cpu.carry())).into(),
             0xD9 => Reti::new().into(),
-            0xDA => JpToImm16::new(JC::Carry(cpu.carry()), gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xDB => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xDC => Call::new(JC::Carry(cpu.carry()), gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xDD => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xDE => SbcImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xDA => JpToImm16::new(JC::Carry(cpu.carry()), gb.load(pc.wrapping_add(1))).into(),
+            0xDB => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xDC => Call::new(JC::Carry(cpu.carry()), gb.load(pc.wrapping_add(1))).into(),
+            0xDD => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xDE => SbcImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xDF => Rst::new(0x18).into(),
-            0xE0 => LdhImm8A::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xE0 => LdhImm8A::new(gb.read(pc.wrapping_add(1))).into(),
             0xE1 => Pop::new(R16::HL).into(),
             0xE2 => LdhCA::new().into(),
-            0xE3 => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xE4 => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
+            0xE3 => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xE4 => return Err(InstructionError::UnusedOpcode(opcode, pc)),
             0xE5 => Push::new(R16::HL).into(),
-            0xE6 => AndImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xE6 => AndImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xE7 => Rst::new(0x20).into(),
-            0xE8 => AddSPImm8::new(gb.read(cpu.pc.wrapping_add(1)) as i8).into(),
+            0xE8 => AddSPImm8::new(gb.read(pc.wrapping_add(1)) as i8).into(),
             0xE9 => JpToHL::new(cpu.hl()).into(),
-            0xEA => LdPointedByImm16A::new(gb.load(cpu.pc.wrapping_add(1))).into(),
-            0xEB => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xEC => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xED => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xEE => XorImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xEA => LdPointedByImm16A::new(gb.load(pc.wrapping_add(1))).into(),
+            0xEB => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xEC => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xED => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xEE => XorImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xEF => Rst::new(0x28).into(),
-            0xF0 => LdhAImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xF0 => LdhAImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xF1 => Pop::new(R16::AF).into(),
             0xF2 => LdhAC::new().into(),
             0xF3 => Di::new().into(),
-            0xF4 => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
+            0xF4 => return Err(InstructionError::UnusedOpcode(opcode, pc)),
             0xF5 => Push::new(R16::AF).into(),
-            0xF6 => OrImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xF6 => OrImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xF7 => Rst::new(0x30).into(),
-            0xF8 => LdHLSPPlusImm8::new(gb.read(cpu.pc.wrapping_add(1)) as i8).into(),
+            0xF8 => LdHLSPPlusImm8::new(gb.read(pc.wrapping_add(1)) as i8).into(),
             0xF9 => LdSPHL::new().into(),
-            0xFA => LdAPointedByImm16::new(gb.load(cpu.pc.wrapping_add(1))).into(),
+            0xFA => LdAPointedByImm16::new(gb.load(pc.wrapping_add(1))).into(),
             0xFB => Ei::new().into(),
-            0xFC => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xFD => return Err(InstructionError::UnusedOpcode(opcode, cpu.pc)),
-            0xFE => CpImm8::new(gb.read(cpu.pc.wrapping_add(1))).into(),
+            0xFC => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xFD => return Err(InstructionError::UnusedOpcode(opcode, pc)),
+            0xFE => CpImm8::new(gb.read(pc.wrapping_add(1))).into(),
             0xFF => Rst::new(0x38).into(),
         };
 
         Ok(instruction)
     }
 
-    fn fetch_cb(gb: &mut Dmg, cb_opcode: u8) -> FetchResult {
+    fn fetch_cb(pc: u16, cb_opcode: u8) -> FetchResult {
         // used bit in res, set and bit instructions
         let bit = (cb_opcode & 0x38) >> 3;
-        let cpu = &gb.cpu;
 
         let instruction: InstructionBox = match cb_opcode {
             0x00 => RlcR8::new(R8::B).into(),
@@ -520,7 +542,7 @@ impl Cpu {
                 5 => BitR8::new(bit, R8::L).into(),
                 6 => BitPointedByHL::new(bit).into(),
                 7 => BitR8::new(bit, R8::A).into(),
-                _ => return Err(InstructionError::OutOfRangeCBOpcode(cb_opcode, cpu.pc)),
+                _ => return Err(InstructionError::OutOfRangeCBOpcode(cb_opcode, pc)),
             },
             0x80..=0xBF => match cb_opcode & 0x07 {
                 0 => ResR8::new(bit, R8::B).into(),
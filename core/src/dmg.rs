@@ -9,7 +9,9 @@ use crate::{
     memory::*,
     ppu::{DMA_REGISTER, PPU_REGISTER_END, PPU_REGISTER_START},
     serial::{SERIAL_REGISTER_END, SERIAL_REGISTER_START},
+    state::{self, StateResult},
     timer::{TIMER_REGISTER_END, TIMER_REGISTER_START},
+    trace::{TraceState, Tracer},
     utils::{high, low, to_u16},
 };
 
@@ -54,6 +56,7 @@ pub struct Dmg {
     pub interrupt_flag: Interrupt,
     pub interrupt_enable: Interrupt,
     pub bank: u8,
+    pub tracer: Option<Tracer>,
 }
 
 impl Dmg {
@@ -75,30 +78,63 @@ impl Dmg {
             interrupt_flag: Interrupt::new(),
             interrupt_enable: Interrupt::new(),
             bank: 0,
+            tracer: None,
         }
     }
 
     pub fn reset(&mut self) { self.cpu.reset(); }
 
+    /// # Save states
+    /// Everything but the ROMs, so it can be loaded back into a `Dmg` running the same game.
+    /// States have the same size for the whole run of a game
+    pub fn save_state(&self) -> Vec<u8> { state::save_dmg(self) }
+
+    /// Load a state made by `save_state`, leaving the `Dmg` as it was when it can't be loaded
+    pub fn load_state(&mut self, data: &[u8]) -> StateResult<()> { state::load_dmg(self, data) }
+
     /// Modifies the DMG state by executing one CPU instruction, and return the executed instruction
     pub fn run<C: Controller>(&mut self, controller: &mut C) -> Result<(), DmgError> {
+        self.run_until(controller, |_| false)?;
+        Ok(())
+    }
+
+    /// Same as `run`, but stops before executing any instruction accepted by `should_break`,
+    /// which sees the whole state right before each instruction.
+    /// Returns true when a breakpoint was hit, calling it again resumes the rest of the frame.
+    /// Callers resuming from a breakpoint should `step` once first to avoid hitting it again
+    pub fn run_until<C, F>(&mut self, controller: &mut C, mut should_break: F) -> Result<bool, DmgError>
+    where
+        C: Controller,
+        F: FnMut(&Dmg) -> bool,
+    {
         // one frame == 70224 T-cycles == 17556 M-cycles
         while self.cpu.cycles < 17556 {
+            // a halted cpu stays on the same address, only break when it's about to execute it
+            if !self.cpu.halted && should_break(self) {
+                return Ok(true);
+            }
             self.step(controller)?;
         }
 
         self.cpu.cycles = 0;
         controller.flush_buffer();
 
-        Ok(())
+        Ok(false)
     }
 
     pub fn step<C: Controller>(&mut self, controller: &mut C) -> Result<Option<InstructionBox>, DmgError> {
         let prev_cycles = self.cpu.cycles;
+        let trace_state = self.tracer.is_some().then(|| TraceState::capture(self));
 
         let instruction = Cpu::step(self)?;
 
-        let delta = self.cpu.cycles.wrapping_sub(prev_cycles) * 4;
+        let cycles = self.cpu.cycles.wrapping_sub(prev_cycles);
+        if let (Some(tracer), Some(state), Some(instruction)) = (&mut self.tracer, trace_state, &instruction)
+        {
+            tracer.log(&state, instruction, cycles);
+        }
+
+        let delta = cycles * 4;
 
         self.ppu.step(controller, delta, &mut self.interrupt_flag);
         self.timer.step(delta, &mut self.interrupt_flag);
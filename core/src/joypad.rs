@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{prelude::*, state::impl_state};
 
 pub const JOYP: u16 = 0xFF00;
 
@@ -45,6 +45,8 @@ pub struct Joypad {
     joyp: u8,
 }
 
+impl_state!(Joypad { input, joyp });
+
 impl Joypad {
     pub fn new() -> Self {
         Self {
@@ -13,21 +13,31 @@ mod dmg;
 mod interrupts;
 mod joypad;
 mod memory;
+mod patch;
 mod ppu;
 pub mod prelude;
+mod save;
 mod serial;
+mod state;
 mod timer;
+mod trace;
 pub mod utils;
 
-pub use apu::{Apu, AudioPlayer, BUFFER_SIZE, DefaultAudioPlayer, SAMPLE_RATE, STEREO_BUFFER_SIZE};
-pub use cartrigde::Cartridge;
+pub use apu::{
+    Apu, AudioChannel, AudioPlayer, BUFFER_SIZE, DefaultAudioPlayer, SAMPLE_RATE, STEREO_BUFFER_SIZE,
+};
+pub use cartrigde::{Cartridge, MAX_ROM_SIZE};
 pub use controller::{Controller, DefaultController};
-pub use cpu::{AFTER_BOOT_CPU, Cpu};
+pub use cpu::{AFTER_BOOT_CPU, Cpu, Disassembly, MAX_INSTRUCTION_LEN};
 pub use dmg::{Dmg, DmgError};
 pub use interrupts::Interrupt;
 pub use joypad::Joypad;
 pub use joypad::JoypadButton;
 pub use memory::*;
-pub use ppu::{DefaultRenderer, Ppu, Renderer};
+pub use patch::{PatchError, PatchFormat, PatchResult, apply_patch, crc32};
+pub use ppu::{DefaultRenderer, MAX_SPRITES_IN_OAM, OamObject, PixelLayer, Ppu, Renderer, Sprite};
+pub use save::{RtcFooter, SaveError, SaveFile, SaveFormat, SaveResult, export_save, import_save};
 pub use serial::{Serial, SerialListener};
+pub use state::{StateError, StateResult};
 pub use timer::Timer;
+pub use trace::{DOCTOR_LY, TraceFormat, TraceOutput, TraceState, Tracer};
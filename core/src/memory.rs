@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{prelude::*, state::impl_state};
 
 /// addressable memory size, 64KB
 pub const ADDRESABLE_MEMORY: u16 = 0xFFFF;
@@ -63,6 +63,9 @@ pub struct Memory {
     pub hram: Box<[u8; HRAM_SIZE as usize]>,
 }
 
+// the boot ROM never changes, and whether it is mapped is up to the bank register
+impl_state!(Memory { ram, hram });
+
 impl Memory {
     pub fn new(boot_rom: Option<Vec<u8>>) -> Memory {
         Memory {
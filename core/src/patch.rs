@@ -0,0 +1,557 @@
+use crate::{cartrigde::MAX_ROM_SIZE, prelude::*};
+
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+/// UPS and BPS end with the source, target and patch CRC32s
+const FOOTER_SIZE: usize = 12;
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC32 used by UPS and BPS, and by most ROM databases
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &b| {
+        CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Ups,
+    Bps,
+}
+
+impl PatchFormat {
+    pub const ALL: [PatchFormat; 3] = [PatchFormat::Ips, PatchFormat::Ups, PatchFormat::Bps];
+
+    /// Format of a patch told by its magic bytes
+    pub fn detect(patch: &[u8]) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| patch.starts_with(format.magic()))
+    }
+
+    fn magic(self) -> &'static [u8] {
+        match self {
+            PatchFormat::Ips => IPS_MAGIC,
+            PatchFormat::Ups => UPS_MAGIC,
+            PatchFormat::Bps => BPS_MAGIC,
+        }
+    }
+
+    /// Extension patches of this format are usually distributed with
+    pub fn extension(self) -> &'static str {
+        match self {
+            PatchFormat::Ips => "ips",
+            PatchFormat::Ups => "ups",
+            PatchFormat::Bps => "bps",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    UnknownFormat,
+    /// The patch ended in the middle of a record
+    Truncated,
+    /// A record reads or writes past the end of the ROM
+    OutOfBounds(usize),
+    /// The patched ROM would be bigger than any cartridge
+    TooLarge(usize),
+    /// The ROM is not the one the patch was made for, calculated and expected CRC32
+    SourceChecksum(u32, u32),
+    TargetChecksum(u32, u32),
+    PatchChecksum(u32, u32),
+}
+
+impl core::fmt::Display for PatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "Unknown patch format"),
+            PatchError::Truncated => write!(f, "Patch ends in the middle of a record"),
+            PatchError::OutOfBounds(offset) => write!(f, "Patch accesses out of bounds offset 0x{offset:X}"),
+            PatchError::TooLarge(size) => write!(
+                f,
+                "Patched ROM would be {size} bytes, more than the {MAX_ROM_SIZE} of the largest cartridges"
+            ),
+            PatchError::SourceChecksum(calculated, expected) => write!(
+                f,
+                "ROM does not match the patch: calculated CRC32 0x{calculated:08X}, expected 0x{expected:08X}"
+            ),
+            PatchError::TargetChecksum(calculated, expected) => write!(
+                f,
+                "Incorrect patched ROM: calculated CRC32 0x{calculated:08X}, expected 0x{expected:08X}"
+            ),
+            PatchError::PatchChecksum(calculated, expected) => write!(
+                f,
+                "Corrupted patch: calculated CRC32 0x{calculated:08X}, expected 0x{expected:08X}"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+pub type PatchResult<T> = core::result::Result<T, PatchError>;
+
+/// # Soft patching
+/// Apply an IPS, UPS or BPS patch to a ROM in memory, returning the patched ROM
+/// to be given to `Cartridge::new`. UPS and BPS patches are verified against their CRC32s
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Ups) => apply_ups(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(PatchError::UnknownFormat),
+    }
+}
+
+/// Cursor over the patch bytes, failing with `Truncated` when reading past the end
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Self { Self { data, offset } }
+
+    fn bytes(&mut self, len: usize) -> PatchResult<&'a [u8]> {
+        let end = self.offset.checked_add(len).ok_or(PatchError::Truncated)?;
+        let bytes = self.data.get(self.offset..end).ok_or(PatchError::Truncated)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> PatchResult<u8> { Ok(self.bytes(1)?[0]) }
+
+    /// Big endian integer of `len` bytes, as IPS stores them
+    fn be(&mut self, len: usize) -> PatchResult<usize> {
+        Ok(self.bytes(len)?.iter().fold(0, |acc, &b| (acc << 8) | b as usize))
+    }
+
+    /// Variable length integer shared by UPS and BPS, where every continuation adds one
+    /// so each value has a single encoding
+    fn number(&mut self) -> PatchResult<usize> {
+        let (mut value, mut shift) = (0usize, 1usize);
+        loop {
+            let b = self.byte()?;
+            value = (b as usize & 0x7F)
+                .checked_mul(shift)
+                .and_then(|n| n.checked_add(value))
+                .ok_or(PatchError::Truncated)?;
+            if b & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::Truncated)?;
+            value += shift;
+        }
+    }
+}
+
+/// # IPS
+/// Records of a 24 bit offset and 16 bit size followed by the data, a zero size being
+/// a run of a single byte repeated. The ROM grows to fit records past its end, and
+/// an offset after the `EOF` marker truncates it
+fn apply_ips(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    let mut target = rom.to_vec();
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        if reader.bytes(IPS_EOF.len())? == IPS_EOF {
+            break;
+        }
+        reader.offset -= IPS_EOF.len();
+
+        let offset = reader.be(3)?;
+        let (len, run) = match reader.be(2)? {
+            0 => (reader.be(2)?, Some(reader.byte()?)),
+            size => (size, None),
+        };
+
+        if offset + len > MAX_ROM_SIZE {
+            return Err(PatchError::TooLarge(offset + len));
+        }
+        if target.len() < offset + len {
+            target.resize(offset + len, 0);
+        }
+        match run {
+            Some(value) => target[offset..offset + len].fill(value),
+            None => target[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+
+    if let Ok(size) = reader.be(3) {
+        target.truncate(size);
+    }
+    Ok(target)
+}
+
+/// Source, target and patch CRC32s of a UPS or BPS patch, after checking the patch one
+fn footer(patch: &[u8]) -> PatchResult<(u32, u32)> {
+    if patch.len() < BPS_MAGIC.len() + FOOTER_SIZE {
+        return Err(PatchError::Truncated);
+    }
+
+    let crc = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+    let end = patch.len() - FOOTER_SIZE;
+
+    let calculated = crc32(&patch[..patch.len() - 4]);
+    match calculated == crc(end + 8) {
+        true => Ok((crc(end), crc(end + 4))),
+        false => Err(PatchError::PatchChecksum(calculated, crc(end + 8))),
+    }
+}
+
+fn check_source(rom: &[u8], expected: u32) -> PatchResult<()> {
+    match crc32(rom) {
+        calculated if calculated == expected => Ok(()),
+        calculated => Err(PatchError::SourceChecksum(calculated, expected)),
+    }
+}
+
+/// Target size read from the patch, checked before anything that big is allocated
+fn check_target_size(size: usize) -> PatchResult<usize> {
+    match size > MAX_ROM_SIZE {
+        true => Err(PatchError::TooLarge(size)),
+        false => Ok(size),
+    }
+}
+
+fn check_target(target: &[u8], expected: u32) -> PatchResult<()> {
+    match crc32(target) {
+        calculated if calculated == expected => Ok(()),
+        calculated => Err(PatchError::TargetChecksum(calculated, expected)),
+    }
+}
+
+/// # UPS
+/// Runs of bytes XORed with the ROM, each after a relative skip and ended by a zero
+fn apply_ups(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    let (source_crc, target_crc) = footer(patch)?;
+    check_source(rom, source_crc)?;
+
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(&patch[..end], UPS_MAGIC.len());
+    let _source_size = reader.number()?;
+    let target_size = check_target_size(reader.number()?)?;
+
+    let mut target = vec![0; target_size];
+    let copied = rom.len().min(target_size);
+    target[..copied].copy_from_slice(&rom[..copied]);
+
+    let mut position = 0usize;
+    let next = |position: usize, skip: usize| {
+        position
+            .checked_add(skip)
+            .ok_or(PatchError::OutOfBounds(position))
+    };
+    while reader.offset < end {
+        position = next(position, reader.number()?)?;
+        loop {
+            let xor = reader.byte()?;
+            if xor == 0 {
+                position = next(position, 1)?;
+                break;
+            }
+            let output = target
+                .get_mut(position)
+                .ok_or(PatchError::OutOfBounds(position))?;
+            *output = rom.get(position).copied().unwrap_or(0) ^ xor;
+            position += 1;
+        }
+    }
+
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+/// # BPS
+/// Actions building the target from the ROM, the patch itself or the already written
+/// target, the last two through cursors moved by signed relative offsets
+fn apply_bps(rom: &[u8], patch: &[u8]) -> PatchResult<Vec<u8>> {
+    let (source_crc, target_crc) = footer(patch)?;
+    check_source(rom, source_crc)?;
+
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(&patch[..end], BPS_MAGIC.len());
+    let _source_size = reader.number()?;
+    let target_size = check_target_size(reader.number()?)?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0usize, 0usize);
+
+    let relative = |reader: &mut Reader, cursor: usize| -> PatchResult<usize> {
+        let data = reader.number()?;
+        let delta = data >> 1;
+        match data & 1 {
+            1 => cursor.checked_sub(delta),
+            _ => cursor.checked_add(delta),
+        }
+        .ok_or(PatchError::OutOfBounds(cursor))
+    };
+
+    while reader.offset < end {
+        let data = reader.number()?;
+        let len = (data >> 2) + 1;
+        if target.len() + len > target_size {
+            return Err(PatchError::OutOfBounds(target.len() + len));
+        }
+
+        match data & 3 {
+            // source read, the ROM at the same offset
+            0 => {
+                let at = target.len();
+                let bytes = rom.get(at..at + len).ok_or(PatchError::OutOfBounds(at))?;
+                target.extend_from_slice(bytes);
+            }
+            // target read, bytes from the patch
+            1 => target.extend_from_slice(reader.bytes(len)?),
+            // source copy, the ROM anywhere
+            2 => {
+                source_offset = relative(&mut reader, source_offset)?;
+                let bytes = source_offset
+                    .checked_add(len)
+                    .and_then(|end| rom.get(source_offset..end))
+                    .ok_or(PatchError::OutOfBounds(source_offset))?;
+                target.extend_from_slice(bytes);
+                source_offset += len;
+            }
+            // target copy, one byte at a time since it may overlap what it writes
+            _ => {
+                target_offset = relative(&mut reader, target_offset)?;
+                for _ in 0..len {
+                    let b = *target
+                        .get(target_offset)
+                        .ok_or(PatchError::OutOfBounds(target_offset))?;
+                    target.push(b);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    target.resize(target_size, 0);
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn number(mut value: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let x = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                bytes.push(0x80 | x);
+                return bytes;
+            }
+            bytes.push(x);
+            value -= 1;
+        }
+    }
+
+    /// Append the source, target and patch CRC32s
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend(crc32(source).to_le_bytes());
+        patch.extend(crc32(target).to_le_bytes());
+        patch.extend(crc32(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(PatchFormat::detect(b"PATCHEOF"), Some(PatchFormat::Ips));
+        assert_eq!(PatchFormat::detect(b"UPS1"), Some(PatchFormat::Ups));
+        assert_eq!(PatchFormat::detect(b"BPS1"), Some(PatchFormat::Bps));
+        assert_eq!(apply_patch(&[0; 4], b"GBS"), Err(PatchError::UnknownFormat));
+    }
+
+    #[test]
+    fn test_ips_records() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend([0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+        // run of 3 bytes past the end of the ROM
+        patch.extend([0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xCC]);
+        patch.extend(b"EOF");
+
+        assert_eq!(
+            apply_patch(&[0; 4], &patch).unwrap(),
+            [0x00, 0xAA, 0xBB, 0x00, 0x00, 0xCC, 0xCC, 0xCC]
+        );
+    }
+
+    #[test]
+    fn test_ips_truncate() {
+        let mut patch = b"PATCHEOF".to_vec();
+        patch.extend([0x00, 0x00, 0x02]);
+        assert_eq!(apply_patch(&[1, 2, 3, 4], &patch).unwrap(), [1, 2]);
+
+        assert_eq!(
+            apply_patch(&[0; 4], b"PATCH\x00\x00\x01\x00\x04\xAA"),
+            Err(PatchError::Truncated)
+        );
+    }
+
+    #[test]
+    fn test_ups() {
+        let source = [1, 2, 3, 4];
+        let target = [1, 9, 3, 4, 7];
+
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        // skip 1, xor 2 ^ 9, end
+        patch.extend(number(1));
+        patch.extend([2 ^ 9, 0]);
+        // skip 1 more after the byte ended by the zero, the target past the source is xored with 0
+        patch.extend(number(1));
+        patch.extend([7, 0]);
+        let patch = with_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+        assert!(matches!(
+            apply_patch(&[0, 2, 3, 4], &patch),
+            Err(PatchError::SourceChecksum(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_bps() {
+        let source = [1, 2, 3, 4, 5, 6];
+        let target = [1, 2, 0xAA, 5, 6, 6, 6, 6];
+
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(number(source.len()));
+        patch.extend(number(target.len()));
+        patch.extend(number(0));
+        // source read of 2
+        patch.extend(number(1 << 2));
+        // target read of 1
+        patch.extend(number(1));
+        patch.push(0xAA);
+        // source copy of 2 from offset 4
+        patch.extend(number((1 << 2) | 2));
+        patch.extend(number(4 << 1));
+        // target copy of 3 from offset 4, overlapping its own output
+        patch.extend(number((2 << 2) | 3));
+        patch.extend(number(4 << 1));
+        let mut patch = with_footer(patch, &source, &target);
+
+        assert_eq!(apply_patch(&source, &patch).unwrap(), target);
+
+        let last = patch.len() - 1;
+        patch[last] ^= 0xFF;
+        assert!(matches!(
+            apply_patch(&source, &patch),
+            Err(PatchError::PatchChecksum(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_headers() {
+        // the sizes are missing, only the footer follows the magic
+        let ups = with_footer(b"UPS1".to_vec(), &[0; 4], &[0; 4]);
+        assert_eq!(apply_patch(&[0; 4], &ups), Err(PatchError::Truncated));
+
+        let mut bps = b"BPS1".to_vec();
+        bps.extend(number(4));
+        let bps = with_footer(bps, &[0; 4], &[0; 4]);
+        assert_eq!(apply_patch(&[0; 4], &bps), Err(PatchError::Truncated));
+
+        // metadata running way past the end of the patch
+        let mut bps = b"BPS1".to_vec();
+        bps.extend(number(4));
+        bps.extend(number(4));
+        bps.extend(number(usize::MAX - 3));
+        let bps = with_footer(bps, &[0; 4], &[0; 4]);
+        assert_eq!(apply_patch(&[0; 4], &bps), Err(PatchError::Truncated));
+    }
+
+    #[test]
+    fn test_oversized_targets() {
+        let mut ups = b"UPS1".to_vec();
+        ups.extend(number(4));
+        ups.extend(number(usize::MAX >> 1));
+        let ups = with_footer(ups, &[0; 4], &[0; 4]);
+        assert_eq!(
+            apply_patch(&[0; 4], &ups),
+            Err(PatchError::TooLarge(usize::MAX >> 1))
+        );
+
+        let mut bps = b"BPS1".to_vec();
+        bps.extend(number(4));
+        bps.extend(number(MAX_ROM_SIZE + 1));
+        bps.extend(number(0));
+        let bps = with_footer(bps, &[0; 4], &[0; 4]);
+        assert_eq!(
+            apply_patch(&[0; 4], &bps),
+            Err(PatchError::TooLarge(MAX_ROM_SIZE + 1))
+        );
+
+        let mut ips = b"PATCH".to_vec();
+        ips.extend([0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x01, 0xCC]);
+        ips.extend(b"EOF");
+        assert_eq!(apply_patch(&[0; 4], &ips), Err(PatchError::TooLarge(0x100_0000)));
+    }
+
+    #[test]
+    fn test_huge_offsets() {
+        // a skip that would wrap the position around
+        let mut ups = b"UPS1".to_vec();
+        ups.extend(number(4));
+        ups.extend(number(4));
+        ups.extend(number(usize::MAX >> 1));
+        ups.push(0);
+        ups.extend(number(usize::MAX >> 1));
+        ups.push(0);
+        let ups = with_footer(ups, &[0; 4], &[0; 4]);
+        assert!(matches!(
+            apply_patch(&[0; 4], &ups),
+            Err(PatchError::OutOfBounds(_))
+        ));
+
+        // source copy from an offset the length would wrap around
+        let mut bps = b"BPS1".to_vec();
+        bps.extend(number(4));
+        bps.extend(number(4));
+        bps.extend(number(0));
+        bps.extend(number((3 << 2) | 2));
+        bps.extend(number((usize::MAX >> 1) << 1));
+        let bps = with_footer(bps, &[0; 4], &[0; 4]);
+        assert!(matches!(
+            apply_patch(&[0; 4], &bps),
+            Err(PatchError::OutOfBounds(_))
+        ));
+    }
+}
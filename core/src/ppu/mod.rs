@@ -6,11 +6,13 @@ use crate::{
     dmg::Dmg,
     mem_range,
     memory::{OAM_END, OAM_START, VRAM_START},
-    ppu::sprite::{MAX_SPRITES_IN_OAM, MAX_SPRITES_PER_LINE, Sprite},
+    ppu::sprite::MAX_SPRITES_PER_LINE,
     prelude::*,
+    state::{StateError, StateResult, impl_state},
 };
 
-pub use renderer::{DefaultRenderer, Renderer};
+pub use renderer::{DefaultRenderer, PixelLayer, Renderer};
+pub use sprite::{MAX_SPRITES_IN_OAM, OamObject, Sprite};
 
 mem_range!(PPU_REGISTER, 0xFF40, 0xFF4B);
 
@@ -105,6 +107,9 @@ pub struct Ppu {
 
     /// currently drawn line
     ly: u8,
+    /// Returned by LY reads instead of the current line when set.
+    /// Gameboy Doctor reference logs are taken with LY stuck at 0x90
+    pub ly_override: Option<u8>,
     lyc: u8,
     dma: u8,
     bg_palette: u8,
@@ -115,6 +120,29 @@ pub struct Ppu {
     window_line_counter: u8,
 }
 
+// the overridden LY is a debugging aid set by the frontend, not part of the game state
+impl_state!(Ppu {
+    dots,
+    frames,
+    vram,
+    oam_ram,
+    bg_cache,
+    sprites_this_frame,
+    lcd_control,
+    lcd_status,
+    scroll_y,
+    scroll_x,
+    ly,
+    lyc,
+    dma,
+    bg_palette,
+    obj0_palette,
+    obj1_palette,
+    wy,
+    wx,
+    window_line_counter,
+}, validate: check_loaded);
+
 impl Default for Ppu {
     fn default() -> Self { Self::new() }
 }
@@ -159,6 +187,7 @@ impl Ppu {
             scroll_y: 0,
             scroll_x: 0,
             ly: 0,
+            ly_override: None,
             lyc: 0,
             dma: 0,
             bg_palette: 0xFC,
@@ -206,6 +235,19 @@ impl Ppu {
         }
     }
 
+    /// Lines past the visible ones only happen in VBlank and the dots never get past a frame,
+    /// stepping from anything else would run LY past the last line
+    fn check_loaded(&self) -> StateResult<()> {
+        let vblank = matches!(self.get_mode(), LCDMode::VBlank);
+        if self.ly as usize >= SCANLINES_PER_FRAME || (self.ly >= DMG_SCREEN_HEIGHT as u8) != vblank {
+            return Err(StateError::InvalidValue("PPU line"));
+        }
+        if self.dots >= DOTS_PER_SCANLINE * SCANLINES_PER_FRAME {
+            return Err(StateError::InvalidValue("PPU dot count"));
+        }
+        Ok(())
+    }
+
     #[inline(always)]
     pub fn get_scroll(&self) -> (u8, u8) { (self.scroll_x, self.scroll_y) }
 
@@ -215,6 +257,43 @@ impl Ppu {
     #[inline(always)]
     pub fn get_bg_palette(&self) -> u8 { self.bg_palette }
 
+    #[inline(always)]
+    pub fn get_obj_palettes(&self) -> (u8, u8) { (self.obj0_palette, self.obj1_palette) }
+
+    /// Line being drawn, not affected by `ly_override`
+    #[inline(always)]
+    pub fn current_line(&self) -> u8 { self.ly }
+
+    #[inline(always)]
+    pub fn sprite_height(&self) -> u8 { if self.obj_size() { 16 } else { 8 } }
+
+    pub fn sprite(&self, index: u8) -> Sprite {
+        let start = index as usize * 4;
+        Sprite::from_oam(&self.oam_ram[start..start + 4])
+    }
+
+    /// Every OAM entry, marking the ones `line` would show and the ones left out by the 10 objects per line limit
+    pub fn oam_objects(&self, line: u8) -> [OamObject; MAX_SPRITES_IN_OAM as usize] {
+        let height = self.sprite_height();
+        let mut selected = 0;
+
+        core::array::from_fn(|index| {
+            let sprite = self.sprite(index as u8);
+            let on_line = sprite.on_line(line, height);
+            let culled = on_line && selected >= MAX_SPRITES_PER_LINE;
+            if on_line {
+                selected += 1;
+            }
+
+            OamObject {
+                index: index as u8,
+                sprite,
+                on_line,
+                culled,
+            }
+        })
+    }
+
     #[inline(always)]
     /// If LY equals LYC, the LYC=LY flag in STAT register is set.
     /// And if the corresponding interrupt is enabled, the LCD STAT interrupt is requested.
@@ -397,7 +476,13 @@ impl Ppu {
             let color_id = (high_pixel << 1) | low_pixel;
 
             self.bg_cache[pixel] = color_id;
-            renderer.write_pixel(pixel, current_line as usize, self.bg_palette, color_id);
+            renderer.write_layer_pixel(
+                pixel,
+                current_line as usize,
+                PixelLayer::Background,
+                self.bg_palette,
+                color_id,
+            );
 
             if bit_index == 0 {
                 bit_index = 7;
@@ -467,7 +552,13 @@ impl Ppu {
             let color_id = (high_pixel << 1) | low_pixel;
 
             self.bg_cache[pixel] = color_id;
-            renderer.write_pixel(pixel, current_line as usize, self.bg_palette, color_id);
+            renderer.write_layer_pixel(
+                pixel,
+                current_line as usize,
+                PixelLayer::Background,
+                self.bg_palette,
+                color_id,
+            );
 
             if bit_index == 0 {
                 bit_index = 7;
@@ -493,7 +584,7 @@ impl Ppu {
 
     fn draw_sprites<R: Renderer>(&mut self, renderer: &mut R) -> usize {
         let current_line = self.ly;
-        let sprite_height = if self.obj_size() { 16 } else { 8 };
+        let sprite_height = self.sprite_height();
         let mut drawn_sprites = 0u8;
 
         // track which sprite owns each pixel in the current line
@@ -504,14 +595,13 @@ impl Ppu {
                 break;
             }
 
-            let oam_addr = (sprites_count as u16) * 4;
-            let sprite = Sprite::from_oam(&self.oam_ram[oam_addr as usize..oam_addr as usize + 4]);
+            let sprite = self.sprite(sprites_count);
 
             // is sprite in current line?
-            let line_offset = current_line.wrapping_sub(sprite.ypos);
-            if line_offset >= sprite_height {
+            if !sprite.on_line(current_line, sprite_height) {
                 continue;
             }
+            let line_offset = current_line.wrapping_sub(sprite.ypos);
 
             let mut line_in_sprite = if sprite.yflip() {
                 sprite_height - 1 - line_offset
@@ -541,10 +631,10 @@ impl Ppu {
                 high_tile_byte = high_tile_byte.reverse_bits();
             }
 
-            let palette = if sprite.palette_number() {
-                self.obj1_palette
+            let (layer, palette) = if sprite.palette_number() {
+                (PixelLayer::Object1, self.obj1_palette)
             } else {
-                self.obj0_palette
+                (PixelLayer::Object0, self.obj0_palette)
             };
 
             for pixel in 0..8 {
@@ -579,7 +669,7 @@ impl Ppu {
                 }
 
                 pixel_owner[sx] = Some(sprite.xpos);
-                renderer.write_pixel(sx, current_line as usize, palette, color_id);
+                renderer.write_layer_pixel(sx, current_line as usize, layer, palette, color_id);
             }
 
             drawn_sprites += 1;
@@ -619,7 +709,7 @@ impl Accessible<u16> for Ppu {
             LCD_STATUS => self.lcd_status,
             SCROLL_Y => self.scroll_y,
             SCROLL_X => self.scroll_x,
-            LY => self.ly,
+            LY => self.ly_override.unwrap_or(self.ly),
             LYC => self.lyc,
             DMA_REGISTER => self.dma,
             BG_PALETTE => self.bg_palette,
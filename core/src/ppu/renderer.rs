@@ -1,10 +1,24 @@
 use crate::ppu::{DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH, Ppu};
 
+/// Palette register a pixel was drawn with, the window sharing the background one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelLayer {
+    Background,
+    Object0,
+    Object1,
+}
+
 /// UI crates that uses gbeed must implement
 pub trait Renderer {
     fn read_pixel(&self, x: usize, y: usize) -> u32;
     fn write_pixel(&mut self, x: usize, y: usize, palette: u8, color_id: u8);
     fn update_screen(&mut self, ppu: &Ppu);
+
+    /// Called by the PPU instead of `write_pixel`, so renderers can color every layer differently
+    /// like the Game Boy Color does with DMG games
+    fn write_layer_pixel(&mut self, x: usize, y: usize, _layer: PixelLayer, palette: u8, color_id: u8) {
+        self.write_pixel(x, y, palette, color_id)
+    }
 }
 
 pub struct DefaultRenderer {
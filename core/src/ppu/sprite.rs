@@ -9,8 +9,9 @@ pub const MAX_SPRITES_PER_LINE: u8 = 10;
 pub const MAX_SPRITES_IN_OAM: u8 = 40;
 
 /// Composed by one (normally) or two tiles.
-/// Instead of create the struct to easily manipulate, for better optimization we could cast the OAM memory as a Sprite
-#[derive(Debug, Default)]
+/// Instead of create the struct to easily manipulate, for better optimization we could cast the OAM memory as a Sprite.
+/// Positions are already moved to screen coordinates, wrapping around for the ones partially off the top left
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Sprite {
     pub xpos: u8,
     pub ypos: u8,
@@ -28,13 +29,26 @@ impl Sprite {
         }
     }
 
-    pub fn _to_oam(&self, sprite_data: &mut [u8]) {
+    pub fn to_oam(&self, sprite_data: &mut [u8]) {
         sprite_data[0] = self.ypos.wrapping_add(16);
         sprite_data[1] = self.xpos.wrapping_add(8);
         sprite_data[2] = self.tile_index;
         sprite_data[3] = self.flags;
     }
 
+    #[inline(always)]
+    pub fn flags(&self) -> u8 { self.flags }
+
+    /// Signed horizontal position, from -8 to 247
+    pub fn screen_x(&self) -> i16 { self.xpos as i16 - if self.xpos >= 248 { 256 } else { 0 } }
+
+    /// Signed vertical position, from -16 to 239
+    pub fn screen_y(&self) -> i16 { self.ypos as i16 - if self.ypos >= 240 { 256 } else { 0 } }
+
+    /// Whether any of its rows is drawn in `line`, `height` is 8 or 16 depending on LCDC
+    #[inline(always)]
+    pub fn on_line(&self, line: u8, height: u8) -> bool { line.wrapping_sub(self.ypos) < height }
+
     bit_accessors! {
         target: flags;
 
@@ -44,3 +58,50 @@ impl Sprite {
         PALETTE_NUMBER
     }
 }
+
+/// # OamObject
+/// One of the 40 OAM entries as the PPU sees it when drawing a line.
+/// Only the first 10 objects on a line are drawn, in OAM order, the rest are culled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OamObject {
+    pub index: u8,
+    pub sprite: Sprite,
+    pub on_line: bool,
+    pub culled: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OAM_START;
+
+    #[test]
+    fn test_oam_objects_culled_after_ten_per_line() {
+        let mut ppu = Ppu::new();
+        for index in 0..12 {
+            let address = OAM_START + index * 4;
+            ppu.write(address, 16 + 20);
+            ppu.write(address + 1, 8 + index as u8 * 8);
+        }
+        // off the line
+        ppu.write(OAM_START + 12 * 4, 16 + 40);
+
+        let objects = ppu.oam_objects(20);
+
+        assert!(objects[..10].iter().all(|o| o.on_line && !o.culled));
+        assert!(objects[10..12].iter().all(|o| o.on_line && o.culled));
+        assert!(!objects[12].on_line && !objects[12].culled);
+        assert_eq!(objects[12].index, 12);
+    }
+
+    #[test]
+    fn test_sprite_screen_position() {
+        let sprite = Sprite::from_oam(&[4, 2, 0, 0]);
+
+        assert_eq!(sprite.screen_x(), -6);
+        assert_eq!(sprite.screen_y(), -12);
+        assert!(!sprite.on_line(0, 8));
+        assert!(sprite.on_line(3, 16));
+        assert!(!sprite.on_line(4, 16));
+    }
+}
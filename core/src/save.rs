@@ -0,0 +1,325 @@
+use crate::prelude::*;
+
+/// MBC2 has 512 half bytes of RAM built in, the only cartridge with that much
+const MBC2_RAM_SIZE: usize = 512;
+/// Sizes of the battery backed RAM of every cartridge, the first being MBC3 carts with only a clock
+/// and the second MBC2's
+const RAM_SIZES: [usize; 7] = [
+    0,
+    MBC2_RAM_SIZE,
+    2 * 1024,
+    8 * 1024,
+    32 * 1024,
+    64 * 1024,
+    128 * 1024,
+];
+/// Clock registers and their latched copies, each as a 32 bit little endian number
+const RTC_REGISTERS_SIZE: usize = 10 * 4;
+const RTC_FOOTER_SIZE: usize = RTC_REGISTERS_SIZE + 8;
+const RTC_FOOTER_SIZE_32: usize = RTC_REGISTERS_SIZE + 4;
+
+/// # Save formats
+/// How other emulators and flash carts lay out battery saves. They all start with the RAM as the
+/// game sees it, some adding the MBC3 clock after it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// Only the RAM, like gbeed, SameBoy and flash carts save it
+    Raw,
+    /// Only the RAM, like RetroArch cores save it, which keep the clock in a separate `.rtc`
+    Srm,
+    /// RAM followed by the 48 byte clock of VBA-M, BGB and mGBA
+    Rtc,
+    /// RAM followed by the 44 byte clock of older VBA-M versions, with a 32 bit timestamp
+    Rtc32,
+}
+
+impl SaveFormat {
+    pub const ALL: [SaveFormat; 4] = [
+        SaveFormat::Raw,
+        SaveFormat::Srm,
+        SaveFormat::Rtc,
+        SaveFormat::Rtc32,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SaveFormat::Raw => "raw",
+            SaveFormat::Srm => "srm",
+            SaveFormat::Rtc => "rtc",
+            SaveFormat::Rtc32 => "rtc32",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Emulators using this format, to tell users which one to pick
+    pub fn description(self) -> &'static str {
+        match self {
+            SaveFormat::Raw => "gbeed, SameBoy, flash carts",
+            SaveFormat::Srm => "RetroArch",
+            SaveFormat::Rtc => "VBA-M, BGB, mGBA",
+            SaveFormat::Rtc32 => "old VBA-M",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SaveFormat::Srm => "srm",
+            _ => "sav",
+        }
+    }
+
+    /// Whether the MBC3 clock is kept after the RAM
+    pub fn has_rtc(self) -> bool { self.footer_size() > 0 }
+
+    fn footer_size(self) -> usize {
+        match self {
+            SaveFormat::Raw | SaveFormat::Srm => 0,
+            SaveFormat::Rtc => RTC_FOOTER_SIZE,
+            SaveFormat::Rtc32 => RTC_FOOTER_SIZE_32,
+        }
+    }
+}
+
+/// MBC3 clock registers as VBA-M and BGB save them, along with when they were saved
+/// so the time passed since can be added when loading them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RtcFooter {
+    /// Seconds, minutes, hours, lower day counter and upper day counter
+    pub registers: [u8; 5],
+    pub latched: [u8; 5],
+    /// Unix time in seconds
+    pub timestamp: u64,
+}
+
+impl RtcFooter {
+    /// Size of the footer in the `Rtc` format, the layout RetroArch keeps `.rtc` files in too
+    pub const SIZE: usize = RTC_FOOTER_SIZE;
+
+    /// Read a footer laid out like the `Rtc` or `Rtc32` formats do
+    pub fn from_bytes(footer: &[u8]) -> SaveResult<Self> {
+        match footer.len() {
+            RTC_FOOTER_SIZE | RTC_FOOTER_SIZE_32 => Ok(Self::read(footer)),
+            size => Err(SaveError::UnknownSize(size)),
+        }
+    }
+
+    /// Lay out the footer like `format` does after the RAM, empty for formats without a clock
+    pub fn to_bytes(&self, format: SaveFormat) -> Vec<u8> {
+        let mut footer = Vec::with_capacity(format.footer_size());
+        if format.has_rtc() {
+            self.write(format, &mut footer);
+        }
+        footer
+    }
+
+    fn read(footer: &[u8]) -> Self {
+        let register = |i: usize| footer[i * 4];
+        let mut timestamp = [0; 8];
+        let len = footer.len() - RTC_REGISTERS_SIZE;
+        timestamp[..len].copy_from_slice(&footer[RTC_REGISTERS_SIZE..]);
+
+        Self {
+            registers: core::array::from_fn(register),
+            latched: core::array::from_fn(|i| register(i + 5)),
+            timestamp: u64::from_le_bytes(timestamp),
+        }
+    }
+
+    fn write(&self, format: SaveFormat, save: &mut Vec<u8>) {
+        for register in self.registers.iter().chain(&self.latched) {
+            save.extend_from_slice(&(*register as u32).to_le_bytes());
+        }
+        match format {
+            SaveFormat::Rtc32 => save.extend_from_slice(&(self.timestamp as u32).to_le_bytes()),
+            _ => save.extend_from_slice(&self.timestamp.to_le_bytes()),
+        }
+    }
+}
+
+/// A battery save with the layout of its format removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveFile {
+    /// RAM like `Cartridge::save_game` returns it, MBC2 half bytes in the lower half of every byte
+    pub ram: Vec<u8>,
+    pub rtc: Option<RtcFooter>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The file size is no RAM size with or without a clock, or doesn't fit the cartridge's RAM
+    UnknownSize(usize),
+}
+
+impl core::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SaveError::UnknownSize(size) => write!(f, "Unknown save layout of {size} bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SaveError {}
+
+pub type SaveResult<T> = core::result::Result<T, SaveError>;
+
+/// # Save import
+/// Read a save in any of the `SaveFormat`s, told apart by their size. `ram_size` is the RAM of the
+/// cartridge the save is for, when known, which otherwise is guessed from the cartridge RAM sizes.
+/// MBC2 saves get the upper half of their bytes cleared, as other emulators fill it with ones or garbage
+pub fn import_save(data: &[u8], ram_size: Option<usize>) -> SaveResult<SaveFile> {
+    if data.is_empty() {
+        return Err(SaveError::UnknownSize(0));
+    }
+    let fits = |ram: usize| match ram_size {
+        Some(size) => ram == size,
+        None => RAM_SIZES.contains(&ram),
+    };
+    let footer = [0, RTC_FOOTER_SIZE, RTC_FOOTER_SIZE_32]
+        .into_iter()
+        .find(|&footer| data.len().checked_sub(footer).is_some_and(fits))
+        .ok_or(SaveError::UnknownSize(data.len()))?;
+
+    let (ram, rtc) = data.split_at(data.len() - footer);
+    let mut ram = ram.to_vec();
+    if ram.len() == MBC2_RAM_SIZE {
+        ram.iter_mut().for_each(|nibble| *nibble &= 0x0F);
+    }
+
+    Ok(SaveFile {
+        ram,
+        rtc: (footer > 0).then(|| RtcFooter::read(rtc)),
+    })
+}
+
+/// # Save export
+/// Lay out `save` in `format`, formats with a clock taking the one of the save or, if it has none,
+/// a stopped one at zero saved at `timestamp`
+pub fn export_save(save: &SaveFile, format: SaveFormat, timestamp: u64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(save.ram.len() + format.footer_size());
+    data.extend_from_slice(&save.ram);
+
+    if format.has_rtc() {
+        let rtc = save.rtc.unwrap_or(RtcFooter {
+            timestamp,
+            ..Default::default()
+        });
+        rtc.write(format, &mut data);
+    }
+    data
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rtc() -> RtcFooter {
+        RtcFooter {
+            registers: [30, 15, 12, 0x2A, 0x01],
+            latched: [29, 15, 12, 0x2A, 0x01],
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_raw_round_trip() {
+        let save = SaveFile {
+            ram: (0..8 * 1024).map(|i| i as u8).collect(),
+            rtc: None,
+        };
+
+        for format in [SaveFormat::Raw, SaveFormat::Srm] {
+            let data = export_save(&save, format, 0);
+            assert_eq!(data, save.ram);
+            assert_eq!(import_save(&data, None), Ok(save.clone()));
+        }
+    }
+
+    #[test]
+    fn test_rtc_round_trip() {
+        let save = SaveFile {
+            ram: vec![0x55; 32 * 1024],
+            rtc: Some(rtc()),
+        };
+
+        let data = export_save(&save, SaveFormat::Rtc, 0);
+        assert_eq!(data.len(), 32 * 1024 + 48);
+        assert_eq!(&data[32 * 1024..32 * 1024 + 4], &[30, 0, 0, 0]);
+        assert_eq!(import_save(&data, None), Ok(save.clone()));
+        assert_eq!(import_save(&data, Some(32 * 1024)), Ok(save.clone()));
+
+        let data = export_save(&save, SaveFormat::Rtc32, 0);
+        assert_eq!(data.len(), 32 * 1024 + 44);
+        assert_eq!(import_save(&data, None), Ok(save));
+    }
+
+    #[test]
+    fn test_rtc_footer_bytes() {
+        let footer = rtc().to_bytes(SaveFormat::Rtc);
+        assert_eq!(footer.len(), RtcFooter::SIZE);
+        assert_eq!(RtcFooter::from_bytes(&footer), Ok(rtc()));
+        assert_eq!(
+            RtcFooter::from_bytes(&rtc().to_bytes(SaveFormat::Rtc32)),
+            Ok(rtc())
+        );
+        assert!(rtc().to_bytes(SaveFormat::Raw).is_empty());
+        assert_eq!(RtcFooter::from_bytes(&[0; 8]), Err(SaveError::UnknownSize(8)));
+    }
+
+    #[test]
+    fn test_rtc_dropped_and_added() {
+        let data = export_save(
+            &SaveFile {
+                ram: vec![1; 8 * 1024],
+                rtc: Some(rtc()),
+            },
+            SaveFormat::Raw,
+            0,
+        );
+        assert_eq!(data.len(), 8 * 1024);
+
+        let save = import_save(&data, None).unwrap();
+        let data = export_save(&save, SaveFormat::Rtc, 1234);
+        let rtc = import_save(&data, None).unwrap().rtc.unwrap();
+        assert_eq!(rtc.registers, [0; 5]);
+        assert_eq!(rtc.timestamp, 1234);
+    }
+
+    #[test]
+    fn test_rtc_only() {
+        let save = SaveFile {
+            ram: Vec::new(),
+            rtc: Some(rtc()),
+        };
+
+        let data = export_save(&save, SaveFormat::Rtc, 0);
+        assert_eq!(data.len(), RtcFooter::SIZE);
+        assert_eq!(import_save(&data, None), Ok(save.clone()));
+        assert_eq!(import_save(&data, Some(0)), Ok(save.clone()));
+
+        let data = export_save(&save, SaveFormat::Rtc32, 0);
+        assert_eq!(import_save(&data, None), Ok(save));
+        assert_eq!(import_save(&[], None), Err(SaveError::UnknownSize(0)));
+    }
+
+    #[test]
+    fn test_mbc2_nibbles() {
+        let data: Vec<u8> = (0..512).map(|i| 0xF0 | (i % 16) as u8).collect();
+        let save = import_save(&data, None).unwrap();
+        assert!(save.ram.iter().enumerate().all(|(i, &b)| b == (i % 16) as u8));
+    }
+
+    #[test]
+    fn test_unknown_size() {
+        assert_eq!(import_save(&[0; 1000], None), Err(SaveError::UnknownSize(1000)));
+        assert_eq!(
+            import_save(&[0; 8 * 1024], Some(32 * 1024)),
+            Err(SaveError::UnknownSize(8 * 1024))
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::prelude::*;
+use crate::{prelude::*, state::impl_state};
 
 mem_range!(SERIAL_REGISTER, SB, SC);
 
@@ -39,6 +39,9 @@ pub struct Serial {
     pub pending_data: Vec<u8>,
 }
 
+// bytes pending for the listener were already sent over the link
+impl_state!(Serial { sb, sc });
+
 impl core::fmt::Debug for Serial {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Serial {{ sb: {:#04X}, sc: {:#04X} }}", self.sb, self.sc)
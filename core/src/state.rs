@@ -0,0 +1,432 @@
+use crate::prelude::*;
+
+/// Written first in every state, so random files are told apart from states
+const STATE_MAGIC: [u8; 4] = *b"GBST";
+/// Bumped whenever a component saves its fields differently, as older states would load garbage
+const STATE_VERSION: u8 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The data doesn't start like a gbeed state
+    NotAState,
+    /// Saved by a gbeed version with a different layout
+    UnsupportedVersion(u8),
+    /// Saved while running a game with other header checksums
+    OtherGame,
+    /// The data ends before every component was loaded, or goes on after them
+    InvalidSize(usize, usize),
+    /// Saved while running the boot ROM, which isn't loaded now
+    MissingBootRom,
+    /// A component has a value it can't get to while running, like a line past the last one
+    InvalidValue(&'static str),
+}
+
+impl core::fmt::Display for StateError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StateError::NotAState => write!(f, "Not a gbeed save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "Save state version {version} is not supported, expected {STATE_VERSION}"
+                )
+            }
+            StateError::OtherGame => write!(f, "Save state belongs to another game"),
+            StateError::InvalidSize(size, expected) => {
+                write!(f, "Save state has {size} bytes, expected {expected}")
+            }
+            StateError::MissingBootRom => write!(f, "Save state was made while running the boot ROM"),
+            StateError::InvalidValue(field) => write!(f, "Save state has an invalid {field}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+pub type StateResult<T> = core::result::Result<T, StateError>;
+
+/// Bytes of a state being saved, every value little endian
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn write(&mut self, bytes: &[u8]) { self.data.extend_from_slice(bytes); }
+
+    pub fn into_inner(self) -> Vec<u8> { self.data }
+}
+
+/// Bytes of a state being loaded, read in the same order `StateWriter` got them
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self { Self { data, position: 0 } }
+
+    pub fn read<const N: usize>(&mut self) -> StateResult<[u8; N]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + N)
+            .ok_or(StateError::InvalidSize(self.data.len(), self.position + N))?;
+        self.position += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    pub fn read_into(&mut self, bytes: &mut [u8]) -> StateResult<()> {
+        let end = self.position + bytes.len();
+        let data = self
+            .data
+            .get(self.position..end)
+            .ok_or(StateError::InvalidSize(self.data.len(), end))?;
+        bytes.copy_from_slice(data);
+        self.position = end;
+        Ok(())
+    }
+}
+
+/// # Save states
+/// Components saving the fields that change while a game runs, loading them back in the same order.
+/// Data fixed by the cartridge or the frontend, like the ROM or the audio sample rate, is left out
+pub trait State {
+    fn save(&self, state: &mut StateWriter);
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()>;
+}
+
+/// Implement `State` for a struct by saving `fields` in order, optionally calling `after_load`
+/// once they are all loaded, after `validate` rejects values the struct can't have while running.
+/// Invoked next to the struct so private fields can be listed
+macro_rules! impl_state {
+    ($ty:ty { $($field:ident),* $(,)? } $(, after_load: $after_load:ident)? $(, validate: $validate:ident)?) => {
+        impl $crate::state::State for $ty {
+            fn save(&self, state: &mut $crate::state::StateWriter) {
+                $($crate::state::State::save(&self.$field, state);)*
+            }
+
+            fn load(&mut self, state: &mut $crate::state::StateReader) -> $crate::state::StateResult<()> {
+                $($crate::state::State::load(&mut self.$field, state)?;)*
+                $(self.$validate()?;)?
+                $(self.$after_load();)?
+                Ok(())
+            }
+        }
+    };
+}
+
+pub(crate) use impl_state;
+
+macro_rules! impl_state_number {
+    ($($ty:ty),*) => {
+        $(
+            impl State for $ty {
+                fn save(&self, state: &mut StateWriter) { state.write(&self.to_le_bytes()); }
+
+                fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+                    *self = <$ty>::from_le_bytes(state.read()?);
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+impl_state_number!(u8, u16, u32, u64, i16, i32);
+
+/// Saved as 64 bits so states move between 32 and 64 bit machines
+impl State for usize {
+    fn save(&self, state: &mut StateWriter) { (*self as u64).save(state); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        *self = u64::from_le_bytes(state.read()?) as usize;
+        Ok(())
+    }
+}
+
+impl State for bool {
+    fn save(&self, state: &mut StateWriter) { (*self as u8).save(state); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        *self = state.read::<1>()?[0] != 0;
+        Ok(())
+    }
+}
+
+impl<T: State, const N: usize> State for [T; N] {
+    fn save(&self, state: &mut StateWriter) { self.iter().for_each(|value| value.save(state)); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        self.iter_mut().try_for_each(|value| value.load(state))
+    }
+}
+
+/// The length is fixed by the component, like the RAM size by the cartridge, so it isn't saved
+impl State for [u8] {
+    fn save(&self, state: &mut StateWriter) { state.write(self); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> { state.read_into(self) }
+}
+
+impl<T: State + ?Sized> State for Box<T> {
+    fn save(&self, state: &mut StateWriter) { self.as_ref().save(state); }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> { self.as_mut().load(state) }
+}
+
+/// Optional components, like the cartridge RAM, exist or not depending on the cartridge,
+/// so a state for the same game always has the same ones
+impl<T: State> State for Option<T> {
+    fn save(&self, state: &mut StateWriter) {
+        if let Some(value) = self {
+            value.save(state);
+        }
+    }
+
+    fn load(&mut self, state: &mut StateReader) -> StateResult<()> {
+        match self {
+            Some(value) => value.load(state),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether the boot ROM is mapped over the cartridge ROM, which is swapped into it meanwhile
+fn running_boot_rom(dmg: &Dmg) -> bool { dmg.bank == 0 && dmg.memory.boot_rom.is_some() }
+
+/// Saves `dmg` behind a header telling the state version and the game it belongs to
+pub(crate) fn save_dmg(dmg: &Dmg) -> Vec<u8> {
+    let mut state = StateWriter::new();
+    state.write(&STATE_MAGIC);
+    STATE_VERSION.save(&mut state);
+    dmg.cartridge.header.header_checksum.save(&mut state);
+    dmg.cartridge.header.global_checksum.save(&mut state);
+    running_boot_rom(dmg).save(&mut state);
+
+    dmg.cpu.save(&mut state);
+    dmg.cartridge.save(&mut state);
+    dmg.memory.save(&mut state);
+    dmg.ppu.save(&mut state);
+    dmg.joypad.save(&mut state);
+    dmg.serial.save(&mut state);
+    dmg.timer.save(&mut state);
+    dmg.apu.save(&mut state);
+    dmg.interrupt_flag.0.save(&mut state);
+    dmg.interrupt_enable.0.save(&mut state);
+    dmg.bank.save(&mut state);
+    state.into_inner()
+}
+
+/// Checks the header written by `save_dmg` and the size of the whole state before loading
+/// anything, and restores the previous state when a component rejects a loaded value, so a state
+/// that can't be loaded leaves `dmg` untouched
+pub(crate) fn load_dmg(dmg: &mut Dmg, data: &[u8]) -> StateResult<()> {
+    let mut state = StateReader::new(data);
+    let booting = read_header(dmg, &mut state)?;
+
+    let previous = save_dmg(dmg);
+    if data.len() != previous.len() {
+        return Err(StateError::InvalidSize(data.len(), previous.len()));
+    }
+
+    // the bank register is loaded last, so it still tells whether the boot ROM is mapped if loading fails
+    let swapped = dmg.memory.boot_rom.is_some() && dmg.bank == 0;
+    if let Err(e) = load_components(dmg, &mut state) {
+        let mut previous = StateReader::new(&previous);
+        read_header(dmg, &mut previous)?;
+        load_components(dmg, &mut previous)?;
+        return Err(e);
+    }
+
+    // the cartridge ROM goes back to its place, and the boot ROM over it when booting
+    if let Some(boot) = dmg.memory.boot_rom.as_mut().filter(|_| swapped) {
+        dmg.cartridge.swap_boot_rom(boot);
+    }
+    match booting {
+        true => {
+            if let Some(boot) = &mut dmg.memory.boot_rom {
+                dmg.cartridge.swap_boot_rom(boot);
+            }
+        }
+        // saved after booting, or without a boot ROM, where the bank register may still be 0
+        false => dmg.memory.boot_rom = None,
+    }
+    Ok(())
+}
+
+/// Whether the state was saved while booting, after checking it's one of this game
+fn read_header(dmg: &Dmg, state: &mut StateReader) -> StateResult<bool> {
+    if state.read::<4>().ok() != Some(STATE_MAGIC) {
+        return Err(StateError::NotAState);
+    }
+
+    let version = state.read::<1>()?[0];
+    if version != STATE_VERSION {
+        return Err(StateError::UnsupportedVersion(version));
+    }
+
+    let (mut header_checksum, mut global_checksum, mut booting) = (0u8, 0u16, false);
+    header_checksum.load(state)?;
+    global_checksum.load(state)?;
+    if header_checksum != dmg.cartridge.header.header_checksum
+        || global_checksum != dmg.cartridge.header.global_checksum
+    {
+        return Err(StateError::OtherGame);
+    }
+
+    booting.load(state)?;
+    if booting && dmg.memory.boot_rom.is_none() {
+        return Err(StateError::MissingBootRom);
+    }
+    Ok(booting)
+}
+
+fn load_components(dmg: &mut Dmg, state: &mut StateReader) -> StateResult<()> {
+    dmg.cpu.load(state)?;
+    dmg.cartridge.load(state)?;
+    dmg.memory.load(state)?;
+    dmg.ppu.load(state)?;
+    dmg.joypad.load(state)?;
+    dmg.serial.load(state)?;
+    dmg.timer.load(state)?;
+    dmg.apu.load(state)?;
+    dmg.interrupt_flag.0.load(state)?;
+    dmg.interrupt_enable.0.load(state)?;
+    dmg.bank.load(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DefaultController, OAM_SIZE, VRAM_SIZE};
+
+    /// MBC1 game with battery backed RAM counting up a byte of it and one of WRAM forever
+    fn counting_game(global_checksum: u16) -> Dmg {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        rom[0x014E..=0x014F].copy_from_slice(&global_checksum.to_be_bytes());
+        #[rustfmt::skip]
+        let program = [
+            0x3E, 0x0A,       // ld a, $0A
+            0xEA, 0x00, 0x00, // ld [$0000], a
+            0x21, 0x00, 0xA0, // ld hl, $A000
+            0x34,             // inc [hl]
+            0x21, 0x00, 0xC0, // ld hl, $C000
+            0x34,             // inc [hl]
+            0x18, 0xF6,       // jr -10
+        ];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+
+        Dmg::new(Cartridge::new(&rom, None).unwrap(), None)
+    }
+
+    fn run_frames(gb: &mut Dmg, frames: usize) {
+        let mut controller = DefaultController::new();
+        for _ in 0..frames {
+            gb.run(&mut controller).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut gb = counting_game(0);
+        run_frames(&mut gb, 5);
+        let saved = gb.save_state();
+
+        run_frames(&mut gb, 5);
+        let expected = gb.save_state();
+        assert_ne!(saved, expected);
+
+        gb.load_state(&saved).unwrap();
+        assert_eq!(gb.save_state(), saved);
+        assert!(gb.cartridge.save_changed(), "loaded RAM has to be saved again");
+
+        run_frames(&mut gb, 5);
+        assert_eq!(gb.save_state(), expected);
+    }
+
+    #[test]
+    fn test_state_rejected() {
+        let mut gb = counting_game(0);
+        run_frames(&mut gb, 1);
+        let saved = gb.save_state();
+
+        let mut other = counting_game(0x1234);
+        assert_eq!(other.load_state(&saved), Err(StateError::OtherGame));
+
+        run_frames(&mut gb, 1);
+        let current = gb.save_state();
+        assert_eq!(gb.load_state(&[0; 16]), Err(StateError::NotAState));
+        assert_eq!(
+            gb.load_state(&saved[..saved.len() - 1]),
+            Err(StateError::InvalidSize(saved.len() - 1, saved.len()))
+        );
+
+        let mut newer = saved.clone();
+        newer[STATE_MAGIC.len()] = STATE_VERSION + 1;
+        assert_eq!(
+            gb.load_state(&newer),
+            Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+        );
+        assert_eq!(
+            gb.save_state(),
+            current,
+            "rejected states leave the game untouched"
+        );
+    }
+
+    /// Offset of the saved PPU in `state`, found by its bytes as they are saved on their own
+    fn ppu_offset(gb: &Dmg, state: &[u8]) -> usize {
+        let mut ppu = StateWriter::new();
+        gb.ppu.save(&mut ppu);
+        let ppu = ppu.into_inner();
+        state.windows(ppu.len()).position(|window| window == ppu).unwrap()
+    }
+
+    #[test]
+    fn test_invalid_values_rejected() {
+        let mut gb = counting_game(0);
+        run_frames(&mut gb, 3);
+        let saved = gb.save_state();
+        let ppu = ppu_offset(&gb, &saved);
+
+        // LY after the dots, frames, VRAM, OAM, background cache, sprite count and 4 registers
+        let ly = ppu + 8 + 8 + VRAM_SIZE as usize + OAM_SIZE as usize + DMG_SCREEN_WIDTH + 8 + 4;
+        let mut corrupted = saved.clone();
+        corrupted[ly] = 255;
+        assert_eq!(
+            gb.load_state(&corrupted),
+            Err(StateError::InvalidValue("PPU line"))
+        );
+
+        let mut corrupted = saved.clone();
+        corrupted[ppu..ppu + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            gb.load_state(&corrupted),
+            Err(StateError::InvalidValue("PPU dot count"))
+        );
+
+        // the MBC1 banking mode comes first, then the ROM bank
+        let mut cpu = StateWriter::new();
+        gb.cpu.save(&mut cpu);
+        let mbc = STATE_MAGIC.len() + 5 + cpu.into_inner().len();
+        let mut corrupted = saved.clone();
+        corrupted[mbc + 1] = 0xFF;
+        assert_eq!(
+            gb.load_state(&corrupted),
+            Err(StateError::InvalidValue("MBC1 bank"))
+        );
+
+        assert_eq!(
+            gb.save_state(),
+            saved,
+            "states with invalid values leave the game untouched"
+        );
+        run_frames(&mut gb, 1);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{interrupts::Interrupt, prelude::*};
+use crate::{interrupts::Interrupt, prelude::*, state::impl_state};
 
 pub const DIV: u16 = 0xFF04;
 pub const TIMA: u16 = 0xFF05;
@@ -24,6 +24,16 @@ pub struct Timer {
     overflow_delay: i32,
 }
 
+impl_state!(Timer {
+    internal_counter,
+    tima,
+    tma,
+    tac,
+    previous_bit,
+    overflow_pending,
+    overflow_delay,
+});
+
 impl Timer {
     pub fn new() -> Self {
         Timer {
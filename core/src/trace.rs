@@ -0,0 +1,222 @@
+use alloc::collections::VecDeque;
+use core::fmt::Write as _;
+
+use crate::{dmg::Dmg, prelude::*};
+
+/// LY value the Gameboy Doctor reference logs were recorded with, see `Ppu::ly_override`
+pub const DOCTOR_LY: u8 = 0x90;
+
+/// Layout of every logged line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Gameboy Doctor compatible, can be diffed against its reference logs
+    #[default]
+    Doctor,
+    /// Doctor line followed by the disassembly and the M-cycles taken by the instruction
+    Extended,
+}
+
+/// Where the trace lines end up
+#[derive(Debug)]
+pub enum TraceOutput {
+    /// Keeps only the last `capacity` lines, useful to see what led to a crash
+    RingBuffer {
+        lines: VecDeque<String>,
+        capacity: usize,
+    },
+    #[cfg(feature = "std")]
+    File(std::io::BufWriter<std::fs::File>),
+}
+
+/// CPU state right before an instruction is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceState {
+    pub a: u8,
+    pub f: u8,
+    pub b: u8,
+    pub c: u8,
+    pub d: u8,
+    pub e: u8,
+    pub h: u8,
+    pub l: u8,
+    pub sp: u16,
+    pub pc: u16,
+    pub pcmem: [u8; 4],
+}
+
+impl TraceState {
+    pub fn capture(gb: &Dmg) -> Self {
+        let cpu = &gb.cpu;
+        let pc = cpu.pc;
+
+        Self {
+            a: cpu.a,
+            f: cpu.f,
+            b: cpu.b,
+            c: cpu.c,
+            d: cpu.d,
+            e: cpu.e,
+            h: cpu.h,
+            l: cpu.l,
+            sp: cpu.sp,
+            pc,
+            pcmem: [0, 1, 2, 3].map(|i| gb.read(pc.wrapping_add(i))),
+        }
+    }
+}
+
+impl core::fmt::Display for TraceState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+            self.a,
+            self.f,
+            self.b,
+            self.c,
+            self.d,
+            self.e,
+            self.h,
+            self.l,
+            self.sp,
+            self.pc,
+            self.pcmem[0],
+            self.pcmem[1],
+            self.pcmem[2],
+            self.pcmem[3],
+        )
+    }
+}
+
+/// # Tracer
+/// Opt-in log of every executed instruction, enabled by setting `Dmg::tracer`.
+/// Interrupt dispatches and halted cycles don't execute instructions, so they aren't logged
+#[derive(Debug)]
+pub struct Tracer {
+    pub format: TraceFormat,
+    output: TraceOutput,
+}
+
+impl Tracer {
+    pub fn ring_buffer(format: TraceFormat, capacity: usize) -> Self {
+        Self {
+            format,
+            output: TraceOutput::RingBuffer {
+                lines: VecDeque::with_capacity(capacity),
+                capacity,
+            },
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn file<P: AsRef<std::path::Path>>(format: TraceFormat, path: P) -> std::io::Result<Self> {
+        let file = std::fs::File::create(path)?;
+
+        Ok(Self {
+            format,
+            output: TraceOutput::File(std::io::BufWriter::new(file)),
+        })
+    }
+
+    pub fn output(&self) -> &TraceOutput { &self.output }
+
+    /// Lines kept in memory, oldest first. Always empty when logging to a file
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        let lines = match &self.output {
+            TraceOutput::RingBuffer { lines, .. } => Some(lines.iter().map(String::as_str)),
+            #[cfg(feature = "std")]
+            TraceOutput::File(_) => None,
+        };
+        lines.into_iter().flatten()
+    }
+
+    pub fn clear(&mut self) {
+        match &mut self.output {
+            TraceOutput::RingBuffer { lines, .. } => lines.clear(),
+            #[cfg(feature = "std")]
+            TraceOutput::File(_) => {}
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.output {
+            TraceOutput::File(writer) => std::io::Write::flush(writer),
+            TraceOutput::RingBuffer { .. } => Ok(()),
+        }
+    }
+
+    pub fn log(&mut self, state: &TraceState, instruction: &InstructionBox, cycles: usize) {
+        let mut line = String::with_capacity(96);
+        let _ = write!(line, "{state}");
+        if self.format == TraceFormat::Extended {
+            let _ = write!(line, " | {:<16} | {cycles}", instruction.disassembly());
+        }
+
+        match &mut self.output {
+            TraceOutput::RingBuffer { lines, capacity } => {
+                if *capacity == 0 {
+                    return;
+                }
+                if lines.len() >= *capacity {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+            // tracing must not stop emulation, a failing disk only loses lines
+            #[cfg(feature = "std")]
+            TraceOutput::File(writer) => {
+                let _ = std::io::Write::write_all(writer, line.as_bytes());
+                let _ = std::io::Write::write_all(writer, b"\n");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::DefaultController;
+
+    #[test]
+    fn test_doctor_line_format() {
+        let mut gb = Dmg::default();
+        gb.cpu.pc = 0xC000;
+        gb.cpu.sp = 0xFFFE;
+        gb.write(0xC000, 0x00);
+        gb.write(0xC001, 0xC3);
+        gb.write(0xC002, 0x13);
+        gb.write(0xC003, 0x02);
+
+        let state = TraceState::capture(&gb);
+
+        assert_eq!(
+            state.to_string(),
+            format!(
+                "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:FFFE PC:C000 PCMEM:00,C3,13,02",
+                gb.cpu.a, gb.cpu.f, gb.cpu.b, gb.cpu.c, gb.cpu.d, gb.cpu.e, gb.cpu.h, gb.cpu.l
+            )
+        );
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_last_lines() {
+        let mut gb = Dmg::default();
+        let mut controller = DefaultController::new();
+        gb.cpu.pc = 0xC000;
+        for address in 0xC000..0xC004 {
+            gb.write(address, 0x00);
+        }
+        gb.tracer = Some(Tracer::ring_buffer(TraceFormat::Extended, 2));
+
+        for _ in 0..3 {
+            gb.step(&mut controller).unwrap();
+        }
+
+        let lines: Vec<&str> = gb.tracer.as_ref().unwrap().lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("PC:C001"));
+        assert!(lines[1].contains("PC:C002"));
+        assert!(lines[1].ends_with("| nop              | 1"));
+    }
+}
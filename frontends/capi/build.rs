@@ -0,0 +1,32 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Set to also write the header over the committed `include/gbeed.h`
+const UPDATE_HEADER: &str = "GBEED_UPDATE_HEADER";
+
+/// Generates the header into `OUT_DIR`, where a test compares it with the committed one,
+/// so building never writes into the source tree unless asked to
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("invalid cbindgen.toml");
+
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed={UPDATE_HEADER}");
+
+    let header = out_dir.join("gbeed.h");
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(&header);
+            if env::var_os(UPDATE_HEADER).is_some() {
+                bindings.write_to_file(crate_dir.join("include").join("gbeed.h"));
+            }
+        }
+        // the committed header is still usable, the test comparing them fails instead of the build
+        Err(e) => {
+            println!("cargo:warning=failed to generate gbeed.h: {e}");
+            std::fs::write(&header, "").expect("failed to write an empty gbeed.h");
+        }
+    }
+}
@@ -0,0 +1,205 @@
+/* gbeed C API, generated by cbindgen from frontends/capi, don't edit it by hand */
+
+#ifndef GBEED_H
+#define GBEED_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define GBEED_SCREEN_WIDTH 160
+
+#define GBEED_SCREEN_HEIGHT 144
+
+/**
+ * Why the last failing call failed
+ */
+typedef enum {
+  GBEED_ERROR_NONE,
+  /**
+   * `gbeed_new` couldn't load the ROM or the save
+   */
+  GBEED_ERROR_INVALID_ROM,
+  /**
+   * `gbeed_run_frame` found an instruction the CPU doesn't have
+   */
+  GBEED_ERROR_INVALID_INSTRUCTION,
+} GbeedError;
+
+/**
+ * Layer a pixel was drawn from, so frontends can color sprites differently
+ */
+typedef enum {
+  GBEED_LAYER_BACKGROUND,
+  GBEED_LAYER_OBJECT0,
+  GBEED_LAYER_OBJECT1,
+} GbeedLayer;
+
+typedef enum {
+  GBEED_BUTTON_RIGHT,
+  GBEED_BUTTON_LEFT,
+  GBEED_BUTTON_UP,
+  GBEED_BUTTON_DOWN,
+  GBEED_BUTTON_A,
+  GBEED_BUTTON_B,
+  GBEED_BUTTON_SELECT,
+  GBEED_BUTTON_START,
+} GbeedButton;
+
+/**
+ * A Game Boy running a game, only handled through pointers from C
+ */
+typedef struct GbeedMachine GbeedMachine;
+
+/**
+ * # Callbacks
+ * The `Controller` of the machine, every one of them can be null.
+ * They're called from inside `gbeed_run_frame`, on its thread
+ */
+typedef struct {
+  /**
+   * Handed back to every callback
+   */
+  void *user_data;
+  /**
+   * Every pixel the PPU draws, with its shade from 0 (lightest) to 3 (darkest)
+   */
+  void (*write_pixel)(void *user_data, uint8_t x, uint8_t y, GbeedLayer layer, uint8_t shade);
+  /**
+   * The PPU entered VBlank, `framebuffer` being the same as `gbeed_framebuffer`
+   */
+  void (*frame_ready)(void *user_data, const uint8_t *framebuffer);
+  /**
+   * Every stereo sample, which are buffered for `gbeed_drain_audio` instead when it's null
+   */
+  void (*push_sample)(void *user_data, int16_t left, int16_t right);
+  /**
+   * Every byte the game sends through the link cable
+   */
+  void (*serial_transfer)(void *user_data, uint8_t data);
+} GbeedCallbacks;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Message of the last error on this thread, or null when there wasn't any.
+ * It stays valid until the next failing call on the same thread. Without std, it's a fixed
+ * message for `gbeed_last_error_code`
+ */
+const char *gbeed_last_error(void);
+
+/**
+ * Code of the last error on this thread, `GBEED_ERROR_NONE` when there wasn't any.
+ * Without std, it's shared by every thread
+ */
+GbeedError gbeed_last_error_code(void);
+
+/**
+ * Create a machine running `rom`, with the battery backed RAM loaded from `save` when it isn't null.
+ * Both buffers are copied, `callbacks` can be null to only use the framebuffer and the audio buffer.
+ * Returns null when the game can't be loaded, see `gbeed_last_error`
+ *
+ * # Safety
+ * `rom` has to point to `rom_len` readable bytes, `save` to `save_len` ones if it isn't null,
+ * and `callbacks` has to be null or point to a `GbeedCallbacks`
+ */
+GbeedMachine *gbeed_new(const uint8_t *rom,
+                        size_t rom_len,
+                        const uint8_t *save,
+                        size_t save_len,
+                        const GbeedCallbacks *callbacks);
+
+/**
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`, and isn't valid anymore after this
+ */
+void gbeed_free(GbeedMachine *machine);
+
+/**
+ * Run a whole frame, calling the callbacks while it runs.
+ * Returns false when the CPU found an invalid instruction, see `gbeed_last_error`
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+bool gbeed_run_frame(GbeedMachine *machine);
+
+/**
+ * Shades of the last frame from 0 (lightest) to 3 (darkest), `GBEED_SCREEN_WIDTH` *
+ * `GBEED_SCREEN_HEIGHT` bytes one row after another. The pointer stays the same for the
+ * whole life of the machine
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+const uint8_t *gbeed_framebuffer(const GbeedMachine *machine);
+
+/**
+ * Press or release `button`, one of the `GbeedButton` values.
+ * Returns false when it's none of them, or `machine` is null
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+bool gbeed_set_button(GbeedMachine *machine, uint32_t button, bool pressed);
+
+/**
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+uint32_t gbeed_sample_rate(const GbeedMachine *machine);
+
+/**
+ * Samples per second of each channel, 44100 by default
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+void gbeed_set_sample_rate(GbeedMachine *machine, uint32_t sample_rate);
+
+/**
+ * Move up to `frames` interleaved left and right samples into `out`, oldest first, returning
+ * how many were moved. Only samples not handed to a `push_sample` callback are kept,
+ * up to about a second of them
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`, and `out` has to point to `frames` * 2
+ * writable samples
+ */
+size_t gbeed_drain_audio(GbeedMachine *machine, int16_t *out, size_t frames);
+
+/**
+ * Size of the battery backed RAM, 0 when the cartridge has no battery
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+size_t gbeed_save_ram_size(const GbeedMachine *machine);
+
+/**
+ * Whether the game wrote the battery backed RAM since it was last copied by `gbeed_save_ram`
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`
+ */
+bool gbeed_save_ram_changed(const GbeedMachine *machine);
+
+/**
+ * Copy the battery backed RAM into `out`, in the same layout `gbeed_new` loads it.
+ * Returns the copied size, or 0 when there's no battery or `out` is smaller than
+ * `gbeed_save_ram_size`
+ *
+ * # Safety
+ * `machine` has to be null or come from `gbeed_new`, and `out` has to point to `len` writable bytes
+ */
+size_t gbeed_save_ram(GbeedMachine *machine, uint8_t *out, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* GBEED_H */
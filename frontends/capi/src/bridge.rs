@@ -0,0 +1,135 @@
+use alloc::collections::VecDeque;
+use core::ffi::c_void;
+use gbeed_core::prelude::*;
+use gbeed_core::{AudioPlayer, Controller, PixelLayer, SAMPLE_RATE};
+
+/// About a second of stereo samples, older ones are dropped when nobody drains them
+const MAX_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize * 2;
+
+/// Layer a pixel was drawn from, so frontends can color sprites differently
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GbeedLayer {
+    Background,
+    Object0,
+    Object1,
+}
+
+impl From<PixelLayer> for GbeedLayer {
+    fn from(layer: PixelLayer) -> Self {
+        match layer {
+            PixelLayer::Background => GbeedLayer::Background,
+            PixelLayer::Object0 => GbeedLayer::Object0,
+            PixelLayer::Object1 => GbeedLayer::Object1,
+        }
+    }
+}
+
+/// # Callbacks
+/// The `Controller` of the machine, every one of them can be null.
+/// They're called from inside `gbeed_run_frame`, on its thread
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GbeedCallbacks {
+    /// Handed back to every callback
+    pub user_data: *mut c_void,
+    /// Every pixel the PPU draws, with its shade from 0 (lightest) to 3 (darkest)
+    pub write_pixel:
+        Option<unsafe extern "C" fn(user_data: *mut c_void, x: u8, y: u8, layer: GbeedLayer, shade: u8)>,
+    /// The PPU entered VBlank, `framebuffer` being the same as `gbeed_framebuffer`
+    pub frame_ready: Option<unsafe extern "C" fn(user_data: *mut c_void, framebuffer: *const u8)>,
+    /// Every stereo sample, which are buffered for `gbeed_drain_audio` instead when it's null
+    pub push_sample: Option<unsafe extern "C" fn(user_data: *mut c_void, left: i16, right: i16)>,
+    /// Every byte the game sends through the link cable
+    pub serial_transfer: Option<unsafe extern "C" fn(user_data: *mut c_void, data: u8)>,
+}
+
+impl Default for GbeedCallbacks {
+    fn default() -> Self {
+        Self {
+            user_data: core::ptr::null_mut(),
+            write_pixel: None,
+            frame_ready: None,
+            push_sample: None,
+            serial_transfer: None,
+        }
+    }
+}
+
+/// Forwards the core to the C callbacks, keeping the shades and samples for the ones left null
+pub struct Bridge {
+    callbacks: GbeedCallbacks,
+    framebuffer: Vec<u8>,
+    samples: VecDeque<i16>,
+}
+
+impl Bridge {
+    pub fn new(callbacks: GbeedCallbacks) -> Self {
+        Self {
+            callbacks,
+            framebuffer: vec![0; DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT],
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Shades of the screen from 0 to 3, one row after another
+    pub fn framebuffer(&self) -> &[u8] { &self.framebuffer }
+
+    /// Move up to `out.len() / 2` stereo samples into `out`, returning how many were moved
+    pub fn drain_samples(&mut self, out: &mut [i16]) -> usize {
+        let len = (out.len() / 2 * 2).min(self.samples.len());
+        for (out, sample) in out.iter_mut().zip(self.samples.drain(..len)) {
+            *out = sample;
+        }
+        len / 2
+    }
+}
+
+impl Renderer for Bridge {
+    fn read_pixel(&self, x: usize, y: usize) -> u32 { self.framebuffer[y * DMG_SCREEN_WIDTH + x] as u32 }
+
+    fn write_pixel(&mut self, x: usize, y: usize, palette: u8, color_id: u8) {
+        self.write_layer_pixel(x, y, PixelLayer::Background, palette, color_id);
+    }
+
+    fn update_screen(&mut self, _: &Ppu) {
+        if let Some(frame_ready) = self.callbacks.frame_ready {
+            unsafe { frame_ready(self.callbacks.user_data, self.framebuffer.as_ptr()) };
+        }
+    }
+
+    fn write_layer_pixel(&mut self, x: usize, y: usize, layer: PixelLayer, palette: u8, color_id: u8) {
+        let shade = (palette >> (color_id * 2)) & 0x03;
+        self.framebuffer[y * DMG_SCREEN_WIDTH + x] = shade;
+
+        if let Some(write_pixel) = self.callbacks.write_pixel {
+            unsafe { write_pixel(self.callbacks.user_data, x as u8, y as u8, layer.into(), shade) };
+        }
+    }
+}
+
+impl AudioPlayer for Bridge {
+    fn playing_stereo(&self) -> bool { true }
+
+    fn push_sample(&mut self, left: i16, right: i16) {
+        if let Some(push_sample) = self.callbacks.push_sample {
+            unsafe { push_sample(self.callbacks.user_data, left, right) };
+            return;
+        }
+
+        if self.samples.len() >= MAX_BUFFERED_SAMPLES {
+            self.samples.drain(..2);
+        }
+        self.samples.extend([left, right]);
+    }
+}
+
+impl SerialListener for Bridge {
+    fn on_transfer(&mut self, data: u8) {
+        if let Some(serial_transfer) = self.callbacks.serial_transfer {
+            unsafe { serial_transfer(self.callbacks.user_data, data) };
+        }
+    }
+}
+
+impl Controller for Bridge {}
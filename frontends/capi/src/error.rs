@@ -0,0 +1,81 @@
+use core::ffi::c_char;
+use core::fmt::Display;
+
+/// Why the last failing call failed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GbeedError {
+    None,
+    /// `gbeed_new` couldn't load the ROM or the save
+    InvalidRom,
+    /// `gbeed_run_frame` found an instruction the CPU doesn't have
+    InvalidInstruction,
+}
+
+impl GbeedError {
+    const ALL: [GbeedError; 3] = [
+        GbeedError::None,
+        GbeedError::InvalidRom,
+        GbeedError::InvalidInstruction,
+    ];
+
+    fn from_raw(code: u8) -> Self { Self::ALL.get(code as usize).copied().unwrap_or(GbeedError::None) }
+}
+
+/// The message of the error and its code, kept for each thread
+#[cfg(feature = "std")]
+mod last {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::ffi::CString;
+    use std::string::ToString;
+    use std::thread_local;
+
+    thread_local! {
+        static CODE: Cell<u8> = const { Cell::new(GbeedError::None as u8) };
+        static MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+    }
+
+    pub fn set(code: GbeedError, e: impl Display) {
+        let message = CString::new(e.to_string()).unwrap_or_default();
+        CODE.with(|last| last.set(code as u8));
+        MESSAGE.with(|last| *last.borrow_mut() = Some(message));
+    }
+
+    pub fn code() -> u8 { CODE.with(Cell::get) }
+
+    pub fn message() -> *const c_char {
+        MESSAGE.with(|last| {
+            last.borrow()
+                .as_ref()
+                .map_or(core::ptr::null(), |message| message.as_ptr())
+        })
+    }
+}
+
+/// Only the code of the error, shared by the whole program, with a fixed message for it
+#[cfg(not(feature = "std"))]
+mod last {
+    use super::*;
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    static CODE: AtomicU8 = AtomicU8::new(GbeedError::None as u8);
+
+    pub fn set(code: GbeedError, _: impl Display) { CODE.store(code as u8, Ordering::Relaxed); }
+
+    pub fn code() -> u8 { CODE.load(Ordering::Relaxed) }
+
+    pub fn message() -> *const c_char {
+        match GbeedError::from_raw(code()) {
+            GbeedError::None => core::ptr::null(),
+            GbeedError::InvalidRom => c"invalid ROM or save".as_ptr(),
+            GbeedError::InvalidInstruction => c"invalid instruction".as_ptr(),
+        }
+    }
+}
+
+pub fn set_last_error(code: GbeedError, e: impl Display) { last::set(code, e); }
+
+pub fn last_error() -> GbeedError { GbeedError::from_raw(last::code()) }
+
+pub fn last_error_message() -> *const c_char { last::message() }
@@ -0,0 +1,28 @@
+//! What std gives the static library otherwise, taken from the C library the firmware links
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::ffi::c_void;
+
+unsafe extern "C" {
+    fn aligned_alloc(align: usize, size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+    fn abort() -> !;
+}
+
+struct CAllocator;
+
+unsafe impl GlobalAlloc for CAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // C wants at least the alignment of a pointer, and a size that's a multiple of it
+        let align = layout.align().max(size_of::<usize>());
+        unsafe { aligned_alloc(align, layout.size().next_multiple_of(align)) }.cast()
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _: Layout) { unsafe { free(ptr.cast()) } }
+}
+
+#[global_allocator]
+static ALLOCATOR: CAllocator = CAllocator;
+
+#[panic_handler]
+fn panic(_: &core::panic::PanicInfo) -> ! { unsafe { abort() } }
@@ -0,0 +1,382 @@
+//! # gbeed C API
+//! Embeds the core in C and C++ programs through an opaque `GbeedMachine`.
+//! `include/gbeed.h` is generated from this crate by cbindgen, and only written over when
+//! building with `GBEED_UPDATE_HEADER` set.
+//! Without the default `std` feature it builds for microcontroller firmwares, taking its memory
+//! from the C library and only keeping the code of the last error
+
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+mod bridge;
+mod error;
+#[cfg(not(any(feature = "std", test)))]
+mod firmware;
+
+pub use bridge::{GbeedCallbacks, GbeedLayer};
+pub use error::GbeedError;
+
+use bridge::Bridge;
+use core::ffi::c_char;
+use error::set_last_error;
+use gbeed_core::prelude::*;
+
+pub const GBEED_SCREEN_WIDTH: usize = 160;
+pub const GBEED_SCREEN_HEIGHT: usize = 144;
+
+const _: () = assert!(GBEED_SCREEN_WIDTH == DMG_SCREEN_WIDTH && GBEED_SCREEN_HEIGHT == DMG_SCREEN_HEIGHT);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GbeedButton {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl GbeedButton {
+    const ALL: [GbeedButton; 8] = [
+        GbeedButton::Right,
+        GbeedButton::Left,
+        GbeedButton::Up,
+        GbeedButton::Down,
+        GbeedButton::A,
+        GbeedButton::B,
+        GbeedButton::Select,
+        GbeedButton::Start,
+    ];
+
+    /// The button with the value C gave, which could be any integer
+    fn from_raw(button: u32) -> Option<Self> { Self::ALL.get(button as usize).copied() }
+}
+
+impl From<GbeedButton> for JoypadButton {
+    fn from(button: GbeedButton) -> Self {
+        match button {
+            GbeedButton::Right => JoypadButton::Right,
+            GbeedButton::Left => JoypadButton::Left,
+            GbeedButton::Up => JoypadButton::Up,
+            GbeedButton::Down => JoypadButton::Down,
+            GbeedButton::A => JoypadButton::A,
+            GbeedButton::B => JoypadButton::B,
+            GbeedButton::Select => JoypadButton::Select,
+            GbeedButton::Start => JoypadButton::Start,
+        }
+    }
+}
+
+/// A Game Boy running a game, only handled through pointers from C
+pub struct GbeedMachine {
+    gb: Dmg,
+    bridge: Bridge,
+}
+
+/// Slice from a C buffer, empty when it's null
+unsafe fn slice<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    match data.is_null() {
+        true => &[],
+        false => unsafe { core::slice::from_raw_parts(data, len) },
+    }
+}
+
+unsafe fn slice_mut<'a, T>(data: *mut T, len: usize) -> &'a mut [T] {
+    match data.is_null() {
+        true => &mut [],
+        false => unsafe { core::slice::from_raw_parts_mut(data, len) },
+    }
+}
+
+/// Message of the last error on this thread, or null when there wasn't any.
+/// It stays valid until the next failing call on the same thread. Without std, it's a fixed
+/// message for `gbeed_last_error_code`
+#[unsafe(no_mangle)]
+pub extern "C" fn gbeed_last_error() -> *const c_char { error::last_error_message() }
+
+/// Code of the last error on this thread, `GBEED_ERROR_NONE` when there wasn't any.
+/// Without std, it's shared by every thread
+#[unsafe(no_mangle)]
+pub extern "C" fn gbeed_last_error_code() -> GbeedError { error::last_error() }
+
+/// Create a machine running `rom`, with the battery backed RAM loaded from `save` when it isn't null.
+/// Both buffers are copied, `callbacks` can be null to only use the framebuffer and the audio buffer.
+/// Returns null when the game can't be loaded, see `gbeed_last_error`
+///
+/// # Safety
+/// `rom` has to point to `rom_len` readable bytes, `save` to `save_len` ones if it isn't null,
+/// and `callbacks` has to be null or point to a `GbeedCallbacks`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_new(
+    rom: *const u8,
+    rom_len: usize,
+    save: *const u8,
+    save_len: usize,
+    callbacks: *const GbeedCallbacks,
+) -> *mut GbeedMachine {
+    let rom = unsafe { slice(rom, rom_len) };
+    let save = (!save.is_null()).then(|| unsafe { slice(save, save_len) }.to_vec());
+    let callbacks = unsafe { callbacks.as_ref() }.copied().unwrap_or_default();
+
+    match Cartridge::new(rom, save) {
+        Ok(cartridge) => Box::into_raw(Box::new(GbeedMachine {
+            gb: Dmg::new(cartridge, None),
+            bridge: Bridge::new(callbacks),
+        })),
+        Err(e) => {
+            set_last_error(GbeedError::InvalidRom, e);
+            core::ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`, and isn't valid anymore after this
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_free(machine: *mut GbeedMachine) {
+    if !machine.is_null() {
+        drop(unsafe { Box::from_raw(machine) });
+    }
+}
+
+/// Run a whole frame, calling the callbacks while it runs.
+/// Returns false when the CPU found an invalid instruction, see `gbeed_last_error`
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_run_frame(machine: *mut GbeedMachine) -> bool {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return false;
+    };
+
+    machine
+        .gb
+        .run(&mut machine.bridge)
+        .inspect_err(|e| set_last_error(GbeedError::InvalidInstruction, e))
+        .is_ok()
+}
+
+/// Shades of the last frame from 0 (lightest) to 3 (darkest), `GBEED_SCREEN_WIDTH` *
+/// `GBEED_SCREEN_HEIGHT` bytes one row after another. The pointer stays the same for the
+/// whole life of the machine
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_framebuffer(machine: *const GbeedMachine) -> *const u8 {
+    unsafe { machine.as_ref() }.map_or(core::ptr::null(), |machine| machine.bridge.framebuffer().as_ptr())
+}
+
+/// Press or release `button`, one of the `GbeedButton` values.
+/// Returns false when it's none of them, or `machine` is null
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_set_button(machine: *mut GbeedMachine, button: u32, pressed: bool) -> bool {
+    let (Some(machine), Some(button)) = (unsafe { machine.as_mut() }, GbeedButton::from_raw(button)) else {
+        return false;
+    };
+
+    machine.gb.joypad.button_down(button.into(), pressed);
+    true
+}
+
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_sample_rate(machine: *const GbeedMachine) -> u32 {
+    unsafe { machine.as_ref() }.map_or(0, |machine| machine.gb.apu.sample_rate())
+}
+
+/// Samples per second of each channel, 44100 by default
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_set_sample_rate(machine: *mut GbeedMachine, sample_rate: u32) {
+    if let Some(machine) = unsafe { machine.as_mut() } {
+        machine.gb.apu.set_sample_rate(sample_rate);
+    }
+}
+
+/// Move up to `frames` interleaved left and right samples into `out`, oldest first, returning
+/// how many were moved. Only samples not handed to a `push_sample` callback are kept,
+/// up to about a second of them
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`, and `out` has to point to `frames` * 2
+/// writable samples
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_drain_audio(
+    machine: *mut GbeedMachine,
+    out: *mut i16,
+    frames: usize,
+) -> usize {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return 0;
+    };
+
+    machine
+        .bridge
+        .drain_samples(unsafe { slice_mut(out, frames.saturating_mul(2)) })
+}
+
+/// Size of the battery backed RAM, 0 when the cartridge has no battery
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_save_ram_size(machine: *const GbeedMachine) -> usize {
+    unsafe { machine.as_ref() }
+        .and_then(|machine| machine.gb.cartridge.save_game())
+        .map_or(0, <[u8]>::len)
+}
+
+/// Whether the game wrote the battery backed RAM since it was last copied by `gbeed_save_ram`
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_save_ram_changed(machine: *const GbeedMachine) -> bool {
+    unsafe { machine.as_ref() }.is_some_and(|machine| machine.gb.cartridge.save_changed())
+}
+
+/// Copy the battery backed RAM into `out`, in the same layout `gbeed_new` loads it.
+/// Returns the copied size, or 0 when there's no battery or `out` is smaller than
+/// `gbeed_save_ram_size`
+///
+/// # Safety
+/// `machine` has to be null or come from `gbeed_new`, and `out` has to point to `len` writable bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gbeed_save_ram(machine: *mut GbeedMachine, out: *mut u8, len: usize) -> usize {
+    let Some(machine) = (unsafe { machine.as_mut() }) else {
+        return 0;
+    };
+    let out = unsafe { slice_mut(out, len) };
+
+    match machine.gb.cartridge.save_game() {
+        Some(save) if save.len() <= out.len() => {
+            let len = save.len();
+            out[..len].copy_from_slice(save);
+            machine.gb.cartridge.mark_saved();
+            len
+        }
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// MBC1 game with 8 KB of battery backed RAM, counting up its first byte forever
+    fn counting_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x03;
+        rom[0x0149] = 0x02;
+        #[rustfmt::skip]
+        let program = [
+            0x3E, 0x0A,       // ld a, $0A
+            0xEA, 0x00, 0x00, // ld [$0000], a
+            0x21, 0x00, 0xA0, // ld hl, $A000
+            0x34,             // inc [hl]
+            0x18, 0xFD,       // jr -3
+        ];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+        rom
+    }
+
+    fn new_machine(rom: &[u8]) -> *mut GbeedMachine {
+        unsafe { gbeed_new(rom.as_ptr(), rom.len(), std::ptr::null(), 0, std::ptr::null()) }
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let machine = new_machine(&counting_rom());
+        assert!(!machine.is_null());
+
+        for _ in 0..3 {
+            assert!(unsafe { gbeed_run_frame(machine) });
+        }
+        assert!(!unsafe { gbeed_framebuffer(machine) }.is_null());
+
+        let mut samples = vec![0; 44100 * 2];
+        let frames = unsafe { gbeed_drain_audio(machine, samples.as_mut_ptr(), 44100) };
+        assert!(
+            frames > 0,
+            "three frames of audio are buffered without a push_sample callback"
+        );
+        assert_eq!(
+            unsafe { gbeed_drain_audio(machine, samples.as_mut_ptr(), 44100) },
+            0
+        );
+
+        unsafe { gbeed_free(machine) };
+    }
+
+    #[test]
+    fn test_save_ram() {
+        let machine = new_machine(&counting_rom());
+        assert!(unsafe { gbeed_run_frame(machine) } && unsafe { gbeed_run_frame(machine) });
+
+        assert_eq!(unsafe { gbeed_save_ram_size(machine) }, 8 * 1024);
+        assert!(unsafe { gbeed_save_ram_changed(machine) });
+
+        let mut small = [0; 16];
+        assert_eq!(
+            unsafe { gbeed_save_ram(machine, small.as_mut_ptr(), small.len()) },
+            0
+        );
+        assert!(unsafe { gbeed_save_ram_changed(machine) });
+
+        let mut save = vec![0; 8 * 1024];
+        assert_eq!(
+            unsafe { gbeed_save_ram(machine, save.as_mut_ptr(), save.len()) },
+            8 * 1024
+        );
+        assert_ne!(save[0], 0, "the game counted up the first byte");
+        assert!(!unsafe { gbeed_save_ram_changed(machine) });
+
+        unsafe { gbeed_free(machine) };
+    }
+
+    #[test]
+    fn test_set_button() {
+        let machine = new_machine(&counting_rom());
+        assert!(unsafe { gbeed_set_button(machine, GbeedButton::Start as u32, true) });
+        assert!(!unsafe { gbeed_set_button(machine, 8, true) });
+        assert!(!unsafe { gbeed_set_button(machine, u32::MAX, false) });
+        assert!(!unsafe { gbeed_set_button(std::ptr::null_mut(), 0, true) });
+        unsafe { gbeed_free(machine) };
+    }
+
+    #[test]
+    fn test_invalid_rom() {
+        assert!(new_machine(&[0; 16]).is_null());
+        let error = unsafe { CStr::from_ptr(gbeed_last_error()) };
+        assert!(!error.is_empty());
+        assert_eq!(gbeed_last_error_code(), GbeedError::InvalidRom);
+
+        assert!(!unsafe { gbeed_run_frame(std::ptr::null_mut()) });
+        assert_eq!(unsafe { gbeed_save_ram_size(std::ptr::null()) }, 0);
+    }
+
+    #[test]
+    fn test_header_up_to_date() {
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/gbeed.h"));
+        assert!(
+            generated == include_str!("../include/gbeed.h"),
+            "include/gbeed.h is outdated, build with GBEED_UPDATE_HEADER=1 to update it"
+        );
+    }
+}
@@ -1,30 +1,53 @@
 use gbeed_core::{
-    AudioPlayer, BUFFER_SIZE, Controller, Ppu, Renderer, SAMPLE_RATE, SerialListener,
-    prelude::DMG_SCREEN_WIDTH,
+    AudioPlayer, Cartridge, Controller, Ppu, Renderer, SerialListener, prelude::DMG_SCREEN_WIDTH,
 };
 use gbeed_raylib_common::{
-    Texture, color,
-    settings::{SpeedUpMode, SpeedUpMultiplier, TargetedFps},
+    Texture,
+    audio::AudioQueue,
+    color,
+    config::{Config, Settings},
+    filter::ScreenFilters,
+    input::Turbo,
+    library::Library,
+    recording::Recorder,
+    settings::{SpeedUpMode, SpeedUpMultiplier, SyncMode, TargetedFps},
 };
 use raylib::prelude::*;
+use std::path::PathBuf;
+
+use crate::utils::roms::RomScan;
 
 pub struct ConsoleController<'a> {
     pub screen: Texture,
     pub palette: color::Palette,
     pub palette_color: color::PaletteColor,
+    pub filters: ScreenFilters,
 
-    sample_idx: usize,
-    audio_buffer: Box<[i16; BUFFER_SIZE]>,
-    audio_stream: AudioStream<'a>,
+    pub audio: AudioQueue<'a>,
+    pub sync_mode: SyncMode,
+    pub recorder: Recorder,
 
     pub speed_up_mode: SpeedUpMode,
     pub speed_up_multiplier: SpeedUpMultiplier,
+    pub turbo: Turbo,
     pub targeted_fps: TargetedFps,
     pub draw_debug_info: bool,
 
+    pub config: Config,
+    /// Key of the loaded game in `config.games`
+    pub game: Option<String>,
+    /// Patch applied to the loaded game
+    pub patch: Option<PathBuf>,
+    /// Off when the config file couldn't be read, so it isn't replaced with the defaults
+    pub save_config: bool,
+    pub library: Library,
+    /// Off when the library file couldn't be read, like `save_config`
+    pub save_library: bool,
+    /// ROMs of the ROM roots, scanned once for every time the ROM list is opened
+    pub roms: RomScan,
+
     pub rl: &'a mut RaylibHandle,
     pub thread: &'a RaylibThread,
-    _audio: &'a RaylibAudio,
 }
 
 impl<'a> ConsoleController<'a> {
@@ -33,34 +56,116 @@ impl<'a> ConsoleController<'a> {
         thread: &'a RaylibThread,
         audio: &'a RaylibAudio,
         screen: Texture,
-        palette: color::Palette,
+        config: Config,
+        save_config: bool,
     ) -> Self {
-        let palette_color = palette.get_palette_color();
-
-        // match raylib's internal subbuffer size to our flush size so
-        // UpdateAudioStream never has to reject a chunk for being "too many frames"
-        audio.set_audio_stream_buffer_size_default(BUFFER_SIZE as i32);
-
-        let audio_stream = audio.new_audio_stream(SAMPLE_RATE, 16, 1);
-        audio_stream.play();
-
-        Self {
+        let settings = config.settings;
+        let (library, save_library) = match Library::load() {
+            Ok(library) => (library, true),
+            Err(e) => {
+                eprintln!("Failed to read the library, starting an empty one without saving it: {e}");
+                (Library::default(), false)
+            }
+        };
+        let filters = ScreenFilters::new(rl, thread);
+
+        let mut controller = Self {
             screen,
-            palette,
-            palette_color,
+            palette: settings.palette,
+            palette_color: settings.palette.get_palette_color(),
+            filters,
 
-            sample_idx: 0,
-            audio_buffer: Box::new([0; BUFFER_SIZE]),
-            audio_stream,
+            audio: AudioQueue::new(audio, settings.sample_rate, settings.audio_output),
+            sync_mode: SyncMode::default(),
+            recorder: Recorder::default(),
 
             speed_up_mode: SpeedUpMode::default(),
             speed_up_multiplier: SpeedUpMultiplier::default(),
+            turbo: Turbo::default(),
             targeted_fps: TargetedFps::default(),
             draw_debug_info: false,
 
+            config,
+            game: None,
+            patch: None,
+            save_config,
+            library,
+            save_library,
+            roms: RomScan::default(),
+
             rl,
             thread,
-            _audio: audio,
+        };
+        controller.apply_settings(settings);
+        controller
+    }
+
+    /// Current values of everything the settings menu edits
+    pub fn settings(&self) -> Settings {
+        Settings {
+            palette: self.palette,
+            screen_filter: self.filters.filter,
+            frame_blending: self.filters.frame_blending,
+            screen_scaling: self.filters.scaling,
+            speed_up_mode: self.speed_up_mode,
+            speed_up_multiplier: self.speed_up_multiplier,
+            turbo_rate: self.turbo.rate,
+            targeted_fps: self.targeted_fps,
+            sample_rate: self.audio.sample_rate(),
+            audio_output: self.audio.output(),
+            sync_mode: self.sync_mode,
+            draw_debug_info: self.draw_debug_info,
+        }
+    }
+
+    /// Switch to `settings`, the audio stream is only reopened when its format changes
+    pub fn apply_settings(&mut self, settings: Settings) {
+        self.palette = settings.palette;
+        self.palette_color = settings.palette.get_palette_color();
+        self.filters.filter = settings.screen_filter;
+        self.filters.frame_blending = settings.frame_blending;
+        self.filters.scaling = settings.screen_scaling;
+        self.speed_up_mode = settings.speed_up_mode;
+        self.speed_up_multiplier = settings.speed_up_multiplier;
+        self.turbo.rate = settings.turbo_rate;
+        self.targeted_fps = settings.targeted_fps;
+        self.rl.set_target_fps(settings.targeted_fps as u32);
+        if self.audio.sample_rate() != settings.sample_rate {
+            self.audio.set_sample_rate(settings.sample_rate);
+        }
+        if self.audio.output() != settings.audio_output {
+            self.audio.set_output(settings.audio_output);
+        }
+        self.sync_mode = settings.sync_mode;
+        self.draw_debug_info = settings.draw_debug_info;
+    }
+
+    /// Apply the settings of a game about to be played, its overrides included
+    pub fn load_game_settings(&mut self, cartridge: &Cartridge) {
+        self.game = Some(Config::game_key(cartridge));
+        self.apply_settings(self.config.settings_for(self.game.as_deref()));
+    }
+
+    /// Keep the current settings in the config file, a failed write is only reported
+    pub fn save_settings(&mut self) {
+        self.config.store(self.settings(), self.game.as_deref());
+        if !self.save_config {
+            return;
+        }
+
+        if let Err(e) = self.config.save() {
+            eprintln!("Failed to save the config: {e}");
+        }
+    }
+
+    /// Keep favourites and recents in the library file, a failed write is only reported
+    pub fn write_library(&self) {
+        if !self.save_library {
+            return;
+        }
+
+        if let Err(e) = self.library.save() {
+            eprintln!("Failed to save the library: {e}");
         }
     }
 }
@@ -85,7 +190,12 @@ impl Renderer for ConsoleController<'_> {
         self.screen[index + 2] = color.b;
     }
 
-    fn update_screen(&mut self, _: &Ppu) { self.screen.update(); }
+    fn update_screen(&mut self, _: &Ppu) {
+        self.turbo.next_frame();
+        self.screen.update();
+        self.filters.apply(self.screen.pixels());
+        self.recorder.push_frame(self.screen.pixels());
+    }
 }
 
 impl SerialListener for ConsoleController<'_> {
@@ -95,29 +205,14 @@ impl SerialListener for ConsoleController<'_> {
 }
 
 impl AudioPlayer for ConsoleController<'_> {
-    fn playing_stereo(&self) -> bool { false }
+    fn playing_stereo(&self) -> bool { self.audio.is_stereo() }
 
     fn push_sample(&mut self, left: i16, right: i16) {
-        // drop new samples when the buffer is full instead of wrapping
-        if self.sample_idx >= BUFFER_SIZE {
-            return;
-        }
-
-        // let the audio mono for now
-        let mono = ((left as i32 + right as i32) / 2) as i16;
-        self.audio_buffer[self.sample_idx] = mono;
-        self.sample_idx += 1;
+        self.audio.push(left, right);
+        self.recorder.push_sample(left, right);
     }
 
-    fn flush_buffer(&mut self) {
-        if self.sample_idx == 0 || !self.audio_stream.is_processed() {
-            return;
-        }
-        if let Err(e) = self.audio_stream.update(&self.audio_buffer[..self.sample_idx]) {
-            eprintln!("update error: {e}");
-        }
-        self.sample_idx = 0;
-    }
+    fn flush_buffer(&mut self) { self.audio.update(); }
 }
 
 impl Controller for ConsoleController<'_> {}
@@ -3,13 +3,19 @@ mod scenes;
 mod utils;
 
 use gbeed_core::prelude::*;
-use gbeed_raylib_common::{Texture, color, settings::SpeedUpMultiplier};
+use gbeed_raylib_common::{
+    Texture, color,
+    config::{Config, Paths},
+    input::{InputEvdevTriggers, InputGamepadTriggers, InputGpioButtons, InputKeyTriggers},
+    settings::SpeedUpMultiplier,
+};
 use raylib::prelude::*;
 use std::path::PathBuf;
 
 use crate::controller::ConsoleController;
 use crate::scenes::{EmulatorState, SelectionMenuState};
 use crate::utils::layout::{SCREEN_HEIGHT, SCREEN_WIDTH, draw_footer, draw_header};
+use crate::utils::roms;
 
 struct EmulatorApp<'a> {
     state: EmulatorState,
@@ -23,7 +29,19 @@ impl<'a> EmulatorApp<'a> {
     pub fn new(rl: &'a mut RaylibHandle, thread: &'a RaylibThread, audio: &'a RaylibAudio) -> Self {
         let screen = Texture::new(rl, thread, DMG_SCREEN_WIDTH as i32, DMG_SCREEN_HEIGHT as i32);
 
-        let palette = color::Palette::default();
+        let (config, save_config) = match Config::load() {
+            Ok(config) => (config, true),
+            Err(e) => {
+                eprintln!("Failed to read the config, using the defaults without saving them: {e}");
+                (Config::default(), false)
+            }
+        };
+        InputKeyTriggers::configure(config.keys.triggers());
+        InputGamepadTriggers::configure(config.gamepad.triggers());
+        InputEvdevTriggers::configure(config.evdev.triggers());
+        InputGpioButtons::configure(config.gpio.triggers());
+        InputGamepadTriggers::add_mappings(Config::gamepad_mappings_path().as_deref());
+        Paths::configure(config.paths.clone());
 
         Self {
             state: EmulatorState::SelectionMenu(SelectionMenuState::new()),
@@ -31,7 +49,7 @@ impl<'a> EmulatorApp<'a> {
             rom_path: None,
             save_path: None,
 
-            controller: ConsoleController::new(rl, thread, audio, screen, palette),
+            controller: ConsoleController::new(rl, thread, audio, screen, config, save_config),
         }
     }
 
@@ -40,12 +58,21 @@ impl<'a> EmulatorApp<'a> {
         self.controller.rl.window_should_close() || matches!(self.state, EmulatorState::Exit)
     }
 
+    /// Write the save of the loaded game, if it changed, before closing
+    pub fn save_game(&mut self) {
+        if let Some(gb) = &mut self.gb
+            && let Err(e) = roms::save_cartridge(gb, &self.save_path)
+        {
+            eprintln!("Failed to save the game: {e}");
+        }
+    }
+
     pub fn update(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let dt = self.controller.rl.get_frame_time();
 
         let next_state = match &mut self.state {
             EmulatorState::SelectionMenu(state) => state.update(
-                self.controller.rl,
+                &mut self.controller,
                 dt,
                 &mut self.rom_path,
                 &mut self.gb,
@@ -58,7 +85,20 @@ impl<'a> EmulatorApp<'a> {
                 &mut self.save_path,
                 &mut self.controller,
             )?,
-            EmulatorState::GameMenu(state) => state.update(self.controller.rl, dt, &self.gb),
+            EmulatorState::GameMenu(state) => state.update(
+                &mut self.controller,
+                dt,
+                &mut self.gb,
+                &self.rom_path,
+                &mut self.save_path,
+            ),
+            EmulatorState::SaveMenu(state) => state.update(
+                &mut self.controller,
+                dt,
+                &mut self.gb,
+                &self.rom_path,
+                &mut self.save_path,
+            ),
             EmulatorState::SettingsMenu(state) => state.update(dt, self.gb.as_ref(), &mut self.controller),
 
             // emulator should have already been closed at this point
@@ -73,16 +113,22 @@ impl<'a> EmulatorApp<'a> {
     }
 
     pub fn draw(&mut self) {
+        let sample_rate = self.controller.audio.sample_rate();
+        let audio_output = self.controller.audio.output();
+        let turbo_rate = self.controller.turbo.rate;
         let ConsoleController {
             rl,
             thread,
             screen,
             palette,
             palette_color,
+            filters,
             speed_up_mode,
             speed_up_multiplier,
             targeted_fps,
+            sync_mode,
             draw_debug_info,
+            recorder,
             ..
         } = &mut self.controller;
 
@@ -91,17 +137,25 @@ impl<'a> EmulatorApp<'a> {
 
             match &self.state {
                 EmulatorState::SelectionMenu(state) => state.draw(&mut d, palette_color),
-                EmulatorState::Emulation(state) => state.draw(&mut d, screen),
+                EmulatorState::Emulation(state) => {
+                    state.draw(&mut d, screen, filters, recorder.is_recording())
+                }
                 EmulatorState::GameMenu(state) => {
                     state.draw(&mut d, screen, &self.gb, &self.rom_path, palette_color)
                 }
+                EmulatorState::SaveMenu(state) => state.draw(&mut d, &self.gb, palette_color),
                 EmulatorState::SettingsMenu(state) => state.draw(
                     &mut d,
                     palette,
                     palette_color,
+                    filters,
                     speed_up_mode,
                     speed_up_multiplier,
+                    &turbo_rate,
                     targeted_fps,
+                    &sample_rate,
+                    &audio_output,
+                    sync_mode,
                     *draw_debug_info,
                 ),
 
@@ -144,6 +198,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         app.update()?;
         app.draw();
     }
+    app.save_game();
 
     Ok(())
 }
@@ -2,9 +2,14 @@ use crate::controller::ConsoleController;
 use crate::scenes::EmulatorState;
 use crate::scenes::GameMenuState;
 use crate::utils::layout::*;
-use crate::utils::roms::save_cartridge;
+use crate::utils::roms::{recordings_dir, save_cartridge, screenshots_dir};
 use gbeed_core::prelude::*;
-use gbeed_raylib_common::{input::InputManager, settings::SpeedUpMode};
+use gbeed_raylib_common::{
+    filter::ScreenFilters,
+    input::InputManager,
+    screenshot::save_screenshot,
+    settings::{SpeedUpMode, SyncMode},
+};
 use raylib::prelude::*;
 use std::path::PathBuf;
 
@@ -16,6 +21,8 @@ const MAX_ACCUMULATOR: f32 = GB_FRAME_TIME * MAX_STEPS as f32;
 pub struct EmulationState {
     pub input: InputManager,
     pub accumulator: f32,
+    /// Seconds since the save was last checked for changes to write
+    pub since_autosave: f32,
 }
 
 impl EmulationState {
@@ -23,6 +30,7 @@ impl EmulationState {
         Self {
             input: InputManager::default(),
             accumulator: 0.0,
+            since_autosave: 0.0,
         }
     }
 
@@ -30,7 +38,7 @@ impl EmulationState {
         &mut self,
         dt: f32,
         gb: &mut Option<Dmg>,
-        _rom_path: &mut Option<PathBuf>,
+        rom_path: &mut Option<PathBuf>,
         save_path: &mut Option<PathBuf>,
         controller: &mut ConsoleController,
     ) -> Result<Option<EmulatorState>, Box<dyn std::error::Error>> {
@@ -41,11 +49,34 @@ impl EmulationState {
         self.input.update(controller.rl, dt);
 
         if self.input.is_pressed_escape() {
+            controller.recorder.stop();
             save_cartridge(gb, save_path)?;
             return Ok(Some(EmulatorState::GameMenu(GameMenuState::new())));
         }
 
-        self.input.state().apply(&mut gb.joypad);
+        let name = rom_path
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+
+        if self.input.is_pressed_record() {
+            let sample_rate = controller.audio.sample_rate().hz();
+            controller.recorder.toggle(&recordings_dir(), &name, sample_rate);
+        }
+
+        if self.input.is_pressed_screenshot() {
+            match save_screenshot(&screenshots_dir(), &name, controller.screen.pixels()) {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(e) => eprintln!("Failed to save screenshot: {e}"),
+            }
+        }
+
+        // a game loaded before the rate was changed still runs at the previous one
+        let sample_rate = controller.audio.sample_rate().hz();
+        if gb.apu.sample_rate() != sample_rate {
+            gb.apu.set_sample_rate(sample_rate);
+        }
 
         let selected_speed_up = controller.speed_up_multiplier.get_multiplier();
 
@@ -62,30 +93,70 @@ impl EmulationState {
             }
         };
 
-        // avoid spiral of death by capping the accumulator if the hardware can't keep up
-        self.accumulator += dt * speed;
-        self.accumulator = self.accumulator.min(MAX_ACCUMULATOR);
+        // the stream keeps asking for samples even when no frame runs
+        controller.audio.update();
 
+        let input = self.input.state();
         let mut steps = 0;
 
-        while self.accumulator >= GB_FRAME_TIME && steps < MAX_STEPS {
-            gb.run(controller)?;
-            self.accumulator -= GB_FRAME_TIME;
-            steps += 1;
+        match controller.sync_mode {
+            // sped up games produce samples faster than they are played, so they fall back to video pacing
+            SyncMode::Audio if speed == 1.0 => {
+                gb.apu.set_rate_adjustment(1.0);
+                while controller.audio.needs_samples() && steps < MAX_STEPS {
+                    input.apply(&mut gb.joypad, &controller.turbo);
+                    gb.run(controller)?;
+                    steps += 1;
+                }
+                self.accumulator = 0.0;
+            }
+            _ => {
+                gb.apu.set_rate_adjustment(controller.audio.rate_adjustment());
+
+                // avoid spiral of death by capping the accumulator if the hardware can't keep up
+                self.accumulator += dt * speed;
+                self.accumulator = self.accumulator.min(MAX_ACCUMULATOR);
+
+                while self.accumulator >= GB_FRAME_TIME && steps < MAX_STEPS {
+                    input.apply(&mut gb.joypad, &controller.turbo);
+                    gb.run(controller)?;
+                    self.accumulator -= GB_FRAME_TIME;
+                    steps += 1;
+                }
+            }
+        }
+
+        let interval = controller.config.saves.autosave_interval;
+        self.since_autosave += dt;
+        if interval > 0 && self.since_autosave >= interval as f32 {
+            self.since_autosave = 0.0;
+            if let Err(e) = save_cartridge(gb, save_path) {
+                eprintln!("Failed to autosave: {e}");
+            }
         }
 
         Ok(None)
     }
 
-    pub fn draw(&self, d: &mut RaylibDrawHandle, screen: &gbeed_raylib_common::Texture) {
+    pub fn draw(
+        &self,
+        d: &mut RaylibDrawHandle,
+        screen: &gbeed_raylib_common::Texture,
+        filters: &ScreenFilters,
+        recording: bool,
+    ) {
         d.draw_texture_pro(
-            screen,
-            Rectangle::new(0.0, 0.0, DMG_SCREEN_WIDTH as f32, DMG_SCREEN_HEIGHT as f32),
-            Rectangle::new(0.0, 0.0, SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
+            filters.output(screen),
+            filters.source_rect(),
+            filters.destination(SCREEN_WIDTH, SCREEN_HEIGHT),
             Vector2::new(0.0, 0.0),
             0.0,
             Color::WHITE,
         );
         // d.draw_texture(&screen.texture, 0, 0, Color::WHITE);
+
+        if recording {
+            d.draw_circle(SCREEN_WIDTH - PADDING_X - 4, PADDING_X + 4, 4.0, Color::RED);
+        }
     }
 }
@@ -1,31 +1,136 @@
-use crate::scenes::{EmulationState, EmulatorState, SelectionMenuState, SettingsMenuState};
-use crate::utils::layout::*;
+use crate::controller::ConsoleController;
+use crate::scenes::{EmulationState, EmulatorState, SaveMenuState, SelectionMenuState};
+use crate::utils::{layout::*, roms, truncate_name};
 use gbeed_core::prelude::{DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH, Dmg};
-use gbeed_raylib_common::{color, input::InputManager};
+use gbeed_raylib_common::{
+    color, identify,
+    input::InputManager,
+    library::elapsed_since,
+    patch,
+    saves::{self, Backup},
+};
 use raylib::prelude::*;
 use std::path::PathBuf;
 
 #[derive(Debug)]
 pub struct GameMenuState {
     pub input: InputManager,
+    /// Patches found next to the loaded ROM, listed on the first update
+    pub patches: Option<Vec<PathBuf>>,
+    /// Picked patch, 0 for the unpatched ROM and the index in `patches` plus one otherwise
+    pub selected_patch: usize,
+    /// The picked patch didn't apply, the game is kept as it was
+    pub patch_failed: bool,
+    /// The loaded ROM being a known bad dump or not matching its header checksums,
+    /// checked along with the patch listing
+    pub warning: Option<&'static str>,
+    /// Backups of the save, listed along with the patches
+    pub backups: Vec<Backup>,
+    /// Picked backup, 0 for the current save and the index in `backups` plus one otherwise
+    pub selected_backup: usize,
+    /// The picked backup couldn't be restored, the save is kept as it was
+    pub restore_failed: bool,
 }
 
 impl GameMenuState {
     pub fn new() -> Self {
         Self {
             input: InputManager::default(),
+            patches: None,
+            selected_patch: 0,
+            patch_failed: false,
+            warning: None,
+            backups: Vec::new(),
+            selected_backup: 0,
+            restore_failed: false,
         }
     }
 
-    pub fn update(&mut self, rl: &RaylibHandle, dt: f32, gb: &Option<Dmg>) -> Option<EmulatorState> {
-        self.input.update(rl, dt);
+    #[inline(always)]
+    fn picked_patch(&self) -> Option<&PathBuf> {
+        let index = self.selected_patch.checked_sub(1)?;
+        self.patches.as_ref()?.get(index)
+    }
+
+    #[inline(always)]
+    fn picked_backup(&self) -> Option<&Backup> { self.backups.get(self.selected_backup.checked_sub(1)?) }
+
+    pub fn update(
+        &mut self,
+        controller: &mut ConsoleController,
+        dt: f32,
+        gb: &mut Option<Dmg>,
+        rom_path: &Option<PathBuf>,
+        save_path: &mut Option<PathBuf>,
+    ) -> Option<EmulatorState> {
+        self.input.update(controller.rl, dt);
+
+        if self.patches.is_none()
+            && let Some(rom_path) = rom_path
+        {
+            let patches = patch::find_patches(rom_path);
+            self.selected_patch = controller
+                .patch
+                .as_ref()
+                .and_then(|applied| patches.iter().position(|patch| patch == applied))
+                .map_or(0, |index| index + 1);
+            self.patches = Some(patches);
+            self.warning = match identify::identify_file(rom_path) {
+                Some(info) if info.bad_dump => Some("Known bad dump"),
+                _ => gb.as_ref().and_then(checksum_warning),
+            };
+            self.backups = save_path.as_deref().map(saves::list_backups).unwrap_or_default();
+        }
+
+        let choices = self.patches.as_ref().map_or(0, Vec::len) + 1;
+        if self.input.is_repeated_up(dt) {
+            self.selected_patch = (self.selected_patch + choices - 1) % choices;
+            self.patch_failed = false;
+        }
+        if self.input.is_repeated_down(dt) {
+            self.selected_patch = (self.selected_patch + 1) % choices;
+            self.patch_failed = false;
+        }
+        if self.input.is_pressed_b() {
+            self.selected_backup = (self.selected_backup + 1) % (self.backups.len() + 1);
+            self.restore_failed = false;
+        }
 
-        if (self.input.is_pressed_a()
-            || self.input.is_pressed_start()
-            || self.input.is_pressed_select()
-            || self.input.is_pressed_escape())
+        // restart the game when a different patch or a backup was picked, the save was already written when pausing
+        if (self.input.is_pressed_a() || self.input.is_pressed_start())
             && gb.is_some()
+            && let Some(rom_path) = rom_path
         {
+            if let (Some(backup), Some(save_path)) = (self.picked_backup(), save_path.as_deref()) {
+                if let Err(e) =
+                    saves::restore_backup(save_path, &backup.path, controller.config.saves.backups)
+                {
+                    eprintln!("Failed to restore {:?}: {e}", backup.path);
+                    self.restore_failed = true;
+                    return None;
+                }
+                println!("Restored {save_path:?} from {:?}", backup.path);
+            }
+
+            let picked = self.picked_patch().cloned();
+            if picked != controller.patch || self.picked_backup().is_some() {
+                match roms::load_cartridge(rom_path, picked.as_deref(), save_path) {
+                    Ok(cartridge) => {
+                        controller.load_game_settings(&cartridge);
+                        controller.patch = picked;
+                        *gb = Some(Dmg::new(cartridge, None));
+                    }
+                    Err(e) => {
+                        eprintln!("{e}");
+                        self.patch_failed = true;
+                        return None;
+                    }
+                }
+            }
+            return Some(EmulatorState::Emulation(EmulationState::new()));
+        }
+
+        if (self.input.is_pressed_select() || self.input.is_pressed_escape()) && gb.is_some() {
             return Some(EmulatorState::Emulation(EmulationState::new()));
         }
 
@@ -34,7 +139,7 @@ impl GameMenuState {
         }
 
         if self.input.is_repeated_right(dt) {
-            return Some(EmulatorState::SettingsMenu(SettingsMenuState::new()));
+            return Some(EmulatorState::SaveMenu(SaveMenuState::new()));
         }
 
         None
@@ -94,10 +199,63 @@ impl GameMenuState {
             color::primary(palette_color),
         );
 
-        let mut y_offset = info_y + 20;
+        let patch = match (self.patch_failed, self.picked_patch()) {
+            (true, _) => "failed to apply, see the log".to_string(),
+            (false, Some(patch)) => {
+                let name = patch.file_name().and_then(|n| n.to_str()).unwrap_or("Unknown");
+                format!("< {} >", truncate_name(name, 30))
+            }
+            (false, None) => "< none >".to_string(),
+        };
+        d.draw_text(
+            &format!("Patch: {patch}"),
+            info_x,
+            info_y + 20,
+            10,
+            color::foreground(palette_color),
+        );
+
+        let backup = match (self.restore_failed, self.picked_backup()) {
+            (true, _) => "failed to restore, see the log".to_string(),
+            (false, Some(backup)) => format!(
+                "< backup {} of {}, {} >",
+                self.selected_backup,
+                self.backups.len(),
+                elapsed_since(backup.time)
+            ),
+            (false, None) => format!("< current, {} backups >", self.backups.len()),
+        };
+        d.draw_text(
+            &format!("Save: {backup}"),
+            info_x,
+            info_y + 32,
+            10,
+            color::foreground(palette_color),
+        );
+
+        let mut y_offset = info_y + 46;
         for line in header_info {
             d.draw_text(&line, info_x, y_offset, 10, color::primary(palette_color));
-            y_offset += 14;
+            y_offset += 12;
+        }
+
+        if let Some(warning) = self.warning {
+            d.draw_text(warning, info_x, y_offset, 10, color::foreground(palette_color));
         }
     }
 }
+
+/// Which of the header and global checksums don't match the ROM. The boot ROM refuses to run games
+/// with a wrong header checksum, while the global one is never checked but tells of a bad dump or patch
+fn checksum_warning(gb: &Dmg) -> Option<&'static str> {
+    let rom = gb.cartridge.rom();
+    let header = gb.cartridge.check_header_checksum(rom).is_err();
+    let global = gb.cartridge.check_global_checksum(rom).is_err();
+
+    match (header, global) {
+        (true, true) => Some("Header and global checksums don't match"),
+        (true, false) => Some("Header checksum doesn't match"),
+        (false, true) => Some("Global checksum doesn't match"),
+        (false, false) => None,
+    }
+}
@@ -1,10 +1,12 @@
 mod emulation;
 mod game_menu;
+mod save_menu;
 mod selection_menu;
 mod settings_menu;
 
 pub use emulation::EmulationState;
 pub use game_menu::GameMenuState;
+pub use save_menu::SaveMenuState;
 pub use selection_menu::SelectionMenuState;
 pub use settings_menu::SettingsMenuState;
 
@@ -13,6 +15,7 @@ pub enum EmulatorState {
     SelectionMenu(SelectionMenuState),
     Emulation(EmulationState),
     GameMenu(GameMenuState),
+    SaveMenu(SaveMenuState),
     SettingsMenu(SettingsMenuState),
     Exit,
 }
@@ -0,0 +1,270 @@
+use crate::controller::ConsoleController;
+use crate::scenes::{EmulationState, EmulatorState, GameMenuState, SettingsMenuState};
+use crate::utils::{layout::*, roms, truncate_name};
+use gbeed_core::prelude::Dmg;
+use gbeed_core::{RtcFooter, SaveFile, SaveFormat, export_save, import_save};
+use gbeed_raylib_common::{archive, color, impl_cyclic_enum, input::InputManager, saves};
+use raylib::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directories in the saves directory where saves of other emulators are picked up and written to
+const IMPORT_DIR: &str = "import";
+const EXPORT_DIR: &str = "export";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOption {
+    Export,
+    Import,
+}
+
+use SaveOption::*;
+
+impl_cyclic_enum!(SaveOption, [Export, Import]);
+
+impl SaveOption {
+    pub fn name(&self) -> &str {
+        match self {
+            Export => "Export as",
+            Import => "Import",
+        }
+    }
+}
+
+/// # Save management
+/// Moves the save of the loaded game to and from the layouts of other emulators and flash carts.
+/// Exports go to `saves/export` and imports are picked from `saves/import` and next to the ROM
+#[derive(Debug)]
+pub struct SaveMenuState {
+    pub input: InputManager,
+    pub selected: SaveOption,
+    pub format: SaveFormat,
+    /// Saves that can be imported, listed on the first update
+    pub imports: Option<Vec<PathBuf>>,
+    pub selected_import: usize,
+    /// Outcome of the last import or export
+    pub message: Option<String>,
+}
+
+impl SaveMenuState {
+    pub fn new() -> Self {
+        Self {
+            input: InputManager::default(),
+            selected: Export,
+            format: SaveFormat::Raw,
+            imports: None,
+            selected_import: 0,
+            message: None,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        controller: &mut ConsoleController,
+        dt: f32,
+        gb: &mut Option<Dmg>,
+        rom_path: &Option<PathBuf>,
+        save_path: &mut Option<PathBuf>,
+    ) -> Option<EmulatorState> {
+        self.input.update(controller.rl, dt);
+
+        if self.imports.is_none() {
+            self.imports = Some(rom_path.as_deref().map(find_imports).unwrap_or_default());
+        }
+
+        if self.input.is_pressed_up() {
+            self.selected = self.selected.prev();
+        }
+        if self.input.is_pressed_down() {
+            self.selected = self.selected.next();
+        }
+
+        if self.input.is_repeated_left(dt) {
+            return Some(EmulatorState::GameMenu(GameMenuState::new()));
+        }
+        if self.input.is_repeated_right(dt) {
+            return Some(EmulatorState::SettingsMenu(SettingsMenuState::new()));
+        }
+        if (self.input.is_pressed_select() || self.input.is_pressed_escape()) && gb.is_some() {
+            return Some(EmulatorState::Emulation(EmulationState::new()));
+        }
+
+        let (Some(rom_path), Some(save_file)) = (rom_path, save_path.as_deref()) else {
+            return None;
+        };
+
+        match self.selected {
+            Export => {
+                if self.input.is_pressed_b() {
+                    let index = SaveFormat::ALL.iter().position(|format| *format == self.format);
+                    self.format = SaveFormat::ALL[index.map_or(0, |index| index + 1) % SaveFormat::ALL.len()];
+                }
+                if self.input.is_pressed_a()
+                    && let Some(gb) = gb
+                {
+                    self.message = Some(match export(gb, save_file, self.format) {
+                        Ok(path) => format!("Exported to {}", path.display()),
+                        Err(e) => format!("Failed to export: {e}"),
+                    });
+                }
+            }
+
+            Import => {
+                let imports = self.imports.as_deref().unwrap_or_default();
+                if self.input.is_pressed_b() && !imports.is_empty() {
+                    self.selected_import = (self.selected_import + 1) % imports.len();
+                }
+                if self.input.is_pressed_a()
+                    && let Some(import) = imports.get(self.selected_import).cloned()
+                {
+                    self.message = Some(match import_into(controller, gb, rom_path, save_path, &import) {
+                        Ok(()) => format!("Imported {}", import.display()),
+                        Err(e) => format!("Failed to import: {e}"),
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, gb: &Option<Dmg>, palette_color: &color::PaletteColor) {
+        if !gb.as_ref().is_some_and(has_save) {
+            let text = match gb {
+                Some(_) => "The game has no battery save",
+                None => "No ROM loaded",
+            };
+            let text_w = d.measure_text(text, 14);
+            d.draw_text(
+                text,
+                (SCREEN_WIDTH - text_w) / 2,
+                SCREEN_HEIGHT / 2,
+                14,
+                color::primary(palette_color),
+            );
+            return;
+        }
+
+        let format = format!("{} ({})", self.format.name(), self.format.description());
+        let import = match self
+            .imports
+            .as_ref()
+            .and_then(|imports| imports.get(self.selected_import))
+        {
+            Some(import) => {
+                let name = import.file_name().and_then(|n| n.to_str()).unwrap_or("Unknown");
+                truncate_name(name, 24)
+            }
+            None => "none found".to_string(),
+        };
+        let items: Vec<(&str, &str)> = SaveOption::ALL
+            .iter()
+            .map(|option| {
+                let value = match option {
+                    Export => format.as_str(),
+                    Import => import.as_str(),
+                };
+                (option.name(), value)
+            })
+            .collect();
+
+        draw_menu_list(d, &items, self.selected.position(), 0, palette_color);
+
+        let y = VISIBLE_TOP + (items.len() as i32 + 1) * ITEM_H;
+        let help = format!(
+            "Put saves to import in {}",
+            roms::saves_dir().join(IMPORT_DIR).display()
+        );
+        let message = self.message.as_deref().unwrap_or(&help);
+        d.draw_text(
+            &truncate_name(message, 44),
+            PADDING_X + 12,
+            y,
+            FONT_SIZE,
+            color::foreground(palette_color),
+        );
+    }
+}
+
+/// Saves of other emulators for the ROM at `rom_path`: everything in the import directory, the saves
+/// named after the ROM next to it and the one older gbeed versions kept, but the one gbeed itself uses
+fn find_imports(rom_path: &Path) -> Vec<PathBuf> {
+    let unpacked = archive::unpacked_path(rom_path);
+    let own = roms::save_path_from_rom(rom_path);
+
+    let mut imports: Vec<PathBuf> = fs::read_dir(roms::saves_dir().join(IMPORT_DIR))
+        .map(|entries| entries.filter_map(Result::ok).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    imports.extend(
+        SaveFormat::ALL
+            .iter()
+            .map(|format| unpacked.with_extension(format.extension())),
+    );
+    imports.push(roms::legacy_save_path(rom_path));
+
+    imports.retain(|path| path.is_file() && *path != own);
+    imports.sort();
+    imports.dedup();
+    imports
+}
+
+/// Battery backed RAM or an MBC3 clock, some cartridges only having the clock
+fn has_save(gb: &Dmg) -> bool { gb.cartridge.save_game().is_some() || gb.cartridge.rtc().is_some() }
+
+/// Replace the save with `import`, after backing up the current one, and restart the game with it
+fn import_into(
+    controller: &mut ConsoleController,
+    gb: &mut Option<Dmg>,
+    rom_path: &Path,
+    save_path: &mut Option<PathBuf>,
+    import: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(dmg) = gb.as_mut() else {
+        return Ok(());
+    };
+    if !has_save(dmg) {
+        return Err("the game has no battery save".into());
+    }
+    let ram_size = dmg.cartridge.save_game().map_or(0, <[u8]>::len);
+    let save = import_save(&fs::read(import)?, Some(ram_size))?;
+
+    if ram_size > 0 {
+        roms::save_cartridge(dmg, save_path)?;
+        let path = save_path.as_deref().ok_or("Save path not set")?;
+        saves::backup_save(path, controller.config.saves.backups.max(1))?;
+        saves::write_save(path, &save.ram)?;
+    }
+
+    let mut cartridge = roms::load_cartridge(rom_path, controller.patch.as_deref(), save_path)?;
+    if let Some(rtc) = &save.rtc {
+        cartridge.set_rtc(rtc);
+    }
+    controller.load_game_settings(&cartridge);
+    *gb = Some(Dmg::new(cartridge, None));
+    Ok(())
+}
+
+/// Write the save of `gb` in `format` to the export directory, named like its save
+fn export(gb: &Dmg, save_path: &Path, format: SaveFormat) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if !has_save(gb) {
+        return Err("the game has no battery save".into());
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    // saved now, so emulators loading it don't add the time passed since 1970 to the clock
+    let save = SaveFile {
+        ram: gb.cartridge.save_game().unwrap_or_default().to_vec(),
+        rtc: gb.cartridge.rtc().map(|rtc| RtcFooter { timestamp, ..rtc }),
+    };
+
+    let name = save_path.file_name().unwrap_or(save_path.as_os_str());
+    let path = roms::saves_dir()
+        .join(EXPORT_DIR)
+        .join(name)
+        .with_extension(format.extension());
+    saves::write_save(&path, &export_save(&save, format, timestamp))?;
+    Ok(path)
+}
@@ -1,94 +1,224 @@
 use gbeed_core::Dmg;
-use gbeed_raylib_common::{color, input::InputManager};
+use gbeed_raylib_common::{
+    color, impl_cyclic_enum,
+    input::InputManager,
+    library::{Library, elapsed_since},
+    saves,
+};
 use raylib::prelude::*;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 
 use crate::{
+    controller::ConsoleController,
     scenes::{EmulationState, EmulatorState, GameMenuState, SettingsMenuState},
-    utils::{layout::*, roms, truncate_name},
+    utils::{
+        layout::*,
+        roms::{self, Rom, RomScan},
+        truncate_name,
+    },
 };
 
+/// Characters the search is typed with, picked one at a time with up and down
+const SEARCH_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryView {
+    Folders,
+    Favourites,
+    Recents,
+    Search,
+}
+
+impl_cyclic_enum!(
+    LibraryView,
+    [
+        LibraryView::Folders,
+        LibraryView::Favourites,
+        LibraryView::Recents,
+        LibraryView::Search
+    ]
+);
+
+impl LibraryView {
+    pub fn name(&self) -> &str {
+        match self {
+            LibraryView::Folders => "Library",
+            LibraryView::Favourites => "Favourites",
+            LibraryView::Recents => "Recents",
+            LibraryView::Search => "Search",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    /// Back to the parent directory
+    Parent,
+    Folder(PathBuf),
+    /// Index in `roms`
+    Rom(usize),
+    /// The search being typed
+    Query,
+}
+
+/// # Library browser
+/// Every ROM in the ROM roots, browsed by folder, among the favourites, by when it was last played
+/// or searched by name. Everything is reachable with the d-pad and face buttons alone: select
+/// switches views, select held with up and down jumps between initial letters, and the search is
+/// typed a letter at a time
 #[derive(Debug)]
 pub struct SelectionMenuState {
-    pub roms: Vec<PathBuf>,
+    pub roms: Vec<Rom>,
+    /// ROM roots holding any ROM
+    pub roots: Vec<PathBuf>,
+    pub view: LibraryView,
+    /// Directory listed in the folders view, `None` for the roots themselves
+    pub dir: Option<PathBuf>,
+    pub query: String,
+    /// Typing the search, up and down change its last letter instead of moving
+    pub editing_query: bool,
+    /// Items of the current view along with their labels, listed again whenever it changes
+    pub items: Vec<(Item, String)>,
+    pub listed: bool,
     pub selected: usize,
     pub scroll_offset: usize,
     pub input: InputManager,
-    pub confirming_new_game: bool,
+    /// Select was held to jump between letters, so releasing it doesn't switch views
+    pub jumped: bool,
+    /// ROM to load once loading it over the running game is confirmed
+    pub confirming: Option<PathBuf>,
+    /// Generation of the ROM scan `roms` were copied from, `None` until the first frame
+    pub generation: Option<u64>,
+    /// The ROM scan is still running, so an empty list doesn't mean there are no ROMs
+    pub scanning: bool,
 }
 
 impl SelectionMenuState {
     pub fn new() -> Self {
         Self {
-            roms: roms::find_roms(),
+            roms: Vec::new(),
+            roots: Vec::new(),
+            view: LibraryView::Folders,
+            dir: None,
+            query: String::new(),
+            editing_query: false,
+            items: Vec::new(),
+            listed: false,
             selected: 0,
             scroll_offset: 0,
             input: InputManager::default(),
-            confirming_new_game: false,
+            jumped: false,
+            confirming: None,
+            generation: None,
+            scanning: true,
         }
     }
 
     pub fn update(
         &mut self,
-        rl: &RaylibHandle,
+        controller: &mut ConsoleController,
         dt: f32,
         rom_path: &mut Option<PathBuf>,
         gb: &mut Option<Dmg>,
         save_path: &mut Option<PathBuf>,
     ) -> Result<Option<EmulatorState>, Box<dyn std::error::Error>> {
-        self.input.update(rl, dt);
+        self.input.update(controller.rl, dt);
+        if self.generation.is_none() {
+            controller.roms.refresh();
+        }
+        controller.roms.poll();
+        self.sync(&controller.roms, &controller.library);
+        if !self.listed {
+            self.list(&controller.library);
+        }
 
-        if self.confirming_new_game {
+        if let Some(path) = &self.confirming {
             if self.input.is_pressed_a() || self.input.is_pressed_start() {
-                self.confirming_new_game = false;
-                let path = self.roms[self.selected].clone();
-                let cartridge = roms::load_cartridge(&path, save_path)?;
-                *rom_path = Some(path);
-                *gb = Some(Dmg::new(cartridge, None));
-
-                return Ok(Some(EmulatorState::Emulation(EmulationState::new())));
+                let path = path.clone();
+                self.confirming = None;
+                return self.play(controller, path, rom_path, gb, save_path);
             } else if self.input.is_pressed_b() {
-                self.confirming_new_game = false;
+                self.confirming = None;
             }
             return Ok(None);
         }
 
-        let move_up = self.input.is_repeated_up(dt);
-        let move_down = self.input.is_repeated_down(dt);
+        if self.editing_query {
+            self.edit_query(dt, &controller.library);
+            return Ok(None);
+        }
 
-        let visible_count = ((VISIBLE_BOTTOM - VISIBLE_TOP) / ITEM_H) as usize;
+        if self.input.is_held_select() {
+            if self.input.is_repeated_up(dt) {
+                self.jump_letter(false);
+                self.jumped = true;
+            }
+            if self.input.is_repeated_down(dt) {
+                self.jump_letter(true);
+                self.jumped = true;
+            }
+        } else {
+            if self.input.is_released_select() {
+                if !self.jumped {
+                    self.view = self.view.next();
+                    self.selected = 0;
+                    self.list(&controller.library);
+                }
+                self.jumped = false;
+            }
 
-        if !self.roms.is_empty() {
-            if move_up && self.selected > 0 {
+            if self.input.is_repeated_up(dt) && self.selected > 0 {
                 self.selected -= 1;
             }
-            if move_down && self.selected + 1 < self.roms.len() {
+            if self.input.is_repeated_down(dt) && self.selected + 1 < self.items.len() {
                 self.selected += 1;
             }
+        }
+        self.scroll_to_selected();
 
-            if self.selected < self.scroll_offset {
-                self.scroll_offset = self.selected;
-            }
-            if self.selected >= self.scroll_offset + visible_count {
-                self.scroll_offset = self.selected + 1 - visible_count;
-            }
+        let item = self.items.get(self.selected).map(|(item, _)| item.clone());
+
+        if self.input.is_pressed_start()
+            && let Some(Item::Rom(index)) = item
+        {
+            controller.library.toggle_favourite(&self.roms[index].path);
+            controller.write_library();
+            self.list(&controller.library);
         }
 
-        if self.input.is_pressed_a() && !self.roms.is_empty() {
-            if gb.is_some() {
-                self.confirming_new_game = true;
-                return Ok(None);
+        if self.input.is_pressed_a() {
+            match item {
+                Some(Item::Parent) => self.go_up(&controller.library),
+                Some(Item::Folder(dir)) => {
+                    self.dir = Some(dir);
+                    self.selected = 0;
+                    self.list(&controller.library);
+                }
+                Some(Item::Rom(index)) => {
+                    let path = self.roms[index].path.clone();
+                    if gb.is_some() {
+                        self.confirming = Some(path);
+                        return Ok(None);
+                    }
+                    return self.play(controller, path, rom_path, gb, save_path);
+                }
+                Some(Item::Query) => {
+                    self.editing_query = true;
+                    if self.query.is_empty() {
+                        self.query.push(SEARCH_CHARS[0] as char);
+                        self.list(&controller.library);
+                    }
+                }
+                None => {}
             }
+        }
 
-            let path = self.roms[self.selected].clone();
-            let cartridge = roms::load_cartridge(&path, save_path)?;
-            *rom_path = Some(path);
-            *gb = Some(Dmg::new(cartridge, None));
-
-            return Ok(Some(EmulatorState::Emulation(EmulationState::new())));
+        if self.input.is_pressed_b() && self.view == LibraryView::Folders {
+            self.go_up(&controller.library);
         }
 
-        if self.input.is_pressed_right() && !self.confirming_new_game {
+        if self.input.is_pressed_right() {
             return Ok(Some(EmulatorState::GameMenu(GameMenuState::new())));
         }
         if self.input.is_repeated_left(dt) {
@@ -101,10 +231,293 @@ impl SelectionMenuState {
         Ok(None)
     }
 
+    fn play(
+        &mut self,
+        controller: &mut ConsoleController,
+        path: PathBuf,
+        rom_path: &mut Option<PathBuf>,
+        gb: &mut Option<Dmg>,
+        save_path: &mut Option<PathBuf>,
+    ) -> Result<Option<EmulatorState>, Box<dyn std::error::Error>> {
+        let (cartridge, patch) = roms::load_default_patched_cartridge(&path, save_path)?;
+        // keep the save as it was before this session, in case it goes wrong
+        if let Some(save_path) = save_path.as_deref()
+            && let Err(e) = saves::backup_save(save_path, controller.config.saves.backups)
+        {
+            eprintln!("Failed to back up {save_path:?}: {e}");
+        }
+        controller.load_game_settings(&cartridge);
+        controller.patch = patch;
+        controller.library.played(&path);
+        controller.write_library();
+        *rom_path = Some(path);
+        *gb = Some(Dmg::new(cartridge, None));
+
+        Ok(Some(EmulatorState::Emulation(EmulationState::new())))
+    }
+
+    /// Copy the ROMs of the scan whenever they change, keeping the selected ROM selected
+    fn sync(&mut self, scan: &RomScan, library: &Library) {
+        if self.generation == Some(scan.generation) {
+            return;
+        }
+        self.generation = Some(scan.generation);
+        self.scanning = scan.is_scanning();
+
+        // indices change with the order, so the selected ROM is found again by its path
+        let selected = match self.items.get(self.selected) {
+            Some((Item::Rom(index), _)) => Some(self.roms[*index].path.clone()),
+            _ => None,
+        };
+
+        self.roms = scan.roms.clone();
+        self.roots = roms::rom_roots();
+        self.roots
+            .retain(|root| self.roms.iter().any(|rom| rom.dir.starts_with(root)));
+
+        // a single root is opened right away, there's no point in choosing it,
+        // and a directory on a drive that went away leaves nothing to list
+        match &self.dir {
+            None if self.roots.len() == 1 => self.dir = self.roots.first().cloned(),
+            Some(dir) if !self.roots.iter().any(|root| dir.starts_with(root)) => self.dir = None,
+            _ => {}
+        }
+
+        if self.listed {
+            self.list(library);
+        }
+        if let Some(path) = selected
+            && let Some(position) = self
+                .items
+                .iter()
+                .position(|(item, _)| matches!(item, Item::Rom(index) if self.roms[*index].path == path))
+        {
+            self.selected = position;
+            self.scroll_to_selected();
+        }
+    }
+
+    /// Up and down cycle the last letter, a starts the next one, b deletes it and start is done
+    fn edit_query(&mut self, dt: f32, library: &Library) {
+        let last = self
+            .query
+            .bytes()
+            .last()
+            .and_then(|c| SEARCH_CHARS.iter().position(|&s| s == c))
+            .unwrap_or(0);
+        let step = |delta: usize| SEARCH_CHARS[(last + delta) % SEARCH_CHARS.len()] as char;
+
+        let mut changed = true;
+        if self.input.is_repeated_up(dt) {
+            self.query.pop();
+            self.query.push(step(SEARCH_CHARS.len() - 1));
+        } else if self.input.is_repeated_down(dt) {
+            self.query.pop();
+            self.query.push(step(1));
+        } else if self.input.is_pressed_a() {
+            self.query.push(SEARCH_CHARS[0] as char);
+        } else if self.input.is_pressed_b() {
+            self.query.pop();
+            self.editing_query = !self.query.is_empty();
+        } else {
+            changed = false;
+        }
+
+        if self.input.is_pressed_start() || self.input.is_pressed_escape() {
+            self.editing_query = false;
+            changed = true;
+        }
+        if changed {
+            self.list(library);
+        }
+    }
+
+    /// Back to the parent directory, or to the roots from the top of one, with the folder left selected
+    fn go_up(&mut self, library: &Library) {
+        let Some(dir) = self.dir.take() else {
+            return;
+        };
+
+        self.dir = match self.roots.contains(&dir) {
+            true if self.roots.len() > 1 => None,
+            true => Some(dir.clone()),
+            false => dir.parent().map(Path::to_path_buf),
+        };
+        self.list(library);
+
+        let left = Item::Folder(dir);
+        self.selected = self.items.iter().position(|(item, _)| *item == left).unwrap_or(0);
+        self.scroll_to_selected();
+    }
+
+    /// List the items of the current view, keeping the selection in range
+    fn list(&mut self, library: &Library) {
+        let items = match self.view {
+            LibraryView::Folders => self.folder_items(),
+            LibraryView::Favourites => (0..self.roms.len())
+                .filter(|&index| library.is_favourite(&self.roms[index].path))
+                .map(Item::Rom)
+                .collect(),
+            LibraryView::Recents => library
+                .recents
+                .iter()
+                .filter_map(|recent| self.roms.iter().position(|rom| rom.path == recent.path))
+                .map(Item::Rom)
+                .collect(),
+            LibraryView::Search => {
+                let query = self.query.trim().to_uppercase();
+                let matches = (0..self.roms.len()).filter(|&index| {
+                    let rom = &self.roms[index];
+                    let file_name = rom.path.file_name().unwrap_or_default().to_string_lossy();
+                    !query.is_empty()
+                        && (rom.name.to_uppercase().contains(&query)
+                            || file_name.to_uppercase().contains(&query))
+                });
+                std::iter::once(Item::Query)
+                    .chain(matches.map(Item::Rom))
+                    .collect()
+            }
+        };
+
+        self.items = items
+            .into_iter()
+            .map(|item| {
+                let label = self.label(&item, library);
+                (item, label)
+            })
+            .collect();
+        self.selected = self.selected.min(self.items.len().saturating_sub(1));
+        self.listed = true;
+        self.scroll_to_selected();
+    }
+
+    /// Subdirectories holding ROMs first, then the ROMs in the listed directory
+    fn folder_items(&self) -> Vec<Item> {
+        let Some(dir) = &self.dir else {
+            return self.roots.iter().cloned().map(Item::Folder).collect();
+        };
+
+        let mut items = Vec::new();
+        if self.roots.len() > 1 || !self.roots.contains(dir) {
+            items.push(Item::Parent);
+        }
+
+        let mut folders = BTreeSet::new();
+        let mut roms = Vec::new();
+        for (index, rom) in self.roms.iter().enumerate() {
+            if rom.dir == *dir {
+                roms.push(Item::Rom(index));
+            } else if let Ok(rest) = rom.dir.strip_prefix(dir)
+                && let Some(first) = rest.components().next()
+            {
+                folders.insert(dir.join(first));
+            }
+        }
+
+        items.extend(folders.into_iter().map(Item::Folder));
+        items.extend(roms);
+        items
+    }
+
+    fn label(&self, item: &Item, library: &Library) -> String {
+        match item {
+            Item::Parent => "..".to_string(),
+            // roots go by their whole path, they may be anywhere
+            Item::Folder(dir) if self.dir.is_none() => dir.display().to_string(),
+            Item::Folder(dir) => format!("{}/", dir.file_name().unwrap_or_default().to_string_lossy()),
+            Item::Rom(index) => {
+                let rom = &self.roms[*index];
+                let recent = library.recents.iter().find(|recent| recent.path == rom.path);
+
+                match (self.view, recent) {
+                    (LibraryView::Recents, Some(recent)) => {
+                        format!("{:>3} {}", elapsed_since(recent.played), rom.name)
+                    }
+                    _ if library.is_favourite(&rom.path) => format!("* {}", rom.name),
+                    _ => rom.name.clone(),
+                }
+            }
+            Item::Query => match self.editing_query {
+                true => format!("Search: {}_", self.query),
+                false => format!("Search: {}", self.query),
+            },
+        }
+    }
+
+    /// Initial letter items are grouped by, ignoring favourite marks and the like
+    fn letter(&self, index: usize) -> Option<char> {
+        let name = match &self.items.get(index)?.0 {
+            Item::Folder(dir) => dir.file_name()?.to_string_lossy().into_owned(),
+            Item::Rom(index) => self.roms[*index].name.clone(),
+            Item::Parent | Item::Query => return None,
+        };
+
+        name.chars().find(|c| c.is_alphanumeric()).map(|c| {
+            if c.is_alphabetic() {
+                c.to_ascii_uppercase()
+            } else {
+                '#'
+            }
+        })
+    }
+
+    /// Select the first item of the next or previous initial letter
+    fn jump_letter(&mut self, forward: bool) {
+        let current = self.letter(self.selected);
+
+        self.selected = if forward {
+            (self.selected..self.items.len())
+                .find(|&index| self.letter(index) != current)
+                .unwrap_or(self.selected)
+        } else {
+            let group_start = |mut index: usize| {
+                let letter = self.letter(index);
+                while index > 0 && self.letter(index - 1) == letter {
+                    index -= 1;
+                }
+                index
+            };
+
+            match group_start(self.selected) {
+                0 => 0,
+                start if start < self.selected => start,
+                start => group_start(start - 1),
+            }
+        };
+        self.scroll_to_selected();
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let visible_count = ((VISIBLE_BOTTOM - VISIBLE_TOP) / ITEM_H) as usize;
+
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        }
+        if self.selected >= self.scroll_offset + visible_count {
+            self.scroll_offset = self.selected + 1 - visible_count;
+        }
+    }
+
+    /// Controls of the current view for the footer
+    pub fn hint(&self) -> &'static str {
+        match self.view {
+            _ if self.editing_query => "w/s letter  a next  b delete  start done",
+            LibraryView::Folders => "a open  b back  start fav  select view",
+            LibraryView::Search => "a on search to type  select view",
+            LibraryView::Favourites | LibraryView::Recents => "a play  start fav  select view",
+        }
+    }
+
     pub fn draw(&self, d: &mut RaylibDrawHandle, palette_color: &color::PaletteColor) {
-        if self.roms.is_empty() {
+        if self.items.is_empty() {
+            let text = match self.view {
+                LibraryView::Folders | LibraryView::Search if self.scanning => "looking for roms...",
+                LibraryView::Folders | LibraryView::Search => "no roms found",
+                LibraryView::Favourites => "start on a rom to add it here",
+                LibraryView::Recents => "no games played yet",
+            };
             d.draw_text(
-                "no roms found",
+                text,
                 PADDING_X,
                 VISIBLE_TOP + SECTION_PAD,
                 FONT_SIZE,
@@ -117,19 +530,16 @@ impl SelectionMenuState {
         let max_chars = (text_area_w / (FONT_SIZE / 2).max(1)) as usize;
 
         let names: Vec<String> = self
-            .roms
+            .items
             .iter()
-            .map(|path| {
-                let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("MissingNo");
-                truncate_name(name, max_chars)
-            })
+            .map(|(_, label)| truncate_name(label, max_chars))
             .collect();
 
         let items: Vec<(&str, &str)> = names.iter().map(|n| (n.as_str(), "")).collect();
 
         draw_menu_list(d, &items, self.selected, self.scroll_offset, palette_color);
 
-        if self.confirming_new_game {
+        if self.confirming.is_some() {
             d.draw_rectangle(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT, Color::new(0, 0, 0, 180));
 
             let texts = [
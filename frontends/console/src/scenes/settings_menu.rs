@@ -1,23 +1,34 @@
 use std::fmt::Debug;
 
 use crate::controller::ConsoleController;
-use crate::scenes::{EmulationState, EmulatorState, GameMenuState, SelectionMenuState};
+use crate::scenes::{EmulationState, EmulatorState, SaveMenuState, SelectionMenuState};
 use crate::utils::layout::{self, *};
 use gbeed_core::Dmg;
 use gbeed_raylib_common::{
     color::{Palette, PaletteColor},
+    filter::ScreenFilters,
     impl_cyclic_enum,
     input::InputManager,
-    settings::{SpeedUpMode, SpeedUpMultiplier, TargetedFps},
+    settings::{
+        AudioOutput, SampleRate, ScreenFilter, ScreenScaling, SpeedUpMode, SpeedUpMultiplier, SyncMode,
+        TargetedFps, TurboRate,
+    },
 };
 use raylib::prelude::*;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SettingsOption {
     ColorPalette,
+    ScreenFilter,
+    FrameBlending,
+    ScreenScaling,
     SpeedUpMode,
     SpeedUpMultiplier,
+    TurboRate,
     TargetedFps,
+    SampleRate,
+    AudioOutput,
+    SyncMode,
     DrawDebugInfo,
     Exit,
 }
@@ -28,9 +39,16 @@ impl_cyclic_enum!(
     SettingsOption,
     [
         ColorPalette,
+        ScreenFilter,
+        FrameBlending,
+        ScreenScaling,
         SpeedUpMode,
         SpeedUpMultiplier,
+        TurboRate,
         TargetedFps,
+        SampleRate,
+        AudioOutput,
+        SyncMode,
         DrawDebugInfo,
         Exit
     ]
@@ -40,9 +58,16 @@ impl SettingsOption {
     pub fn name(&self) -> &str {
         match self {
             ColorPalette => "Color Palette",
+            ScreenFilter => "Screen Filter",
+            FrameBlending => "Frame Blending",
+            ScreenScaling => "Screen Scaling",
             SpeedUpMode => "Speed Up Mode",
             SpeedUpMultiplier => "Speed Up Multiplier",
+            TurboRate => "Turbo Rate",
             TargetedFps => "Targeted FPS",
+            SampleRate => "Sample Rate",
+            AudioOutput => "Audio Output",
+            SyncMode => "Sync Mode",
             DrawDebugInfo => "Draw Debug Info",
             Exit => "Exit",
         }
@@ -72,6 +97,7 @@ impl SettingsMenuState {
         controller: &mut ConsoleController,
     ) -> Option<EmulatorState> {
         self.input.update(controller.rl, dt);
+        let previous = controller.settings();
 
         let visible_count = ((VISIBLE_BOTTOM - VISIBLE_TOP) / ITEM_H) as usize;
 
@@ -93,7 +119,7 @@ impl SettingsMenuState {
         }
 
         if self.input.is_repeated_left(dt) {
-            return Some(EmulatorState::GameMenu(GameMenuState::new()));
+            return Some(EmulatorState::SaveMenu(SaveMenuState::new()));
         }
         if self.input.is_repeated_right(dt) {
             return Some(EmulatorState::SelectionMenu(SelectionMenuState::new()));
@@ -111,6 +137,27 @@ impl SettingsMenuState {
                     controller.palette = controller.palette.prev();
                 }
             }
+
+            ScreenFilter => {
+                if self.input.is_pressed_a() {
+                    controller.filters.filter = controller.filters.filter.next();
+                }
+                if self.input.is_pressed_b() {
+                    controller.filters.filter = controller.filters.filter.prev();
+                }
+            }
+
+            FrameBlending => {
+                if self.input.is_pressed_a() || self.input.is_pressed_b() {
+                    controller.filters.frame_blending = !controller.filters.frame_blending;
+                }
+            }
+
+            ScreenScaling => {
+                if self.input.is_pressed_a() || self.input.is_pressed_b() {
+                    controller.filters.scaling = controller.filters.scaling.next();
+                }
+            }
             SpeedUpMode => {
                 if self.input.is_pressed_a() || self.input.is_pressed_b() {
                     controller.speed_up_mode = controller.speed_up_mode.next();
@@ -126,6 +173,15 @@ impl SettingsMenuState {
                 }
             }
 
+            TurboRate => {
+                if self.input.is_pressed_a() {
+                    controller.turbo.rate = controller.turbo.rate.next();
+                }
+                if self.input.is_pressed_b() {
+                    controller.turbo.rate = controller.turbo.rate.prev();
+                }
+            }
+
             TargetedFps => {
                 if self.input.is_pressed_a() {
                     controller.targeted_fps = controller.targeted_fps.next();
@@ -137,6 +193,31 @@ impl SettingsMenuState {
                 }
             }
 
+            SampleRate => {
+                if self.input.is_pressed_a() {
+                    controller
+                        .audio
+                        .set_sample_rate(controller.audio.sample_rate().next());
+                }
+                if self.input.is_pressed_b() {
+                    controller
+                        .audio
+                        .set_sample_rate(controller.audio.sample_rate().prev());
+                }
+            }
+
+            AudioOutput => {
+                if self.input.is_pressed_a() || self.input.is_pressed_b() {
+                    controller.audio.set_output(controller.audio.output().next());
+                }
+            }
+
+            SyncMode => {
+                if self.input.is_pressed_a() || self.input.is_pressed_b() {
+                    controller.sync_mode = controller.sync_mode.next();
+                }
+            }
+
             DrawDebugInfo => {
                 if self.input.is_pressed_a() || self.input.is_pressed_b() {
                     controller.draw_debug_info = !controller.draw_debug_info;
@@ -151,6 +232,9 @@ impl SettingsMenuState {
         }
 
         controller.palette_color = controller.palette.get_palette_color();
+        if controller.settings() != previous {
+            controller.save_settings();
+        }
 
         None
     }
@@ -161,9 +245,14 @@ impl SettingsMenuState {
         d: &mut RaylibDrawHandle,
         palette: &Palette,
         palette_color: &PaletteColor,
+        filters: &ScreenFilters,
         speed_up_mode: &SpeedUpMode,
         speed_up_multiplier: &SpeedUpMultiplier,
+        turbo_rate: &TurboRate,
         targeted_fps: &TargetedFps,
+        sample_rate: &SampleRate,
+        audio_output: &AudioOutput,
+        sync_mode: &SyncMode,
         draw_debug_info: bool,
     ) {
         let items: Vec<(&str, &str)> = SettingsOption::ALL
@@ -171,6 +260,18 @@ impl SettingsMenuState {
             .map(|opt| {
                 let value: &str = match opt {
                     ColorPalette => palette.name(),
+                    ScreenFilter => match filters.filter {
+                        ScreenFilter::Off => "Off",
+                        ScreenFilter::LcdGrid => "LCD Grid",
+                        ScreenFilter::Scale2x => "Scale2x",
+                        ScreenFilter::Scale3x => "Scale3x",
+                    },
+                    FrameBlending if filters.frame_blending => "On",
+                    FrameBlending => "Off",
+                    ScreenScaling => match filters.scaling {
+                        ScreenScaling::Stretch => "Stretch",
+                        ScreenScaling::Integer => "Integer",
+                    },
                     SpeedUpMode => match speed_up_mode {
                         SpeedUpMode::Toggle(_) => "Toggle",
                         SpeedUpMode::Hold => "Hold",
@@ -180,11 +281,31 @@ impl SettingsMenuState {
                         SpeedUpMultiplier::Double => "2x",
                         SpeedUpMultiplier::Cuadruple => "4x",
                     },
+                    TurboRate => match turbo_rate {
+                        TurboRate::Turbo7_5 => "7.5 Hz",
+                        TurboRate::Turbo10 => "10 Hz",
+                        TurboRate::Turbo15 => "15 Hz",
+                        TurboRate::Turbo30 => "30 Hz",
+                    },
                     TargetedFps => match targeted_fps {
                         TargetedFps::Target30 => "30",
                         TargetedFps::Target60 => "60",
                         TargetedFps::Unlimited => "Unlimited",
                     },
+                    SampleRate => match sample_rate {
+                        SampleRate::Rate22050 => "22050 Hz",
+                        SampleRate::Rate32000 => "32000 Hz",
+                        SampleRate::Rate44100 => "44100 Hz",
+                        SampleRate::Rate48000 => "48000 Hz",
+                    },
+                    AudioOutput => match audio_output {
+                        AudioOutput::Stereo => "Stereo",
+                        AudioOutput::Mono => "Mono",
+                    },
+                    SyncMode => match sync_mode {
+                        SyncMode::Video => "Video",
+                        SyncMode::Audio => "Audio",
+                    },
                     DrawDebugInfo if draw_debug_info => "On",
                     DrawDebugInfo => "Off",
 
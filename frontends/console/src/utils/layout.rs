@@ -36,10 +36,11 @@ pub fn draw_header(
     current_state: &EmulatorState,
     palette_color: &color::PaletteColor,
 ) {
-    let (sel_active, game_active, set_active) = match current_state {
-        EmulatorState::SelectionMenu(_) => (true, false, false),
-        EmulatorState::GameMenu(_) => (false, true, false),
-        EmulatorState::SettingsMenu(_) => (false, false, true),
+    let (sel_active, game_active, saves_active, set_active) = match current_state {
+        EmulatorState::SelectionMenu(_) => (true, false, false, false),
+        EmulatorState::GameMenu(_) => (false, true, false, false),
+        EmulatorState::SaveMenu(_) => (false, false, true, false),
+        EmulatorState::SettingsMenu(_) => (false, false, false, true),
         EmulatorState::Emulation(_) | EmulatorState::Exit => return,
     };
 
@@ -55,19 +56,39 @@ pub fn draw_header(
 
     let (sel_size, sel_color) = tab_style(sel_active);
     let (game_size, game_color) = tab_style(game_active);
+    let (saves_size, saves_color) = tab_style(saves_active);
     let (set_size, set_color) = tab_style(set_active);
 
     let y = PADDING_Y + (HEADER_H - FONT_SIZE) / 2;
 
-    let sel_text = "Selection";
+    let sel_text = match current_state {
+        EmulatorState::SelectionMenu(state) => state.view.name(),
+        _ => "Selection",
+    };
     let game_text = "Game";
+    let saves_text = "Saves";
     let set_text = "Settings";
 
     let game_w = d.measure_text(game_text, game_size);
+    let saves_w = d.measure_text(saves_text, saves_size);
     let set_w = d.measure_text(set_text, set_size);
 
     d.draw_text(sel_text, PADDING_X, y, sel_size, sel_color);
-    d.draw_text(game_text, (SCREEN_WIDTH - game_w) / 2, y, game_size, game_color);
+    // the middle tabs split the room between the ones at the edges
+    d.draw_text(
+        game_text,
+        SCREEN_WIDTH * 5 / 12 - game_w / 2,
+        y,
+        game_size,
+        game_color,
+    );
+    d.draw_text(
+        saves_text,
+        SCREEN_WIDTH * 7 / 12 - saves_w / 2,
+        y,
+        saves_size,
+        saves_color,
+    );
     d.draw_text(set_text, SCREEN_WIDTH - PADDING_X - set_w, y, set_size, set_color);
 
     d.draw_line(
@@ -169,8 +190,9 @@ pub fn draw_menu_list(
 /// Draws the selection menu footer with control hints
 pub fn draw_footer(d: &mut RaylibDrawHandle, state: &EmulatorState, palette_color: &color::PaletteColor) {
     let hint = match state {
-        EmulatorState::SelectionMenu(_) => "w/s to navigate roms and a to select",
-        EmulatorState::GameMenu(_) => "a to enter back the game",
+        EmulatorState::SelectionMenu(state) => state.hint(),
+        EmulatorState::GameMenu(_) => "w/s to pick a patch, b a backup and a to enter the game",
+        EmulatorState::SaveMenu(_) => "w/s to navigate, b to change and a to export or import",
         EmulatorState::SettingsMenu(_) => "w/s to navigate and a/b to change values",
         EmulatorState::Emulation(_) | EmulatorState::Exit => return,
     };
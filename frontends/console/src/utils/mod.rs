@@ -2,9 +2,10 @@ pub mod layout;
 pub mod roms;
 
 pub fn truncate_name(name: &str, max_chars: usize) -> String {
-    if name.len() <= max_chars {
+    if name.chars().count() <= max_chars {
         name.to_string()
     } else {
-        format!("{}...", &name[..max_chars.saturating_sub(3)])
+        let kept: String = name.chars().take(max_chars.saturating_sub(3)).collect();
+        format!("{kept}...")
     }
 }
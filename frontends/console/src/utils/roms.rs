@@ -1,66 +1,239 @@
 use std::{
+    collections::{HashMap, HashSet},
     fs, io,
     path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
 };
 
-use gbeed_core::{Cartridge, Dmg};
+use gbeed_core::{Cartridge, Dmg, crc32};
+use gbeed_raylib_common::{
+    archive,
+    config::{Paths, resolve_dir},
+    identify, patch, saves,
+};
 
 #[inline(always)]
-fn home_dir() -> PathBuf {
-    std::env::var("HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from("."))
-}
+pub fn roms_dir() -> PathBuf { resolve_dir(Paths::configured(), |paths| &paths.roms, "roms") }
 
 #[inline(always)]
-pub fn roms_dir() -> PathBuf {
-    if cfg!(debug_assertions) {
-        PathBuf::from(env!("WORKSPACE_ROOT")).join("roms")
-    } else {
-        home_dir().join("roms")
-    }
+pub fn saves_dir() -> PathBuf { resolve_dir(Paths::configured(), |paths| &paths.saves, "saves") }
+
+#[inline(always)]
+pub fn recordings_dir() -> PathBuf {
+    resolve_dir(Paths::configured(), |paths| &paths.recordings, "recordings")
 }
 
 #[inline(always)]
-pub fn saves_dir() -> PathBuf {
-    if cfg!(debug_assertions) {
-        PathBuf::from(env!("WORKSPACE_ROOT")).join("saves")
-    } else {
-        home_dir().join("saves")
-    }
+pub fn screenshots_dir() -> PathBuf {
+    resolve_dir(Paths::configured(), |paths| &paths.screenshots, "screenshots")
 }
-pub fn find_roms() -> Vec<PathBuf> {
-    let dir = roms_dir();
 
-    let mut roms: Vec<PathBuf> = fs::read_dir(&dir)
+/// Default mount point of removable drives
+const MEDIA_DIR: &str = "/media";
+/// How deep into a ROM directory ROMs are looked for, which also stops at symlink loops
+const MAX_DEPTH: usize = 8;
+
+/// Every directory ROMs are looked for in: the ROMs directory, the extra ones set in the config,
+/// and each drive mounted in the media directory, those automounted by udisks being a level deeper
+/// in a directory per user that is searched as a whole
+pub fn rom_roots() -> Vec<PathBuf> {
+    let paths = Paths::configured();
+
+    let mut roots = vec![roms_dir()];
+    roots.extend(paths.iter().flat_map(|paths| paths.extra_roms.iter().cloned()));
+
+    let media = paths
+        .and_then(|paths| paths.media.clone())
+        .unwrap_or_else(|| PathBuf::from(MEDIA_DIR));
+    let mut drives: Vec<PathBuf> = fs::read_dir(media)
         .map(|entries| {
             entries
                 .filter_map(Result::ok)
                 .map(|entry| entry.path())
-                .filter(|path| {
-                    path.is_file()
-                        && path
-                            .extension()
-                            .and_then(|ext| ext.to_str())
-                            .map(|ext| ext.eq_ignore_ascii_case("gb") || ext.eq_ignore_ascii_case("gbc"))
-                            .unwrap_or(false)
-                })
+                .filter(|path| path.is_dir())
                 .collect()
         })
         .unwrap_or_default();
+    drives.sort();
+    roots.extend(drives);
+
+    let mut seen = HashSet::new();
+    roots.retain(|root| seen.insert(root.clone()));
+    roots
+}
+
+/// Every ROM in `roots` and their subdirectories, archives included
+pub fn find_roms(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut roms = Vec::new();
+    for root in roots {
+        find_roms_in(root, MAX_DEPTH, &mut roms);
+    }
 
     roms.sort();
+    roms.dedup();
     roms
 }
 
+fn find_roms_in(dir: &Path, depth: usize, roms: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+        // hidden directories are trash bins and version control more often than not
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        if path.is_dir() && !hidden && depth > 0 {
+            find_roms_in(&path, depth - 1, roms);
+        } else if path.is_file() {
+            roms.extend(
+                archive::list_roms(&path)
+                    .inspect_err(|e| eprintln!("Failed to list ROMs in {path:?}: {e}"))
+                    .unwrap_or_default(),
+            );
+        }
+    }
+}
+
+/// A ROM found in the ROM roots
+#[derive(Debug, Clone)]
+pub struct Rom {
+    pub path: PathBuf,
+    /// Directory it is listed in, the one holding its archive for ROMs inside one
+    pub dir: PathBuf,
+    /// Canonical name when the DATs know it, file name otherwise
+    pub name: String,
+}
+
+impl Rom {
+    fn new(path: PathBuf, name: Option<String>) -> Self {
+        let unpacked = archive::unpacked_path(&path);
+        let name = name.unwrap_or_else(|| {
+            path.file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("MissingNo")
+                .to_string()
+        });
+
+        Self {
+            dir: unpacked.parent().map(Path::to_path_buf).unwrap_or_default(),
+            name,
+            path,
+        }
+    }
+}
+
+enum ScanEvent {
+    Found(Vec<PathBuf>),
+    Identified(PathBuf, String),
+}
+
+/// # ROM scan
+/// Every ROM in the ROM roots, found and identified on a background thread as walking every drive
+/// and hashing a whole library takes a while on a Pi. It's kept for the whole session, and only
+/// scanned again when the roots change, like when a drive is plugged in
+#[derive(Debug, Default)]
+pub struct RomScan {
+    /// Sorted by name once every ROM is identified
+    pub roms: Vec<Rom>,
+    /// Changes every time `roms` does
+    pub generation: u64,
+    /// Roots of the last scan
+    roots: Option<Vec<PathBuf>>,
+    events: Option<Receiver<ScanEvent>>,
+}
+
+impl RomScan {
+    #[inline(always)]
+    pub fn is_scanning(&self) -> bool { self.events.is_some() }
+
+    /// Scan the ROM roots unless they are the ones already scanned, or a scan is still running
+    pub fn refresh(&mut self) {
+        let roots = rom_roots();
+        if self.is_scanning() || self.roots.as_ref() == Some(&roots) {
+            return;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let scanned = roots.clone();
+        thread::spawn(move || {
+            let paths = find_roms(&scanned);
+            if tx.send(ScanEvent::Found(paths.clone())).is_err() {
+                return;
+            }
+
+            for path in paths {
+                if let Some(info) = identify::identify_file(&path)
+                    && tx.send(ScanEvent::Identified(path, info.name)).is_err()
+                {
+                    break;
+                }
+            }
+            if let Err(e) = identify::save_cache() {
+                eprintln!("Failed to save the identified ROMs: {e}");
+            }
+        });
+        self.roots = Some(roots);
+        self.events = Some(rx);
+    }
+
+    /// Take what the scan found since the last call, sorting the ROMs again once it's done
+    pub fn poll(&mut self) {
+        let Some(events) = &self.events else {
+            return;
+        };
+
+        let mut changed = false;
+        let done = loop {
+            match events.try_recv() {
+                Ok(ScanEvent::Found(paths)) => {
+                    // names of the last scan are kept until this one identifies them again
+                    let mut known: HashMap<PathBuf, String> =
+                        self.roms.drain(..).map(|rom| (rom.path, rom.name)).collect();
+                    self.roms = paths
+                        .into_iter()
+                        .map(|path| {
+                            let name = known.remove(&path);
+                            Rom::new(path, name)
+                        })
+                        .collect();
+                    self.roms.sort_by_cached_key(|rom| rom.name.to_lowercase());
+                    changed = true;
+                }
+                Ok(ScanEvent::Identified(path, name)) => {
+                    if let Some(rom) = self.roms.iter_mut().find(|rom| rom.path == path) {
+                        rom.name = name;
+                        changed = true;
+                    }
+                }
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+
+        if done {
+            self.events = None;
+            self.roms.sort_by_cached_key(|rom| rom.name.to_lowercase());
+            changed = true;
+        }
+        if changed {
+            self.generation += 1;
+        }
+    }
+}
+
 pub fn load_cartridge(
-    game_path: &PathBuf,
+    game_path: &Path,
+    patch: Option<&Path>,
     save_path: &mut Option<PathBuf>,
 ) -> Result<Cartridge, Box<dyn std::error::Error>> {
     let s_path = save_path_from_rom(game_path);
     *save_path = Some(s_path.clone());
 
-    let game_data = fs::read(game_path).map_err(|e| {
+    let game_data = patch::read_patched_rom(game_path, patch).map_err(|e| {
         io::Error::new(
             io::ErrorKind::NotFound,
             format!("Failed to read game ROM at {game_path:?}: {e}"),
@@ -85,33 +258,76 @@ pub fn load_cartridge(
     })
 }
 
-pub fn save_cartridge(gb: &Dmg, save_path: &Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+/// Load a ROM with the patch named after it applied, or unpatched when it doesn't apply,
+/// returning the patch that was applied along with the cartridge
+pub fn load_default_patched_cartridge(
+    game_path: &Path,
+    save_path: &mut Option<PathBuf>,
+) -> Result<(Cartridge, Option<PathBuf>), Box<dyn std::error::Error>> {
+    if let Some(patch) = patch::default_patch(game_path) {
+        match load_cartridge(game_path, Some(&patch), save_path) {
+            Ok(cartridge) => return Ok((cartridge, Some(patch))),
+            Err(e) => eprintln!("Loading {game_path:?} unpatched: {e}"),
+        }
+    }
+
+    Ok((load_cartridge(game_path, None, save_path)?, None))
+}
+
+/// Write the save if it changed since it was last written, in a way that survives losing power
+pub fn save_cartridge(gb: &mut Dmg, save_path: &Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
     let Some(save_path) = save_path else {
         return Err("Save path not set".into());
     };
 
+    if !gb.cartridge.save_changed() {
+        return Ok(());
+    }
     if let Some(save_data) = gb.cartridge.save_game() {
-        // Ensure the saves directory exists before writing
-        if let Some(parent) = save_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        fs::write(save_path, save_data)?;
+        saves::write_save(save_path, save_data)?;
+        gb.cartridge.mark_saved();
     }
 
     Ok(())
 }
 
 /// Builds the .sav path for a given ROM path, redirecting it to the
-/// correct saves directory depending on the build profile.
+/// configured saves directory, or the default one for the build profile.
+/// Saves mirror the folders of the ROMs directory, so ROMs sharing a name in different
+/// folders keep their own saves, and ROMs anywhere else get a short hash of their path.
 ///
 /// # Debug build
-/// `/path/to/project/roms/pokemon.gb` -> `/path/to/project/saves/pokemon.sav`
+/// `/path/to/project/roms/EU/pokemon.gb` -> `/path/to/project/saves/EU/pokemon.sav`
 ///
 /// # Release build
 /// `/home/user/roms/pokemon.gb` -> `/home/user/saves/pokemon.sav`
+///
+/// # Elsewhere
+/// `/media/usb/pokemon.gb` -> `/home/user/saves/pokemon-1a2b3c4d.sav`
 #[inline(always)]
 pub fn save_path_from_rom(rom_path: &Path) -> PathBuf {
-    let stem = rom_path.file_stem().unwrap_or(rom_path.as_os_str());
+    // ROMs inside archives are saved as if the archive was unpacked where it is
+    let unpacked = archive::unpacked_path(rom_path);
+    let roms = roms_dir();
+
+    match unpacked.strip_prefix(&roms) {
+        Ok(relative) => saves_dir().join(relative).with_extension("sav"),
+        Err(_) => {
+            let stem = unpacked
+                .file_stem()
+                .unwrap_or(unpacked.as_os_str())
+                .to_string_lossy();
+            let hash = crc32(unpacked.as_os_str().as_encoded_bytes());
+            saves_dir().join(format!("{stem}-{hash:08x}.sav"))
+        }
+    }
+}
+
+/// Where [`save_path_from_rom`] put the save of the ROM at `rom_path` before saves followed
+/// the folders ROMs are in, offered as an import when it isn't the same path
+pub fn legacy_save_path(rom_path: &Path) -> PathBuf {
+    let unpacked = archive::unpacked_path(rom_path);
+    let stem = unpacked.file_stem().unwrap_or(unpacked.as_os_str());
 
     saves_dir().join(stem).with_extension("sav")
 }
@@ -0,0 +1,81 @@
+use gbeed_core::prelude::*;
+
+/// Entry points of the five interrupt handlers
+const INTERRUPT_VECTORS: [u16; 5] = [0x40, 0x48, 0x50, 0x58, 0x60];
+
+/// A routine entered by a call, rst or interrupt dispatch
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    /// Address of the call instruction, or of the instruction interrupted
+    pub call_site: u16,
+    /// Stack pointer right after pushing the return address
+    pub sp: u16,
+    pub interrupt: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Observed {
+    pc: u16,
+    sp: u16,
+    opcode: u8,
+}
+
+/// # CallStack
+/// Rebuilds the call stack by watching the CPU between instructions, there's no way to unwind it afterwards.
+/// A frame is pushed when a call, rst or interrupt stores its return address,
+/// and dropped once the stack pointer moves above that address, which covers ret, reti and stack resets
+#[derive(Debug, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    last: Option<Observed>,
+}
+
+impl CallStack {
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.last = None;
+    }
+
+    /// Innermost frame last
+    pub fn frames(&self) -> &[Frame] { &self.frames }
+
+    pub fn depth(&self) -> usize { self.frames.len() }
+
+    /// Must be called at every instruction boundary, observing the same state twice is harmless
+    pub fn observe(&mut self, gb: &Dmg) {
+        let (pc, sp) = (gb.cpu.pc, gb.cpu.sp);
+
+        if let Some(last) = self.last
+            && (last.pc != pc || last.sp != sp)
+        {
+            while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+                self.frames.pop();
+            }
+
+            if sp == last.sp.wrapping_sub(2) {
+                let is_call = is_call(last.opcode);
+                let is_interrupt = !is_call && INTERRUPT_VECTORS.contains(&pc) && !is_push(last.opcode);
+
+                if is_call || is_interrupt {
+                    self.frames.push(Frame {
+                        call_site: last.pc,
+                        sp,
+                        interrupt: is_interrupt,
+                    });
+                }
+            }
+        }
+
+        self.last = Some(Observed {
+            pc,
+            sp,
+            opcode: gb.read(pc),
+        });
+    }
+}
+
+/// call n16, its conditional versions and rst vec, which only push when taken
+fn is_call(opcode: u8) -> bool { matches!(opcode, 0xC4 | 0xCC | 0xCD | 0xD4 | 0xDC) || opcode & 0xC7 == 0xC7 }
+
+/// push r16
+fn is_push(opcode: u8) -> bool { opcode & 0xCF == 0xC5 }
@@ -0,0 +1,126 @@
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod callstack;
+mod protocol;
+mod session;
+mod symbols;
+
+use protocol::{Connection, spawn_reader};
+use session::{LaunchOptions, Session};
+
+/// One frame of emulated time, the game runs at its real speed while the client waits
+const FRAME_DURATION: Duration = Duration::from_nanos(16_742_706);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut options = LaunchOptions::default();
+    let mut port = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-g" | "--game" if i + 1 < args.len() => {
+                options.game_path = Some(PathBuf::from(&args[i + 1]));
+                i += 1;
+            }
+            "-b" | "--boot" | "--boot_rom" if i + 1 < args.len() => {
+                options.boot_path = Some(PathBuf::from(&args[i + 1]));
+                i += 1;
+            }
+            "-s" | "--symbols" if i + 1 < args.len() => {
+                options.symbols_path = Some(PathBuf::from(&args[i + 1]));
+                i += 1;
+            }
+            "-p" | "--port" if i + 1 < args.len() => {
+                port = Some(args[i + 1].parse::<u16>()?);
+                i += 1;
+            }
+            "-h" | "--help" => {
+                print_help();
+                return Ok(());
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut session = Session::new();
+    // a game given from the command line is waiting for clients to attach to it
+    if options.game_path.is_some() {
+        session.load(&options)?;
+    }
+
+    match port {
+        Some(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("Listening for debug adapter clients on 127.0.0.1:{port}");
+
+            for stream in listener.incoming() {
+                let stream = stream?;
+                let output = stream.try_clone()?;
+                if let Err(e) = serve(&mut session, stream, output) {
+                    eprintln!("Client connection failed: {e}");
+                }
+                session.disconnected();
+            }
+        }
+        None => serve(&mut session, io::stdin(), io::stdout())?,
+    }
+
+    Ok(())
+}
+
+/// Handle requests from one client until it disconnects,
+/// running the emulation in between while the client lets it run
+fn serve<R, W>(session: &mut Session, input: R, output: W) -> io::Result<()>
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static,
+{
+    let requests = spawn_reader(input);
+    let mut conn = Connection::new(Box::new(output));
+
+    loop {
+        let request = if session.is_running() {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return Ok(()),
+            }
+        };
+
+        if let Some(request) = request {
+            if !session.handle(&request, &mut conn)? {
+                return Ok(());
+            }
+            continue;
+        }
+
+        let frame_start = Instant::now();
+        session.run_frame(&mut conn)?;
+        if let Some(remaining) = FRAME_DURATION.checked_sub(frame_start.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+fn print_help() {
+    println!("Usage: gbeed-dap [OPTIONS]");
+    println!("Debug Adapter Protocol server, talks to the client over stdio unless a port is given");
+    println!("Options:");
+    println!("  -g, --game <PATH>      Game to run before any client attaches (optional)");
+    println!("  -b, --boot <PATH>      Path to the boot ROM file (optional)");
+    println!("  -s, --symbols <PATH>   RGBDS symbol file, defaults to the game path with the .sym extension");
+    println!("  -p, --port <PORT>      Listen for clients on 127.0.0.1:<PORT> instead of stdio");
+    println!("  -h, --help             Print this help message");
+}
@@ -0,0 +1,107 @@
+use serde_json::{Value, json};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Read every message sent by the client in a background thread,
+/// so the emulation can keep running while waiting for requests.
+/// The channel is closed when the client disconnects
+pub fn spawn_reader<R: Read + Send + 'static>(input: R) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut reader = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+/// Messages are a JSON body preceded by HTTP like headers, only `Content-Length` is meaningful
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(length) = content_length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body).map(Some).map_err(io::Error::from)
+}
+
+/// Sending half of a client connection, numbering every message
+pub struct Connection {
+    output: Box<dyn Write + Send>,
+    seq: u64,
+}
+
+impl Connection {
+    pub fn new(output: Box<dyn Write + Send>) -> Self { Self { output, seq: 1 } }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = json!(self.seq);
+        self.seq += 1;
+
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+
+    pub fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    pub fn respond_error(&mut self, request: &Value, error: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": error,
+            "body": { "error": { "id": 1, "format": error } },
+        }))
+    }
+
+    pub fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }))
+    }
+
+    pub fn output(&mut self, category: &str, text: &str) -> io::Result<()> {
+        self.event("output", json!({ "category": category, "output": text }))
+    }
+}
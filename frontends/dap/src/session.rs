@@ -0,0 +1,1007 @@
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use gbeed_core::prelude::*;
+use gbeed_core::{AudioPlayer, Cpu, DefaultAudioPlayer, DefaultRenderer, DmgError, MAX_INSTRUCTION_LEN, Ppu};
+use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::callstack::CallStack;
+use crate::protocol::Connection;
+use crate::symbols::{Symbols, parse_address, parse_value};
+
+const THREAD_ID: u64 = 1;
+
+const REGISTERS_REF: u64 = 1;
+const HARDWARE_REF: u64 = 2;
+const SYMBOLS_REF: u64 = 3;
+const MEMORY_REF: u64 = 4;
+/// Each memory region gets its own reference starting here, expanding to rows of `ROW_SIZE` bytes
+const MEMORY_REGION_REF: u64 = 0x100;
+const ROW_SIZE: u16 = 16;
+/// Bytes addressable by the CPU
+const ADDRESS_SPACE: usize = 0x10000;
+/// Dots the PPU takes to draw a frame, 154 scanlines of 456 dots
+const FRAME_DOTS: usize = 70_224;
+/// CPU cycles in a frame, one every 4 dots. A halted CPU is given this long to be woken up
+const FRAME_CYCLES: usize = FRAME_DOTS / 4;
+
+const MEMORY_REGIONS: [(&str, u16, u16); 5] = [
+    ("VRAM", 0x8000, 0x9FFF),
+    ("External RAM", 0xA000, 0xBFFF),
+    ("WRAM", 0xC000, 0xDFFF),
+    ("OAM", 0xFE00, 0xFE9F),
+    ("HRAM", 0xFF80, 0xFFFE),
+];
+
+const HARDWARE_REGISTERS: [(&str, u16); 22] = [
+    ("JOYP", 0xFF00),
+    ("SB", 0xFF01),
+    ("SC", 0xFF02),
+    ("DIV", 0xFF04),
+    ("TIMA", 0xFF05),
+    ("TMA", 0xFF06),
+    ("TAC", 0xFF07),
+    ("IF", 0xFF0F),
+    ("NR52", 0xFF26),
+    ("LCDC", 0xFF40),
+    ("STAT", 0xFF41),
+    ("SCY", 0xFF42),
+    ("SCX", 0xFF43),
+    ("LY", 0xFF44),
+    ("LYC", 0xFF45),
+    ("DMA", 0xFF46),
+    ("BGP", 0xFF47),
+    ("OBP0", 0xFF48),
+    ("OBP1", 0xFF49),
+    ("WY", 0xFF4A),
+    ("WX", 0xFF4B),
+    ("IE", 0xFFFF),
+];
+
+/// Collects the bytes sent through the link cable, forwarded to the client as program output
+#[derive(Default)]
+pub struct SerialBuffer {
+    data: Vec<u8>,
+}
+
+impl SerialListener for SerialBuffer {
+    fn on_transfer(&mut self, data: u8) { self.data.push(data); }
+}
+
+controller!(DapController, SerialBuffer, DefaultRenderer, DefaultAudioPlayer);
+
+impl DapController {
+    pub fn new() -> Self {
+        Self {
+            listener: SerialBuffer::default(),
+            renderer: DefaultRenderer::new(),
+            audio_player: DefaultAudioPlayer::new(),
+        }
+    }
+
+    fn take_serial(&mut self) -> Vec<u8> { std::mem::take(&mut self.listener.data) }
+}
+
+/// What the emulation is doing between requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    Stopped,
+    Running,
+    /// Running until the call stack is back to this depth, used by step over and step out
+    RunningUntilDepth(usize),
+}
+
+/// Game to load when the client launches or attaches
+#[derive(Debug, Default, Clone)]
+pub struct LaunchOptions {
+    pub game_path: Option<PathBuf>,
+    pub boot_path: Option<PathBuf>,
+    pub symbols_path: Option<PathBuf>,
+}
+
+/// # Session
+/// Debugger state shared by every client connection, so clients can attach again to a running game
+pub struct Session {
+    gb: Option<Dmg>,
+    controller: DapController,
+    symbols: Symbols,
+    call_stack: CallStack,
+    state: RunState,
+    stop_on_entry: bool,
+
+    /// Function breakpoints as written by the user, resolved again when symbols change
+    function_breakpoints: Vec<String>,
+    instruction_breakpoints: BTreeSet<u16>,
+    breakpoints: BTreeSet<u16>,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            gb: None,
+            controller: DapController::new(),
+            symbols: Symbols::default(),
+            call_stack: CallStack::default(),
+            state: RunState::Stopped,
+            stop_on_entry: false,
+            function_breakpoints: Vec::new(),
+            instruction_breakpoints: BTreeSet::new(),
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool { self.gb.is_some() && self.state != RunState::Stopped }
+
+    /// Load the game, and its symbols, next to it with the `.sym` extension unless given
+    pub fn load(&mut self, options: &LaunchOptions) -> Result<(), String> {
+        let game_path = options.game_path.as_ref().ok_or("no program to launch")?;
+        let game_data = fs::read(game_path).map_err(|e| format!("{}: {e}", game_path.display()))?;
+        let boot_rom = match &options.boot_path {
+            Some(path) => Some(fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?),
+            None => None,
+        };
+        let game = Cartridge::new(&game_data, None).map_err(|e| format!("{e}"))?;
+
+        let symbols_path = options
+            .symbols_path
+            .clone()
+            .unwrap_or_else(|| game_path.with_extension("sym"));
+        self.symbols = match Symbols::load(&symbols_path) {
+            Ok(symbols) => symbols,
+            Err(_) if options.symbols_path.is_none() => Symbols::default(),
+            Err(e) => return Err(format!("{}: {e}", symbols_path.display())),
+        };
+
+        self.gb = Some(Dmg::new(game, boot_rom));
+        self.controller = DapController::new();
+        self.call_stack.clear();
+        self.resolve_breakpoints();
+        Ok(())
+    }
+
+    /// A client connection is gone, keep the game around for the next one to attach
+    pub fn disconnected(&mut self) { self.state = RunState::Stopped; }
+
+    fn resolve_breakpoints(&mut self) -> Vec<Option<u16>> {
+        let resolved: Vec<Option<u16>> = self
+            .function_breakpoints
+            .iter()
+            .map(|name| parse_address(name, &self.symbols))
+            .collect();
+
+        self.breakpoints = self.instruction_breakpoints.clone();
+        self.breakpoints.extend(resolved.iter().flatten());
+        resolved
+    }
+
+    /// Handle a request, returns false once the client disconnected
+    pub fn handle(&mut self, request: &Value, conn: &mut Connection) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsWriteMemoryRequest": true,
+                "supportsDisassembleRequest": true,
+                "supportsSetVariable": true,
+                "supportsEvaluateForHovers": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => {
+                let options = LaunchOptions {
+                    game_path: args["program"].as_str().map(PathBuf::from),
+                    boot_path: args["bootRom"].as_str().map(PathBuf::from),
+                    symbols_path: args["symbols"].as_str().map(PathBuf::from),
+                };
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                self.load(&options).map(|_| Value::Null)
+            }
+            "attach" => match self.gb {
+                Some(_) => {
+                    self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                    Ok(Value::Null)
+                }
+                None => Err("no game is running, start gbeed-dap with -g or use launch".to_string()),
+            },
+            "setBreakpoints" => Ok(self.set_source_breakpoints(args)),
+            "setFunctionBreakpoints" => Ok(self.set_function_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setExceptionBreakpoints" => Ok(json!({ "breakpoints": [] })),
+            "configurationDone" => Ok(Value::Null),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "SM83" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(json!({ "scopes": [
+                { "name": "Registers", "variablesReference": REGISTERS_REF, "expensive": false },
+                { "name": "Hardware registers", "variablesReference": HARDWARE_REF, "expensive": false },
+                { "name": "Symbols", "variablesReference": SYMBOLS_REF, "expensive": false },
+                { "name": "Memory", "variablesReference": MEMORY_REF, "expensive": true },
+            ]})),
+            "variables" => self.variables(args),
+            "setVariable" => self.set_variable(args),
+            "evaluate" => self.evaluate(args),
+            "readMemory" => self.read_memory(args),
+            "writeMemory" => self.write_memory(args),
+            "disassemble" => self.disassemble(args),
+            "continue" => self
+                .resume(RunState::Running)
+                .map(|_| json!({ "allThreadsContinued": true })),
+            "next" => self.step_over(),
+            "stepIn" => self.step_in(),
+            "stepOut" => {
+                let depth = self.call_stack.depth().saturating_sub(1);
+                self.resume(RunState::RunningUntilDepth(depth))
+                    .map(|_| Value::Null)
+            }
+            "pause" => {
+                self.state = RunState::Stopped;
+                Ok(Value::Null)
+            }
+            "terminate" | "disconnect" => {
+                if command == "terminate" || args["terminateDebuggee"].as_bool().unwrap_or(false) {
+                    self.gb = None;
+                }
+                conn.respond(request, Value::Null)?;
+                if command == "terminate" {
+                    conn.event("terminated", json!({}))?;
+                }
+                return Ok(command != "disconnect");
+            }
+            _ => Err(format!("unsupported request: {command}")),
+        };
+
+        let success = result.is_ok();
+        match result {
+            Ok(body) => conn.respond(request, body)?,
+            Err(error) => conn.respond_error(request, &error)?,
+        }
+
+        // events that must follow their request response
+        match command {
+            // a failed launch may leave an earlier game loaded, which must not be configured
+            "launch" | "attach" if success => conn.event("initialized", json!({}))?,
+            "configurationDone" if self.gb.is_some() => {
+                if self.stop_on_entry {
+                    self.stopped(conn, "entry", None)?;
+                } else {
+                    self.state = RunState::Running;
+                }
+            }
+            "next" | "stepIn" | "stepOut" if success && self.state == RunState::Stopped => {
+                self.stopped(conn, "step", None)?
+            }
+            "pause" => self.stopped(conn, "pause", None)?,
+            _ => {}
+        }
+        self.forward_serial(conn)?;
+
+        Ok(true)
+    }
+
+    /// Run one frame, or less if a breakpoint or the step target is hit
+    pub fn run_frame(&mut self, conn: &mut Connection) -> io::Result<()> {
+        let Some(gb) = self.gb.as_mut() else {
+            return Ok(());
+        };
+        let (call_stack, breakpoints) = (&mut self.call_stack, &self.breakpoints);
+        let target_depth = match self.state {
+            RunState::RunningUntilDepth(depth) => Some(depth),
+            _ => None,
+        };
+
+        let result = gb.run_until(&mut self.controller, |gb| {
+            call_stack.observe(gb);
+            breakpoints.contains(&gb.cpu.pc) || target_depth.is_some_and(|depth| call_stack.depth() <= depth)
+        });
+        let pc = gb.cpu.pc;
+
+        match result {
+            Ok(false) => {}
+            Ok(true) if target_depth.is_some_and(|depth| self.call_stack.depth() <= depth) => {
+                self.stopped(conn, "step", None)?
+            }
+            Ok(true) if self.instruction_breakpoints.contains(&pc) => {
+                self.stopped(conn, "instruction breakpoint", None)?
+            }
+            Ok(true) => self.stopped(conn, "function breakpoint", None)?,
+            Err(e) => self.stopped(conn, "exception", Some(e))?,
+        }
+
+        self.forward_serial(conn)
+    }
+
+    fn stopped(&mut self, conn: &mut Connection, reason: &str, error: Option<DmgError>) -> io::Result<()> {
+        self.state = RunState::Stopped;
+
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(error) = error {
+            body["description"] = json!("Invalid instruction");
+            body["text"] = json!(error.to_string());
+        }
+        conn.event("stopped", body)
+    }
+
+    fn forward_serial(&mut self, conn: &mut Connection) -> io::Result<()> {
+        let data = self.controller.take_serial();
+        if data.is_empty() {
+            return Ok(());
+        }
+        conn.output("stdout", &String::from_utf8_lossy(&data))
+    }
+
+    fn gb(&self) -> Result<&Dmg, String> { self.gb.as_ref().ok_or_else(|| "no game is running".to_string()) }
+
+    fn gb_mut(&mut self) -> Result<&mut Dmg, String> {
+        self.gb.as_mut().ok_or_else(|| "no game is running".to_string())
+    }
+
+    /// Execute one instruction, waiting for an interrupt to wake up the CPU if it's halted
+    fn step_instruction(&mut self) -> Result<(), String> {
+        let gb = self.gb.as_mut().ok_or("no game is running")?;
+        let start = gb.cpu.cycles;
+
+        self.call_stack.observe(gb);
+        loop {
+            let executed = gb
+                .step(&mut self.controller)
+                .map_err(|e| e.to_string())?
+                .is_some();
+            if executed || !gb.cpu.halted || gb.cpu.cycles.wrapping_sub(start) > FRAME_CYCLES {
+                break;
+            }
+        }
+        self.call_stack.observe(gb);
+
+        Ok(())
+    }
+
+    /// Leave the current instruction, which may be a breakpoint, then keep running
+    fn resume(&mut self, state: RunState) -> Result<(), String> {
+        let depth = self.call_stack.depth();
+        self.step_instruction()?;
+
+        self.state = match state {
+            // stepping out of the instruction already did it, like a ret
+            RunState::RunningUntilDepth(target) if self.call_stack.depth() <= target && depth > target => {
+                RunState::Stopped
+            }
+            state => state,
+        };
+        Ok(())
+    }
+
+    fn step_in(&mut self) -> Result<Value, String> {
+        self.step_instruction()?;
+        self.state = RunState::Stopped;
+        Ok(Value::Null)
+    }
+
+    /// Steps over calls and interrupts by running until the call stack is back to the same depth
+    fn step_over(&mut self) -> Result<Value, String> {
+        let depth = self.call_stack.depth();
+        self.step_instruction()?;
+
+        self.state = if self.call_stack.depth() > depth {
+            RunState::RunningUntilDepth(depth)
+        } else {
+            RunState::Stopped
+        };
+        Ok(Value::Null)
+    }
+
+    fn set_source_breakpoints(&mut self, args: &Value) -> Value {
+        // .sym files have no line information, so lines can't be mapped to addresses
+        let breakpoints: Vec<Value> = args["breakpoints"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .map(|_| {
+                json!({
+                    "verified": false,
+                    "message": "source breakpoints are not supported, use function or instruction breakpoints",
+                })
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_function_breakpoints(&mut self, args: &Value) -> Value {
+        self.function_breakpoints = args["breakpoints"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|bp| bp["name"].as_str().map(String::from))
+            .collect();
+
+        let breakpoints: Vec<Value> = self
+            .resolve_breakpoints()
+            .into_iter()
+            .map(|address| match address {
+                Some(address) => json!({ "verified": true, "instructionReference": reference(address) }),
+                None => json!({ "verified": false, "message": "unknown symbol" }),
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        let requested = args["breakpoints"]
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let addresses: Vec<Option<u16>> = requested
+            .iter()
+            .map(|bp| {
+                let base = parse_address(bp["instructionReference"].as_str()?, &self.symbols)?;
+                Some(base.wrapping_add(bp["offset"].as_i64().unwrap_or(0) as u16))
+            })
+            .collect();
+
+        self.instruction_breakpoints = addresses.iter().flatten().copied().collect();
+        self.resolve_breakpoints();
+
+        let breakpoints: Vec<Value> = addresses
+            .into_iter()
+            .map(|address| match address {
+                Some(address) => json!({ "verified": true, "instructionReference": reference(address) }),
+                None => json!({ "verified": false, "message": "invalid address" }),
+            })
+            .collect();
+
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn stack_trace(&self) -> Result<Value, String> {
+        let gb = self.gb()?;
+        let frame = |id: usize, address: u16, name: String| {
+            json!({
+                "id": id,
+                "name": name,
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": reference(address),
+            })
+        };
+
+        let mut frames = vec![frame(0, gb.cpu.pc, self.symbols.describe(gb.cpu.pc))];
+        for (i, call) in self.call_stack.frames().iter().rev().enumerate() {
+            let mut name = self.symbols.describe(call.call_site);
+            if call.interrupt {
+                name.push_str(" (interrupted)");
+            }
+            frames.push(frame(i + 1, call.call_site, name));
+        }
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&self, args: &Value) -> Result<Value, String> {
+        let gb = self.gb()?;
+        let reference = args["variablesReference"].as_u64().unwrap_or_default();
+
+        let variables: Vec<Value> = match reference {
+            REGISTERS_REF => registers(gb),
+            HARDWARE_REF => HARDWARE_REGISTERS
+                .iter()
+                .map(|&(name, address)| byte_variable(name, address, gb.read(address)))
+                .collect(),
+            SYMBOLS_REF => self
+                .symbols
+                .variables()
+                .map(|(address, name)| byte_variable(name, address, gb.read(address)))
+                .collect(),
+            MEMORY_REF => MEMORY_REGIONS
+                .iter()
+                .enumerate()
+                .map(|(i, &(name, start, end))| {
+                    json!({
+                        "name": name,
+                        "value": format!("${start:04X}-${end:04X}"),
+                        "variablesReference": MEMORY_REGION_REF + i as u64,
+                        "indexedVariables": (end - start) / ROW_SIZE + 1,
+                        "memoryReference": self::reference(start),
+                    })
+                })
+                .collect(),
+            _ => {
+                let index = reference
+                    .checked_sub(MEMORY_REGION_REF)
+                    .ok_or("invalid variables reference")?;
+                let &(_, start, end) = MEMORY_REGIONS
+                    .get(index as usize)
+                    .ok_or("invalid variables reference")?;
+                let first = args["start"].as_u64().unwrap_or(0) as usize;
+                let count = args["count"].as_u64().map_or(usize::MAX, |count| count as usize);
+
+                (start..=end)
+                    .step_by(ROW_SIZE as usize)
+                    .skip(first)
+                    .take(count)
+                    .map(|row| {
+                        let bytes: Vec<String> = (row..=end.min(row + ROW_SIZE - 1))
+                            .map(|address| format!("{:02X}", gb.read(address)))
+                            .collect();
+                        json!({
+                            "name": format!("${row:04X}"),
+                            "value": bytes.join(" "),
+                            "variablesReference": 0,
+                            "memoryReference": self::reference(row),
+                        })
+                    })
+                    .collect()
+            }
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or_default();
+        let value = args["value"]
+            .as_str()
+            .and_then(parse_value)
+            .ok_or("invalid value")?;
+
+        let written = match args["variablesReference"].as_u64().unwrap_or_default() {
+            REGISTERS_REF => {
+                let gb = self.gb_mut()?;
+                set_register(&mut gb.cpu, name, value).ok_or("unknown register")?;
+                format_register(name, value)
+            }
+            HARDWARE_REF => {
+                let (_, address) = HARDWARE_REGISTERS
+                    .iter()
+                    .find(|(register, _)| *register == name)
+                    .ok_or("unknown hardware register")?;
+                let gb = self.gb_mut()?;
+                gb.write(*address, value as u8);
+                format!("${:02X}", gb.read(*address))
+            }
+            SYMBOLS_REF => {
+                let address = self.symbols.address(name).ok_or("unknown symbol")?;
+                let gb = self.gb_mut()?;
+                gb.write(address, value as u8);
+                format!("${:02X}", gb.read(address))
+            }
+            _ => return Err("only registers and symbols can be modified, use the memory view".to_string()),
+        };
+
+        Ok(json!({ "value": written }))
+    }
+
+    /// Registers, labels and addresses, labels and addresses evaluate to the byte they point to
+    fn evaluate(&self, args: &Value) -> Result<Value, String> {
+        let gb = self.gb()?;
+        let expression = args["expression"].as_str().unwrap_or_default().trim();
+
+        if let Some(value) = register(&gb.cpu, expression) {
+            return Ok(json!({ "result": format_register(expression, value), "variablesReference": 0 }));
+        }
+
+        let address = parse_address(expression, &self.symbols).ok_or("unknown register or symbol")?;
+        Ok(json!({
+            "result": format!("${:02X} at ${address:04X}", gb.read(address)),
+            "variablesReference": 0,
+            "memoryReference": reference(address),
+        }))
+    }
+
+    fn read_memory(&self, args: &Value) -> Result<Value, String> {
+        let gb = self.gb()?;
+        let start = memory_address(args, &self.symbols)?;
+        // the address space ends at $FFFF, reads don't wrap around
+        let count = args["count"]
+            .as_u64()
+            .unwrap_or(0)
+            .min((ADDRESS_SPACE - start as usize) as u64);
+        let data: Vec<u8> = (0..count).map(|offset| gb.read(start + offset as u16)).collect();
+
+        Ok(json!({ "address": reference(start), "data": BASE64.encode(data) }))
+    }
+
+    fn write_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let start = memory_address(args, &self.symbols)?;
+        let data = BASE64
+            .decode(args["data"].as_str().unwrap_or_default())
+            .map_err(|e| e.to_string())?;
+        let gb = self.gb_mut()?;
+
+        let written = data.len().min(ADDRESS_SPACE - start as usize);
+        for (offset, byte) in data.iter().take(written).enumerate() {
+            gb.write(start + offset as u16, *byte);
+        }
+
+        Ok(json!({ "bytesWritten": written }))
+    }
+
+    fn disassemble(&self, args: &Value) -> Result<Value, String> {
+        let gb = self.gb()?;
+        let start = memory_address(args, &self.symbols)?;
+        // there can't be more instructions than bytes in the address space
+        let limit = ADDRESS_SPACE as i64;
+        let offset = args["instructionOffset"]
+            .as_i64()
+            .unwrap_or(0)
+            .clamp(-limit, limit);
+        let count = args["instructionCount"]
+            .as_u64()
+            .unwrap_or(0)
+            .min(ADDRESS_SPACE as u64) as usize;
+
+        // instructions can't be decoded backwards, decode from far enough before `start`
+        // and keep the ones leading to it, usually they line up after a few instructions
+        let before = offset.clamp(-(count as i64), 0).unsigned_abs() as usize;
+        let from = (start as usize).saturating_sub(before * MAX_INSTRUCTION_LEN) as u16;
+        let mut previous = Cpu::disassemble_range(gb, from, (start - from) as usize);
+        previous.retain(|line| line.address < start);
+        previous.drain(..previous.len().saturating_sub(before));
+
+        let skip = offset.max(0) as usize;
+        let next = Cpu::disassemble_range(gb, start, skip + count - before);
+
+        // missing instructions before the start are reported as invalid to keep the requested offset
+        let missing = (0..before - previous.len())
+            .map(|_| json!({ "address": reference(0), "instruction": "", "presentationHint": "invalid" }));
+        let decoded = previous.iter().chain(next.iter().skip(skip)).map(|line| {
+            let bytes: Vec<String> = line.bytes().iter().map(|b| format!("{b:02X}")).collect();
+            let mut instruction = json!({
+                "address": reference(line.address),
+                "instructionBytes": bytes.join(" "),
+                "instruction": match line.target {
+                    Some(target) => format!("{}  ; {}", line.text, self.symbols.describe(target)),
+                    None => line.text.clone(),
+                },
+            });
+            if let Some(symbol) = self.symbols.at(line.address) {
+                instruction["symbol"] = json!(symbol);
+            }
+            instruction
+        });
+        let instructions: Vec<Value> = missing.chain(decoded).take(count).collect();
+
+        Ok(json!({ "instructions": instructions }))
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self { Self::new() }
+}
+
+/// Memory references are plain addresses, so clients can show them and do arithmetic on them
+fn reference(address: u16) -> String { format!("0x{address:04X}") }
+
+fn memory_address(args: &Value, symbols: &Symbols) -> Result<u16, String> {
+    let base = args["memoryReference"]
+        .as_str()
+        .and_then(|reference| parse_address(reference, symbols))
+        .ok_or("invalid memory reference")?;
+    Ok(base.wrapping_add(args["offset"].as_i64().unwrap_or(0) as u16))
+}
+
+fn byte_variable(name: &str, address: u16, value: u8) -> Value {
+    json!({
+        "name": name,
+        "value": format!("${value:02X}"),
+        "type": format!("${address:04X}"),
+        "evaluateName": name,
+        "variablesReference": 0,
+        "memoryReference": reference(address),
+    })
+}
+
+const REGISTER_NAMES: [&str; 12] = ["a", "f", "b", "c", "d", "e", "h", "l", "af", "bc", "de", "hl"];
+
+fn register(cpu: &Cpu, name: &str) -> Option<u16> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "a" => cpu.a as u16,
+        "f" => cpu.f as u16,
+        "b" => cpu.b as u16,
+        "c" => cpu.c as u16,
+        "d" => cpu.d as u16,
+        "e" => cpu.e as u16,
+        "h" => cpu.h as u16,
+        "l" => cpu.l as u16,
+        "af" => cpu.af(),
+        "bc" => cpu.bc(),
+        "de" => cpu.de(),
+        "hl" => cpu.hl(),
+        "sp" => cpu.sp,
+        "pc" => cpu.pc,
+        _ => return None,
+    })
+}
+
+fn set_register(cpu: &mut Cpu, name: &str, value: u16) -> Option<()> {
+    match name.to_ascii_lowercase().as_str() {
+        "a" => cpu.a = value as u8,
+        // the low nibble of F doesn't exist
+        "f" => cpu.f = value as u8 & 0xF0,
+        "b" => cpu.b = value as u8,
+        "c" => cpu.c = value as u8,
+        "d" => cpu.d = value as u8,
+        "e" => cpu.e = value as u8,
+        "h" => cpu.h = value as u8,
+        "l" => cpu.l = value as u8,
+        "af" => cpu.set_af(value & 0xFFF0),
+        "bc" => cpu.set_bc(value),
+        "de" => cpu.set_de(value),
+        "hl" => cpu.set_hl(value),
+        "sp" => cpu.sp = value,
+        "pc" => cpu.pc = value,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn format_register(name: &str, value: u16) -> String {
+    match name.len() {
+        1 => format!("${value:02X}"),
+        _ => format!("${value:04X}"),
+    }
+}
+
+fn registers(gb: &Dmg) -> Vec<Value> {
+    let cpu = &gb.cpu;
+    let flag = |mask: u8, name: char| if cpu.f & mask != 0 { name } else { '-' };
+    let flags: String = [flag(0x80, 'Z'), flag(0x40, 'N'), flag(0x20, 'H'), flag(0x10, 'C')]
+        .iter()
+        .collect();
+
+    let mut variables: Vec<Value> = REGISTER_NAMES
+        .iter()
+        .chain(&["sp", "pc"])
+        .map(|name| {
+            let value = register(cpu, name).unwrap_or_default();
+            let mut variable = json!({
+                "name": name.to_ascii_uppercase(),
+                "value": format_register(name, value),
+                "evaluateName": name,
+                "variablesReference": 0,
+            });
+            // register pairs are usually pointers
+            if name.len() == 2 {
+                variable["memoryReference"] = json!(reference(value));
+            }
+            variable
+        })
+        .collect();
+
+    variables.push(json!({ "name": "flags", "value": flags, "variablesReference": 0 }));
+    variables.push(json!({ "name": "IME", "value": cpu.ime.to_string(), "variablesReference": 0 }));
+    variables.push(json!({ "name": "halted", "value": cpu.halted.to_string(), "variablesReference": 0 }));
+    variables
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    /// Messages sent to the client, kept to be read back by the tests
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    impl Output {
+        /// Every message sent since they were last taken
+        fn take(&self) -> Vec<Value> {
+            let data = std::mem::take(&mut *self.0.lock().unwrap());
+            String::from_utf8(data)
+                .unwrap()
+                .split("Content-Length: ")
+                .skip(1)
+                .map(|message| serde_json::from_str(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+                .collect()
+        }
+    }
+
+    /// Loops at $0150 loading $42 into A, with `Main` labelling the loop
+    fn session() -> (Session, Connection, Output) {
+        let mut rom = vec![0; 0x8000];
+        rom[0x150..0x155].copy_from_slice(&[0x3E, 0x42, 0xC3, 0x50, 0x01]);
+
+        let mut session = Session::new();
+        session.gb = Some(Dmg::new(Cartridge::new(&rom, None).unwrap(), None));
+        session.symbols = Symbols::parse("00:0150 Main\n");
+
+        let output = Output::default();
+        let conn = Connection::new(Box::new(output.clone()));
+        (session, conn, output)
+    }
+
+    /// Send a request and return every message sent back
+    fn messages(
+        session: &mut Session,
+        conn: &mut Connection,
+        output: &Output,
+        command: &str,
+        args: Value,
+    ) -> Vec<Value> {
+        let request = json!({ "seq": 1, "type": "request", "command": command, "arguments": args });
+        session.handle(&request, conn).unwrap();
+        output.take()
+    }
+
+    /// Send a request and return its response
+    fn request(
+        session: &mut Session,
+        conn: &mut Connection,
+        output: &Output,
+        command: &str,
+        args: Value,
+    ) -> Value {
+        messages(session, conn, output, command, args)
+            .into_iter()
+            .find(|message| message["type"] == "response")
+            .unwrap()
+    }
+
+    /// Run frames until the session stops, returning the reason
+    fn run(session: &mut Session, conn: &mut Connection, output: &Output) -> Value {
+        for _ in 0..4 {
+            session.run_frame(conn).unwrap();
+            if let Some(stopped) = output
+                .take()
+                .into_iter()
+                .find(|message| message["event"] == "stopped")
+            {
+                return stopped["body"]["reason"].clone();
+            }
+        }
+        panic!("the session never stopped");
+    }
+
+    fn addresses(response: &Value) -> Vec<&str> {
+        response["body"]["instructions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|instruction| instruction["address"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_request_dispatch() {
+        let (mut session, mut conn, output) = session();
+
+        let response = request(&mut session, &mut conn, &output, "initialize", json!({}));
+        assert_eq!(response["success"], true);
+        assert_eq!(response["body"]["supportsDisassembleRequest"], true);
+
+        let response = request(&mut session, &mut conn, &output, "threads", json!({}));
+        assert_eq!(response["body"]["threads"][0]["id"], THREAD_ID);
+
+        let response = request(&mut session, &mut conn, &output, "reverseContinue", json!({}));
+        assert_eq!(response["success"], false);
+        assert_eq!(response["message"], "unsupported request: reverseContinue");
+
+        session.gb = None;
+        let response = request(&mut session, &mut conn, &output, "stackTrace", json!({}));
+        assert_eq!(response["success"], false);
+    }
+
+    #[test]
+    fn test_initialized_after_launch() {
+        let (mut session, mut conn, output) = session();
+        let initialized =
+            |messages: &[Value]| messages.iter().any(|message| message["event"] == "initialized");
+
+        // the game from before is still loaded, but it isn't the one asked for
+        let args = json!({ "program": "/nonexistent/game.gb" });
+        let sent = messages(&mut session, &mut conn, &output, "launch", args);
+        assert_eq!(sent[0]["success"], false);
+        assert!(session.gb.is_some());
+        assert!(!initialized(&sent));
+
+        let sent = messages(&mut session, &mut conn, &output, "attach", json!({}));
+        assert_eq!(sent[0]["success"], true);
+        assert!(initialized(&sent));
+
+        session.gb = None;
+        let sent = messages(&mut session, &mut conn, &output, "attach", json!({}));
+        assert_eq!(sent[0]["success"], false);
+        assert!(!initialized(&sent));
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let (mut session, mut conn, output) = session();
+
+        let args = json!({ "breakpoints": [{ "name": "Main" }, { "name": "Missing" }] });
+        let response = request(&mut session, &mut conn, &output, "setFunctionBreakpoints", args);
+        let breakpoints = &response["body"]["breakpoints"];
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["instructionReference"], "0x0150");
+        assert_eq!(breakpoints[1]["verified"], false);
+
+        let args = json!({ "breakpoints": [{ "instructionReference": "0x0150", "offset": 2 }] });
+        let response = request(
+            &mut session,
+            &mut conn,
+            &output,
+            "setInstructionBreakpoints",
+            args,
+        );
+        assert_eq!(
+            response["body"]["breakpoints"][0]["instructionReference"],
+            "0x0152"
+        );
+
+        session.state = RunState::Running;
+        assert_eq!(run(&mut session, &mut conn, &output), "function breakpoint");
+        assert_eq!(session.gb.as_ref().unwrap().cpu.pc, 0x0150);
+
+        request(&mut session, &mut conn, &output, "continue", json!({}));
+        assert_eq!(run(&mut session, &mut conn, &output), "instruction breakpoint");
+        assert_eq!(session.gb.as_ref().unwrap().cpu.pc, 0x0152);
+    }
+
+    #[test]
+    fn test_disassemble_offsets() {
+        let (mut session, mut conn, output) = session();
+        let mut disassemble = |reference: &str, offset: i64, count: u64| {
+            let args = json!({ "memoryReference": reference, "instructionOffset": offset, "instructionCount": count });
+            request(&mut session, &mut conn, &output, "disassemble", args)
+        };
+
+        let response = disassemble("0x0150", 0, 3);
+        assert_eq!(addresses(&response), ["0x0150", "0x0152", "0x0155"]);
+        assert_eq!(response["body"]["instructions"][0]["symbol"], "Main");
+
+        assert_eq!(addresses(&disassemble("0x0150", 1, 2)), ["0x0152", "0x0155"]);
+        assert_eq!(
+            addresses(&disassemble("0x0150", -2, 4)),
+            ["0x014E", "0x014F", "0x0150", "0x0152"]
+        );
+
+        // only two instructions before $0002, the missing ones are padded as invalid
+        let response = disassemble("0x0002", -4, 5);
+        assert_eq!(
+            addresses(&response),
+            ["0x0000", "0x0000", "0x0000", "0x0001", "0x0002"]
+        );
+        assert_eq!(response["body"]["instructions"][0]["presentationHint"], "invalid");
+    }
+
+    #[test]
+    fn test_disassemble_huge_arguments() {
+        let (mut session, mut conn, output) = session();
+        let mut disassemble = |offset: i64, count: u64| {
+            let args = json!({ "memoryReference": "0xFFF0", "instructionOffset": offset, "instructionCount": count });
+            let response = request(&mut session, &mut conn, &output, "disassemble", args);
+            response["body"]["instructions"].as_array().unwrap().len()
+        };
+
+        assert_eq!(disassemble(i64::MAX, u64::MAX), ADDRESS_SPACE);
+        assert_eq!(disassemble(i64::MIN, u64::MAX), ADDRESS_SPACE);
+        assert_eq!(disassemble(i64::MIN, 4), 4);
+    }
+
+    #[test]
+    fn test_read_whole_address_space() {
+        let (mut session, mut conn, output) = session();
+
+        let args = json!({ "memoryReference": "0x0000", "count": u64::MAX });
+        let response = request(&mut session, &mut conn, &output, "readMemory", args);
+        let data = BASE64.decode(response["body"]["data"].as_str().unwrap()).unwrap();
+        assert_eq!(data.len(), ADDRESS_SPACE);
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Labels exported by the assembler, read from a RGBDS/BGB `.sym` file with `bank:address name` lines.
+/// The selected ROM bank isn't known here, so banked labels match regardless of their bank
+#[derive(Debug, Default)]
+pub struct Symbols {
+    by_name: HashMap<String, u16>,
+    by_address: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> { Ok(Self::parse(&fs::read_to_string(path)?)) }
+
+    pub fn parse(text: &str) -> Self {
+        let mut symbols = Self::default();
+
+        for line in text.lines() {
+            let line = line.split(';').next().unwrap_or("").trim();
+            let Some((location, name)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let address = match location.split_once(':') {
+                Some((_bank, address)) => u16::from_str_radix(address, 16),
+                None => u16::from_str_radix(location, 16),
+            };
+            let Ok(address) = address else {
+                continue;
+            };
+
+            let name = name.trim().to_string();
+            // the first label wins, usually the one that isn't a local alias
+            symbols.by_address.entry(address).or_insert_with(|| name.clone());
+            symbols.by_name.insert(name, address);
+        }
+
+        symbols
+    }
+
+    pub fn address(&self, name: &str) -> Option<u16> { self.by_name.get(name).copied() }
+
+    pub fn at(&self, address: u16) -> Option<&str> { self.by_address.get(&address).map(String::as_str) }
+
+    /// Labels placed in RAM or I/O, which work as the program variables
+    pub fn variables(&self) -> impl Iterator<Item = (u16, &str)> {
+        self.by_address
+            .range(0x8000..)
+            .map(|(address, name)| (*address, name.as_str()))
+    }
+
+    /// Closest label at or before `address` in the same memory area, as `Label+$offset`
+    pub fn describe(&self, address: u16) -> String {
+        let area = |address: u16| {
+            if address < 0x8000 {
+                address & 0xC000
+            } else {
+                address & 0xE000
+            }
+        };
+
+        match self.by_address.range(..=address).next_back() {
+            Some((&start, name)) if area(start) == area(address) && start == address => name.clone(),
+            Some((&start, name)) if area(start) == area(address) => format!("{name}+${:X}", address - start),
+            _ => format!("${address:04X}"),
+        }
+    }
+}
+
+/// Parse an address typed by the user, as `$1234`, `0x1234` or a label
+pub fn parse_address(text: &str, symbols: &Symbols) -> Option<u16> {
+    let text = text.trim();
+    let hex = text
+        .strip_prefix('$')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"));
+
+    match hex {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => symbols.address(text),
+    }
+}
+
+/// Parse a value typed by the user, as `$1F`, `0x1F`, `%00011111` or decimal
+pub fn parse_value(text: &str) -> Option<u16> {
+    let text = text.trim();
+
+    if let Some(hex) = text.strip_prefix('$').or_else(|| text.strip_prefix("0x")) {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%').or_else(|| text.strip_prefix("0b")) {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        text.parse().ok()
+    }
+}
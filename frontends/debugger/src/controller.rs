@@ -1,9 +1,16 @@
+use crate::utils::BACKGROUND;
 use gbeed_core::prelude::*;
 use gbeed_core::{
-    AudioPlayer, BUFFER_SIZE, Controller, Ppu, Renderer, SAMPLE_RATE, SerialListener,
-    prelude::DMG_SCREEN_WIDTH,
+    AudioPlayer, Controller, MAX_SPRITES_IN_OAM, Ppu, Renderer, SerialListener, prelude::DMG_SCREEN_WIDTH,
+};
+use gbeed_raylib_common::{
+    Texture,
+    audio::AudioQueue,
+    color::DMG_CLASSIC_PALETTE,
+    input::Turbo,
+    recording::Recorder,
+    settings::{AudioOutput, SampleRate, SpeedUpMultiplier, SyncMode},
 };
-use gbeed_raylib_common::{Texture, color::DMG_CLASSIC_PALETTE, settings::SpeedUpMultiplier};
 use raylib::prelude::*;
 
 pub const TILES_PER_ROW: i32 = 16;
@@ -14,34 +21,31 @@ pub const TILE_TEXTURE_WIDTH: i32 = TILES_PER_ROW * TILE_PIXEL_SIZE;
 pub const TILE_TEXTURE_HEIGHT: i32 = TILES_PER_COLUMN * TILE_PIXEL_SIZE;
 pub const TILE_DISPLAY_WIDTH: i32 = TILE_TEXTURE_WIDTH * TILE_DISPLAY_SCALE;
 pub const TILE_DISPLAY_HEIGHT: i32 = TILE_TEXTURE_HEIGHT * TILE_DISPLAY_SCALE;
+/// Every OAM entry side by side, as tall as a 8x16 object
+pub const SPRITE_TEXTURE_WIDTH: i32 = TILE_PIXEL_SIZE * MAX_SPRITES_IN_OAM as i32;
+pub const SPRITE_TEXTURE_HEIGHT: i32 = TILE_PIXEL_SIZE * 2;
 
 pub struct DebuggerController<'a> {
     pub screen_texture: Texture,
     pub tile_textures: [Texture; 3],
     pub bg_map_texture: Texture,
+    pub sprite_texture: Texture,
     pub scroll_x: i32,
     pub scroll_y: i32,
 
-    sample_idx: usize,
-    audio_buffer: Box<[i16; BUFFER_SIZE]>,
-    audio_stream: AudioStream<'a>,
+    pub audio: AudioQueue<'a>,
+    pub sync_mode: SyncMode,
+    pub recorder: Recorder,
 
     pub speed_up_multiplier: SpeedUpMultiplier,
+    pub turbo: Turbo,
 
     pub rl: &'a mut RaylibHandle,
     pub thread: &'a RaylibThread,
-    _audio: &'a RaylibAudio,
 }
 
 impl<'a> DebuggerController<'a> {
     pub fn new(rl: &'a mut RaylibHandle, thread: &'a RaylibThread, audio: &'a RaylibAudio) -> Self {
-        // Match raylib's internal subbuffer size to our flush size so
-        // UpdateAudioStream never has to reject a chunk for being "too many frames"
-        audio.set_audio_stream_buffer_size_default(BUFFER_SIZE as i32);
-
-        let audio_stream = audio.new_audio_stream(SAMPLE_RATE, 16, 1);
-        audio_stream.play();
-
         Self {
             screen_texture: Texture::new(rl, thread, DMG_SCREEN_WIDTH as i32, DMG_SCREEN_HEIGHT as i32),
             tile_textures: [
@@ -50,18 +54,19 @@ impl<'a> DebuggerController<'a> {
                 Texture::new(rl, thread, TILE_TEXTURE_WIDTH, TILE_TEXTURE_HEIGHT),
             ],
             bg_map_texture: Texture::new(rl, thread, 256, 256),
+            sprite_texture: Texture::new(rl, thread, SPRITE_TEXTURE_WIDTH, SPRITE_TEXTURE_HEIGHT),
             scroll_x: 0,
             scroll_y: 0,
 
-            sample_idx: 0,
-            audio_buffer: Box::new([0; BUFFER_SIZE]),
-            audio_stream,
+            audio: AudioQueue::new(audio, SampleRate::default(), AudioOutput::default()),
+            sync_mode: SyncMode::default(),
+            recorder: Recorder::default(),
 
             speed_up_multiplier: SpeedUpMultiplier::OneAndHalf,
+            turbo: Turbo::default(),
 
             rl,
             thread,
-            _audio: audio,
         }
     }
 }
@@ -86,7 +91,9 @@ impl Renderer for DebuggerController<'_> {
     }
 
     fn update_screen(&mut self, ppu: &Ppu) {
+        self.turbo.next_frame();
         self.screen_texture.update();
+        self.recorder.push_frame(self.screen_texture.pixels());
 
         update_tiles(&mut self.tile_textures[0], ppu.tile_block0());
         update_tiles(&mut self.tile_textures[1], ppu.tile_block1());
@@ -100,6 +107,8 @@ impl Renderer for DebuggerController<'_> {
             ppu.get_bg_palette(),
         );
 
+        update_sprites(&mut self.sprite_texture, ppu);
+
         update_scroll(&mut self.scroll_x, &mut self.scroll_y, ppu.get_scroll());
     }
 }
@@ -113,29 +122,14 @@ impl SerialListener for DebuggerController<'_> {
 impl Controller for DebuggerController<'_> {}
 
 impl AudioPlayer for DebuggerController<'_> {
-    fn playing_stereo(&self) -> bool { false }
+    fn playing_stereo(&self) -> bool { self.audio.is_stereo() }
 
     fn push_sample(&mut self, left: i16, right: i16) {
-        // drop new samples when the buffer is full instead of wrapping
-        if self.sample_idx >= BUFFER_SIZE {
-            return;
-        }
-
-        // let the audio mono for now
-        let mono = ((left as i32 + right as i32) / 2) as i16;
-        self.audio_buffer[self.sample_idx] = mono;
-        self.sample_idx += 1;
+        self.audio.push(left, right);
+        self.recorder.push_sample(left, right);
     }
 
-    fn flush_buffer(&mut self) {
-        if self.sample_idx == 0 || !self.audio_stream.is_processed() {
-            return;
-        }
-        if let Err(e) = self.audio_stream.update(&self.audio_buffer[..self.sample_idx]) {
-            eprintln!("update error: {e}");
-        }
-        self.sample_idx = 0;
-    }
+    fn flush_buffer(&mut self) { self.audio.update(); }
 }
 
 pub fn update_bg_map(
@@ -192,6 +186,48 @@ pub fn update_tiles(texture: &mut Texture, data: &[u8]) {
     texture.update();
 }
 
+/// Draw every object with its own palette and flips, transparent pixels use the panel background
+pub fn update_sprites(texture: &mut Texture, ppu: &Ppu) {
+    let height = ppu.sprite_height() as usize;
+    let (obp0, obp1) = ppu.get_obj_palettes();
+    let tile_data = ppu.tile_data();
+
+    for index in 0..MAX_SPRITES_IN_OAM as usize {
+        let sprite = ppu.sprite(index as u8);
+        let palette = if sprite.palette_number() { obp1 } else { obp0 };
+        // 8x16 objects ignore the lowest bit of the tile index
+        let first_tile = if height == 16 {
+            sprite.tile_index & 0xFE
+        } else {
+            sprite.tile_index
+        };
+
+        for row in 0..SPRITE_TEXTURE_HEIGHT as usize {
+            let (lb, hb) = if row < height {
+                let source_row = if sprite.yflip() { height - 1 - row } else { row };
+                let base = first_tile as usize * 16 + source_row * 2;
+                (tile_data[base], tile_data[base + 1])
+            } else {
+                (0, 0)
+            };
+
+            for col in 0..8 {
+                let bit = if sprite.xflip() { col } else { 7 - col };
+                let cid = (((hb >> bit) & 1) << 1) | ((lb >> bit) & 1);
+                let color = match cid {
+                    0 => BACKGROUND,
+                    _ => DMG_CLASSIC_PALETTE[((palette >> (cid * 2)) & 0x03) as usize],
+                };
+                let idx = (row * SPRITE_TEXTURE_WIDTH as usize + index * 8 + col) * 3;
+                texture[idx] = color.r;
+                texture[idx + 1] = color.g;
+                texture[idx + 2] = color.b;
+            }
+        }
+    }
+    texture.update();
+}
+
 pub fn update_scroll(scroll_x: &mut i32, scroll_y: &mut i32, scroll: (u8, u8)) {
     (*scroll_x, *scroll_y) = (scroll.0 as i32, scroll.1 as i32);
 }
@@ -0,0 +1,160 @@
+use crate::controller::{TILE_PIXEL_SIZE, TILE_TEXTURE_HEIGHT, TILE_TEXTURE_WIDTH, TILES_PER_ROW};
+use crate::utils::dirs::dumps_dir;
+use gbeed_core::{MAX_SPRITES_IN_OAM, Ppu};
+use gbeed_raylib_common::color::{DMG_CLASSIC_PALETTE, GRAYSCALE_PALETTE};
+use gbeed_raylib_common::screenshot::write_png;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Palette register mapping every color id to the shade with its same number
+const IDENTITY_PALETTE: u8 = 0b11_10_01_00;
+const BG_MAP_SIZE: usize = 256;
+
+/// Colors the exported images are drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportPalette {
+    /// Palette registers set by the game, shown with the debugger colors
+    Current,
+    /// Color ids as gray levels, ignoring the palette registers
+    Grayscale,
+}
+
+impl ExportPalette {
+    /// RGBA of a color id through a palette register
+    #[inline(always)]
+    fn color(self, register: u8, id: u8) -> [u8; 4] {
+        let (register, colors) = match self {
+            ExportPalette::Current => (register, &DMG_CLASSIC_PALETTE),
+            ExportPalette::Grayscale => (IDENTITY_PALETTE, &GRAYSCALE_PALETTE),
+        };
+        let color = colors[((register >> (id * 2)) & 0x03) as usize];
+        [color.r, color.g, color.b, 255]
+    }
+}
+
+/// Write every tile block, both BG maps and the OAM objects as PNGs next to the memory dumps
+pub fn export_vram(ppu: &Ppu, palette: ExportPalette) {
+    let dir = dumps_dir();
+    let result = fs::create_dir_all(&dir).and_then(|_| {
+        let bg_palette = ppu.get_bg_palette();
+
+        for (i, block) in [ppu.tile_block0(), ppu.tile_block1(), ppu.tile_block2()]
+            .into_iter()
+            .enumerate()
+        {
+            let pixels = render_tiles(block, palette, bg_palette);
+            save(&dir, &format!("tiles{i}"), TILE_TEXTURE_WIDTH as u32, &pixels)?;
+        }
+
+        for (i, map) in [ppu.bg_map0(), ppu.bg_map1()].into_iter().enumerate() {
+            let pixels = render_bg_map(map, ppu, palette);
+            save(&dir, &format!("bg_map{i}"), BG_MAP_SIZE as u32, &pixels)?;
+        }
+
+        let pixels = render_sprites(ppu, palette);
+        save(
+            &dir,
+            "sprites",
+            (TILE_PIXEL_SIZE as usize * MAX_SPRITES_IN_OAM as usize) as u32,
+            &pixels,
+        )
+    });
+
+    match result {
+        Ok(_) => println!("Exported vram images to {}", dir.display()),
+        Err(e) => eprintln!("Failed to export vram images: {e}"),
+    }
+}
+
+fn save(dir: &Path, name: &str, width: u32, pixels: &[u8]) -> io::Result<()> {
+    let height = (pixels.len() / 4) as u32 / width;
+    write_png(&dir.join(name).with_extension("png"), width, height, pixels)
+}
+
+/// Color ids of a tile row, leftmost pixel first
+#[inline(always)]
+fn tile_row(tile_data: &[u8], tile: usize, row: usize) -> [u8; 8] {
+    let (lb, hb) = (tile_data[tile * 16 + row * 2], tile_data[tile * 16 + row * 2 + 1]);
+    core::array::from_fn(|col| {
+        let bit = 7 - col;
+        (((hb >> bit) & 1) << 1) | ((lb >> bit) & 1)
+    })
+}
+
+/// 128 tiles laid out like the tile panels
+fn render_tiles(block: &[u8], palette: ExportPalette, register: u8) -> Vec<u8> {
+    let width = TILE_TEXTURE_WIDTH as usize;
+    let mut pixels = vec![0; width * TILE_TEXTURE_HEIGHT as usize * 4];
+
+    for tile in 0..block.len() / 16 {
+        let bx = (tile % TILES_PER_ROW as usize) * 8;
+        let by = (tile / TILES_PER_ROW as usize) * 8;
+        for row in 0..8 {
+            for (col, id) in tile_row(block, tile, row).into_iter().enumerate() {
+                let idx = ((by + row) * width + bx + col) * 4;
+                pixels[idx..idx + 4].copy_from_slice(&palette.color(register, id));
+            }
+        }
+    }
+    pixels
+}
+
+/// The whole 32x32 tile map, with the tile data addressing currently selected in LCDC
+fn render_bg_map(map: &[u8], ppu: &Ppu, palette: ExportPalette) -> Vec<u8> {
+    let tile_data = ppu.tile_data();
+    let register = ppu.get_bg_palette();
+    let mut pixels = vec![0; BG_MAP_SIZE * BG_MAP_SIZE * 4];
+
+    for (i, &tile_number) in map.iter().enumerate() {
+        let (tx, ty) = (i % 32, i / 32);
+        let tile = if ppu.bg_and_window_tile_data() {
+            tile_number as usize
+        } else {
+            (0x100 + tile_number as i8 as i32) as usize
+        };
+
+        for row in 0..8 {
+            for (col, id) in tile_row(tile_data, tile, row).into_iter().enumerate() {
+                let idx = ((ty * 8 + row) * BG_MAP_SIZE + tx * 8 + col) * 4;
+                pixels[idx..idx + 4].copy_from_slice(&palette.color(register, id));
+            }
+        }
+    }
+    pixels
+}
+
+/// Every object side by side with its own palette and flips, color 0 left transparent
+fn render_sprites(ppu: &Ppu, palette: ExportPalette) -> Vec<u8> {
+    let height = ppu.sprite_height() as usize;
+    let width = TILE_PIXEL_SIZE as usize * MAX_SPRITES_IN_OAM as usize;
+    let (obp0, obp1) = ppu.get_obj_palettes();
+    let tile_data = ppu.tile_data();
+    let mut pixels = vec![0; width * height * 4];
+
+    for index in 0..MAX_SPRITES_IN_OAM as usize {
+        let sprite = ppu.sprite(index as u8);
+        let register = if sprite.palette_number() { obp1 } else { obp0 };
+        // 8x16 objects ignore the lowest bit of the tile index
+        let first_tile = match height {
+            16 => sprite.tile_index & 0xFE,
+            _ => sprite.tile_index,
+        } as usize;
+
+        for row in 0..height {
+            let source_row = if sprite.yflip() { height - 1 - row } else { row };
+            let mut ids = tile_row(tile_data, first_tile + source_row / 8, source_row % 8);
+            if sprite.xflip() {
+                ids.reverse();
+            }
+
+            for (col, id) in ids.into_iter().enumerate() {
+                if id != 0 {
+                    let idx = (row * width + index * 8 + col) * 4;
+                    pixels[idx..idx + 4].copy_from_slice(&palette.color(register, id));
+                }
+            }
+        }
+    }
+    pixels
+}
@@ -1,10 +1,16 @@
 use gbeed_core::prelude::*;
+use gbeed_core::{DOCTOR_LY, TraceFormat, Tracer};
+use gbeed_raylib_common::{archive, identify, patch};
+use gbeed_raylib_common::config::{Config, Paths, SaveOptions};
+use gbeed_raylib_common::input::{InputEvdevTriggers, InputGamepadTriggers, InputGpioButtons, InputKeyTriggers};
+use gbeed_raylib_common::settings::{AudioOutput, SampleRate, SyncMode, TurboRate};
 use raylib::prelude::*;
-use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
+use std::fs;
 
 mod controller;
+mod export;
+mod panels;
 mod scenes;
 mod utils;
 
@@ -15,6 +21,8 @@ use controller::DebuggerController;
 use scenes::{EmulationScene, EmulatorState, WaitingFileScene};
 use utils::{BACKGROUND, Layout};
 
+#[cfg(not(target_arch = "wasm32"))]
+use gbeed_raylib_common::saves;
 #[cfg(target_arch = "wasm32")]
 use web::{
     APP_PTR, emscripten_set_main_loop_arg, load_rom_from_js, local_storage, save_game_wasm, wasm_main_loop,
@@ -24,6 +32,24 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
     let mut game_path = None;
     let mut boot_path = None;
+    let mut trace_path = None;
+    let mut trace_format = TraceFormat::default();
+    let mut doctor = false;
+
+    // key bindings and audio options are shared with the console, the arguments below take precedence
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("Failed to read the config, using the defaults: {e}");
+        Config::default()
+    });
+    InputKeyTriggers::configure(config.keys.triggers());
+    InputGamepadTriggers::configure(config.gamepad.triggers());
+    InputEvdevTriggers::configure(config.evdev.triggers());
+    InputGpioButtons::configure(config.gpio.triggers());
+    Paths::configure(config.paths.clone());
+    let mut sample_rate = Some(config.settings.sample_rate);
+    let mut audio_output = config.settings.audio_output;
+    let mut sync_mode = config.settings.sync_mode;
+    let mut turbo_rate = config.settings.turbo_rate;
 
     let mut i = 1;
     while i < args.len() {
@@ -36,6 +62,35 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 boot_path = Some(args[i + 1].clone());
                 i += 1;
             }
+            "-t" | "--trace" if i + 1 < args.len() => {
+                trace_path = Some(PathBuf::from(&args[i + 1]));
+                i += 1;
+            }
+            "--trace-format" if i + 1 < args.len() => {
+                trace_format = match args[i + 1].as_str() {
+                    "doctor" => TraceFormat::Doctor,
+                    "extended" => TraceFormat::Extended,
+                    format => return Err(format!("unknown trace format: {format}").into()),
+                };
+                i += 1;
+            }
+            "--doctor" => doctor = true,
+            "-r" | "--sample-rate" if i + 1 < args.len() => {
+                sample_rate = args[i + 1].parse().ok().and_then(SampleRate::from_hz);
+                if sample_rate.is_none() {
+                    eprintln!("Unsupported sample rate {}, using the default one", args[i + 1]);
+                }
+                i += 1;
+            }
+            "--turbo-rate" if i + 1 < args.len() => {
+                match args[i + 1].parse().ok().and_then(TurboRate::from_hz) {
+                    Some(rate) => turbo_rate = rate,
+                    None => eprintln!("Unsupported turbo rate {}, using the default one", args[i + 1]),
+                }
+                i += 1;
+            }
+            "-m" | "--mono" => audio_output = AudioOutput::Mono,
+            "-a" | "--audio-sync" => sync_mode = SyncMode::Audio,
             "-h" | "--help" => {
                 print_help();
                 return Ok(());
@@ -61,8 +116,20 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     rl.set_target_fps(60);
     rl.set_exit_key(None);
+    InputGamepadTriggers::add_mappings(Config::gamepad_mappings_path().as_deref());
 
     let mut app = EmulatorApp::new(rl, thread, audio, boot_path, is_mobile);
+    app.trace = trace_path.map(|path| (path, trace_format));
+    app.doctor = doctor;
+    app.saves = config.saves;
+    if let Some(sample_rate) = sample_rate {
+        app.controller.audio.set_sample_rate(sample_rate);
+    }
+    if audio_output != AudioOutput::default() {
+        app.controller.audio.set_output(audio_output);
+    }
+    app.controller.sync_mode = sync_mode;
+    app.controller.turbo.rate = turbo_rate;
 
     // load ROM if its provided via command line args
     if let Some(path) = game_path {
@@ -126,6 +193,13 @@ pub struct EmulatorApp<'a> {
     boot_rom: Option<Vec<u8>>,
     state: EmulatorState,
     layout: Layout,
+    /// File every loaded game traces its instructions to
+    trace: Option<(PathBuf, TraceFormat)>,
+    /// Report LY as `DOCTOR_LY`, for traces compared against Gameboy Doctor logs
+    doctor: bool,
+    saves: SaveOptions,
+    /// Seconds since the save was last checked for changes to write
+    since_autosave: f32,
 }
 
 impl<'a> EmulatorApp<'a> {
@@ -151,26 +225,52 @@ impl<'a> EmulatorApp<'a> {
             boot_rom,
             state,
             layout,
+            trace: None,
+            doctor: false,
+            saves: SaveOptions::default(),
+            since_autosave: 0.0,
         }
     }
 
     pub fn should_close(&self) -> bool { self.controller.rl.window_should_close() }
 
     pub fn load_rom(&mut self, path: &str) -> Result<EmulatorState, Box<dyn std::error::Error>> {
-        let game_data = fs::read(path)?;
+        // archives open their first ROM, which is saved as if it was unpacked next to them
+        let rom_path = archive::resolve_rom(Path::new(path))?;
+        let game_data = match patch::default_patch(&rom_path) {
+            Some(patch) => match patch::read_patched_rom(&rom_path, Some(&patch)) {
+                Ok(data) => {
+                    println!("Applied patch {}", patch.display());
+                    data
+                }
+                Err(e) => {
+                    eprintln!("Loading the ROM unpatched: {e}");
+                    archive::read_rom(&rom_path)?
+                }
+            },
+            None => archive::read_rom(&rom_path)?,
+        };
+        let unpacked_path = archive::unpacked_path(&rom_path);
+        if let Some(info) = identify::identify_file(&rom_path) {
+            println!("Identified as {}{}", info.name, if info.bad_dump { ", a known bad dump" } else { "" });
+        }
 
         let save_path = if cfg!(target_arch = "wasm32") {
-            PathBuf::from(format!(
-                "saves/{}",
-                save_path_from_rom(path.split('/').next_back().unwrap_or("")).to_string_lossy()
-            ))
+            let name = unpacked_path.file_name().unwrap_or_default();
+            Path::new("saves").join(save_path_from_rom(Path::new(name)))
         } else {
-            save_path_from_rom(path)
+            save_path_from_rom(&unpacked_path)
         };
 
         #[cfg(target_arch = "wasm32")]
         let save = local_storage::load_save(&save_path);
 
+        // keep the save as it was before this session, in case it goes wrong
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = saves::backup_save(&save_path, self.saves.backups) {
+            eprintln!("Failed to back up {}: {e}", save_path.display());
+        }
+
         #[cfg(not(target_arch = "wasm32"))]
         let save = match fs::read(&save_path) {
             Ok(data) => {
@@ -184,7 +284,15 @@ impl<'a> EmulatorApp<'a> {
         let title = game.header.title.clone();
         let region = format!("{:?}", game.header.destination);
 
-        self.gb = Some(Dmg::new(game, self.boot_rom.clone()));
+        let mut gb = Dmg::new(game, self.boot_rom.clone());
+        gb.apu.set_sample_rate(self.controller.audio.sample_rate().hz());
+        if let Some((path, format)) = &self.trace {
+            gb.tracer = Some(Tracer::file(*format, path)?);
+        }
+        if self.doctor {
+            gb.ppu.ly_override = Some(DOCTOR_LY);
+        }
+        self.gb = Some(gb);
         self.save_path = Some(save_path);
 
         #[cfg(target_arch = "wasm32")]
@@ -229,6 +337,15 @@ impl<'a> EmulatorApp<'a> {
             self.state = state;
         }
 
+        let interval = self.saves.autosave_interval;
+        self.since_autosave += dt;
+        if interval > 0 && self.since_autosave >= interval as f32 {
+            self.since_autosave = 0.0;
+            if let Err(e) = self.save_game() {
+                eprintln!("Failed to autosave: {e}");
+            }
+        }
+
         Ok(())
     }
 
@@ -246,16 +363,22 @@ impl<'a> EmulatorApp<'a> {
                     &controller.screen_texture,
                     &controller.tile_textures,
                     &controller.bg_map_texture,
+                    &controller.sprite_texture,
+                    controller.recorder.is_recording(),
                 ),
             }
         });
     }
 
+    /// Write the save if it changed since it was last written
     pub fn save_game(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let (gb, save_path) = match (self.gb.as_ref(), self.save_path.as_ref()) {
+        let (gb, save_path) = match (self.gb.as_mut(), self.save_path.as_ref()) {
             (Some(gb), Some(path)) => (gb, path),
             _ => return Ok(()),
         };
+        if !gb.cartridge.save_changed() {
+            return Ok(());
+        }
 
         if let Some(save_data) = gb.cartridge.save_game() {
             #[cfg(target_arch = "wasm32")]
@@ -263,20 +386,17 @@ impl<'a> EmulatorApp<'a> {
 
             #[cfg(not(target_arch = "wasm32"))]
             {
-                if let Some(parent) = save_path.parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                fs::write(save_path, save_data)?;
+                saves::write_save(save_path, save_data)?;
                 println!("Game saved successfully to {}", save_path.display());
             }
         }
+        gb.cartridge.mark_saved();
 
         Ok(())
     }
 }
 
-fn save_path_from_rom(rom_path: &str) -> PathBuf {
-    let path = Path::new(rom_path);
+fn save_path_from_rom(path: &Path) -> PathBuf {
     match path.extension().and_then(|e| e.to_str()) {
         Some("gb" | "gbc") => path.with_extension("sav"),
         _ => path.with_added_extension("sav"),
@@ -288,5 +408,12 @@ fn print_help() {
     println!("Options:");
     println!("  -g, --game <PATH>      Path to the game ROM file");
     println!("  -b, --boot <PATH>      Path to the boot ROM file (optional)");
+    println!("  -t, --trace <PATH>     Log every executed instruction to a file");
+    println!("  --trace-format <FMT>   `doctor` (default) or `extended` trace lines");
+    println!("  --doctor               Report LY as $90, as expected by Gameboy Doctor logs");
+    println!("  -r, --sample-rate <HZ> Audio output rate: 22050, 32000, 44100 (default) or 48000");
+    println!("  -m, --mono             Play both audio sides through a single channel");
+    println!("  -a, --audio-sync       Pace the emulation with the audio device instead of the display");
+    println!("  --turbo-rate <HZ>      Turbo A (U) and B (I) rate: 7.5, 10, 15 (default) or 30");
     println!("  -h, --help             Print this help message");
 }
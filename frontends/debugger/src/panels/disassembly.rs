@@ -0,0 +1,253 @@
+use std::collections::BTreeSet;
+
+use crate::panels::DebuggerAction;
+use crate::utils::{BACKGROUND, FOREGROUND, PRIMARY, SECONDARY};
+use gbeed_core::{Cpu, Disassembly, prelude::*};
+use gbeed_raylib_common::input::MouseButtonArea;
+use raylib::prelude::*;
+
+const TITLE_HEIGHT: i32 = 24;
+const LINE_HEIGHT: i32 = 18;
+const FONT_SIZE: i32 = 14;
+const GUTTER_WIDTH: i32 = 18;
+const BYTES_OFFSET: i32 = GUTTER_WIDTH + 60;
+const TEXT_OFFSET: i32 = BYTES_OFFSET + 90;
+const BUTTON_WIDTH: i32 = 76;
+const BUTTON_HEIGHT: i32 = 16;
+const MAX_HISTORY: usize = 32;
+
+/// Live disassembly around PC, decoded from the currently mapped memory without executing anything.
+/// Keeps following PC until the user scrolls or follows a jump, `F6` goes back to it.
+/// `F7` toggles the instruction trace and `F8` saves its last lines
+#[derive(Debug, Default)]
+pub struct DisassemblyPanel {
+    area: MouseButtonArea,
+    top: u16,
+    follow_pc: bool,
+    pc: u16,
+    paused: bool,
+    tracing: bool,
+    history: Vec<u16>,
+    listing: Vec<Disassembly>,
+}
+
+impl DisassemblyPanel {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            area: MouseButtonArea::new(x, y, width, height),
+            follow_pc: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn update_layout(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.area = MouseButtonArea::new(x, y, width, height);
+    }
+
+    fn visible_lines(&self) -> usize { ((self.area.height - TITLE_HEIGHT) / LINE_HEIGHT).max(0) as usize }
+
+    fn line_area(&self, row: usize) -> MouseButtonArea {
+        MouseButtonArea::new(
+            self.area.x,
+            self.area.y + TITLE_HEIGHT + row as i32 * LINE_HEIGHT,
+            self.area.width,
+            LINE_HEIGHT,
+        )
+    }
+
+    fn button_area(&self, index: i32) -> MouseButtonArea {
+        let x = self.area.x + self.area.width - (BUTTON_WIDTH + 6) * (3 - index);
+        MouseButtonArea::new(x, self.area.y, BUTTON_WIDTH, BUTTON_HEIGHT)
+    }
+
+    fn jump_to(&mut self, address: u16) {
+        if self.history.len() == MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(self.top);
+        self.top = address;
+        self.follow_pc = false;
+    }
+
+    /// Handle mouse and keyboard interaction and decode the visible instructions again.
+    /// Keyboard shortcuts are ignored without `shortcuts`, while another panel is being typed in
+    pub fn update(
+        &mut self,
+        rl: &RaylibHandle,
+        gb: &Dmg,
+        breakpoints: &mut BTreeSet<u16>,
+        paused: bool,
+        shortcuts: bool,
+    ) -> Option<DebuggerAction> {
+        let mut action = None;
+        let mouse = rl.get_mouse_position();
+        let clicked = rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT);
+        let pressed = |key| shortcuts && rl.is_key_pressed(key);
+
+        if pressed(KeyboardKey::KEY_F5) || (clicked && self.button_area(0).contains(mouse)) {
+            action = Some(DebuggerAction::TogglePause);
+        }
+        if pressed(KeyboardKey::KEY_F10) || (clicked && self.button_area(1).contains(mouse)) {
+            action = Some(DebuggerAction::Step);
+        }
+        if pressed(KeyboardKey::KEY_F6) || (clicked && self.button_area(2).contains(mouse)) {
+            self.follow_pc = true;
+            self.history.clear();
+        }
+        if pressed(KeyboardKey::KEY_F7) {
+            action = Some(DebuggerAction::ToggleTrace);
+        }
+        if pressed(KeyboardKey::KEY_F8) {
+            action = Some(DebuggerAction::SaveTrace);
+        }
+        if pressed(KeyboardKey::KEY_BACKSPACE)
+            && let Some(address) = self.history.pop()
+        {
+            self.top = address;
+        }
+
+        if self.area.contains(mouse) {
+            let wheel = rl.get_mouse_wheel_move();
+            if wheel < 0.0 {
+                // scroll down by whole instructions to keep the listing aligned
+                let lines = (-wheel).ceil() as usize;
+                if let Some(line) = self.listing.get(lines.min(self.listing.len().saturating_sub(1))) {
+                    self.top = line.address;
+                }
+                self.follow_pc = false;
+            } else if wheel > 0.0 {
+                // instructions can't be decoded backwards, go back byte by byte
+                self.top = self.top.wrapping_sub(wheel.ceil() as u16);
+                self.follow_pc = false;
+            }
+
+            if clicked {
+                let row = (0..self.listing.len()).find(|&row| self.line_area(row).contains(mouse));
+                if let Some(line) = row.map(|row| &self.listing[row]) {
+                    if (mouse.x as i32) < self.area.x + GUTTER_WIDTH {
+                        if !breakpoints.remove(&line.address) {
+                            breakpoints.insert(line.address);
+                        }
+                    } else if let Some(target) = line.target {
+                        self.jump_to(target);
+                    }
+                }
+            }
+        }
+
+        self.pc = gb.cpu.pc;
+        self.paused = paused;
+        self.tracing = gb.tracer.is_some();
+
+        // re-anchor on PC when it leaves the visible listing or falls in the middle of a decoded instruction
+        let lines = self.visible_lines();
+        if self.follow_pc
+            && !self
+                .listing
+                .iter()
+                .take(lines.saturating_sub(2))
+                .any(|l| l.address == self.pc)
+        {
+            self.top = self.pc;
+        }
+        self.listing = Cpu::disassemble_range(gb, self.top, lines);
+
+        action
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, breakpoints: &BTreeSet<u16>) {
+        let (x, y) = (self.area.x, self.area.y);
+
+        let mut status = if self.paused {
+            format!("disassembly  paused at ${:04X}", self.pc)
+        } else {
+            "disassembly  running".to_string()
+        };
+        if self.tracing {
+            status.push_str("  tracing");
+        }
+        d.draw_text(&status, x, y + 2, FONT_SIZE, SECONDARY);
+
+        let labels = [
+            if self.paused { "continue F5" } else { "pause F5" },
+            "step F10",
+            "goto pc F6",
+        ];
+        for (i, label) in labels.iter().enumerate() {
+            let button = self.button_area(i as i32);
+            let fg = if i == 2 && self.follow_pc {
+                FOREGROUND
+            } else {
+                SECONDARY
+            };
+            d.draw_rectangle_lines(button.x, button.y, button.width, button.height, fg);
+            let tw = d.measure_text(label, 10);
+            d.draw_text(label, button.x + (button.width - tw) / 2, button.y + 3, 10, fg);
+        }
+
+        for (row, line) in self.listing.iter().enumerate() {
+            let area = self.line_area(row);
+            let text_y = area.y + (LINE_HEIGHT - FONT_SIZE) / 2;
+            let is_current = line.address == self.pc;
+
+            let fg = if is_current {
+                d.draw_rectangle(area.x, area.y, area.width, area.height, PRIMARY);
+                BACKGROUND
+            } else {
+                FOREGROUND
+            };
+
+            if breakpoints.contains(&line.address) {
+                d.draw_circle(
+                    area.x + GUTTER_WIDTH / 2,
+                    area.y + LINE_HEIGHT / 2,
+                    5.0,
+                    Color::RED,
+                );
+            }
+
+            let bytes = line
+                .bytes()
+                .iter()
+                .map(|b| format!("{b:02X}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            d.draw_text(
+                &format!("${:04X}", line.address),
+                x + GUTTER_WIDTH,
+                text_y,
+                FONT_SIZE,
+                fg,
+            );
+            d.draw_text(
+                &bytes,
+                x + BYTES_OFFSET,
+                text_y,
+                FONT_SIZE,
+                if is_current { fg } else { SECONDARY },
+            );
+            d.draw_text(&line.text, x + TEXT_OFFSET, text_y, FONT_SIZE, fg);
+
+            if line.target.is_some() {
+                let tw = d.measure_text(&line.text, FONT_SIZE);
+                d.draw_line(
+                    x + TEXT_OFFSET,
+                    text_y + FONT_SIZE,
+                    x + TEXT_OFFSET + tw,
+                    text_y + FONT_SIZE,
+                    fg,
+                );
+            }
+
+            let cycles = line.cycles.to_string();
+            let cw = d.measure_text(&cycles, FONT_SIZE);
+            d.draw_text(
+                &cycles,
+                area.x + area.width - cw - 4,
+                text_y,
+                FONT_SIZE,
+                SECONDARY,
+            );
+        }
+    }
+}
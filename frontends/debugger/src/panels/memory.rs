@@ -0,0 +1,545 @@
+use std::{fs, path::PathBuf};
+
+use crate::utils::dirs::dumps_dir;
+use crate::utils::{BACKGROUND, FOREGROUND, PRIMARY, SECONDARY};
+use gbeed_core::{OAM_END, OAM_SIZE, OAM_START, VRAM_END, VRAM_SIZE, VRAM_START, prelude::*};
+use gbeed_raylib_common::{impl_cyclic_enum, input::MouseButtonArea};
+use raylib::prelude::*;
+
+const TITLE_HEIGHT: i32 = 24;
+const LINE_HEIGHT: i32 = 16;
+const FONT_SIZE: i32 = 12;
+const BYTES_PER_ROW: usize = 16;
+const ADDRESS_WIDTH: i32 = 72;
+const BYTE_WIDTH: i32 = 22;
+const ASCII_OFFSET: i32 = ADDRESS_WIDTH + BYTE_WIDTH * BYTES_PER_ROW as i32 + 8;
+const ASCII_WIDTH: i32 = 9;
+const TAB_WIDTH: i32 = 44;
+const GOTO_WIDTH: i32 = 86;
+const EXPORT_WIDTH: i32 = 56;
+const SCROLL_ROWS: f32 = 3.0;
+/// Frames a changed byte stays highlighted
+const CHANGE_HIGHLIGHT_FRAMES: u8 = 30;
+const CHANGED_COLOR: Color = Color {
+    r: 255,
+    g: 220,
+    b: 0,
+    a: 255,
+};
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+const VRAM_START_USIZE: usize = VRAM_START as usize;
+const VRAM_END_USIZE: usize = VRAM_END as usize;
+const OAM_START_USIZE: usize = OAM_START as usize;
+const OAM_END_USIZE: usize = OAM_END as usize;
+
+/// Memory areas that can be inspected, the cartridge ones show every bank and not only the mapped one
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryRegion {
+    #[default]
+    Map,
+    Rom,
+    ExternalRam,
+    Vram,
+    Oam,
+}
+
+use MemoryRegion::*;
+impl_cyclic_enum!(MemoryRegion, [Map, Rom, ExternalRam, Vram, Oam]);
+
+impl MemoryRegion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Map => "map",
+            Rom => "rom",
+            ExternalRam => "sram",
+            Vram => "vram",
+            Oam => "oam",
+        }
+    }
+
+    pub fn len(&self, gb: &Dmg) -> usize {
+        match self {
+            Map => 0x10000,
+            Rom => gb.cartridge.rom().len(),
+            ExternalRam => gb.cartridge.ram().map_or(0, |ram| ram.len()),
+            Vram => VRAM_SIZE as usize,
+            Oam => OAM_SIZE as usize,
+        }
+    }
+
+    pub fn read(&self, gb: &Dmg, offset: usize) -> u8 {
+        match self {
+            Map => gb.read(offset as u16),
+            Rom => gb.cartridge.rom()[offset],
+            ExternalRam => gb.cartridge.ram().map_or(0xFF, |ram| ram[offset]),
+            Vram => gb.read(VRAM_START + offset as u16),
+            Oam => gb.read(OAM_START + offset as u16),
+        }
+    }
+
+    /// Returns false when the region can't be written, writes in the ROM area are MBC commands
+    pub fn write(&self, gb: &mut Dmg, offset: usize, value: u8) -> bool {
+        match self {
+            Map => gb.write(offset as u16, value),
+            Rom => return false,
+            ExternalRam => match gb.cartridge.ram_mut() {
+                Some(ram) => ram[offset] = value,
+                None => return false,
+            },
+            Vram => gb.write(VRAM_START + offset as u16, value),
+            Oam => gb.write(OAM_START + offset as u16, value),
+        }
+        true
+    }
+
+    /// Address as seen by the CPU, prefixed with the bank in the cartridge regions
+    pub fn label(&self, offset: usize) -> String {
+        match self {
+            Map => format!("${offset:04X}"),
+            Rom => {
+                let bank = offset / ROM_BANK_SIZE;
+                let address = if bank == 0 {
+                    offset
+                } else {
+                    ROM_BANK_SIZE + offset % ROM_BANK_SIZE
+                };
+                format!("{bank:02X}:{address:04X}")
+            }
+            ExternalRam => format!(
+                "{:02X}:{:04X}",
+                offset / RAM_BANK_SIZE,
+                0xA000 + offset % RAM_BANK_SIZE
+            ),
+            Vram => format!("${:04X}", VRAM_START as usize + offset),
+            Oam => format!("${:04X}", OAM_START as usize + offset),
+        }
+    }
+
+    /// Parse a hex address typed by the user, `bank:address` is accepted in the cartridge regions
+    pub fn parse_address(&self, input: &str) -> Option<usize> {
+        let parse = |s: &str| usize::from_str_radix(s.trim().trim_start_matches('$'), 16).ok();
+
+        let offset = match (self, input.split_once(':')) {
+            (Rom, Some((bank, address))) => parse(bank)? * ROM_BANK_SIZE + parse(address)? % ROM_BANK_SIZE,
+            (ExternalRam, Some((bank, address))) => {
+                parse(bank)? * RAM_BANK_SIZE + parse(address)? % RAM_BANK_SIZE
+            }
+            (_, Some(_)) => return None,
+            (ExternalRam, None) => match parse(input)? {
+                address @ 0xA000..=0xBFFF => address - 0xA000,
+                offset => offset,
+            },
+            (Vram, None) => match parse(input)? {
+                address @ VRAM_START_USIZE..=VRAM_END_USIZE => address - VRAM_START_USIZE,
+                offset => offset,
+            },
+            (Oam, None) => match parse(input)? {
+                address @ OAM_START_USIZE..=OAM_END_USIZE => address - OAM_START_USIZE,
+                offset => offset,
+            },
+            (Map | Rom, None) => parse(input)?,
+        };
+
+        Some(offset)
+    }
+}
+
+/// Hex viewer and editor.
+/// Click a byte and type hex digits to write it, arrows move the selection.
+/// Files dropped on the panel are imported at the selected byte, export dumps the whole region
+#[derive(Debug, Default)]
+pub struct MemoryPanel {
+    area: MouseButtonArea,
+    region: MemoryRegion,
+    region_len: usize,
+    top_row: usize,
+    selected: Option<usize>,
+    high_nibble: Option<u8>,
+    goto_input: Option<String>,
+
+    // visible bytes of the last frame and how long ago each one changed
+    snapshot_start: usize,
+    snapshot: Vec<u8>,
+    ages: Vec<u8>,
+}
+
+impl MemoryPanel {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            area: MouseButtonArea::new(x, y, width, height),
+            ..Default::default()
+        }
+    }
+
+    pub fn update_layout(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.area = MouseButtonArea::new(x, y, width, height);
+    }
+
+    /// While editing a byte or typing an address the keyboard shouldn't reach the joypad
+    pub fn captures_keyboard(&self) -> bool { self.selected.is_some() || self.goto_input.is_some() }
+
+    fn visible_rows(&self) -> usize { ((self.area.height - TITLE_HEIGHT) / LINE_HEIGHT).max(0) as usize }
+
+    fn total_rows(&self) -> usize { self.region_len.div_ceil(BYTES_PER_ROW) }
+
+    fn tab_area(&self, index: usize) -> MouseButtonArea {
+        MouseButtonArea::new(
+            self.area.x + 64 + index as i32 * (TAB_WIDTH + 4),
+            self.area.y,
+            TAB_WIDTH,
+            16,
+        )
+    }
+
+    fn goto_area(&self) -> MouseButtonArea {
+        MouseButtonArea::new(
+            self.area.x + self.area.width - EXPORT_WIDTH - GOTO_WIDTH - 8,
+            self.area.y,
+            GOTO_WIDTH,
+            16,
+        )
+    }
+
+    fn export_area(&self) -> MouseButtonArea {
+        MouseButtonArea::new(
+            self.area.x + self.area.width - EXPORT_WIDTH,
+            self.area.y,
+            EXPORT_WIDTH,
+            16,
+        )
+    }
+
+    fn byte_area(&self, offset: usize) -> Option<MouseButtonArea> {
+        let row = (offset / BYTES_PER_ROW).checked_sub(self.top_row)?;
+        if row >= self.visible_rows() {
+            return None;
+        }
+
+        Some(MouseButtonArea::new(
+            self.area.x + ADDRESS_WIDTH + (offset % BYTES_PER_ROW) as i32 * BYTE_WIDTH,
+            self.area.y + TITLE_HEIGHT + row as i32 * LINE_HEIGHT,
+            BYTE_WIDTH,
+            LINE_HEIGHT,
+        ))
+    }
+
+    fn scroll_to(&mut self, offset: usize) {
+        let row = offset / BYTES_PER_ROW;
+        let visible = self.visible_rows();
+        if row < self.top_row || row >= self.top_row + visible {
+            self.top_row = row.saturating_sub(visible / 2);
+        }
+        self.clamp_scroll();
+    }
+
+    fn clamp_scroll(&mut self) {
+        self.top_row = self
+            .top_row
+            .min(self.total_rows().saturating_sub(self.visible_rows()));
+    }
+
+    fn select_region(&mut self, region: MemoryRegion) {
+        self.region = region;
+        self.top_row = 0;
+        self.selected = None;
+        self.high_nibble = None;
+        self.snapshot.clear();
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle, gb: &mut Dmg) {
+        self.region_len = self.region.len(gb);
+        let mouse = rl.get_mouse_position();
+        let clicked = rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT);
+
+        if clicked {
+            if let Some(region) = (0..MemoryRegion::ALL.len())
+                .find(|&i| self.tab_area(i).contains(mouse))
+                .map(|i| MemoryRegion::ALL[i])
+            {
+                self.select_region(region);
+                self.region_len = region.len(gb);
+            } else if self.goto_area().contains(mouse) {
+                self.goto_input = Some(String::new());
+                self.selected = None;
+            } else if self.export_area().contains(mouse) {
+                self.export(gb);
+            } else if self.area.contains(mouse) {
+                self.goto_input = None;
+                self.high_nibble = None;
+                self.selected = (self.top_row * BYTES_PER_ROW..self.region_len)
+                    .take(self.visible_rows() * BYTES_PER_ROW)
+                    .find(|&offset| self.byte_area(offset).is_some_and(|area| area.contains(mouse)));
+            } else {
+                self.goto_input = None;
+                self.selected = None;
+            }
+        }
+
+        if self.area.contains(mouse) {
+            let wheel = rl.get_mouse_wheel_move();
+            if wheel != 0.0 {
+                let rows = (wheel.abs() * SCROLL_ROWS) as usize;
+                self.top_row = match wheel > 0.0 {
+                    true => self.top_row.saturating_sub(rows),
+                    false => self.top_row + rows,
+                };
+            }
+
+            if rl.is_file_dropped() {
+                let dropped_files = rl.load_dropped_files();
+                if let Some(path) = dropped_files.iter().next() {
+                    let start = self.selected.unwrap_or(self.top_row * BYTES_PER_ROW);
+                    self.import(gb, PathBuf::from(path), start);
+                }
+            }
+        }
+
+        if self.goto_input.is_some() {
+            self.update_goto(rl);
+        } else if self.selected.is_some() {
+            self.update_edit(rl, gb);
+        }
+
+        self.clamp_scroll();
+        self.update_changes(gb);
+    }
+
+    fn update_goto(&mut self, rl: &mut RaylibHandle) {
+        let Some(input) = &mut self.goto_input else { return };
+
+        while let Some(c) = rl.get_char_pressed() {
+            if (c.is_ascii_hexdigit() || c == ':') && input.len() < 8 {
+                input.push(c.to_ascii_uppercase());
+            }
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            input.pop();
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            let target = self
+                .region
+                .parse_address(input)
+                .filter(|&offset| offset < self.region_len);
+            self.goto_input = None;
+
+            if let Some(offset) = target {
+                self.selected = Some(offset);
+                self.scroll_to(offset);
+            }
+        } else if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            self.goto_input = None;
+        }
+    }
+
+    fn update_edit(&mut self, rl: &mut RaylibHandle, gb: &mut Dmg) {
+        let Some(mut offset) = self.selected else { return };
+
+        while let Some(c) = rl.get_char_pressed() {
+            let Some(nibble) = c.to_digit(16) else { continue };
+
+            match self.high_nibble.take() {
+                None => self.high_nibble = Some(nibble as u8),
+                Some(high) => {
+                    let value = (high << 4) | nibble as u8;
+                    if !self.region.write(gb, offset, value) {
+                        eprintln!("{} region is read only", self.region.name());
+                    }
+                    offset = (offset + 1).min(self.region_len.saturating_sub(1));
+                }
+            }
+        }
+
+        let moves = [
+            (KeyboardKey::KEY_LEFT, -1),
+            (KeyboardKey::KEY_RIGHT, 1),
+            (KeyboardKey::KEY_UP, -(BYTES_PER_ROW as isize)),
+            (KeyboardKey::KEY_DOWN, BYTES_PER_ROW as isize),
+        ];
+        for (key, delta) in moves {
+            if rl.is_key_pressed(key) || rl.is_key_pressed_repeat(key) {
+                if let Some(next) = offset.checked_add_signed(delta).filter(|&o| o < self.region_len) {
+                    offset = next;
+                }
+                self.high_nibble = None;
+            }
+        }
+
+        self.selected = Some(offset);
+        self.scroll_to(offset);
+
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) || rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            self.selected = None;
+            self.high_nibble = None;
+        }
+    }
+
+    fn update_changes(&mut self, gb: &Dmg) {
+        let start = self.top_row * BYTES_PER_ROW;
+        let end = (start + self.visible_rows() * BYTES_PER_ROW).min(self.region_len);
+        let current: Vec<u8> = (start..end).map(|offset| self.region.read(gb, offset)).collect();
+
+        // the view moved, there is nothing to compare against
+        if start != self.snapshot_start || current.len() != self.snapshot.len() {
+            self.ages = vec![0; current.len()];
+        } else {
+            for ((age, old), new) in self.ages.iter_mut().zip(&self.snapshot).zip(&current) {
+                *age = if old != new {
+                    CHANGE_HIGHLIGHT_FRAMES
+                } else {
+                    age.saturating_sub(1)
+                };
+            }
+        }
+
+        self.snapshot_start = start;
+        self.snapshot = current;
+    }
+
+    fn export(&self, gb: &Dmg) {
+        let data: Vec<u8> = (0..self.region_len)
+            .map(|offset| self.region.read(gb, offset))
+            .collect();
+        let dir = dumps_dir();
+        let path = dir.join(format!("{}.bin", self.region.name()));
+
+        let result = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, data));
+        match result {
+            Ok(_) => println!("Exported {} region to {}", self.region.name(), path.display()),
+            Err(e) => eprintln!("Failed to export {} region: {e}", self.region.name()),
+        }
+    }
+
+    fn import(&self, gb: &mut Dmg, path: PathBuf, start: usize) {
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) => {
+                eprintln!("Failed to import {}: {e}", path.display());
+                return;
+            }
+        };
+
+        let len = data.len().min(self.region_len.saturating_sub(start));
+        for (i, &value) in data.iter().take(len).enumerate() {
+            if !self.region.write(gb, start + i, value) {
+                eprintln!("{} region is read only", self.region.name());
+                return;
+            }
+        }
+
+        println!(
+            "Imported {len} bytes from {} at {}",
+            path.display(),
+            self.region.label(start)
+        );
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        let (x, y) = (self.area.x, self.area.y);
+
+        d.draw_text("memory", x, y + 2, 14, SECONDARY);
+
+        for (i, region) in MemoryRegion::ALL.iter().enumerate() {
+            let tab = self.tab_area(i);
+            let (bg, fg) = match *region == self.region {
+                true => (FOREGROUND, BACKGROUND),
+                false => (BACKGROUND, SECONDARY),
+            };
+            d.draw_rectangle(tab.x, tab.y, tab.width, tab.height, bg);
+            d.draw_rectangle_lines(tab.x, tab.y, tab.width, tab.height, SECONDARY);
+            let tw = d.measure_text(region.name(), 10);
+            d.draw_text(region.name(), tab.x + (tab.width - tw) / 2, tab.y + 3, 10, fg);
+        }
+
+        let goto = self.goto_area();
+        let goto_text = match &self.goto_input {
+            Some(input) => format!("{input}_"),
+            None => "goto".to_string(),
+        };
+        let goto_color = if self.goto_input.is_some() {
+            FOREGROUND
+        } else {
+            SECONDARY
+        };
+        d.draw_rectangle_lines(goto.x, goto.y, goto.width, goto.height, goto_color);
+        d.draw_text(&goto_text, goto.x + 4, goto.y + 3, 10, goto_color);
+
+        let export = self.export_area();
+        d.draw_rectangle_lines(export.x, export.y, export.width, export.height, SECONDARY);
+        let tw = d.measure_text("export", 10);
+        d.draw_text(
+            "export",
+            export.x + (export.width - tw) / 2,
+            export.y + 3,
+            10,
+            SECONDARY,
+        );
+
+        if self.region_len == 0 {
+            d.draw_text(
+                "no external ram in this cartridge",
+                x,
+                y + TITLE_HEIGHT,
+                FONT_SIZE,
+                SECONDARY,
+            );
+            return;
+        }
+
+        for row in 0..self.visible_rows() {
+            let row_offset = (self.top_row + row) * BYTES_PER_ROW;
+            if row_offset >= self.region_len {
+                break;
+            }
+
+            let row_y = y + TITLE_HEIGHT + row as i32 * LINE_HEIGHT;
+            d.draw_text(&self.region.label(row_offset), x, row_y, FONT_SIZE, SECONDARY);
+
+            for col in 0..BYTES_PER_ROW {
+                let offset = row_offset + col;
+                let Some(index) = offset.checked_sub(self.snapshot_start) else {
+                    break;
+                };
+                let Some(&value) = self.snapshot.get(index) else {
+                    break;
+                };
+
+                let cell_x = x + ADDRESS_WIDTH + col as i32 * BYTE_WIDTH;
+                let age = self.ages.get(index).copied().unwrap_or(0);
+                let fg = if self.selected == Some(offset) {
+                    d.draw_rectangle(cell_x - 2, row_y - 2, BYTE_WIDTH - 2, LINE_HEIGHT, PRIMARY);
+                    BACKGROUND
+                } else if age > 0 {
+                    let mut highlight = CHANGED_COLOR;
+                    highlight.a = (age as u32 * 255 / CHANGE_HIGHLIGHT_FRAMES as u32) as u8;
+                    d.draw_rectangle(cell_x - 2, row_y - 2, BYTE_WIDTH - 2, LINE_HEIGHT, highlight);
+                    FOREGROUND
+                } else {
+                    FOREGROUND
+                };
+
+                let text = match (self.selected == Some(offset), self.high_nibble) {
+                    (true, Some(high)) => format!("{high:X}_"),
+                    _ => format!("{value:02X}"),
+                };
+                d.draw_text(&text, cell_x, row_y, FONT_SIZE, fg);
+
+                let c = if value.is_ascii_graphic() {
+                    value as char
+                } else {
+                    '.'
+                };
+                d.draw_text(
+                    &c.to_string(),
+                    x + ASCII_OFFSET + col as i32 * ASCII_WIDTH,
+                    row_y,
+                    FONT_SIZE,
+                    SECONDARY,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,16 @@
+mod disassembly;
+mod memory;
+mod sprites;
+
+pub use disassembly::DisassemblyPanel;
+pub use memory::MemoryPanel;
+pub use sprites::SpritePanel;
+
+/// Execution requests coming from the debug panels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebuggerAction {
+    TogglePause,
+    Step,
+    ToggleTrace,
+    SaveTrace,
+}
@@ -0,0 +1,189 @@
+use crate::controller::TILE_PIXEL_SIZE;
+use crate::utils::{BACKGROUND, FOREGROUND, Layout, PRIMARY, SECONDARY};
+use gbeed_core::{MAX_SPRITES_IN_OAM, OamObject, prelude::*};
+use gbeed_raylib_common::{Texture, input::MouseButtonArea};
+use raylib::prelude::*;
+
+const TITLE_HEIGHT: i32 = 24;
+const FONT_SIZE: i32 = 10;
+const COLUMNS: i32 = 10;
+const ROWS: i32 = MAX_SPRITES_IN_OAM as i32 / COLUMNS;
+const CULLED_COLOR: Color = Color::RED;
+const HIGHLIGHT_COLOR: Color = Color {
+    r: 255,
+    g: 220,
+    b: 0,
+    a: 255,
+};
+
+/// Every OAM entry with a preview, flagging the ones on the current line and the ones culled at any line.
+/// Clicking one selects it and outlines it on the game screen
+#[derive(Debug, Default)]
+pub struct SpritePanel {
+    area: MouseButtonArea,
+    selected: Option<u8>,
+    line: u8,
+    height: u8,
+    objects: Vec<OamObject>,
+    /// Bit set for every object left out by the 10 objects per line limit in some visible line
+    culled_in_frame: u64,
+}
+
+impl SpritePanel {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self {
+            area: MouseButtonArea::new(x, y, width, height),
+            ..Default::default()
+        }
+    }
+
+    pub fn update_layout(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.area = MouseButtonArea::new(x, y, width, height);
+    }
+
+    fn cell_area(&self, index: usize) -> MouseButtonArea {
+        let width = self.area.width / COLUMNS;
+        let height = ((self.area.height - TITLE_HEIGHT) / ROWS).max(0);
+        let (column, row) = (index as i32 % COLUMNS, index as i32 / COLUMNS);
+
+        MouseButtonArea::new(
+            self.area.x + column * width,
+            self.area.y + TITLE_HEIGHT + row * height,
+            width,
+            height,
+        )
+    }
+
+    fn is_culled(&self, index: u8) -> bool { self.culled_in_frame & (1 << index) != 0 }
+
+    pub fn update(&mut self, rl: &RaylibHandle, gb: &Dmg) {
+        let mouse = rl.get_mouse_position();
+        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) && self.area.contains(mouse) {
+            let clicked = (0..MAX_SPRITES_IN_OAM as usize).find(|&i| self.cell_area(i).contains(mouse));
+            if let Some(index) = clicked {
+                // clicking the selected object again clears the selection
+                self.selected = match self.selected {
+                    Some(selected) if selected as usize == index => None,
+                    _ => Some(index as u8),
+                };
+            }
+        }
+
+        self.line = gb.ppu.current_line();
+        self.height = gb.ppu.sprite_height();
+        self.objects = gb.ppu.oam_objects(self.line).to_vec();
+
+        self.culled_in_frame = 0;
+        for line in 0..DMG_SCREEN_HEIGHT as u8 {
+            for object in gb.ppu.oam_objects(line).iter().filter(|o| o.culled) {
+                self.culled_in_frame |= 1 << object.index;
+            }
+        }
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle, texture: &Texture) {
+        let (x, y) = (self.area.x, self.area.y);
+
+        let title = match self.selected.and_then(|i| self.objects.get(i as usize)) {
+            Some(object) => describe(object, self.is_culled(object.index)),
+            None => format!("oam  8x{}  line {}", self.height, self.line),
+        };
+        d.draw_text(&title, x, y + 2, 14, SECONDARY);
+
+        for object in &self.objects {
+            let index = object.index as usize;
+            let cell = self.cell_area(index);
+            let selected = self.selected == Some(object.index);
+
+            let fg = if selected {
+                d.draw_rectangle(cell.x, cell.y, cell.width - 2, cell.height - 2, PRIMARY);
+                BACKGROUND
+            } else {
+                FOREGROUND
+            };
+            if self.is_culled(object.index) {
+                d.draw_rectangle_lines(cell.x, cell.y, cell.width - 2, cell.height - 2, CULLED_COLOR);
+            }
+
+            // twice the size when there is room for it
+            let scale = if cell.height >= TILE_PIXEL_SIZE * 4 + 4 {
+                2
+            } else {
+                1
+            };
+            let (w, h) = (TILE_PIXEL_SIZE * scale, self.height as i32 * scale);
+            d.draw_texture_pro(
+                texture,
+                Rectangle::new(
+                    (index as i32 * TILE_PIXEL_SIZE) as f32,
+                    0.0,
+                    TILE_PIXEL_SIZE as f32,
+                    self.height as f32,
+                ),
+                Rectangle::new((cell.x + 4) as f32, (cell.y + 4) as f32, w as f32, h as f32),
+                Vector2::ZERO,
+                0.0,
+                Color::WHITE,
+            );
+
+            let text_x = cell.x + 8 + w;
+            d.draw_text(&format!("{index:02}"), text_x, cell.y + 4, FONT_SIZE, fg);
+            d.draw_text(
+                &format!("${:02X}", object.sprite.tile_index),
+                text_x,
+                cell.y + 4 + FONT_SIZE + 2,
+                FONT_SIZE,
+                if selected { fg } else { SECONDARY },
+            );
+            if object.on_line {
+                d.draw_text("ly", text_x, cell.y + 4 + (FONT_SIZE + 2) * 2, FONT_SIZE, fg);
+            }
+        }
+    }
+
+    /// Outline the selected object over the game screen
+    pub fn draw_highlight(&self, d: &mut RaylibDrawHandle, layout: &Layout) {
+        let Some(object) = self.selected.and_then(|i| self.objects.get(i as usize)) else {
+            return;
+        };
+        let scale = layout.scaled_screen_width / DMG_SCREEN_WIDTH as i32;
+        let sprite = object.sprite;
+
+        d.draw_rectangle_lines_ex(
+            Rectangle::new(
+                (layout.game_x + sprite.screen_x() as i32 * scale) as f32,
+                (layout.game_y + sprite.screen_y() as i32 * scale) as f32,
+                (TILE_PIXEL_SIZE * scale) as f32,
+                (self.height as i32 * scale) as f32,
+            ),
+            2.0,
+            HIGHLIGHT_COLOR,
+        );
+    }
+}
+
+fn describe(object: &OamObject, culled_in_frame: bool) -> String {
+    let sprite = &object.sprite;
+    let flip = match (sprite.xflip(), sprite.yflip()) {
+        (false, false) => "--",
+        (true, false) => "x-",
+        (false, true) => "-y",
+        (true, true) => "xy",
+    };
+
+    format!(
+        "oam #{:02}  x {}  y {}  tile ${:02X}  obp{}  flip {flip}  {}{}{}",
+        object.index,
+        sprite.screen_x(),
+        sprite.screen_y(),
+        sprite.tile_index,
+        sprite.palette_number() as u8,
+        if sprite.priority() {
+            "behind bg"
+        } else {
+            "above bg"
+        },
+        if object.on_line { "  on line" } else { "" },
+        if culled_in_frame { "  culled" } else { "" },
+    )
+}
@@ -2,14 +2,27 @@ use crate::controller::{
     DebuggerController, TILE_DISPLAY_HEIGHT, TILE_DISPLAY_SCALE, TILE_DISPLAY_WIDTH, TILE_PIXEL_SIZE,
     TILE_TEXTURE_HEIGHT, TILE_TEXTURE_WIDTH, TILES_PER_COLUMN, TILES_PER_ROW,
 };
+use crate::export::{ExportPalette, export_vram};
+use crate::panels::{DebuggerAction, DisassemblyPanel, MemoryPanel, SpritePanel};
 use crate::scenes::EmulatorState;
+use crate::utils::dirs::{dumps_dir, recordings_dir, screenshots_dir};
 use crate::utils::{
     BACKGROUND, FOREGROUND, HEADER_HEIGHT, Layout, PANEL_PADDING, PRIMARY, SECONDARY, components::*,
 };
 use gbeed_core::prelude::*;
+use gbeed_core::{TraceFormat, TraceOutput, Tracer};
 use gbeed_raylib_common::Texture;
-use gbeed_raylib_common::input::InputManager;
+use gbeed_raylib_common::input::{InputEvdevTriggers, InputGamepadTriggers, InputManager, InputState};
+use gbeed_raylib_common::screenshot::save_screenshot;
+use gbeed_raylib_common::settings::SyncMode;
 use raylib::prelude::*;
+use std::collections::BTreeSet;
+use std::fs;
+
+/// Instructions kept by the trace toggled from the disassembly panel
+const TRACE_RING_LINES: usize = 4096;
+/// Most frames run in a single update while audio synced
+const MAX_AUDIO_SYNC_FRAMES: usize = 4;
 
 #[derive(Default, Debug)]
 pub struct EmulationScene {
@@ -19,23 +32,74 @@ pub struct EmulationScene {
     pub scroll_y: i32,
     pub game_name: String,
     pub game_region: String,
+
+    pub paused: bool,
+    pub breakpoints: BTreeSet<u16>,
+    pub disassembly: DisassemblyPanel,
+    pub memory: MemoryPanel,
+    pub sprites: SpritePanel,
 }
 
 impl EmulationScene {
     pub fn new(layout: Layout, game_name: String, game_region: String) -> Box<Self> {
         Box::new(Self {
-            input: InputManager::new(0.08, None, Some(layout.get_mouse_triggers()), None),
+            input: InputManager::new(
+                0.08,
+                None,
+                Some(layout.get_mouse_triggers()),
+                Some(InputGamepadTriggers::default()),
+                None,
+                InputEvdevTriggers::shared(),
+            ),
             layout,
             scroll_x: 0,
             scroll_y: 0,
             game_name,
             game_region,
+            paused: false,
+            breakpoints: BTreeSet::new(),
+            disassembly: DisassemblyPanel::new(
+                layout.middle_panel_x,
+                layout.debug_panel_y,
+                layout.bg_map_width,
+                layout.debug_panel_height,
+            ),
+            memory: MemoryPanel::new(
+                layout.right_panel_x,
+                layout.debug_panel_y,
+                layout.right_panel_width,
+                layout.debug_panel_height,
+            ),
+            sprites: SpritePanel::new(
+                layout.game_x,
+                layout.sprite_panel_y,
+                layout.scaled_screen_width,
+                layout.sprite_panel_height,
+            ),
         })
     }
 
     pub fn update_layout(&mut self, layout: Layout) {
         self.layout = layout;
         self.input.mouse_triggers = Some(layout.get_mouse_triggers());
+        self.disassembly.update_layout(
+            layout.middle_panel_x,
+            layout.debug_panel_y,
+            layout.bg_map_width,
+            layout.debug_panel_height,
+        );
+        self.memory.update_layout(
+            layout.right_panel_x,
+            layout.debug_panel_y,
+            layout.right_panel_width,
+            layout.debug_panel_height,
+        );
+        self.sprites.update_layout(
+            layout.game_x,
+            layout.sprite_panel_y,
+            layout.scaled_screen_width,
+            layout.sprite_panel_height,
+        );
     }
 
     pub fn update(
@@ -47,8 +111,92 @@ impl EmulationScene {
         self.input.update(controller.rl, dt);
 
         if let Some(gb) = gb {
-            self.input.state().apply(&mut gb.joypad);
-            gb.run(controller)?;
+            // typing in the memory panel shouldn't press buttons nor trigger shortcuts
+            let typing = self.memory.captures_keyboard();
+            let input = match typing {
+                true => InputState::default(),
+                false => self.input.state(),
+            };
+
+            if !typing && self.input.is_pressed_record() {
+                let sample_rate = controller.audio.sample_rate().hz();
+                controller
+                    .recorder
+                    .toggle(&recordings_dir(), &self.game_name, sample_rate);
+            }
+
+            if !typing && self.input.is_pressed_screenshot() {
+                let pixels = controller.screen_texture.pixels();
+                match save_screenshot(&screenshots_dir(), &self.game_name, pixels) {
+                    Ok(path) => println!("Saved screenshot to {}", path.display()),
+                    Err(e) => eprintln!("Failed to save screenshot: {e}"),
+                }
+            }
+
+            // F11 exports the vram viewers with the game palettes, shift+F11 with the raw color ids
+            if !typing && controller.rl.is_key_pressed(KeyboardKey::KEY_F11) {
+                let palette = match controller.rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) {
+                    true => ExportPalette::Grayscale,
+                    false => ExportPalette::Current,
+                };
+                export_vram(&gb.ppu, palette);
+            }
+
+            let action = if self.layout.is_mobile {
+                None
+            } else {
+                self.memory.update(controller.rl, gb);
+                self.sprites.update(controller.rl, gb);
+                self.disassembly
+                    .update(controller.rl, gb, &mut self.breakpoints, self.paused, !typing)
+            };
+
+            match (action, self.paused) {
+                (Some(DebuggerAction::TogglePause), true) => {
+                    // leave the breakpoint we are stopped at before running again
+                    gb.step(controller)?;
+                    self.paused = false;
+                }
+                (Some(DebuggerAction::TogglePause), false) => self.paused = true,
+                (Some(DebuggerAction::Step), _) => {
+                    gb.step(controller)?;
+                    self.paused = true;
+                }
+                (Some(DebuggerAction::ToggleTrace), _) => {
+                    gb.tracer = match gb.tracer.take() {
+                        Some(mut tracer) => {
+                            let _ = tracer.flush();
+                            None
+                        }
+                        None => Some(Tracer::ring_buffer(TraceFormat::Extended, TRACE_RING_LINES)),
+                    };
+                }
+                (Some(DebuggerAction::SaveTrace), _) => save_trace(gb),
+                _ => {}
+            }
+
+            // the stream keeps asking for samples even when no frame runs
+            controller.audio.update();
+
+            let breakpoints = &self.breakpoints;
+            match controller.sync_mode {
+                SyncMode::Video if !self.paused => {
+                    gb.apu.set_rate_adjustment(controller.audio.rate_adjustment());
+                    input.apply(&mut gb.joypad, &controller.turbo);
+                    self.paused = gb.run_until(controller, |gb| breakpoints.contains(&gb.cpu.pc))?;
+                }
+                SyncMode::Audio => {
+                    gb.apu.set_rate_adjustment(1.0);
+                    // bounded, so a stalled audio device can't freeze the window
+                    let mut frames = 0;
+                    while !self.paused && controller.audio.needs_samples() && frames < MAX_AUDIO_SYNC_FRAMES {
+                        input.apply(&mut gb.joypad, &controller.turbo);
+                        self.paused = gb.run_until(controller, |gb| breakpoints.contains(&gb.cpu.pc))?;
+                        frames += 1;
+                    }
+                }
+                _ => {}
+            }
         }
 
         Ok(None)
@@ -60,17 +208,19 @@ impl EmulationScene {
         screen_texture: &Texture,
         tile_textures: &[Texture; 3],
         bg_map_texture: &Texture,
+        sprite_texture: &Texture,
+        recording: bool,
     ) {
         if !self.layout.is_mobile {
             self.draw_separators(d);
         }
 
-        self.draw_header(d);
+        self.draw_header(d, recording);
         self.draw_screen(d, screen_texture);
         self.draw_controls(d);
 
         if !self.layout.is_mobile {
-            self.draw_debug_panels(d, tile_textures, bg_map_texture);
+            self.draw_debug_panels(d, tile_textures, bg_map_texture, sprite_texture);
         }
     }
 
@@ -84,7 +234,7 @@ impl EmulationScene {
         );
     }
 
-    fn draw_header(&self, d: &mut RaylibDrawHandle) {
+    fn draw_header(&self, d: &mut RaylibDrawHandle, recording: bool) {
         let header_y = PANEL_PADDING + HEADER_HEIGHT / 2;
 
         // Game info
@@ -99,14 +249,27 @@ impl EmulationScene {
         );
 
         let region_font_size = 11;
+        let region_x = self.layout.game_x + name_width + 10;
         d.draw_text(
             &self.game_region,
-            self.layout.game_x + name_width + 10,
+            region_x,
             header_y - region_font_size / 2,
             region_font_size,
             SECONDARY,
         );
 
+        if recording {
+            let rec_x = region_x + d.measure_text(&self.game_region, region_font_size) + 12;
+            d.draw_circle(rec_x + 4, header_y, 4.0, Color::RED);
+            d.draw_text(
+                "rec",
+                rec_x + 12,
+                header_y - region_font_size / 2,
+                region_font_size,
+                Color::RED,
+            );
+        }
+
         let fps_font_size = 26;
         let fps_str = format!("{:3}", d.get_fps());
         let fps_width = d.measure_text(&fps_str, fps_font_size);
@@ -245,9 +408,14 @@ impl EmulationScene {
         d: &mut RaylibDrawHandle,
         tile_textures: &[Texture; 3],
         bg_map_texture: &Texture,
+        sprite_texture: &Texture,
     ) {
         self.draw_bg_map_panel(d, bg_map_texture);
         self.draw_tile_panels(d, tile_textures);
+        self.disassembly.draw(d, &self.breakpoints);
+        self.memory.draw(d);
+        self.sprites.draw(d, sprite_texture);
+        self.sprites.draw_highlight(d, &self.layout);
     }
 
     fn draw_bg_map_panel(&self, d: &mut RaylibDrawHandle, texture: &Texture) {
@@ -348,3 +516,26 @@ impl EmulationScene {
         }
     }
 }
+
+/// Write the lines kept by an in-memory trace next to the memory dumps
+fn save_trace(gb: &Dmg) {
+    let Some(tracer) = gb.tracer.as_ref() else {
+        eprintln!("No trace to save, enable it with F7");
+        return;
+    };
+    if let TraceOutput::File(_) = tracer.output() {
+        eprintln!("Trace is already being written to a file");
+        return;
+    }
+
+    let mut data = tracer.lines().collect::<Vec<_>>().join("\n");
+    data.push('\n');
+    let dir = dumps_dir();
+    let path = dir.join("trace.log");
+
+    let result = fs::create_dir_all(&dir).and_then(|_| fs::write(&path, data));
+    match result {
+        Ok(_) => println!("Saved trace to {}", path.display()),
+        Err(e) => eprintln!("Failed to save trace: {e}"),
+    }
+}
@@ -0,0 +1,18 @@
+use gbeed_raylib_common::config::{Paths, resolve_dir};
+use std::path::PathBuf;
+
+/// Memory, trace and VRAM dumps
+#[inline(always)]
+pub fn dumps_dir() -> PathBuf { resolve_dir(Paths::configured(), |paths| &paths.dumps, "dumps") }
+
+/// Recordings toggled with `F9`
+#[inline(always)]
+pub fn recordings_dir() -> PathBuf {
+    resolve_dir(Paths::configured(), |paths| &paths.recordings, "recordings")
+}
+
+/// Screenshots taken with `F12`
+#[inline(always)]
+pub fn screenshots_dir() -> PathBuf {
+    resolve_dir(Paths::configured(), |paths| &paths.screenshots, "screenshots")
+}
@@ -15,8 +15,13 @@ pub struct Layout {
     pub controls_y: i32,
     pub middle_panel_x: i32,
     pub right_panel_x: i32,
+    pub right_panel_width: i32,
     pub bg_map_width: i32,
     pub bg_map_height: i32,
+    pub debug_panel_y: i32,
+    pub debug_panel_height: i32,
+    pub sprite_panel_y: i32,
+    pub sprite_panel_height: i32,
 
     pub dpad_x: i32,
     pub dpad_y: i32,
@@ -33,7 +38,7 @@ pub struct Layout {
 }
 
 impl Layout {
-    pub fn new(screen_width: i32, _screen_height: i32, is_mobile: bool) -> Self {
+    pub fn new(screen_width: i32, screen_height: i32, is_mobile: bool) -> Self {
         let game_y = PANEL_PADDING + HEADER_HEIGHT;
 
         if is_mobile {
@@ -71,8 +76,13 @@ impl Layout {
                 controls_y,
                 middle_panel_x: 0,
                 right_panel_x: 0,
+                right_panel_width: 0,
                 bg_map_width: 0,
                 bg_map_height: 0,
+                debug_panel_y: 0,
+                debug_panel_height: 0,
+                sprite_panel_y: 0,
+                sprite_panel_height: 0,
                 dpad_x,
                 dpad_y,
                 dpad_arm,
@@ -97,6 +107,15 @@ impl Layout {
             let bg_map_width = scaled_screen_height;
             let bg_map_height = scaled_screen_height;
             let right_panel_x = middle_panel_x + bg_map_width + PANEL_PADDING * 2;
+            let right_panel_width = screen_width - right_panel_x - PANEL_PADDING;
+
+            // space left under the bg map and tile blocks
+            let debug_panel_y = game_y + bg_map_height + PANEL_PADDING * 3;
+            let debug_panel_height = (screen_height - debug_panel_y - PANEL_PADDING).max(0);
+
+            // space left under the controls
+            let sprite_panel_y = controls_y + 150;
+            let sprite_panel_height = (screen_height - sprite_panel_y - PANEL_PADDING).max(0);
 
             let dpad_arm = 28;
             let dpad_size = 17;
@@ -123,8 +142,13 @@ impl Layout {
                 controls_y,
                 middle_panel_x,
                 right_panel_x,
+                right_panel_width,
                 bg_map_width,
                 bg_map_height,
+                debug_panel_y,
+                debug_panel_height,
+                sprite_panel_y,
+                sprite_panel_height,
                 dpad_x,
                 dpad_y,
                 dpad_arm,
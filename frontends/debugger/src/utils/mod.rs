@@ -1,4 +1,5 @@
 pub mod components;
+pub mod dirs;
 mod layout;
 
 use gbeed_raylib_common::color::DMG_CLASSIC_PALETTE;
@@ -0,0 +1,37 @@
+use gbeed_core::prelude::*;
+use gbeed_core::{AudioPlayer, DefaultAudioPlayer, DefaultRenderer, Ppu};
+use std::io::Write;
+
+/// Prints every byte sent through the link cable, test ROMs report their results this way
+#[derive(Default)]
+pub struct StdoutSerialListener {
+    pub received: Vec<u8>,
+}
+
+impl SerialListener for StdoutSerialListener {
+    fn on_transfer(&mut self, data: u8) {
+        self.received.push(data);
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(&[data]);
+        let _ = stdout.flush();
+    }
+}
+
+controller!(
+    HeadlessController,
+    StdoutSerialListener,
+    DefaultRenderer,
+    DefaultAudioPlayer
+);
+
+impl HeadlessController {
+    pub fn new() -> Self {
+        Self {
+            listener: StdoutSerialListener::default(),
+            renderer: DefaultRenderer::new(),
+            audio_player: DefaultAudioPlayer::new(),
+        }
+    }
+
+    pub fn serial_output(&self) -> &[u8] { &self.listener.received }
+}
@@ -0,0 +1,109 @@
+use gbeed_core::prelude::*;
+use gbeed_core::{SaveFormat, export_save, import_save};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct ConvertOptions {
+    input: String,
+    output: String,
+    format: Option<SaveFormat>,
+    game_path: Option<String>,
+}
+
+/// `convert-save` subcommand, rewriting a battery save in the layout another emulator expects
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(options) = parse_args(args)? else {
+        print_help();
+        return Ok(());
+    };
+
+    // the cartridge tells the RAM size apart from a clock footer when the file size alone can't
+    let ram_size = match &options.game_path {
+        Some(path) => {
+            let game = Cartridge::new(&fs::read(path)?, None).map_err(|e| format!("{e}"))?;
+            // MBC3 carts with only a clock save an empty RAM followed by it
+            let ram_size = game
+                .save_game()
+                .map(<[u8]>::len)
+                .or_else(|| game.rtc().map(|_| 0));
+            Some(ram_size.ok_or("the game has no battery backed RAM nor clock to save")?)
+        }
+        None => None,
+    };
+
+    let save = import_save(&fs::read(&options.input)?, ram_size)?;
+    let format = options.format.unwrap_or(match options.output.ends_with(".srm") {
+        true => SaveFormat::Srm,
+        false if save.rtc.is_some() => SaveFormat::Rtc,
+        false => SaveFormat::Raw,
+    });
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    fs::write(&options.output, export_save(&save, format, timestamp))?;
+
+    println!(
+        "Converted {} bytes of RAM to the {} format ({}){}",
+        save.ram.len(),
+        format.name(),
+        format.description(),
+        match save.rtc.is_some() && !format.has_rtc() {
+            true => ", which has no room for the clock of the input",
+            false => "",
+        }
+    );
+    Ok(())
+}
+
+/// Returns `None` when help was requested
+fn parse_args(args: &[String]) -> Result<Option<ConvertOptions>, String> {
+    let mut paths = Vec::new();
+    let mut format = None;
+    let mut game_path = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("-f" | "--format", Some(name)) => {
+                format = Some(SaveFormat::from_name(name).ok_or(format!("unknown save format: {name}"))?)
+            }
+            ("-g" | "--game", Some(path)) => game_path = Some(path.clone()),
+            ("-h" | "--help", _) => return Ok(None),
+            (arg, _) if !arg.starts_with('-') => {
+                paths.push(arg.to_string());
+                i += 1;
+                continue;
+            }
+            (arg, _) => return Err(format!("unexpected argument: {arg}")),
+        }
+        i += 2;
+    }
+
+    let [input, output] =
+        <[String; 2]>::try_from(paths).map_err(|_| "expected an input and an output save")?;
+    Ok(Some(ConvertOptions {
+        input,
+        output,
+        format,
+        game_path,
+    }))
+}
+
+fn print_help() {
+    println!("Usage: gbeed-headless convert-save <INPUT> <OUTPUT> [OPTIONS]");
+    println!("Converts a battery save between the layouts of different emulators and flash carts");
+    println!("Options:");
+    println!("  -f, --format <FORMAT>          Output format, guessed from the output name otherwise:");
+    for format in SaveFormat::ALL {
+        println!(
+            "                                   {:<6} {}",
+            format.name(),
+            format.description()
+        );
+    }
+    println!("  -g, --game <PATH>              ROM the save is for, to tell its RAM size");
+    println!("  -h, --help                     Print this help message");
+}
@@ -0,0 +1,145 @@
+use gbeed_core::prelude::*;
+use gbeed_core::{DOCTOR_LY, TraceFormat, Tracer};
+use std::fs;
+
+mod controller;
+mod convert;
+
+use controller::HeadlessController;
+
+/// Frames run when `--frames` is not given, one minute of emulated time
+const DEFAULT_FRAMES: usize = 60 * 60;
+
+struct Options {
+    game_path: String,
+    boot_path: Option<String>,
+    frames: usize,
+    trace_path: Option<String>,
+    trace_ring: Option<usize>,
+    trace_format: TraceFormat,
+    doctor: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|command| command == "convert-save") {
+        return convert::run(&args[2..]);
+    }
+
+    let Some(options) = parse_args()? else {
+        print_help();
+        return Ok(());
+    };
+
+    let game_data = fs::read(&options.game_path)?;
+    let boot_rom = options.boot_path.as_ref().map(fs::read).transpose()?;
+    let game = Cartridge::new(&game_data, None).map_err(|e| format!("{e}"))?;
+
+    let mut gb = Dmg::new(game, boot_rom);
+    let mut controller = HeadlessController::new();
+
+    if options.doctor {
+        gb.ppu.ly_override = Some(DOCTOR_LY);
+    }
+    gb.tracer = match (&options.trace_path, options.trace_ring) {
+        (Some(path), _) => Some(Tracer::file(options.trace_format, path)?),
+        (None, Some(lines)) => Some(Tracer::ring_buffer(options.trace_format, lines)),
+        (None, None) => None,
+    };
+
+    let mut result = Ok(());
+    for _ in 0..options.frames {
+        result = gb.run(&mut controller);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    // trace lines and the shell prompt start on their own line
+    let serial = controller.serial_output();
+    if !serial.is_empty() && !serial.ends_with(b"\n") {
+        println!();
+    }
+    if let Some(tracer) = gb.tracer.as_mut() {
+        tracer.flush()?;
+        for line in tracer.lines() {
+            eprintln!("{line}");
+        }
+    }
+
+    Ok(result?)
+}
+
+/// Returns `None` when help was requested
+fn parse_args() -> Result<Option<Options>, String> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut game_path = None;
+    let mut options = Options {
+        game_path: String::new(),
+        boot_path: None,
+        frames: DEFAULT_FRAMES,
+        trace_path: None,
+        trace_ring: None,
+        trace_format: TraceFormat::default(),
+        doctor: false,
+    };
+
+    let mut i = 1;
+    while i < args.len() {
+        let value = args.get(i + 1);
+        match (args[i].as_str(), value) {
+            ("-g" | "--game", Some(path)) => game_path = Some(path.clone()),
+            ("-b" | "--boot" | "--boot_rom", Some(path)) => options.boot_path = Some(path.clone()),
+            ("-f" | "--frames", Some(frames)) => {
+                options.frames = frames
+                    .parse()
+                    .map_err(|_| format!("invalid frame count: {frames}"))?
+            }
+            ("-t" | "--trace", Some(path)) => options.trace_path = Some(path.clone()),
+            ("--trace-ring", Some(lines)) => {
+                options.trace_ring = Some(
+                    lines
+                        .parse()
+                        .map_err(|_| format!("invalid line count: {lines}"))?,
+                )
+            }
+            ("--trace-format", Some(format)) => {
+                options.trace_format = match format.as_str() {
+                    "doctor" => TraceFormat::Doctor,
+                    "extended" => TraceFormat::Extended,
+                    _ => return Err(format!("unknown trace format: {format}")),
+                }
+            }
+            ("--doctor", _) => {
+                options.doctor = true;
+                i += 1;
+                continue;
+            }
+            ("-h" | "--help", _) => return Ok(None),
+            (arg, _) => return Err(format!("unexpected argument: {arg}")),
+        }
+        i += 2;
+    }
+
+    options.game_path = game_path.ok_or("missing game ROM, use -g <PATH>")?;
+    Ok(Some(options))
+}
+
+fn print_help() {
+    println!("Usage: gbeed-headless -g <PATH> [OPTIONS]");
+    println!("       gbeed-headless convert-save <INPUT> <OUTPUT> [OPTIONS]");
+    println!("Runs a game without a window, printing its serial output to stdout");
+    println!("Options:");
+    println!("  -g, --game <PATH>              Path to the game ROM file");
+    println!("  -b, --boot <PATH>              Path to the boot ROM file (optional)");
+    println!("  -f, --frames <N>               Frames to run before exiting (default {DEFAULT_FRAMES})");
+    println!("  -t, --trace <PATH>             Log every executed instruction to a file");
+    println!(
+        "      --trace-ring <N>           Keep the last N instructions and print them to stderr on exit"
+    );
+    println!(
+        "      --trace-format <FORMAT>    `doctor` (default) or `extended`, with disassembly and cycles"
+    );
+    println!("      --doctor                   Report LY as $90, as expected by Gameboy Doctor logs");
+    println!("  -h, --help                     Print this help message");
+}
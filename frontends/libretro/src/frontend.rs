@@ -0,0 +1,152 @@
+use crate::palette::{Colorization, PaletteColor, layer_index};
+use gbeed_core::prelude::*;
+use gbeed_core::{AudioPlayer, Controller, PixelLayer};
+
+/// One frame at 44100 Hz has about 740 samples per channel
+const FRAME_SAMPLES: usize = 2048;
+
+/// Screen and audio of the last frame, handed to the libretro callbacks once it's done
+pub struct Frontend {
+    colors: [PaletteColor; 3],
+    framebuffer: Vec<u32>,
+    framebuffer_16: Vec<u16>,
+    samples: Vec<i16>,
+}
+
+impl Frontend {
+    pub fn new(colorization: Colorization) -> Self {
+        Self {
+            colors: colorization.colors(),
+            framebuffer: vec![0; DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT],
+            framebuffer_16: vec![0; DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT],
+            samples: Vec::with_capacity(FRAME_SAMPLES),
+        }
+    }
+
+    /// Takes effect from the next drawn pixel
+    pub fn set_colorization(&mut self, colorization: Colorization) { self.colors = colorization.colors(); }
+
+    /// Frame as `0x00RRGGBB` pixels, one row after another
+    pub fn frame_xrgb8888(&self) -> &[u32] { &self.framebuffer }
+
+    /// Frame converted to 16 bits for frontends without 32 bit support
+    pub fn frame_rgb565(&mut self) -> &[u16] {
+        self.frame_16(|r, g, b| ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3))
+    }
+
+    /// Frame in the format frontends use until another is set, for those refusing the other two
+    pub fn frame_0rgb1555(&mut self) -> &[u16] {
+        self.frame_16(|r, g, b| ((r >> 3) << 10) | ((g >> 3) << 5) | (b >> 3))
+    }
+
+    fn frame_16(&mut self, pack: impl Fn(u32, u32, u32) -> u32) -> &[u16] {
+        for (pixel, xrgb) in self.framebuffer_16.iter_mut().zip(&self.framebuffer) {
+            let (r, g, b) = ((xrgb >> 16) & 0xFF, (xrgb >> 8) & 0xFF, xrgb & 0xFF);
+            *pixel = pack(r, g, b) as u16;
+        }
+        &self.framebuffer_16
+    }
+
+    /// Interleaved left and right samples pushed since they were last cleared
+    pub fn samples(&self) -> &[i16] { &self.samples }
+
+    pub fn clear_samples(&mut self) { self.samples.clear(); }
+}
+
+impl Renderer for Frontend {
+    fn read_pixel(&self, x: usize, y: usize) -> u32 { self.framebuffer[y * DMG_SCREEN_WIDTH + x] }
+
+    fn write_pixel(&mut self, x: usize, y: usize, palette: u8, color_id: u8) {
+        self.write_layer_pixel(x, y, PixelLayer::Background, palette, color_id);
+    }
+
+    fn update_screen(&mut self, _: &Ppu) {}
+
+    fn write_layer_pixel(&mut self, x: usize, y: usize, layer: PixelLayer, palette: u8, color_id: u8) {
+        let shade = (palette >> (color_id * 2)) & 0x03;
+        self.framebuffer[y * DMG_SCREEN_WIDTH + x] = self.colors[layer_index(layer)][shade as usize];
+    }
+}
+
+impl AudioPlayer for Frontend {
+    fn playing_stereo(&self) -> bool { true }
+
+    fn push_sample(&mut self, left: i16, right: i16) { self.samples.extend_from_slice(&[left, right]); }
+}
+
+/// There is no link cable, so transfers go nowhere
+impl SerialListener for Frontend {
+    fn on_transfer(&mut self, _data: u8) {}
+}
+
+impl Controller for Frontend {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::palette::Palette;
+
+    /// Frontend with the first pixels in every shade of `palette` and the next one in a sprite colorized red
+    fn frontend(palette: Palette) -> Frontend {
+        let mut frontend = Frontend::new(Colorization {
+            background: palette,
+            object0: Some(Palette::Red),
+            object1: None,
+        });
+        // identity palette, color ids are the shades
+        for shade in 0..4 {
+            frontend.write_pixel(shade as usize, 0, 0b11_10_01_00, shade);
+        }
+        frontend.write_layer_pixel(4, 0, PixelLayer::Object0, 0b11_10_01_00, 0);
+        frontend
+    }
+
+    #[test]
+    fn test_xrgb8888() {
+        let frontend = frontend(Palette::GrayScale);
+        assert_eq!(
+            frontend.frame_xrgb8888()[..5],
+            [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000, 0xCC0001]
+        );
+        assert_eq!(
+            frontend.frame_xrgb8888().len(),
+            DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT
+        );
+    }
+
+    #[test]
+    fn test_rgb565() {
+        let mut frontend = frontend(Palette::GrayScale);
+        // 5 bits of red, 6 of green and 5 of blue, from the top bits of each
+        assert_eq!(
+            frontend.frame_rgb565()[..5],
+            [0xFFFF, 0xAD55, 0x52AA, 0x0000, 0xC800]
+        );
+    }
+
+    #[test]
+    fn test_0rgb1555() {
+        let mut frontend = frontend(Palette::GrayScale);
+        // 5 bits of every color, the top bit unused
+        assert_eq!(
+            frontend.frame_0rgb1555()[..5],
+            [0x7FFF, 0x56B5, 0x294A, 0x0000, 0x6400]
+        );
+    }
+
+    #[test]
+    fn test_colorization_change() {
+        let mut frontend = frontend(Palette::GrayScale);
+        frontend.set_colorization(Colorization::default());
+        assert_eq!(
+            frontend.read_pixel(0, 0),
+            0xFFFFFF,
+            "drawn pixels keep their color"
+        );
+
+        frontend.write_pixel(0, 0, 0b11_10_01_00, 0);
+        frontend.write_layer_pixel(4, 0, PixelLayer::Object0, 0b11_10_01_00, 0);
+        assert_eq!(frontend.read_pixel(0, 0), Palette::DmgClassic.colors()[0]);
+        assert_eq!(frontend.read_pixel(4, 0), Palette::DmgClassic.colors()[0]);
+    }
+}
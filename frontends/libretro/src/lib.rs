@@ -0,0 +1,552 @@
+mod frontend;
+mod libretro;
+mod options;
+mod palette;
+
+use frontend::Frontend;
+use gbeed_core::prelude::*;
+use gbeed_core::{RtcFooter, SaveFormat};
+use libretro::*;
+use std::ffi::{CStr, c_char, c_uint, c_void};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 4194304 Hz over the 70224 dots of a frame
+const FPS: f64 = 4_194_304.0 / 70_224.0;
+
+/// Buttons as libretro numbers them, in the order the joypad is polled
+const BUTTONS: [(c_uint, JoypadButton, &CStr); 8] = [
+    (RETRO_DEVICE_ID_JOYPAD_UP, JoypadButton::Up, c"Up"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, JoypadButton::Down, c"Down"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, JoypadButton::Left, c"Left"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, JoypadButton::Right, c"Right"),
+    (RETRO_DEVICE_ID_JOYPAD_A, JoypadButton::A, c"A"),
+    (RETRO_DEVICE_ID_JOYPAD_B, JoypadButton::B, c"B"),
+    (RETRO_DEVICE_ID_JOYPAD_SELECT, JoypadButton::Select, c"Select"),
+    (RETRO_DEVICE_ID_JOYPAD_START, JoypadButton::Start, c"Start"),
+];
+
+#[derive(Clone, Copy, Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+fn callbacks() -> Callbacks { *CALLBACKS.lock().unwrap_or_else(|e| e.into_inner()) }
+
+fn set_callbacks(set: impl FnOnce(&mut Callbacks)) {
+    set(&mut CALLBACKS.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// The loaded game, with everything the frontend reads through pointers kept at the same address
+/// until it's unloaded
+struct Core {
+    gb: Dmg,
+    frontend: Frontend,
+    pixel_format: c_uint,
+    /// State right after loading the game, restored on resets
+    power_on: Vec<u8>,
+    /// MBC3 clock in the `Rtc` save format, which the frontend reads and writes between frames
+    rtc: [u8; RtcFooter::SIZE],
+    /// `rtc` as last written by the core, to notice when the frontend loaded its own
+    rtc_written: [u8; RtcFooter::SIZE],
+}
+
+static CORE: Mutex<Option<Core>> = Mutex::new(None);
+
+fn with_core<T>(f: impl FnOnce(&mut Core) -> T) -> Option<T> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner()).as_mut().map(f)
+}
+
+impl Core {
+    fn new(gb: Dmg, environment: Option<RetroEnvironment>) -> Self {
+        let colorization = environment.map(options::read_colorization).unwrap_or_default();
+        // frontends without XRGB8888 get RGB565, and those refusing both stay in the 0RGB1555 default
+        let pixel_format = [RETRO_PIXEL_FORMAT_XRGB8888, RETRO_PIXEL_FORMAT_RGB565]
+            .into_iter()
+            .find(|&format| environment.is_some_and(|environment| set_pixel_format(environment, format)))
+            .unwrap_or(RETRO_PIXEL_FORMAT_0RGB1555);
+
+        let mut core = Self {
+            power_on: gb.save_state(),
+            gb,
+            frontend: Frontend::new(colorization),
+            pixel_format,
+            rtc: [0; RtcFooter::SIZE],
+            rtc_written: [0; RtcFooter::SIZE],
+        };
+        core.write_rtc();
+        core
+    }
+
+    fn run(&mut self, callbacks: Callbacks) {
+        if let Some(environment) = callbacks.environment
+            && options::updated(environment)
+        {
+            self.frontend
+                .set_colorization(options::read_colorization(environment));
+        }
+
+        if let (Some(poll), Some(state)) = (callbacks.input_poll, callbacks.input_state) {
+            unsafe { poll() };
+            for (id, button, _) in BUTTONS {
+                let pressed = unsafe { state(0, RETRO_DEVICE_JOYPAD, 0, id) } != 0;
+                self.gb.joypad.button_down(button, pressed);
+            }
+        }
+
+        self.read_rtc();
+        self.frontend.clear_samples();
+        if let Err(e) = self.gb.run(&mut self.frontend) {
+            eprintln!("gbeed: {e}");
+        }
+        self.write_rtc();
+
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let (width, height) = (DMG_SCREEN_WIDTH as c_uint, DMG_SCREEN_HEIGHT as c_uint);
+            let (frame, pitch) = match self.pixel_format {
+                RETRO_PIXEL_FORMAT_XRGB8888 => {
+                    let frame = self.frontend.frame_xrgb8888();
+                    (frame.as_ptr() as *const c_void, DMG_SCREEN_WIDTH * 4)
+                }
+                RETRO_PIXEL_FORMAT_RGB565 => {
+                    let frame = self.frontend.frame_rgb565();
+                    (frame.as_ptr() as *const c_void, DMG_SCREEN_WIDTH * 2)
+                }
+                _ => {
+                    let frame = self.frontend.frame_0rgb1555();
+                    (frame.as_ptr() as *const c_void, DMG_SCREEN_WIDTH * 2)
+                }
+            };
+            unsafe { video_refresh(frame, width, height, pitch) };
+        }
+
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            let mut samples = self.frontend.samples();
+            while samples.len() >= 2 {
+                let frames = unsafe { audio_sample_batch(samples.as_ptr(), samples.len() / 2) };
+                if frames == 0 {
+                    break;
+                }
+                samples = &samples[(frames * 2).min(samples.len())..];
+            }
+        }
+    }
+
+    /// Power cycle the Game Boy, keeping the battery backed RAM and the clock like the cartridge would
+    fn reset(&mut self) {
+        let save = self.gb.cartridge.save_game().map(<[u8]>::to_vec);
+        let rtc = self.gb.cartridge.rtc();
+
+        if let Err(e) = self.gb.load_state(&self.power_on) {
+            eprintln!("gbeed: failed to reset: {e}");
+            return;
+        }
+        if let (Some(save), Some(ram)) = (save, self.gb.cartridge.ram_mut()) {
+            ram.copy_from_slice(&save);
+        }
+        if let Some(rtc) = rtc {
+            self.gb.cartridge.set_rtc(&rtc);
+        }
+    }
+
+    /// Apply the clock the frontend wrote to the RTC memory, if it changed since the core wrote it
+    fn read_rtc(&mut self) {
+        if self.rtc == self.rtc_written {
+            return;
+        }
+
+        // gbeed's clock doesn't tick yet, so the time passed since it was saved isn't added
+        match RtcFooter::from_bytes(&self.rtc) {
+            Ok(rtc) => self.gb.cartridge.set_rtc(&rtc),
+            Err(e) => eprintln!("gbeed: failed to load the clock: {e}"),
+        }
+    }
+
+    fn write_rtc(&mut self) {
+        let Some(mut rtc) = self.gb.cartridge.rtc() else {
+            return;
+        };
+
+        rtc.timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        self.rtc.copy_from_slice(&rtc.to_bytes(SaveFormat::Rtc));
+        self.rtc_written = self.rtc;
+    }
+}
+
+fn set_pixel_format(environment: RetroEnvironment, mut format: c_uint) -> bool {
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        )
+    }
+}
+
+fn set_input_descriptors(environment: RetroEnvironment) {
+    let mut descriptors: Vec<RetroInputDescriptor> = BUTTONS
+        .iter()
+        .map(|(id, _, description)| RetroInputDescriptor {
+            port: 0,
+            device: RETRO_DEVICE_JOYPAD,
+            index: 0,
+            id: *id,
+            description: description.as_ptr(),
+        })
+        .collect();
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: std::ptr::null(),
+    });
+
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr() as *mut c_void,
+        )
+    };
+}
+
+/// ROM handed by the frontend, or read from its path when it only gives that
+unsafe fn read_game(game: &RetroGameInfo) -> Option<Vec<u8>> {
+    if !game.data.is_null() && game.size > 0 {
+        return Some(unsafe { std::slice::from_raw_parts(game.data as *const u8, game.size) }.to_vec());
+    }
+    if game.path.is_null() {
+        return None;
+    }
+
+    let path = unsafe { CStr::from_ptr(game.path) }
+        .to_string_lossy()
+        .into_owned();
+    std::fs::read(&path)
+        .inspect_err(|e| eprintln!("gbeed: failed to read {path}: {e}"))
+        .ok()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_api_version() -> c_uint { RETRO_API_VERSION }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    set_callbacks(|callbacks| callbacks.environment = Some(environment));
+    options::set_variables(environment);
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_video_refresh(video_refresh: RetroVideoRefresh) {
+    set_callbacks(|callbacks| callbacks.video_refresh = Some(video_refresh));
+}
+
+/// Samples are always sent in batches
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample(_: RetroAudioSample) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: RetroAudioSampleBatch) {
+    set_callbacks(|callbacks| callbacks.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_poll(input_poll: RetroInputPoll) {
+    set_callbacks(|callbacks| callbacks.input_poll = Some(input_poll));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_input_state(input_state: RetroInputState) {
+    set_callbacks(|callbacks| callbacks.input_state = Some(input_state));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_init() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_deinit() { *CORE.lock().unwrap_or_else(|e| e.into_inner()) = None; }
+
+/// # Safety
+/// `info` has to point to a `retro_system_info` the frontend owns
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    let Some(info) = (unsafe { info.as_mut() }) else {
+        return;
+    };
+
+    *info = RetroSystemInfo {
+        library_name: c"gbeed".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"gb|dmg".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` has to point to a `retro_system_av_info` the frontend owns
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    let Some(info) = (unsafe { info.as_mut() }) else {
+        return;
+    };
+
+    let sample_rate = with_core(|core| core.gb.apu.sample_rate()).unwrap_or(gbeed_core::SAMPLE_RATE);
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: DMG_SCREEN_WIDTH as c_uint,
+            base_height: DMG_SCREEN_HEIGHT as c_uint,
+            max_width: DMG_SCREEN_WIDTH as c_uint,
+            max_height: DMG_SCREEN_HEIGHT as c_uint,
+            aspect_ratio: DMG_SCREEN_WIDTH as f32 / DMG_SCREEN_HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: FPS,
+            sample_rate: sample_rate as f64,
+        },
+    };
+}
+
+/// Only the joypad is supported, whatever device is plugged
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_reset() { with_core(Core::reset); }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    with_core(|core| core.run(callbacks));
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_core(|core| core.gb.save_state().len()).unwrap_or(0)
+}
+
+/// # Safety
+/// `data` has to point to `size` writable bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let state = match with_core(|core| core.gb.save_state()) {
+        Some(state) if !data.is_null() && state.len() <= size => state,
+        _ => return false,
+    };
+
+    let data = unsafe { std::slice::from_raw_parts_mut(data as *mut u8, state.len()) };
+    data.copy_from_slice(&state);
+    true
+}
+
+/// # Safety
+/// `data` has to point to `size` readable bytes
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let data = unsafe { std::slice::from_raw_parts(data as *const u8, size) };
+    with_core(|core| core.gb.load_state(data))
+        .is_some_and(|loaded| loaded.inspect_err(|e| eprintln!("gbeed: {e}")).is_ok())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// `game` has to be null or point to a `retro_game_info` with valid `data` or `path`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(rom) = (unsafe { game.as_ref() }).and_then(|game| unsafe { read_game(game) }) else {
+        return false;
+    };
+
+    // the frontend loads the battery save into the RAM itself, through `retro_get_memory_data`
+    let cartridge = match Cartridge::new(&rom, None) {
+        Ok(cartridge) => cartridge,
+        Err(e) => {
+            eprintln!("gbeed: failed to load the game: {e}");
+            return false;
+        }
+    };
+
+    let environment = callbacks().environment;
+    if let Some(environment) = environment {
+        set_input_descriptors(environment);
+    }
+
+    let core = Core::new(Dmg::new(cartridge, None), environment);
+    *CORE.lock().unwrap_or_else(|e| e.into_inner()) = Some(core);
+    true
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_load_game_special(_type: c_uint, _info: *const RetroGameInfo, _num: usize) -> bool {
+    false
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_unload_game() { *CORE.lock().unwrap_or_else(|e| e.into_inner()) = None; }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_region() -> c_uint { RETRO_REGION_NTSC }
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    with_core(|core| match id {
+        RETRO_MEMORY_SAVE_RAM if core.gb.cartridge.supports_saves() => core
+            .gb
+            .cartridge
+            .ram_mut()
+            .map_or(std::ptr::null_mut(), |ram| ram.as_mut_ptr() as *mut c_void),
+        RETRO_MEMORY_RTC if core.gb.cartridge.rtc().is_some() => core.rtc.as_mut_ptr() as *mut c_void,
+        RETRO_MEMORY_SYSTEM_RAM => core.gb.memory.ram.as_mut_ptr() as *mut c_void,
+        _ => std::ptr::null_mut(),
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    with_core(|core| match id {
+        RETRO_MEMORY_SAVE_RAM => core.gb.cartridge.save_game().map_or(0, <[u8]>::len),
+        RETRO_MEMORY_RTC if core.gb.cartridge.rtc().is_some() => RtcFooter::SIZE,
+        RETRO_MEMORY_SYSTEM_RAM => core.gb.memory.ram.len(),
+        _ => 0,
+    })
+    .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::MutexGuard;
+
+    /// The libretro API works on a single loaded game, so the tests going through it take turns
+    static API: Mutex<()> = Mutex::new(());
+
+    /// MBC3 game with a clock and 8 KB of battery backed RAM, counting up a byte of it and one of WRAM forever
+    fn counting_rom() -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0x10;
+        rom[0x0149] = 0x02;
+        #[rustfmt::skip]
+        let program = [
+            0x3E, 0x0A,       // ld a, $0A
+            0xEA, 0x00, 0x00, // ld [$0000], a
+            0x21, 0x00, 0xA0, // ld hl, $A000
+            0x34,             // inc [hl]
+            0x21, 0x00, 0xC0, // ld hl, $C000
+            0x34,             // inc [hl]
+            0x18, 0xF6,       // jr -10
+        ];
+        rom[0x0100..0x0100 + program.len()].copy_from_slice(&program);
+        rom
+    }
+
+    fn load_game(rom: &[u8]) -> MutexGuard<'static, ()> {
+        let api = API.lock().unwrap_or_else(|e| e.into_inner());
+        let game = RetroGameInfo {
+            path: std::ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: std::ptr::null(),
+        };
+        assert!(unsafe { retro_load_game(&game) });
+        api
+    }
+
+    fn serialize() -> Vec<u8> {
+        let mut state = vec![0; retro_serialize_size()];
+        assert!(unsafe { retro_serialize(state.as_mut_ptr() as *mut c_void, state.len()) });
+        state
+    }
+
+    fn run_frames(frames: usize) {
+        for _ in 0..frames {
+            retro_run();
+        }
+    }
+
+    #[test]
+    fn test_serialize_size_is_fixed() {
+        let _api = load_game(&counting_rom());
+
+        let size = retro_serialize_size();
+        assert!(size > 0);
+        for _ in 0..10 {
+            retro_run();
+            assert_eq!(retro_serialize_size(), size);
+            assert_eq!(serialize().len(), size);
+        }
+
+        let mut small = vec![0u8; size - 1];
+        assert!(!unsafe { retro_serialize(small.as_mut_ptr() as *mut c_void, small.len()) });
+
+        retro_unload_game();
+        assert_eq!(retro_serialize_size(), 0);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let _api = load_game(&counting_rom());
+
+        run_frames(5);
+        let saved = serialize();
+        run_frames(5);
+        let expected = serialize();
+
+        assert!(unsafe { retro_unserialize(saved.as_ptr() as *const c_void, saved.len()) });
+        run_frames(5);
+        assert_eq!(serialize(), expected);
+
+        let garbage = [0xFFu8; 16];
+        assert!(!unsafe { retro_unserialize(garbage.as_ptr() as *const c_void, garbage.len()) });
+        retro_unload_game();
+    }
+
+    #[test]
+    fn test_reset_keeps_ram_and_rtc() {
+        let cartridge = Cartridge::new(&counting_rom(), None).unwrap();
+        let mut core = Core::new(Dmg::new(cartridge, None), None);
+        let wram = core.gb.memory.ram[0];
+
+        for _ in 0..3 {
+            core.run(Callbacks::default());
+        }
+        let rtc = RtcFooter {
+            registers: [1, 2, 3, 4, 0],
+            ..RtcFooter::default()
+        };
+        core.gb.cartridge.set_rtc(&rtc);
+        let save = core.gb.cartridge.save_game().unwrap().to_vec();
+        assert_ne!(save[0], 0);
+        assert_ne!(core.gb.memory.ram[0], wram);
+
+        core.reset();
+        assert_eq!(core.gb.cartridge.save_game().unwrap(), save);
+        assert_eq!(core.gb.cartridge.rtc().unwrap().registers, rtc.registers);
+        // everything else is back to how it was when the game was loaded
+        assert_eq!(core.gb.memory.ram[0], wram);
+        assert_eq!(core.gb.cpu.pc, 0x0100);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::libretro::*;
+use crate::palette::{Colorization, Palette};
+use std::ffi::{CStr, CString, c_void};
+use std::sync::OnceLock;
+
+const PALETTE_KEY: &str = "gbeed_palette";
+const OBJECT0_PALETTE_KEY: &str = "gbeed_object0_palette";
+const OBJECT1_PALETTE_KEY: &str = "gbeed_object1_palette";
+/// Value of the object palettes when they aren't colorized
+const BACKGROUND_VALUE: &str = "Background";
+
+/// Keys and `description; value|value` definitions, kept alive for as long as the core is loaded
+fn definitions() -> &'static [(CString, CString)] {
+    static DEFINITIONS: OnceLock<Vec<(CString, CString)>> = OnceLock::new();
+
+    DEFINITIONS.get_or_init(|| {
+        let palettes: Vec<&str> = Palette::ALL.iter().map(Palette::name).collect();
+        let objects: Vec<&str> = std::iter::once(BACKGROUND_VALUE)
+            .chain(palettes.iter().copied())
+            .collect();
+
+        [
+            (PALETTE_KEY, "Palette", &palettes),
+            (OBJECT0_PALETTE_KEY, "Colorization > Sprite palette 0", &objects),
+            (OBJECT1_PALETTE_KEY, "Colorization > Sprite palette 1", &objects),
+        ]
+        .into_iter()
+        .map(|(key, description, values)| {
+            let definition = format!("{description}; {}", values.join("|"));
+            (CString::new(key).unwrap(), CString::new(definition).unwrap())
+        })
+        .collect()
+    })
+}
+
+/// Tell the frontend the options of the core, defaulting to the first value of each
+pub fn set_variables(environment: RetroEnvironment) {
+    let mut variables: Vec<RetroVariable> = definitions()
+        .iter()
+        .map(|(key, definition)| RetroVariable {
+            key: key.as_ptr(),
+            value: definition.as_ptr(),
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: std::ptr::null(),
+        value: std::ptr::null(),
+    });
+
+    unsafe {
+        environment(
+            RETRO_ENVIRONMENT_SET_VARIABLES,
+            variables.as_mut_ptr() as *mut c_void,
+        )
+    };
+}
+
+/// Whether the user changed any option since they were last read
+pub fn updated(environment: RetroEnvironment) -> bool {
+    let mut updated = false;
+    let supported = unsafe {
+        environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+            &mut updated as *mut bool as *mut c_void,
+        )
+    };
+    supported && updated
+}
+
+fn variable(environment: RetroEnvironment, key: &str) -> Option<String> {
+    let key = CString::new(key).ok()?;
+    let mut variable = RetroVariable {
+        key: key.as_ptr(),
+        value: std::ptr::null(),
+    };
+
+    let found = unsafe {
+        environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut RetroVariable as *mut c_void,
+        )
+    };
+    if !found || variable.value.is_null() {
+        return None;
+    }
+
+    // the frontend owns the value, which stays valid until the next call
+    let value = unsafe { CStr::from_ptr(variable.value) };
+    Some(value.to_string_lossy().into_owned())
+}
+
+/// The colorization picked in the options, unknown values falling back to the defaults
+pub fn read_colorization(environment: RetroEnvironment) -> Colorization {
+    let palette = |key: &str| variable(environment, key).and_then(|name| Palette::from_name(&name));
+
+    Colorization {
+        background: palette(PALETTE_KEY).unwrap_or_default(),
+        object0: palette(OBJECT0_PALETTE_KEY),
+        object1: palette(OBJECT1_PALETTE_KEY),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::c_uint;
+
+    /// Frontend with the palette set to red, the first sprite palette left to the background and an
+    /// unknown value for the second
+    unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+        match cmd {
+            RETRO_ENVIRONMENT_GET_VARIABLE => {
+                let variable = unsafe { &mut *(data as *mut RetroVariable) };
+                let key = unsafe { CStr::from_ptr(variable.key) };
+                variable.value = match key.to_str().unwrap() {
+                    PALETTE_KEY => c"Red".as_ptr(),
+                    OBJECT0_PALETTE_KEY => c"Background".as_ptr(),
+                    OBJECT1_PALETTE_KEY => c"Magenta".as_ptr(),
+                    _ => return false,
+                };
+                true
+            }
+            RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+                unsafe { *(data as *mut bool) = true };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Frontend without any option support
+    unsafe extern "C" fn unsupported(_cmd: c_uint, _data: *mut c_void) -> bool { false }
+
+    #[test]
+    fn test_read_colorization() {
+        let colorization = read_colorization(environment);
+        assert_eq!(
+            colorization,
+            Colorization {
+                background: Palette::Red,
+                object0: None,
+                object1: None,
+            }
+        );
+        assert!(updated(environment));
+
+        assert_eq!(read_colorization(unsupported), Colorization::default());
+        assert!(!updated(unsupported));
+    }
+
+    #[test]
+    fn test_definitions() {
+        let definitions = definitions();
+        assert_eq!(definitions.len(), 3);
+
+        let (key, definition) = &definitions[0];
+        assert_eq!(key.to_str().unwrap(), PALETTE_KEY);
+        let definition = definition.to_str().unwrap();
+        assert!(
+            definition.starts_with("Palette; DMG Classic|Grayscale|"),
+            "{definition}"
+        );
+        assert_eq!(definition.split('|').count(), Palette::ALL.len());
+
+        // sprites default to the background palette, listed first
+        let (key, definition) = &definitions[1];
+        assert_eq!(key.to_str().unwrap(), OBJECT0_PALETTE_KEY);
+        assert!(definition.to_str().unwrap().contains("; Background|DMG Classic|"));
+        for palette in Palette::ALL {
+            assert_eq!(Palette::from_name(palette.name()), Some(palette));
+        }
+    }
+}
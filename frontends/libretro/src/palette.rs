@@ -0,0 +1,90 @@
+use gbeed_core::PixelLayer;
+
+/// Four shades from lightest to darkest, as `0x00RRGGBB`
+pub type PaletteColor = [u32; 4];
+
+/// Same colors the raylib frontends offer, so games look alike everywhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    #[default]
+    DmgClassic,
+    GrayScale,
+    Red,
+    Turquoise,
+    Blue,
+    Green,
+    Yellow,
+    Purple,
+    Pink,
+}
+
+use Palette::*;
+
+impl Palette {
+    pub const ALL: [Palette; 9] = [
+        DmgClassic, GrayScale, Red, Turquoise, Blue, Green, Yellow, Purple, Pink,
+    ];
+
+    pub fn colors(&self) -> PaletteColor {
+        match self {
+            DmgClassic => [0xC4CFA1, 0x8B956D, 0x4D533C, 0x1F1F1F],
+            GrayScale => [0xFFFFFF, 0xAAAAAA, 0x555555, 0x000000],
+            Red => [0xCC0001, 0x8F0001, 0x520000, 0x140000],
+            Turquoise => [0x03C0C6, 0x02868B, 0x014D4F, 0x001314],
+            Blue => [0x0000FE, 0x0000B2, 0x000066, 0x000019],
+            Green => [0x01CC00, 0x018F00, 0x005200, 0x001400],
+            Yellow => [0xFFFF01, 0xB2B201, 0x666600, 0x191900],
+            Purple => [0x762CA7, 0x531F75, 0x2F1243, 0x0C0411],
+            Pink => [0xFE98BF, 0xB26A86, 0x663D4C, 0x190F13],
+        }
+    }
+
+    /// Shown in the core options, which are also stored by this name
+    pub fn name(&self) -> &'static str {
+        match self {
+            DmgClassic => "DMG Classic",
+            GrayScale => "Grayscale",
+            Red => "Red",
+            Turquoise => "Turquoise",
+            Blue => "Blue",
+            Green => "Green",
+            Yellow => "Yellow",
+            Purple => "Purple",
+            Pink => "Pink",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|palette| palette.name() == name)
+    }
+}
+
+/// # Colorization
+/// The palette of every layer, objects taking the background one unless colorized, like the
+/// Game Boy Color does when running DMG games
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Colorization {
+    pub background: Palette,
+    pub object0: Option<Palette>,
+    pub object1: Option<Palette>,
+}
+
+impl Colorization {
+    pub fn colors(&self) -> [PaletteColor; 3] {
+        let object = |palette: Option<Palette>| palette.unwrap_or(self.background).colors();
+        [
+            self.background.colors(),
+            object(self.object0),
+            object(self.object1),
+        ]
+    }
+}
+
+/// Index of `layer` in `Colorization::colors`
+pub fn layer_index(layer: PixelLayer) -> usize {
+    match layer {
+        PixelLayer::Background => 0,
+        PixelLayer::Object0 => 1,
+        PixelLayer::Object1 => 2,
+    }
+}
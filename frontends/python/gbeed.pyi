@@ -0,0 +1,31 @@
+from os import PathLike
+from typing import Optional, Union
+
+import numpy as np
+import numpy.typing as npt
+
+SCREEN_WIDTH: int
+SCREEN_HEIGHT: int
+
+class GameBoy:
+    """A Game Boy running a game"""
+
+    def __init__(self, rom: bytes, save: Optional[bytes] = None, boot_rom: Optional[bytes] = None) -> None: ...
+    @staticmethod
+    def open(
+        path: Union[str, PathLike[str]], save: Optional[bytes] = None, boot_rom: Optional[bytes] = None
+    ) -> GameBoy: ...
+    @property
+    def title(self) -> str: ...
+    @property
+    def frame_count(self) -> int: ...
+    def tick(self, frames: int = 1) -> None: ...
+    def press(self, button: str) -> None: ...
+    def release(self, button: str) -> None: ...
+    def read(self, address: int) -> int: ...
+    def read_range(self, start: int, length: int) -> bytes: ...
+    def write(self, address: int, value: int) -> None: ...
+    def screen(self) -> npt.NDArray[np.uint8]: ...
+    def save_state(self) -> bytes: ...
+    def load_state(self, state: bytes) -> None: ...
+    def save_ram(self) -> Optional[bytes]: ...
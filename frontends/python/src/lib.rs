@@ -0,0 +1,186 @@
+//! # gbeed Python module
+//! Runs games from Python, for scripts and agents stepping through them frame by frame.
+//! Built with maturin into the `gbeed` package
+
+mod screen;
+
+use gbeed_core::prelude::*;
+use gbeed_core::{Accessible, DmgError};
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use screen::Screen;
+use std::path::PathBuf;
+
+/// Size of the frame count saved before the core state
+const FRAME_COUNT_SIZE: usize = size_of::<u64>();
+
+fn button(name: &str) -> PyResult<JoypadButton> {
+    match name.to_ascii_lowercase().as_str() {
+        "right" => Ok(JoypadButton::Right),
+        "left" => Ok(JoypadButton::Left),
+        "up" => Ok(JoypadButton::Up),
+        "down" => Ok(JoypadButton::Down),
+        "a" => Ok(JoypadButton::A),
+        "b" => Ok(JoypadButton::B),
+        "select" => Ok(JoypadButton::Select),
+        "start" => Ok(JoypadButton::Start),
+        _ => Err(PyValueError::new_err(format!("unknown button: {name}"))),
+    }
+}
+
+/// A Game Boy running a game
+#[pyclass(module = "gbeed")]
+pub struct GameBoy {
+    gb: Dmg,
+    screen: Screen,
+    frame_count: u64,
+}
+
+#[pymethods]
+impl GameBoy {
+    /// Load `rom`, with the battery backed RAM from `save` and booting through `boot_rom` when given
+    #[new]
+    #[pyo3(signature = (rom, save = None, boot_rom = None))]
+    fn new(rom: &[u8], save: Option<Vec<u8>>, boot_rom: Option<Vec<u8>>) -> PyResult<Self> {
+        let cartridge = Cartridge::new(rom, save).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(Self {
+            gb: Dmg::new(cartridge, boot_rom),
+            screen: Screen::new(),
+            frame_count: 0,
+        })
+    }
+
+    /// Same as the constructor, reading the ROM from `path`
+    #[staticmethod]
+    #[pyo3(signature = (path, save = None, boot_rom = None))]
+    fn open(path: PathBuf, save: Option<Vec<u8>>, boot_rom: Option<Vec<u8>>) -> PyResult<Self> {
+        Self::new(&std::fs::read(path)?, save, boot_rom)
+    }
+
+    /// Title in the cartridge header
+    #[getter]
+    fn title(&self) -> String { self.gb.cartridge.header.title.clone() }
+
+    /// Frames run since the game was loaded, going back with the states loaded
+    #[getter]
+    fn frame_count(&self) -> u64 { self.frame_count }
+
+    /// Run `frames` whole frames, letting other Python threads run meanwhile
+    #[pyo3(signature = (frames = 1))]
+    fn tick(&mut self, py: Python<'_>, frames: u32) -> PyResult<()> {
+        let Self {
+            gb,
+            screen,
+            frame_count,
+        } = self;
+
+        py.detach(|| {
+            for _ in 0..frames {
+                gb.run(screen)?;
+                *frame_count += 1;
+            }
+            Ok(())
+        })
+        .map_err(|e: DmgError| PyRuntimeError::new_err(e.to_string()))
+    }
+
+    /// Hold `button` down until it's released, by name like `"a"` or `"start"`
+    fn press(&mut self, button: &str) -> PyResult<()> {
+        self.gb.joypad.button_down(self::button(button)?, true);
+        Ok(())
+    }
+
+    fn release(&mut self, button: &str) -> PyResult<()> {
+        self.gb.joypad.button_down(self::button(button)?, false);
+        Ok(())
+    }
+
+    /// Byte at `address` as the CPU reads it
+    fn read(&self, address: u16) -> u8 { self.gb.read(address) }
+
+    /// `length` bytes from `start`, wrapping around the end of the address space
+    fn read_range(&self, start: u16, length: usize) -> Vec<u8> {
+        (0..length)
+            .map(|offset| self.gb.read(start.wrapping_add(offset as u16)))
+            .collect()
+    }
+
+    /// Write `value` as the CPU would, so writes to the ROM reach the MBC and writes to the
+    /// registers have their side effects
+    fn write(&mut self, address: u16, value: u8) { self.gb.write(address, value) }
+
+    /// Shades of the last frame as a `(144, 160)` `uint8` array, from 0 (lightest) to 3 (darkest)
+    fn screen<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        PyArray1::from_slice(py, self.screen.framebuffer()).reshape([DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH])
+    }
+
+    /// Snapshot of everything but the ROMs, to be restored with `load_state`, along with the frame count
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = self.frame_count.to_le_bytes().to_vec();
+        state.extend_from_slice(&self.gb.save_state());
+        state
+    }
+
+    /// Restore a snapshot of the same game, raising `ValueError` and keeping the current state
+    /// when it can't be loaded
+    fn load_state(&mut self, state: &[u8]) -> PyResult<()> {
+        let Some((frame_count, state)) = state.split_first_chunk::<FRAME_COUNT_SIZE>() else {
+            return Err(PyValueError::new_err("not a gbeed save state"));
+        };
+
+        self.gb
+            .load_state(state)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        self.frame_count = u64::from_le_bytes(*frame_count);
+        Ok(())
+    }
+
+    /// Battery backed RAM, in the same layout the constructor loads it, or `None` without a battery
+    fn save_ram(&self) -> Option<Vec<u8>> { self.gb.cartridge.save_game().map(<[u8]>::to_vec) }
+}
+
+#[pymodule(name = "gbeed")]
+fn gbeed_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<GameBoy>()?;
+    module.add("SCREEN_WIDTH", DMG_SCREEN_WIDTH)?;
+    module.add("SCREEN_HEIGHT", DMG_SCREEN_HEIGHT)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn game() -> GameBoy {
+        let mut rom = vec![0; 0x8000];
+        // jr -2
+        rom[0x0100..0x0102].copy_from_slice(&[0x18, 0xFE]);
+        GameBoy::new(&rom, None, None).unwrap()
+    }
+
+    #[test]
+    fn test_button_names() {
+        assert!(matches!(button("a"), Ok(JoypadButton::A)));
+        assert!(matches!(button("Start"), Ok(JoypadButton::Start)));
+        assert!(matches!(button("DOWN"), Ok(JoypadButton::Down)));
+        assert!(button("turbo").is_err());
+        assert!(button("").is_err());
+    }
+
+    #[test]
+    fn test_state_frame_count() {
+        let mut gb = game();
+        gb.frame_count = 5;
+        let state = gb.save_state();
+
+        gb.frame_count = 9;
+        gb.load_state(&state).unwrap();
+        assert_eq!(gb.frame_count(), 5);
+
+        assert!(gb.load_state(&state[..4]).is_err());
+        assert!(gb.load_state(&state[FRAME_COUNT_SIZE..]).is_err());
+        assert_eq!(gb.frame_count(), 5, "rejected states keep the frame count");
+    }
+}
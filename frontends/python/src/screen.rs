@@ -0,0 +1,67 @@
+use gbeed_core::prelude::*;
+use gbeed_core::{AudioPlayer, Controller};
+
+/// Keeps the shades of the screen for NumPy, there is no sound or link cable to play to
+pub struct Screen {
+    framebuffer: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self {
+            framebuffer: vec![0; DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT],
+        }
+    }
+
+    /// Shades from 0 (lightest) to 3 (darkest), one row after another
+    pub fn framebuffer(&self) -> &[u8] { &self.framebuffer }
+}
+
+impl Renderer for Screen {
+    fn read_pixel(&self, x: usize, y: usize) -> u32 { self.framebuffer[y * DMG_SCREEN_WIDTH + x] as u32 }
+
+    fn write_pixel(&mut self, x: usize, y: usize, palette: u8, color_id: u8) {
+        self.framebuffer[y * DMG_SCREEN_WIDTH + x] = (palette >> (color_id * 2)) & 0x03;
+    }
+
+    fn update_screen(&mut self, _: &Ppu) {}
+}
+
+impl AudioPlayer for Screen {
+    fn playing_stereo(&self) -> bool { false }
+
+    fn push_sample(&mut self, _left: i16, _right: i16) {}
+}
+
+impl SerialListener for Screen {
+    fn on_transfer(&mut self, _data: u8) {}
+}
+
+impl Controller for Screen {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shades_through_palette() {
+        let mut screen = Screen::new();
+        assert_eq!(screen.framebuffer().len(), DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT);
+
+        // color 0 is shade 0, 1 is 1, 2 is 2 and 3 is 3
+        let identity = 0b11_10_01_00;
+        // every color is shade 3 but color 1, which is 0
+        let inverted = 0b11_11_00_11;
+        screen.write_pixel(0, 0, identity, 2);
+        screen.write_pixel(DMG_SCREEN_WIDTH - 1, 0, inverted, 0);
+        screen.write_pixel(1, DMG_SCREEN_HEIGHT - 1, inverted, 1);
+
+        assert_eq!(screen.framebuffer()[0], 2);
+        assert_eq!(screen.read_pixel(DMG_SCREEN_WIDTH - 1, 0), 3);
+        assert_eq!(screen.read_pixel(1, DMG_SCREEN_HEIGHT - 1), 0);
+        assert_eq!(
+            screen.framebuffer()[(DMG_SCREEN_HEIGHT - 1) * DMG_SCREEN_WIDTH + 1],
+            0
+        );
+    }
+}
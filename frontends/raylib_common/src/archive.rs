@@ -0,0 +1,280 @@
+use gbeed_core::MAX_ROM_SIZE;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+const ARCHIVE_EXTENSIONS: [&str; 3] = ["zip", "gz", "7z"];
+
+#[inline(always)]
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+#[inline(always)]
+pub fn is_rom(path: &Path) -> bool { has_extension(path, &ROM_EXTENSIONS) }
+
+#[inline(always)]
+pub fn is_archive(path: &Path) -> bool { has_extension(path, &ARCHIVE_EXTENSIONS) }
+
+/// Whether an entry name stays inside the archive when joined to its path. Absolute names would
+/// replace the archive path and `..` would leave it, pointing at files outside of it instead
+fn is_enclosed(entry: &str) -> bool {
+    Path::new(entry)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// The archive holding the ROM at `path` and the entry name inside it, `None` when it isn't in one
+pub fn split_archive_path(path: &Path) -> Option<(&Path, String)> {
+    let archive = path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+    let entry = path.strip_prefix(archive).ok()?;
+
+    // entries always use forward slashes, whatever the platform
+    let entry = entry
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((archive, entry))
+}
+
+/// Where the ROM at `path` would be if its archive was unpacked next to it,
+/// `roms/collection.zip/Tetris.gb` is `roms/Tetris.gb`. Plain ROM paths are returned as they are
+pub fn unpacked_path(path: &Path) -> PathBuf {
+    match split_archive_path(path) {
+        Some((archive, entry)) => {
+            let name = entry.rsplit('/').next().unwrap_or(&entry);
+            archive.with_file_name(name)
+        }
+        None => path.to_path_buf(),
+    }
+}
+
+/// # ROM listing
+/// Every ROM at `path`: the file itself when it is a ROM, the ROM entries of an archive, and nothing for
+/// any other file. ROMs inside archives are addressed as if the archive was a directory, the archive path
+/// followed by the entry name, like `roms/collection.zip/Tetris (World).gb`. A gzip file holds a single ROM
+/// named after the file itself, `roms/tetris.gb.gz/tetris.gb`. Archives are only ever read in memory,
+/// so a save follows the name of the ROM inside
+pub fn list_roms(path: &Path) -> io::Result<Vec<PathBuf>> {
+    if is_rom(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let entries = match archive_extension(path).as_deref() {
+        Some("zip") => {
+            let archive = zip::ZipArchive::new(BufReader::new(File::open(path)?))?;
+            archive
+                .file_names()
+                .filter(|name| is_enclosed(name))
+                .map(String::from)
+                .collect()
+        }
+        Some("gz") => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .into_iter()
+            .collect(),
+        Some("7z") => seven_zip::list(path)?,
+        _ => Vec::new(),
+    };
+
+    let mut roms: Vec<PathBuf> = entries
+        .into_iter()
+        .filter(|entry| !entry.ends_with('/') && is_rom(Path::new(entry)))
+        .map(|entry| path.join(entry))
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+/// The ROM at `path` itself when it is an archive path alone, its first ROM otherwise.
+/// Lets a whole archive be opened the same way as a ROM
+pub fn resolve_rom(path: &Path) -> io::Result<PathBuf> {
+    if !is_archive(path) || !path.is_file() {
+        return Ok(path.to_path_buf());
+    }
+
+    list_roms(path)?.into_iter().next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} holds no Game Boy ROM", path.display()),
+        )
+    })
+}
+
+/// Read a ROM file, or decompress it when `path` points inside an archive.
+/// Anything bigger than the largest cartridge is refused before it can fill the memory
+pub fn read_rom(path: &Path) -> io::Result<Vec<u8>> {
+    let Some((archive, entry)) = split_archive_path(path) else {
+        return read_limited(File::open(path)?);
+    };
+
+    match archive_extension(archive).as_deref() {
+        Some("zip") => {
+            let mut archive = zip::ZipArchive::new(BufReader::new(File::open(archive)?))?;
+            let file = archive.by_name(&entry)?;
+            if file.size() > MAX_ROM_SIZE as u64 {
+                return Err(too_big());
+            }
+            read_limited(file)
+        }
+        Some("gz") => read_limited(flate2::read::GzDecoder::new(BufReader::new(File::open(archive)?))),
+        Some("7z") => seven_zip::read(archive, &entry),
+        _ => unreachable!("split_archive_path only finds archives"),
+    }
+}
+
+/// Everything `reader` holds, without trusting any size it claims to have
+fn read_limited(reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    reader.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut data)?;
+
+    match data.len() > MAX_ROM_SIZE {
+        true => Err(too_big()),
+        false => Ok(data),
+    }
+}
+
+fn too_big() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("ROMs can't be bigger than {} MiB", MAX_ROM_SIZE / 1024 / 1024),
+    )
+}
+
+#[inline(always)]
+fn archive_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod seven_zip {
+    use super::{is_enclosed, read_limited, too_big};
+    use gbeed_core::MAX_ROM_SIZE;
+    use sevenz_rust::{Archive, Password, SevenZReader};
+    use std::io;
+    use std::path::Path;
+
+    pub(super) fn list(path: &Path) -> io::Result<Vec<String>> {
+        let archive = Archive::open(path).map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(archive
+            .files
+            .iter()
+            .filter(|entry| entry.has_stream() && !entry.is_directory() && is_enclosed(entry.name()))
+            .map(|entry| entry.name().to_string())
+            .collect())
+    }
+
+    pub(super) fn read(path: &Path, name: &str) -> io::Result<Vec<u8>> {
+        let mut reader =
+            SevenZReader::open(path, Password::empty()).map_err(|e| io::Error::other(e.to_string()))?;
+        let mut data = None;
+
+        // solid archives are decoded in order, so entries before the wanted one are skipped through
+        reader
+            .for_each_entries(|entry, source| {
+                if entry.name() != name {
+                    io::copy(source, &mut io::sink())?;
+                    return Ok(true);
+                }
+
+                if entry.size() > MAX_ROM_SIZE as u64 {
+                    return Err(too_big().into());
+                }
+                data = Some(read_limited(source)?);
+                Ok(false)
+            })
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        data.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no {name} in {}", path.display()),
+            )
+        })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod seven_zip {
+    use std::io;
+    use std::path::Path;
+
+    fn unsupported() -> io::Error {
+        io::Error::new(io::ErrorKind::Unsupported, "7z archives aren't supported here")
+    }
+
+    pub(super) fn list(_path: &Path) -> io::Result<Vec<String>> { Err(unsupported()) }
+
+    pub(super) fn read(_path: &Path, _name: &str) -> io::Result<Vec<u8>> { Err(unsupported()) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    fn gzip(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("gbeed-archive-{}-{name}", std::process::id()));
+        let mut encoder =
+            flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_gzip_rom() {
+        let rom = vec![0x42; 0x8000];
+        let archive = gzip("tetris.gb.gz", &rom);
+
+        assert_eq!(read_rom(&archive.join("tetris.gb")).unwrap(), rom);
+        fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn test_skip_entries_outside_the_archive() {
+        let path = std::env::temp_dir().join(format!("gbeed-archive-{}-escape.zip", std::process::id()));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        let options =
+            zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        for name in [
+            "/abs.gb",
+            "../up.gb",
+            "games/../../up.gb",
+            "games/nested.gb",
+            "tetris.gb",
+        ] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&[0x42; 4]).unwrap();
+        }
+        zip.finish().unwrap();
+
+        assert_eq!(
+            list_roms(&path).unwrap(),
+            [path.join("games/nested.gb"), path.join("tetris.gb")]
+        );
+        assert_eq!(read_rom(&path.join("tetris.gb")).unwrap(), [0x42; 4]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_reject_decompression_bomb() {
+        // a few KiB that decompress to more than any cartridge holds
+        let archive = gzip("bomb.gb.gz", &vec![0; MAX_ROM_SIZE + 1]);
+        assert!(fs::metadata(&archive).unwrap().len() < 64 * 1024);
+
+        let e = read_rom(&archive.join("bomb.gb")).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(archive).unwrap();
+    }
+}
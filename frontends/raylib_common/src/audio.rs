@@ -0,0 +1,113 @@
+use crate::settings::{AudioOutput, SampleRate};
+use raylib::prelude::*;
+use std::collections::VecDeque;
+
+/// Frames in each of the two raylib sub-buffers, they are always handed over full
+/// since raylib pads shorter updates with silence
+pub const STREAM_FRAMES: usize = 1024;
+/// Frames the queue tries to keep waiting on top of the sub-buffers being played
+pub const TARGET_FRAMES: usize = STREAM_FRAMES * 2;
+/// Frames past this are dropped, they would only add latency
+const MAX_FRAMES: usize = TARGET_FRAMES * 2;
+
+/// Largest resampling correction, small enough to not be heard as a pitch change
+const MAX_RATE_DELTA: f32 = 0.005;
+
+/// # Audio queue
+/// Samples pushed by the emulator waiting for the raylib stream, which takes them one sub-buffer at a time.
+/// Its fill level drives dynamic rate control: the emulator is asked for slightly more samples
+/// when the queue runs low and slightly fewer when it fills up, so small speed differences between
+/// emulation and the audio device don't end in gaps or dropped samples
+pub struct AudioQueue<'a> {
+    audio: &'a RaylibAudio,
+    stream: AudioStream<'a>,
+    sample_rate: SampleRate,
+    output: AudioOutput,
+
+    /// Interleaved left and right samples when playing stereo
+    queue: VecDeque<i16>,
+    /// Contiguous copy of the next sub-buffer
+    chunk: Vec<i16>,
+}
+
+impl<'a> AudioQueue<'a> {
+    pub fn new(audio: &'a RaylibAudio, sample_rate: SampleRate, output: AudioOutput) -> Self {
+        audio.set_audio_stream_buffer_size_default(STREAM_FRAMES as i32);
+
+        let stream = audio.new_audio_stream(sample_rate.hz(), 16, output.channels());
+        stream.play();
+
+        Self {
+            audio,
+            stream,
+            sample_rate,
+            output,
+            queue: VecDeque::with_capacity(MAX_FRAMES * 2),
+            chunk: Vec::with_capacity(STREAM_FRAMES * 2),
+        }
+    }
+
+    pub fn sample_rate(&self) -> SampleRate { self.sample_rate }
+
+    /// Reopen the stream at the new rate, the emulator has to be switched to it too
+    pub fn set_sample_rate(&mut self, sample_rate: SampleRate) {
+        self.sample_rate = sample_rate;
+        self.reopen();
+    }
+
+    pub fn output(&self) -> AudioOutput { self.output }
+
+    pub fn set_output(&mut self, output: AudioOutput) {
+        self.output = output;
+        self.reopen();
+    }
+
+    fn reopen(&mut self) {
+        let audio: &'a RaylibAudio = self.audio;
+
+        self.stream = audio.new_audio_stream(self.sample_rate.hz(), 16, self.output.channels());
+        self.stream.play();
+        self.queue.clear();
+    }
+
+    #[inline(always)]
+    pub fn is_stereo(&self) -> bool { self.output == AudioOutput::Stereo }
+
+    /// Frames waiting to be handed to the stream
+    #[inline(always)]
+    pub fn queued_frames(&self) -> usize { self.queue.len() / self.output.channels() as usize }
+
+    pub fn push(&mut self, left: i16, right: i16) {
+        if self.queued_frames() >= MAX_FRAMES {
+            return;
+        }
+
+        match self.output {
+            AudioOutput::Stereo => self.queue.extend([left, right]),
+            AudioOutput::Mono => self.queue.push_back(((left as i32 + right as i32) / 2) as i16),
+        }
+    }
+
+    /// Hand full sub-buffers to every sub-buffer the stream already played
+    pub fn update(&mut self) {
+        let samples = STREAM_FRAMES * self.output.channels() as usize;
+
+        while self.queue.len() >= samples && self.stream.is_processed() {
+            self.chunk.clear();
+            self.chunk.extend(self.queue.drain(..samples));
+            if let Err(e) = self.stream.update(&self.chunk) {
+                eprintln!("update error: {e}");
+            }
+        }
+    }
+
+    /// Whether the queue is below its target, audio synced pacing runs frames until it isn't
+    pub fn needs_samples(&self) -> bool { self.queued_frames() < TARGET_FRAMES }
+
+    /// Output rate multiplier for the emulator that moves the queue back towards its target,
+    /// from `1 + MAX_RATE_DELTA` when empty to `1 - MAX_RATE_DELTA` when full
+    pub fn rate_adjustment(&self) -> f32 {
+        let fill = self.queued_frames() as f32 / MAX_FRAMES as f32;
+        1.0 + MAX_RATE_DELTA * (1.0 - 2.0 * fill.min(1.0))
+    }
+}
@@ -1,6 +1,7 @@
 use raylib::prelude::*;
 
 use crate::impl_cyclic_enum;
+use serde::{Deserialize, Serialize};
 
 pub type PaletteColor = [Color; 4];
 
@@ -67,7 +68,8 @@ pub const PINK_PALETTE: PaletteColor = [
     Color::new(25, 15, 19, 255),
 ];
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Palette {
     #[default]
     DmgClassic,
@@ -0,0 +1,599 @@
+use crate::color::Palette;
+use crate::input::evdev::EVDEV_NAMES;
+use crate::input::{
+    GpioButton, InputEvdevTriggers, InputGamepadTriggers, InputGpioButtons, InputKeyTriggers,
+};
+use crate::saves::write_save;
+use crate::settings::{
+    AudioOutput, SampleRate, ScreenFilter, ScreenScaling, SpeedUpMode, SpeedUpMultiplier, SyncMode,
+    TargetedFps, TurboRate,
+};
+use gbeed_core::Cartridge;
+use raylib::prelude::{GamepadButton, KeyboardKey};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const CONFIG_DIR: &str = "gbeed";
+const CONFIG_FILE: &str = "config.toml";
+const GAMEPAD_MAPPINGS_FILE: &str = "gamecontrollerdb.txt";
+const DATS_DIR: &str = "dats";
+
+macro_rules! settings {
+    ($($field:ident: $ty:ty = $default:expr),+ $(,)?) => {
+        /// Everything the settings menu edits, missing values in the file keep their default
+        #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct Settings {
+            $(pub $field: $ty),+
+        }
+
+        impl Default for Settings {
+            fn default() -> Self { Self { $($field: $default),+ } }
+        }
+
+        /// Settings a single game replaces, the rest follow the global ones
+        #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct GameOverrides {
+            $(
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub $field: Option<$ty>,
+            )+
+        }
+
+        impl Settings {
+            pub fn with_overrides(mut self, overrides: &GameOverrides) -> Self {
+                $(
+                    if let Some(value) = overrides.$field {
+                        self.$field = value;
+                    }
+                )+
+                self
+            }
+        }
+
+        impl Config {
+            /// Keep `settings` as they are now, values the game overrides go to its overrides
+            /// and the rest to the global settings
+            pub fn store(&mut self, settings: Settings, game: Option<&str>) {
+                let mut overrides = game.and_then(|game| self.games.get_mut(game));
+                $(
+                    match overrides.as_mut().and_then(|overrides| overrides.$field.as_mut()) {
+                        Some(value) => *value = settings.$field,
+                        None => self.settings.$field = settings.$field,
+                    }
+                )+
+            }
+        }
+    };
+}
+
+settings!(
+    palette: Palette = Palette::default(),
+    screen_filter: ScreenFilter = ScreenFilter::default(),
+    frame_blending: bool = false,
+    screen_scaling: ScreenScaling = ScreenScaling::default(),
+    speed_up_mode: SpeedUpMode = SpeedUpMode::default(),
+    speed_up_multiplier: SpeedUpMultiplier = SpeedUpMultiplier::default(),
+    turbo_rate: TurboRate = TurboRate::default(),
+    targeted_fps: TargetedFps = TargetedFps::default(),
+    sample_rate: SampleRate = SampleRate::default(),
+    audio_output: AudioOutput = AudioOutput::default(),
+    sync_mode: SyncMode = SyncMode::default(),
+    draw_debug_info: bool = false,
+);
+
+/// Directories the frontends read and write, unset ones are left to the frontend
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Paths {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roms: Option<PathBuf>,
+    /// More directories searched for ROMs along with `roms`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub extra_roms: Vec<PathBuf>,
+    /// Where removable drives get mounted, each of them is searched for ROMs too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saves: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recordings: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub screenshots: Option<PathBuf>,
+    /// Memory, trace and VRAM dumps written by the debugger
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dumps: Option<PathBuf>,
+}
+
+/// Directories set in the config file, they take the place of the defaults of `resolve_dir`
+static CONFIGURED_PATHS: OnceLock<Paths> = OnceLock::new();
+
+impl Paths {
+    /// Use `paths` for every directory resolved from now on
+    pub fn configure(paths: Self) {
+        if CONFIGURED_PATHS.set(paths).is_err() {
+            eprintln!("paths were already configured");
+        }
+    }
+
+    #[inline(always)]
+    pub fn configured() -> Option<&'static Self> { CONFIGURED_PATHS.get() }
+}
+
+/// The directory `selector` picks from `paths`, or `name` in the workspace root on debug builds
+/// and in the home directory otherwise
+pub fn resolve_dir(
+    paths: Option<&Paths>,
+    selector: impl Fn(&Paths) -> &Option<PathBuf>,
+    name: &str,
+) -> PathBuf {
+    if let Some(dir) = paths.and_then(|paths| selector(paths).clone()) {
+        return dir;
+    }
+
+    if cfg!(debug_assertions) {
+        PathBuf::from(env!("WORKSPACE_ROOT")).join(name)
+    } else {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(name)
+    }
+}
+
+/// How battery saves are kept, so progress survives losing power
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    /// Seconds between writes of a save that changed while playing, 0 to only write it when pausing
+    pub autosave_interval: u32,
+    /// Older saves kept of every game, one made each time it is loaded with a save that changed
+    pub backups: usize,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            autosave_interval: 10,
+            backups: 5,
+        }
+    }
+}
+
+macro_rules! bindings {
+    (
+        $(#[$meta:meta])*
+        $bindings:ident: $triggers:ident, $names:ident,
+        [$($action:ident),+ $(,)?]
+        $(, $extra:ident: $extra_ty:ty)* $(,)?
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct $bindings {
+            $(pub $extra: $extra_ty,)*
+            $(pub $action: Vec<String>,)+
+        }
+
+        impl Default for $bindings {
+            fn default() -> Self {
+                let builtin = $triggers::builtin();
+                Self {
+                    $($extra: builtin.$extra,)*
+                    $($action: builtin.$action.iter().filter_map(|input| input_name($names, *input)).map(String::from).collect(),)+
+                }
+            }
+        }
+
+        impl $bindings {
+            /// Triggers for the bound inputs, unknown names are reported and skipped
+            pub fn triggers(&self) -> $triggers {
+                $triggers {
+                    $($extra: self.$extra.clone(),)*
+                    $($action: inputs_from_names($names, stringify!($action), &self.$action),)+
+                }
+            }
+        }
+    };
+}
+
+bindings!(
+    /// Key names bound to every action, actions missing in the file keep the built-in keys
+    KeyBindings: InputKeyTriggers, KEY_NAMES,
+    [up, down, left, right, a, b, start, select, turbo_a, turbo_b, escape, speed_up, record, screenshot]
+);
+
+bindings!(
+    /// Buttons of the standard gamepad layout bound to every action, named after their position
+    /// so they mean the same on every controller. Actions missing in the file keep the built-in buttons
+    GamepadBindings: InputGamepadTriggers, BUTTON_NAMES,
+    [up, down, left, right, a, b, start, select, turbo_a, turbo_b, escape, speed_up, record, screenshot],
+    deadzone: f32,
+);
+
+bindings!(
+    /// Event devices read directly and the key codes bound to every action, named like `evtest`
+    /// prints them. Nothing is read until some devices are listed
+    EvdevBindings: InputEvdevTriggers, EVDEV_NAMES,
+    [up, down, left, right, a, b, start, select, turbo_a, turbo_b, escape, speed_up, record, screenshot],
+    devices: Vec<PathBuf>,
+    deadzone: f32,
+);
+
+bindings!(
+    /// GamePi13 buttons bound to every action, by the name printed on them. Turbo isn't bound by default,
+    /// X escapes, Y records and the shoulder buttons speed up
+    GpioBindings: InputGpioButtons, GPIO_NAMES,
+    [up, down, left, right, a, b, start, select, turbo_a, turbo_b, escape, speed_up, record, screenshot]
+);
+
+/// # Config
+/// Settings, input bindings and directories kept in `$XDG_CONFIG_HOME/gbeed/config.toml`, or
+/// `$HOME/.config/gbeed/config.toml` without it. Games can override any setting in a `[games."<key>"]`
+/// table, where the key is the cartridge title followed by its global checksum as given by `game_key`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub settings: Settings,
+    pub paths: Paths,
+    pub saves: SaveOptions,
+    pub keys: KeyBindings,
+    pub gamepad: GamepadBindings,
+    pub evdev: EvdevBindings,
+    pub gpio: GpioBindings,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub games: BTreeMap<String, GameOverrides>,
+}
+
+impl Config {
+    /// Directory of the config file, where other user files like gamepad mappings go too
+    pub fn dir() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(dir.join(CONFIG_DIR))
+    }
+
+    pub fn path() -> Option<PathBuf> { Self::dir().map(|dir| dir.join(CONFIG_FILE)) }
+
+    /// Extra SDL gamepad mappings, one per line like in SDL_GameControllerDB
+    pub fn gamepad_mappings_path() -> Option<PathBuf> {
+        Self::dir().map(|dir| dir.join(GAMEPAD_MAPPINGS_FILE))
+    }
+
+    /// Logiqx XML DATs ROMs are identified with, supplied by the user
+    pub fn dats_dir() -> Option<PathBuf> { Self::dir().map(|dir| dir.join(DATS_DIR)) }
+
+    /// Read the config file, there being none yet is the default config
+    pub fn load() -> io::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither XDG_CONFIG_HOME nor HOME are set",
+            ));
+        };
+
+        // rewritten on every change, so it must survive losing power halfway like a save
+        write_save(&path, toml::to_string(self).map_err(io::Error::other)?.as_bytes())
+    }
+
+    /// `"<title> <global checksum>"`, the checksum tells apart revisions and hacks sharing a title
+    pub fn game_key(cartridge: &Cartridge) -> String {
+        let header = &cartridge.header;
+        format!("{} {:04X}", header.title.trim(), header.global_checksum)
+    }
+
+    /// Global settings with the ones `game` overrides replaced
+    pub fn settings_for(&self, game: Option<&str>) -> Settings {
+        match game.and_then(|game| self.games.get(game)) {
+            Some(overrides) => self.settings.with_overrides(overrides),
+            None => self.settings,
+        }
+    }
+}
+
+fn input_name<T: PartialEq>(names: &[(T, &'static str)], input: T) -> Option<&'static str> {
+    names.iter().find(|(i, _)| *i == input).map(|(_, name)| *name)
+}
+
+fn inputs_from_names<T: Copy>(names: &[(T, &str)], action: &str, bound: &[String]) -> Box<[T]> {
+    bound
+        .iter()
+        .filter_map(|name| {
+            let input = names
+                .iter()
+                .find(|(_, n)| n.eq_ignore_ascii_case(name))
+                .map(|(i, _)| *i);
+            if input.is_none() {
+                eprintln!("config: unknown input `{name}` bound to {action}");
+            }
+            input
+        })
+        .collect()
+}
+
+const BUTTON_NAMES: &[(GamepadButton, &str)] = &[
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP, "dpad_up"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN, "dpad_down"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT, "dpad_left"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT, "dpad_right"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP, "north"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN, "south"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT, "west"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT, "east"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1, "left_shoulder"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_2, "left_trigger"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1, "right_shoulder"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2, "right_trigger"),
+    (GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT, "back"),
+    (GamepadButton::GAMEPAD_BUTTON_MIDDLE, "guide"),
+    (GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT, "start"),
+    (GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB, "left_stick"),
+    (GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB, "right_stick"),
+];
+
+const GPIO_NAMES: &[(GpioButton, &str)] = &[
+    (GpioButton::Up, "up"),
+    (GpioButton::Down, "down"),
+    (GpioButton::Left, "left"),
+    (GpioButton::Right, "right"),
+    (GpioButton::A, "a"),
+    (GpioButton::B, "b"),
+    (GpioButton::X, "x"),
+    (GpioButton::Y, "y"),
+    (GpioButton::L, "l"),
+    (GpioButton::R, "r"),
+    (GpioButton::Start, "start"),
+    (GpioButton::Select, "select"),
+];
+
+const KEY_NAMES: &[(KeyboardKey, &str)] = &[
+    (KeyboardKey::KEY_A, "a"),
+    (KeyboardKey::KEY_B, "b"),
+    (KeyboardKey::KEY_C, "c"),
+    (KeyboardKey::KEY_D, "d"),
+    (KeyboardKey::KEY_E, "e"),
+    (KeyboardKey::KEY_F, "f"),
+    (KeyboardKey::KEY_G, "g"),
+    (KeyboardKey::KEY_H, "h"),
+    (KeyboardKey::KEY_I, "i"),
+    (KeyboardKey::KEY_J, "j"),
+    (KeyboardKey::KEY_K, "k"),
+    (KeyboardKey::KEY_L, "l"),
+    (KeyboardKey::KEY_M, "m"),
+    (KeyboardKey::KEY_N, "n"),
+    (KeyboardKey::KEY_O, "o"),
+    (KeyboardKey::KEY_P, "p"),
+    (KeyboardKey::KEY_Q, "q"),
+    (KeyboardKey::KEY_R, "r"),
+    (KeyboardKey::KEY_S, "s"),
+    (KeyboardKey::KEY_T, "t"),
+    (KeyboardKey::KEY_U, "u"),
+    (KeyboardKey::KEY_V, "v"),
+    (KeyboardKey::KEY_W, "w"),
+    (KeyboardKey::KEY_X, "x"),
+    (KeyboardKey::KEY_Y, "y"),
+    (KeyboardKey::KEY_Z, "z"),
+    (KeyboardKey::KEY_ZERO, "0"),
+    (KeyboardKey::KEY_ONE, "1"),
+    (KeyboardKey::KEY_TWO, "2"),
+    (KeyboardKey::KEY_THREE, "3"),
+    (KeyboardKey::KEY_FOUR, "4"),
+    (KeyboardKey::KEY_FIVE, "5"),
+    (KeyboardKey::KEY_SIX, "6"),
+    (KeyboardKey::KEY_SEVEN, "7"),
+    (KeyboardKey::KEY_EIGHT, "8"),
+    (KeyboardKey::KEY_NINE, "9"),
+    (KeyboardKey::KEY_F1, "f1"),
+    (KeyboardKey::KEY_F2, "f2"),
+    (KeyboardKey::KEY_F3, "f3"),
+    (KeyboardKey::KEY_F4, "f4"),
+    (KeyboardKey::KEY_F5, "f5"),
+    (KeyboardKey::KEY_F6, "f6"),
+    (KeyboardKey::KEY_F7, "f7"),
+    (KeyboardKey::KEY_F8, "f8"),
+    (KeyboardKey::KEY_F9, "f9"),
+    (KeyboardKey::KEY_F10, "f10"),
+    (KeyboardKey::KEY_F11, "f11"),
+    (KeyboardKey::KEY_F12, "f12"),
+    (KeyboardKey::KEY_UP, "up"),
+    (KeyboardKey::KEY_DOWN, "down"),
+    (KeyboardKey::KEY_LEFT, "left"),
+    (KeyboardKey::KEY_RIGHT, "right"),
+    (KeyboardKey::KEY_SPACE, "space"),
+    (KeyboardKey::KEY_ENTER, "enter"),
+    (KeyboardKey::KEY_ESCAPE, "escape"),
+    (KeyboardKey::KEY_TAB, "tab"),
+    (KeyboardKey::KEY_BACKSPACE, "backspace"),
+    (KeyboardKey::KEY_INSERT, "insert"),
+    (KeyboardKey::KEY_DELETE, "delete"),
+    (KeyboardKey::KEY_HOME, "home"),
+    (KeyboardKey::KEY_END, "end"),
+    (KeyboardKey::KEY_PAGE_UP, "page_up"),
+    (KeyboardKey::KEY_PAGE_DOWN, "page_down"),
+    (KeyboardKey::KEY_LEFT_SHIFT, "left_shift"),
+    (KeyboardKey::KEY_RIGHT_SHIFT, "right_shift"),
+    (KeyboardKey::KEY_LEFT_CONTROL, "left_control"),
+    (KeyboardKey::KEY_RIGHT_CONTROL, "right_control"),
+    (KeyboardKey::KEY_LEFT_ALT, "left_alt"),
+    (KeyboardKey::KEY_RIGHT_ALT, "right_alt"),
+    (KeyboardKey::KEY_APOSTROPHE, "apostrophe"),
+    (KeyboardKey::KEY_COMMA, "comma"),
+    (KeyboardKey::KEY_MINUS, "minus"),
+    (KeyboardKey::KEY_PERIOD, "period"),
+    (KeyboardKey::KEY_SLASH, "slash"),
+    (KeyboardKey::KEY_SEMICOLON, "semicolon"),
+    (KeyboardKey::KEY_EQUAL, "equal"),
+    (KeyboardKey::KEY_LEFT_BRACKET, "left_bracket"),
+    (KeyboardKey::KEY_BACKSLASH, "backslash"),
+    (KeyboardKey::KEY_RIGHT_BRACKET, "right_bracket"),
+    (KeyboardKey::KEY_GRAVE, "grave"),
+    (KeyboardKey::KEY_KP_0, "kp_0"),
+    (KeyboardKey::KEY_KP_1, "kp_1"),
+    (KeyboardKey::KEY_KP_2, "kp_2"),
+    (KeyboardKey::KEY_KP_3, "kp_3"),
+    (KeyboardKey::KEY_KP_4, "kp_4"),
+    (KeyboardKey::KEY_KP_5, "kp_5"),
+    (KeyboardKey::KEY_KP_6, "kp_6"),
+    (KeyboardKey::KEY_KP_7, "kp_7"),
+    (KeyboardKey::KEY_KP_8, "kp_8"),
+    (KeyboardKey::KEY_KP_9, "kp_9"),
+    (KeyboardKey::KEY_KP_DECIMAL, "kp_decimal"),
+    (KeyboardKey::KEY_KP_DIVIDE, "kp_divide"),
+    (KeyboardKey::KEY_KP_MULTIPLY, "kp_multiply"),
+    (KeyboardKey::KEY_KP_SUBTRACT, "kp_subtract"),
+    (KeyboardKey::KEY_KP_ADD, "kp_add"),
+    (KeyboardKey::KEY_KP_ENTER, "kp_enter"),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_toml_round_trip() {
+        let mut config = Config::default();
+        config.settings.speed_up_mode = SpeedUpMode::Hold;
+        config.settings.turbo_rate = TurboRate::Turbo7_5;
+        config.settings.targeted_fps = TargetedFps::Unlimited;
+        config.settings.sample_rate = SampleRate::Rate22050;
+        config.keys.a = vec!["Space".to_string(), "j".to_string()];
+        config.gamepad.b = vec!["east".to_string()];
+        config.gamepad.deadzone = 0.25;
+        config.paths.roms = Some(PathBuf::from("/mnt/usb/roms"));
+        config.games.insert(
+            "TETRIS 3BEA".to_string(),
+            GameOverrides {
+                palette: Some(Palette::Green),
+                ..GameOverrides::default()
+            },
+        );
+
+        let text = toml::to_string(&config).unwrap();
+        for line in [
+            "speed_up_mode = \"hold\"",
+            "turbo_rate = 7.5",
+            "targeted_fps = 0",
+            "sample_rate = 22050",
+            "a = [\"Space\", \"j\"]",
+        ] {
+            assert!(text.lines().any(|l| l == line), "no `{line}` in\n{text}");
+        }
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+
+        let keys = config.keys.triggers();
+        assert_eq!(*keys.a, [KeyboardKey::KEY_SPACE, KeyboardKey::KEY_J]);
+        let gamepad = config.gamepad.triggers();
+        assert_eq!(*gamepad.b, [GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT]);
+        assert_eq!(gamepad.deadzone, 0.25);
+    }
+
+    #[test]
+    fn test_parse_bindings_and_values() {
+        let config: Config = toml::from_str(
+            r#"
+            [settings]
+            speed_up_mode = "toggle"
+            turbo_rate = 30.0
+
+            [keys]
+            a = ["k", "not_a_key"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.settings.speed_up_mode, SpeedUpMode::Toggle(false));
+        assert_eq!(config.settings.turbo_rate, TurboRate::Turbo30);
+        assert_eq!(*config.keys.triggers().a, [KeyboardKey::KEY_K]);
+        // actions missing in the file keep the built-in bindings
+        assert_eq!(config.keys.b, KeyBindings::default().b);
+
+        for invalid in [
+            "[settings]\nsample_rate = 12345",
+            "[settings]\ntargeted_fps = 45",
+            "[settings]\nturbo_rate = 20.0",
+            "[settings]\nspeed_up_mode = \"always\"",
+        ] {
+            assert!(toml::from_str::<Config>(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_game_overrides() {
+        let (game, other) = ("TETRIS 3BEA", "TETRIS 0000");
+        let mut config = Config::default();
+        config.games.insert(
+            game.to_string(),
+            GameOverrides {
+                palette: Some(Palette::Green),
+                ..GameOverrides::default()
+            },
+        );
+
+        let mut settings = config.settings_for(Some(game));
+        assert_eq!(settings.palette, Palette::Green);
+        // same title, but another revision
+        assert_eq!(config.settings_for(Some(other)).palette, Palette::default());
+        assert_eq!(config.settings_for(None).palette, Palette::default());
+
+        settings.palette = Palette::Red;
+        settings.frame_blending = true;
+        config.store(settings, Some(game));
+
+        // the overridden setting stays with the game, the rest becomes global
+        assert_eq!(config.games[game].palette, Some(Palette::Red));
+        assert_eq!(config.games[game].frame_blending, None);
+        assert_eq!(config.settings.palette, Palette::default());
+        assert!(config.settings.frame_blending);
+
+        let mut settings = config.settings_for(Some(other));
+        settings.palette = Palette::Blue;
+        config.store(settings, Some(other));
+        assert_eq!(config.settings.palette, Palette::Blue);
+        assert_eq!(config.settings_for(Some(game)).palette, Palette::Red);
+        assert!(!config.games.contains_key(other));
+    }
+
+    #[test]
+    fn test_resolve_dir() {
+        let paths = Paths {
+            saves: Some(PathBuf::from("/mnt/sd/saves")),
+            ..Paths::default()
+        };
+
+        assert_eq!(
+            resolve_dir(Some(&paths), |paths| &paths.saves, "saves"),
+            PathBuf::from("/mnt/sd/saves")
+        );
+        // tests are debug builds, which default to the workspace root
+        let workspace = PathBuf::from(env!("WORKSPACE_ROOT"));
+        assert_eq!(
+            resolve_dir(Some(&paths), |paths| &paths.dumps, "dumps"),
+            workspace.join("dumps")
+        );
+        assert_eq!(
+            resolve_dir(None, |paths| &paths.saves, "saves"),
+            workspace.join("saves")
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use crate::Texture;
+use crate::settings::{ScreenFilter, ScreenScaling};
+use gbeed_core::prelude::{DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH};
+use raylib::prelude::*;
+
+const WIDTH: usize = DMG_SCREEN_WIDTH;
+const HEIGHT: usize = DMG_SCREEN_HEIGHT;
+
+/// Grid lines keep this much of the pixel color, out of 256
+const GRID_SHADE: u32 = 192;
+
+/// # Screen filters
+/// Post-processing done on the CPU between the emulated framebuffer and the window, since DRM devices
+/// may have no GPU to run shaders on. Frame blending averages every frame with the previous one, which
+/// games flickering objects on alternate frames rely on to look transparent. The filters then upscale
+/// the result into a texture of their own, drawn stretched or at an integer scale
+pub struct ScreenFilters {
+    pub filter: ScreenFilter,
+    pub frame_blending: bool,
+    pub scaling: ScreenScaling,
+
+    /// Last frame as it came from the emulator, before blending
+    previous: Box<[u8]>,
+    /// Frame being filtered, packed as `0xRRGGBB`
+    source: Box<[u32]>,
+    /// Filtered frames at 1x, 2x and 3x the native size
+    outputs: [Texture; 3],
+}
+
+impl ScreenFilters {
+    pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread) -> Self {
+        let output = |rl: &mut RaylibHandle, scale: usize| {
+            Texture::new(rl, thread, (WIDTH * scale) as i32, (HEIGHT * scale) as i32)
+        };
+
+        Self {
+            filter: ScreenFilter::default(),
+            frame_blending: false,
+            scaling: ScreenScaling::default(),
+            previous: vec![0; WIDTH * HEIGHT * 3].into_boxed_slice(),
+            source: vec![0; WIDTH * HEIGHT].into_boxed_slice(),
+            outputs: [output(rl, 1), output(rl, 2), output(rl, 3)],
+        }
+    }
+
+    /// Whether frames go through the filters at all, the screen is drawn as it is otherwise
+    #[inline(always)]
+    pub fn is_active(&self) -> bool { self.filter != ScreenFilter::Off || self.frame_blending }
+
+    /// Filter a 160x144 RGB8 frame, meant to be called once per emulated frame
+    pub fn apply(&mut self, frame: &[u8]) {
+        if !self.is_active() {
+            return;
+        }
+
+        blend(frame, &self.previous, self.frame_blending, &mut self.source);
+        self.previous.copy_from_slice(frame);
+
+        let output = &mut self.outputs[self.filter.scale() - 1];
+        let pixels = output.pixels_mut();
+        match self.filter {
+            ScreenFilter::Off => {
+                for (i, &color) in self.source.iter().enumerate() {
+                    put(pixels, i, color);
+                }
+            }
+            ScreenFilter::LcdGrid => lcd_grid(&self.source, pixels),
+            ScreenFilter::Scale2x => scale2x(&self.source, pixels),
+            ScreenFilter::Scale3x => scale3x(&self.source, pixels),
+        }
+        output.update();
+    }
+
+    /// Texture to draw, the filtered one or `screen` when no filter is active
+    pub fn output<'a>(&'a self, screen: &'a Texture) -> &'a Texture {
+        match self.is_active() {
+            true => &self.outputs[self.filter.scale() - 1],
+            false => screen,
+        }
+    }
+
+    /// Source rectangle covering the whole `output` texture
+    pub fn source_rect(&self) -> Rectangle {
+        let scale = if self.is_active() { self.filter.scale() } else { 1 };
+        Rectangle::new(0.0, 0.0, (WIDTH * scale) as f32, (HEIGHT * scale) as f32)
+    }
+
+    /// Where the screen goes in a `width` x `height` area
+    pub fn destination(&self, width: i32, height: i32) -> Rectangle {
+        destination(self.scaling, width, height)
+    }
+}
+
+fn destination(scaling: ScreenScaling, width: i32, height: i32) -> Rectangle {
+    match scaling {
+        ScreenScaling::Stretch => Rectangle::new(0.0, 0.0, width as f32, height as f32),
+        ScreenScaling::Integer => {
+            let scale = (width / WIDTH as i32).min(height / HEIGHT as i32).max(1);
+            let (w, h) = (WIDTH as i32 * scale, HEIGHT as i32 * scale);
+            Rectangle::new(
+                ((width - w) / 2) as f32,
+                ((height - h) / 2) as f32,
+                w as f32,
+                h as f32,
+            )
+        }
+    }
+}
+
+/// Pack an RGB8 frame into `source`, averaged with the previous one when blending
+fn blend(frame: &[u8], previous: &[u8], frame_blending: bool, source: &mut [u32]) {
+    for ((source, rgb), previous) in source
+        .iter_mut()
+        .zip(frame.chunks_exact(3))
+        .zip(previous.chunks_exact(3))
+    {
+        let channel = |i: usize| match frame_blending {
+            true => (rgb[i] as u32 + previous[i] as u32).div_ceil(2),
+            false => rgb[i] as u32,
+        };
+        *source = (channel(0) << 16) | (channel(1) << 8) | channel(2);
+    }
+}
+
+#[inline(always)]
+fn put(pixels: &mut [u8], index: usize, color: u32) {
+    pixels[index * 3] = (color >> 16) as u8;
+    pixels[index * 3 + 1] = (color >> 8) as u8;
+    pixels[index * 3 + 2] = color as u8;
+}
+
+/// Pixel at `x + dx`, `y + dy`, repeating the borders
+#[inline(always)]
+fn neighbor(source: &[u32], x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+    let x = x.saturating_add_signed(dx).min(WIDTH - 1);
+    let y = y.saturating_add_signed(dy).min(HEIGHT - 1);
+    source[y * WIDTH + x]
+}
+
+/// Every pixel as a 3x3 block with its right column and bottom row darkened
+fn lcd_grid(source: &[u32], pixels: &mut [u8]) {
+    let shade = |channel: u32| (channel & 0xFF) * GRID_SHADE / 256;
+
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let color = source[y * WIDTH + x];
+            let line = (shade(color >> 16) << 16) | (shade(color >> 8) << 8) | shade(color);
+
+            for (dy, dx) in (0..3).flat_map(|dy| (0..3).map(move |dx| (dy, dx))) {
+                let index = (y * 3 + dy) * WIDTH * 3 + x * 3 + dx;
+                put(pixels, index, if dx == 2 || dy == 2 { line } else { color });
+            }
+        }
+    }
+}
+
+/// AdvMAME2x, rounds the corners of diagonal edges without blurring any color
+fn scale2x(source: &[u32], pixels: &mut [u8]) {
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let e = source[y * WIDTH + x];
+            let b = neighbor(source, x, y, 0, -1);
+            let d = neighbor(source, x, y, -1, 0);
+            let f = neighbor(source, x, y, 1, 0);
+            let h = neighbor(source, x, y, 0, 1);
+
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if b == f { f } else { e },
+                    if d == h { d } else { e },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 4]
+            };
+
+            for (i, color) in block.into_iter().enumerate() {
+                put(pixels, (y * 2 + i / 2) * WIDTH * 2 + x * 2 + i % 2, color);
+            }
+        }
+    }
+}
+
+/// AdvMAME3x, the same edge rules as Scale2x over a 3x3 block
+fn scale3x(source: &[u32], pixels: &mut [u8]) {
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let at = |dx, dy| neighbor(source, x, y, dx, dy);
+            let (a, b, c) = (at(-1, -1), at(0, -1), at(1, -1));
+            let (d, e, f) = (at(-1, 0), at(0, 0), at(1, 0));
+            let (g, h, i) = (at(-1, 1), at(0, 1), at(1, 1));
+
+            let block = if b != h && d != f {
+                [
+                    if d == b { d } else { e },
+                    if (d == b && e != c) || (b == f && e != a) {
+                        b
+                    } else {
+                        e
+                    },
+                    if b == f { f } else { e },
+                    if (d == b && e != g) || (d == h && e != a) {
+                        d
+                    } else {
+                        e
+                    },
+                    e,
+                    if (b == f && e != i) || (h == f && e != c) {
+                        f
+                    } else {
+                        e
+                    },
+                    if d == h { d } else { e },
+                    if (d == h && e != i) || (h == f && e != g) {
+                        h
+                    } else {
+                        e
+                    },
+                    if h == f { f } else { e },
+                ]
+            } else {
+                [e; 9]
+            };
+
+            for (n, color) in block.into_iter().enumerate() {
+                put(pixels, (y * 3 + n / 3) * WIDTH * 3 + x * 3 + n % 3, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WHITE: u32 = 0xFFFFFF;
+    const BLACK: u32 = 0x000000;
+
+    /// White frame with the given pixels set to black
+    fn white_frame(black: &[(usize, usize)]) -> Vec<u32> {
+        let mut source = vec![WHITE; WIDTH * HEIGHT];
+        for &(x, y) in black {
+            source[y * WIDTH + x] = BLACK;
+        }
+        source
+    }
+
+    /// Colors of the `scale` x `scale` block the pixel at `x`, `y` was scaled to
+    fn block(pixels: &[u8], scale: usize, x: usize, y: usize) -> Vec<u32> {
+        (0..scale * scale)
+            .map(|n| {
+                let index = ((y * scale + n / scale) * WIDTH * scale + x * scale + n % scale) * 3;
+                u32::from_be_bytes([0, pixels[index], pixels[index + 1], pixels[index + 2]])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scale2x() {
+        // black above and to the left of the white pixel at 1,1
+        let source = white_frame(&[(1, 0), (0, 1)]);
+        let mut pixels = vec![0; WIDTH * HEIGHT * 3 * 4];
+        scale2x(&source, &mut pixels);
+
+        // only its top left corner is rounded, the black pixels themselves are left square
+        assert_eq!(block(&pixels, 2, 1, 1), [BLACK, WHITE, WHITE, WHITE]);
+        assert_eq!(block(&pixels, 2, 1, 0), [BLACK; 4]);
+        assert_eq!(block(&pixels, 2, 2, 2), [WHITE; 4]);
+    }
+
+    #[test]
+    fn test_scale3x() {
+        let source = white_frame(&[(1, 0), (0, 1)]);
+        let mut pixels = vec![0; WIDTH * HEIGHT * 3 * 9];
+        scale3x(&source, &mut pixels);
+
+        let mut rounded = [WHITE; 9];
+        rounded[0] = BLACK;
+        assert_eq!(block(&pixels, 3, 1, 1), rounded);
+        assert_eq!(block(&pixels, 3, 0, 1), [BLACK; 9]);
+    }
+
+    #[test]
+    fn test_scale_clamps_edges() {
+        // pixels past the top and left edges repeat the corner, so only its bottom right is rounded
+        let source = white_frame(&[(1, 0), (0, 1)]);
+        let mut pixels = vec![0; WIDTH * HEIGHT * 3 * 4];
+        scale2x(&source, &mut pixels);
+        assert_eq!(block(&pixels, 2, 0, 0), [WHITE, WHITE, WHITE, BLACK]);
+
+        let source = white_frame(&[(WIDTH - 2, HEIGHT - 1), (WIDTH - 1, HEIGHT - 2)]);
+        scale2x(&source, &mut pixels);
+        assert_eq!(
+            block(&pixels, 2, WIDTH - 1, HEIGHT - 1),
+            [BLACK, WHITE, WHITE, WHITE]
+        );
+    }
+
+    #[test]
+    fn test_lcd_grid() {
+        let source = vec![0x808080; WIDTH * HEIGHT];
+        let mut pixels = vec![0; WIDTH * HEIGHT * 3 * 9];
+        lcd_grid(&source, &mut pixels);
+
+        // right column and bottom row of every block keep 3/4 of the color
+        let (color, line) = (0x808080, 0x606060);
+        assert_eq!(
+            block(&pixels, 3, 5, 7),
+            [color, color, line, color, color, line, line, line, line]
+        );
+    }
+
+    #[test]
+    fn test_frame_blending() {
+        let frame = vec![0xFF; WIDTH * HEIGHT * 3];
+        let previous = vec![0x00; WIDTH * HEIGHT * 3];
+        let mut source = vec![0; WIDTH * HEIGHT];
+
+        blend(&frame, &previous, true, &mut source);
+        assert!(source.iter().all(|&color| color == 0x808080));
+
+        blend(&frame, &previous, false, &mut source);
+        assert!(source.iter().all(|&color| color == WHITE));
+    }
+
+    #[test]
+    fn test_integer_scaling_destination() {
+        let destination = |scaling, width, height| {
+            let rect = destination(scaling, width, height);
+            (rect.x, rect.y, rect.width, rect.height)
+        };
+
+        // too small for 2x, so drawn at 1x in the middle
+        assert_eq!(
+            destination(ScreenScaling::Integer, 240, 240),
+            (40.0, 48.0, 160.0, 144.0)
+        );
+        assert_eq!(
+            destination(ScreenScaling::Integer, 480, 432),
+            (0.0, 0.0, 480.0, 432.0)
+        );
+        assert_eq!(
+            destination(ScreenScaling::Stretch, 240, 240),
+            (0.0, 0.0, 240.0, 240.0)
+        );
+    }
+}
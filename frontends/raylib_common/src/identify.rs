@@ -0,0 +1,364 @@
+use crate::{archive, config::Config, saves::write_save};
+use gbeed_core::crc32;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+const DAT_EXTENSIONS: [&str; 2] = ["dat", "xml"];
+const CACHE_FILE: &str = "identified.toml";
+
+/// # ROM identification
+/// What a ROM is according to the DATs, which know every dump by its contents instead of
+/// the 16 byte title and checksums of the cartridge header
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomInfo {
+    /// Canonical name, like `Tetris (World) (Rev 1)`
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Known bad dump, likely to misbehave whatever the emulator does
+    pub bad_dump: bool,
+}
+
+impl RomInfo {
+    fn new(name: &str, bad_dump: bool) -> Self {
+        let tags: Vec<&str> = name
+            .split('(')
+            .skip(1)
+            .filter_map(|tag| tag.split_once(')').map(|(tag, _)| tag))
+            .collect();
+
+        Self {
+            name: name.to_string(),
+            // No-Intro names always have the regions as their first tag
+            region: tags.first().map(|region| region.to_string()),
+            revision: tags
+                .iter()
+                .find(|tag| is_revision(tag))
+                .map(|tag| tag.to_string()),
+            bad_dump: bad_dump || name.contains("[b]"),
+        }
+    }
+}
+
+/// `Rev 1`, `Rev A` or `v1.1`
+#[inline(always)]
+fn is_revision(tag: &str) -> bool {
+    tag.starts_with("Rev ")
+        || tag
+            .strip_prefix('v')
+            .is_some_and(|version| version.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+#[derive(Debug)]
+struct Entry {
+    size: usize,
+    /// Older DATs only have CRC32s
+    sha1: Option<[u8; 20]>,
+    info: RomInfo,
+}
+
+#[derive(Debug, Default)]
+pub struct RomDatabase {
+    /// Entries by CRC32, the SHA-1 only tells apart the ones sharing it
+    entries: HashMap<u32, Vec<Entry>>,
+    /// Names, sizes and modification times of the DATs read, identifications made with others are stale
+    dats: String,
+}
+
+impl RomDatabase {
+    /// The DATs in the `dats` directory next to the config file, loaded the first time it is needed.
+    /// No DAT ships with gbeed, so nothing is identified until the user puts one there
+    pub fn shared() -> &'static RomDatabase {
+        static DATABASE: OnceLock<RomDatabase> = OnceLock::new();
+
+        DATABASE.get_or_init(|| {
+            let mut database = RomDatabase::default();
+
+            let mut dats: Vec<PathBuf> = Config::dats_dir()
+                .and_then(|dir| fs::read_dir(dir).ok())
+                .map(|entries| entries.filter_map(Result::ok).map(|entry| entry.path()).collect())
+                .unwrap_or_default();
+            dats.retain(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| DAT_EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+            });
+            dats.sort();
+
+            for path in dats {
+                if let Err(e) = fs::read_to_string(&path).and_then(|dat| database.add_dat(&dat)) {
+                    eprintln!("Failed to read the DAT at {path:?}: {e}");
+                }
+                if let Some((size, modified)) = stamp(&path) {
+                    database.dats += &format!("{} {size} {modified}\n", path.display());
+                }
+            }
+            database
+        })
+    }
+
+    /// Add every ROM of a Logiqx XML DAT, the format No-Intro publishes them in.
+    /// ROMs already known keep the name they were first added with
+    pub fn add_dat(&mut self, dat: &str) -> io::Result<()> {
+        // No-Intro DATs start with the Logiqx DOCTYPE
+        let options = roxmltree::ParsingOptions {
+            allow_dtd: true,
+            ..Default::default()
+        };
+        let document = roxmltree::Document::parse_with_options(dat, options)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let games = document
+            .descendants()
+            .filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"));
+        for game in games {
+            let Some(name) = game.attribute("name") else {
+                continue;
+            };
+
+            for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                let crc = rom
+                    .attribute("crc")
+                    .and_then(|crc| u32::from_str_radix(crc, 16).ok());
+                let size = rom.attribute("size").and_then(|size| size.parse().ok());
+                let (Some(crc), Some(size)) = (crc, size) else {
+                    continue;
+                };
+
+                self.entries.entry(crc).or_default().push(Entry {
+                    size,
+                    sha1: rom.attribute("sha1").and_then(parse_sha1),
+                    info: RomInfo::new(name, rom.attribute("status") == Some("baddump")),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+
+    /// The entry matching the whole ROM, its SHA-1 only being calculated when there is one
+    /// with the same CRC32 and size
+    pub fn identify(&self, rom: &[u8]) -> Option<&RomInfo> {
+        let candidates = self.entries.get(&crc32(rom))?;
+
+        let mut sha1 = None;
+        candidates
+            .iter()
+            .filter(|entry| entry.size == rom.len())
+            .find(|entry| match entry.sha1 {
+                Some(expected) => {
+                    *sha1.get_or_insert_with(|| sha1_smol::Sha1::from(rom).digest().bytes()) == expected
+                }
+                None => true,
+            })
+            .map(|entry| &entry.info)
+    }
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 {
+        return None;
+    }
+
+    let mut sha1 = [0; 20];
+    for (i, byte) in sha1.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(sha1)
+}
+
+/// Size and modification time in seconds of the file at `path`, the archive holding it for archive paths
+fn stamp(path: &Path) -> Option<(u64, u64)> {
+    let file = archive::split_archive_path(path).map_or(path, |(archive, _)| archive);
+    let metadata = fs::metadata(file).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_secs()))
+}
+
+/// What a file was identified as, while it keeps the same size and modification time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Identified {
+    size: u64,
+    modified: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    info: Option<RomInfo>,
+}
+
+/// # Identification cache
+/// Every file identified so far, kept in `identified.toml` next to the config file so the ROM list
+/// doesn't read and hash every ROM again each time it's opened. It's thrown away when the DATs change
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Cache {
+    dats: String,
+    roms: BTreeMap<PathBuf, Identified>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Cache {
+    fn path() -> Option<PathBuf> { Config::dir().map(|dir| dir.join(CACHE_FILE)) }
+
+    /// The cache file if it was made with the same DATs, an empty cache otherwise
+    fn load(dats: &str) -> Self {
+        let cache: Option<Cache> = Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|text| toml::from_str(&text).ok());
+
+        match cache {
+            Some(cache) if cache.dats == dats => cache,
+            _ => Self {
+                dats: dats.to_string(),
+                ..Self::default()
+            },
+        }
+    }
+
+    fn shared(dats: &str) -> &'static Mutex<Cache> {
+        static CACHE: OnceLock<Mutex<Cache>> = OnceLock::new();
+        CACHE.get_or_init(|| Mutex::new(Self::load(dats)))
+    }
+}
+
+/// Identify the ROM at `path` with the shared database. Results are cached by path, size and
+/// modification time, so only new or changed ROMs are read
+pub fn identify_file(path: &Path) -> Option<RomInfo> {
+    let database = RomDatabase::shared();
+    if database.is_empty() {
+        return None;
+    }
+
+    let stamp = stamp(path);
+    let mut cache = Cache::shared(&database.dats)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let (Some((size, modified)), Some(known)) = (stamp, cache.roms.get(path))
+        && known.size == size
+        && known.modified == modified
+    {
+        return known.info.clone();
+    }
+
+    let info = archive::read_rom(path)
+        .ok()
+        .and_then(|rom| database.identify(&rom).cloned());
+    if let Some((size, modified)) = stamp {
+        let identified = Identified {
+            size,
+            modified,
+            info: info.clone(),
+        };
+        cache.roms.insert(path.to_path_buf(), identified);
+        cache.changed = true;
+    }
+    info
+}
+
+/// Write the identification cache file, if anything new was identified since it was read
+pub fn save_cache() -> io::Result<()> {
+    let database = RomDatabase::shared();
+    let Some(path) = Cache::path().filter(|_| !database.is_empty()) else {
+        return Ok(());
+    };
+
+    let mut cache = Cache::shared(&database.dats)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if !cache.changed {
+        return Ok(());
+    }
+
+    write_save(
+        &path,
+        toml::to_string(&*cache).map_err(io::Error::other)?.as_bytes(),
+    )?;
+    cache.changed = false;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DAT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/Dats/datafile.dtd">
+<datafile>
+    <header><name>Nintendo - Game Boy</name></header>
+    <game name="Test Game (World) (Rev 1)">
+        <rom name="Test Game (World) (Rev 1).gb" size="4" crc="B63CFBCD"/>
+    </game>
+    <game name="Test Game (Japan) (v1.1)">
+        <rom name="Test Game (Japan) (v1.1).gb" size="4" crc="E951A406" sha1="0000000000000000000000000000000000000000" status="baddump"/>
+    </game>
+</datafile>"#;
+
+    #[test]
+    fn test_identify() {
+        let mut database = RomDatabase::default();
+        database.add_dat(DAT).unwrap();
+
+        let info = database.identify(&[1, 2, 3, 4]).unwrap();
+        assert_eq!(info.name, "Test Game (World) (Rev 1)");
+        assert_eq!(info.region.as_deref(), Some("World"));
+        assert_eq!(info.revision.as_deref(), Some("Rev 1"));
+        assert!(!info.bad_dump);
+
+        // listed with its CRC32 and size, but a different SHA-1
+        assert_eq!(database.identify(&[4, 3, 2, 1]), None);
+        assert_eq!(database.identify(&[1, 2, 3, 4, 5]), None);
+    }
+
+    #[test]
+    fn test_first_dat_wins() {
+        let mut database = RomDatabase::default();
+        database
+            .add_dat(&DAT.replace("Test Game (World) (Rev 1)", "Renamed (Europe)"))
+            .unwrap();
+        database.add_dat(DAT).unwrap();
+
+        assert_eq!(database.identify(&[1, 2, 3, 4]).unwrap().name, "Renamed (Europe)");
+    }
+
+    #[test]
+    fn test_cache_round_trip() {
+        let mut cache = Cache {
+            dats: "gb.dat 1 2\n".to_string(),
+            ..Cache::default()
+        };
+        let info = Some(RomInfo::new("Test Game (Europe)", false));
+        cache.roms.insert(
+            PathBuf::from("roms/collection.zip/test.gb"),
+            Identified {
+                size: 4,
+                modified: 5,
+                info,
+            },
+        );
+        cache.roms.insert(
+            PathBuf::from("roms/homebrew.gb"),
+            Identified {
+                size: 6,
+                modified: 7,
+                info: None,
+            },
+        );
+
+        let loaded: Cache = toml::from_str(&toml::to_string(&cache).unwrap()).unwrap();
+        assert_eq!(loaded.dats, cache.dats);
+        assert_eq!(loaded.roms.len(), 2);
+        assert_eq!(
+            loaded.roms[Path::new("roms/collection.zip/test.gb")].info,
+            cache.roms[Path::new("roms/collection.zip/test.gb")].info
+        );
+        assert_eq!(loaded.roms[Path::new("roms/homebrew.gb")].info, None);
+    }
+}
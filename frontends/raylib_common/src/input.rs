@@ -1,5 +1,10 @@
+use crate::settings::TurboRate;
 use gbeed_core::{Joypad, JoypadButton};
 use raylib::prelude::*;
+use std::ffi::CString;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
 
 #[derive(Debug, Default, Copy, Clone)]
 pub struct InputState {
@@ -11,8 +16,12 @@ pub struct InputState {
     pub b: bool,
     pub start: bool,
     pub select: bool,
+    pub turbo_a: bool,
+    pub turbo_b: bool,
     pub escape: bool,
     pub speed_up: bool,
+    pub record: bool,
+    pub screenshot: bool,
 }
 
 pub trait ToInputState {
@@ -29,12 +38,32 @@ pub struct InputKeyTriggers {
     pub b: Box<[KeyboardKey]>,
     pub start: Box<[KeyboardKey]>,
     pub select: Box<[KeyboardKey]>,
+    pub turbo_a: Box<[KeyboardKey]>,
+    pub turbo_b: Box<[KeyboardKey]>,
     pub escape: Box<[KeyboardKey]>,
     pub speed_up: Box<[KeyboardKey]>,
+    pub record: Box<[KeyboardKey]>,
+    pub screenshot: Box<[KeyboardKey]>,
 }
 
+/// Bindings set by `InputKeyTriggers::configure`, which every default built afterwards copies
+static CONFIGURED_KEYS: OnceLock<InputKeyTriggers> = OnceLock::new();
+
 impl Default for InputKeyTriggers {
-    fn default() -> Self {
+    fn default() -> Self { CONFIGURED_KEYS.get().cloned().unwrap_or_else(Self::builtin) }
+}
+
+impl InputKeyTriggers {
+    /// Use `triggers` in every `InputManager` built from now on. Scenes build their own managers
+    /// all the time, so bindings are set once at startup instead of being passed around
+    pub fn configure(triggers: Self) {
+        if CONFIGURED_KEYS.set(triggers).is_err() {
+            eprintln!("key bindings were already configured");
+        }
+    }
+
+    /// Bindings used when there are no configured ones
+    pub fn builtin() -> Self {
         Self {
             up: [KeyboardKey::KEY_W, KeyboardKey::KEY_UP].into(),
             down: [KeyboardKey::KEY_S, KeyboardKey::KEY_DOWN].into(),
@@ -44,8 +73,12 @@ impl Default for InputKeyTriggers {
             b: [KeyboardKey::KEY_K, KeyboardKey::KEY_C].into(),
             start: [KeyboardKey::KEY_L, KeyboardKey::KEY_X].into(),
             select: [KeyboardKey::KEY_SEMICOLON, KeyboardKey::KEY_Z].into(),
+            turbo_a: [KeyboardKey::KEY_U].into(),
+            turbo_b: [KeyboardKey::KEY_I].into(),
             escape: [KeyboardKey::KEY_ESCAPE].into(),
             speed_up: [KeyboardKey::KEY_LEFT_SHIFT].into(),
+            record: [KeyboardKey::KEY_F9].into(),
+            screenshot: [KeyboardKey::KEY_F12].into(),
         }
     }
 }
@@ -61,8 +94,12 @@ impl ToInputState for InputKeyTriggers {
             b: self.b.iter().any(|k| rl.is_key_down(*k)),
             start: self.start.iter().any(|k| rl.is_key_down(*k)),
             select: self.select.iter().any(|k| rl.is_key_down(*k)),
+            turbo_a: self.turbo_a.iter().any(|k| rl.is_key_down(*k)),
+            turbo_b: self.turbo_b.iter().any(|k| rl.is_key_down(*k)),
             escape: self.escape.iter().any(|k| rl.is_key_down(*k)),
             speed_up: self.speed_up.iter().any(|k| rl.is_key_down(*k)),
+            record: self.record.iter().any(|k| rl.is_key_down(*k)),
+            screenshot: self.screenshot.iter().any(|k| rl.is_key_down(*k)),
         }
     }
 }
@@ -103,8 +140,11 @@ pub struct InputMouseTriggers {
     pub b: MouseButtonArea,
     pub start: MouseButtonArea,
     pub select: MouseButtonArea,
+    pub turbo_a: Option<MouseButtonArea>,
+    pub turbo_b: Option<MouseButtonArea>,
     pub escape: Option<MouseButtonArea>,
     pub speed_up: Option<MouseButtonArea>,
+    pub record: Option<MouseButtonArea>,
 }
 
 impl ToInputState for InputMouseTriggers {
@@ -144,20 +184,235 @@ impl ToInputState for InputMouseTriggers {
             }
         }
 
+        if let Some(area) = &self.turbo_a {
+            state.turbo_a = active_positions.iter().any(|p| area.contains(*p));
+        }
+        if let Some(area) = &self.turbo_b {
+            state.turbo_b = active_positions.iter().any(|p| area.contains(*p));
+        }
         if let Some(area) = &self.escape {
             state.escape = active_positions.iter().any(|p| area.contains(*p));
         }
         if let Some(area) = &self.speed_up {
             state.speed_up = active_positions.iter().any(|p| area.contains(*p));
         }
+        if let Some(area) = &self.record {
+            state.record = active_positions.iter().any(|p| area.contains(*p));
+        }
+
+        state
+    }
+}
+
+/// Gamepads raylib tracks, any of them can drive the emulator
+const MAX_GAMEPADS: i32 = 4;
+/// Stick travel, out of 1, needed before it counts as a d-pad direction
+const DEFAULT_DEADZONE: f32 = 0.5;
+
+/// Bindings set by `InputGamepadTriggers::configure`, which every default built afterwards copies
+static CONFIGURED_GAMEPAD: OnceLock<InputGamepadTriggers> = OnceLock::new();
+
+/// # Gamepad triggers
+/// Buttons of the standard layout raylib maps every known controller to, using the SDL_GameControllerDB
+/// mappings it ships. Every connected pad is read each frame, so pads can be plugged and unplugged at
+/// any time. The left stick works as the d-pad past `deadzone`
+#[derive(Debug, Clone)]
+pub struct InputGamepadTriggers {
+    pub up: Box<[GamepadButton]>,
+    pub down: Box<[GamepadButton]>,
+    pub left: Box<[GamepadButton]>,
+    pub right: Box<[GamepadButton]>,
+    pub a: Box<[GamepadButton]>,
+    pub b: Box<[GamepadButton]>,
+    pub start: Box<[GamepadButton]>,
+    pub select: Box<[GamepadButton]>,
+    pub turbo_a: Box<[GamepadButton]>,
+    pub turbo_b: Box<[GamepadButton]>,
+    pub escape: Box<[GamepadButton]>,
+    pub speed_up: Box<[GamepadButton]>,
+    pub record: Box<[GamepadButton]>,
+    pub screenshot: Box<[GamepadButton]>,
+    pub deadzone: f32,
+}
+
+impl Default for InputGamepadTriggers {
+    fn default() -> Self { CONFIGURED_GAMEPAD.get().cloned().unwrap_or_else(Self::builtin) }
+}
+
+impl InputGamepadTriggers {
+    /// Use `triggers` in every `InputManager` built from now on, like `InputKeyTriggers::configure`
+    pub fn configure(triggers: Self) {
+        if CONFIGURED_GAMEPAD.set(triggers).is_err() {
+            eprintln!("gamepad bindings were already configured");
+        }
+    }
+
+    /// A and B on the right and bottom face buttons like on Nintendo pads, turbo on the other two.
+    /// Pads without a guide button can escape with the left shoulder
+    pub fn builtin() -> Self {
+        Self {
+            up: [GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP].into(),
+            down: [GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN].into(),
+            left: [GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT].into(),
+            right: [GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT].into(),
+            a: [GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT].into(),
+            b: [GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN].into(),
+            start: [GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT].into(),
+            select: [GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT].into(),
+            turbo_a: [GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP].into(),
+            turbo_b: [GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT].into(),
+            escape: [
+                GamepadButton::GAMEPAD_BUTTON_MIDDLE,
+                GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1,
+            ]
+            .into(),
+            speed_up: [
+                GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1,
+                GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2,
+            ]
+            .into(),
+            record: [GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB].into(),
+            screenshot: [GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB].into(),
+            deadzone: DEFAULT_DEADZONE,
+        }
+    }
+
+    /// Hand raylib extra SDL mappings for pads its database lacks, from the `SDL_GAMECONTROLLERCONFIG`
+    /// variable and the file at `path`. Needs the window to be open already
+    pub fn add_mappings(path: Option<&Path>) {
+        let mut mappings = std::env::var("SDL_GAMECONTROLLERCONFIG").unwrap_or_default();
+        if let Some(path) = path
+            && let Ok(file) = fs::read_to_string(path)
+        {
+            mappings.push('\n');
+            mappings.push_str(&file);
+        }
+        if mappings.trim().is_empty() {
+            return;
+        }
+
+        match CString::new(mappings) {
+            // raylib hands them over to GLFW right away, which keeps its own copy
+            Ok(mappings) => unsafe {
+                raylib::ffi::SetGamepadMappings(mappings.as_ptr());
+            },
+            Err(e) => eprintln!("gamepad: invalid mappings: {e}"),
+        }
+    }
+}
+
+impl ToInputState for InputGamepadTriggers {
+    fn to_input(&self, rl: &RaylibHandle) -> InputState {
+        let mut state = InputState::default();
+
+        for gamepad in (0..MAX_GAMEPADS).filter(|gamepad| rl.is_gamepad_available(*gamepad)) {
+            let down = |buttons: &[GamepadButton]| {
+                buttons
+                    .iter()
+                    .any(|button| rl.is_gamepad_button_down(gamepad, *button))
+            };
+            let x = rl.get_gamepad_axis_movement(gamepad, GamepadAxis::GAMEPAD_AXIS_LEFT_X);
+            let y = rl.get_gamepad_axis_movement(gamepad, GamepadAxis::GAMEPAD_AXIS_LEFT_Y);
+
+            state.up |= down(&self.up) || y < -self.deadzone;
+            state.down |= down(&self.down) || y > self.deadzone;
+            state.left |= down(&self.left) || x < -self.deadzone;
+            state.right |= down(&self.right) || x > self.deadzone;
+            state.a |= down(&self.a);
+            state.b |= down(&self.b);
+            state.start |= down(&self.start);
+            state.select |= down(&self.select);
+            state.turbo_a |= down(&self.turbo_a);
+            state.turbo_b |= down(&self.turbo_b);
+            state.escape |= down(&self.escape);
+            state.speed_up |= down(&self.speed_up);
+            state.record |= down(&self.record);
+            state.screenshot |= down(&self.screenshot);
+        }
 
         state
     }
 }
 
+pub mod evdev;
+
+pub use evdev::InputEvdevTriggers;
+
+/// Buttons of the GamePi13 handheld, wired straight to GPIO pins
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GpioButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    X,
+    Y,
+    L,
+    R,
+    Start,
+    Select,
+}
+
+/// GamePi13 buttons bound to every action, read through `InputGpioTriggers`
+#[derive(Debug, Clone)]
+pub struct InputGpioButtons {
+    pub up: Box<[GpioButton]>,
+    pub down: Box<[GpioButton]>,
+    pub left: Box<[GpioButton]>,
+    pub right: Box<[GpioButton]>,
+    pub a: Box<[GpioButton]>,
+    pub b: Box<[GpioButton]>,
+    pub start: Box<[GpioButton]>,
+    pub select: Box<[GpioButton]>,
+    pub turbo_a: Box<[GpioButton]>,
+    pub turbo_b: Box<[GpioButton]>,
+    pub escape: Box<[GpioButton]>,
+    pub speed_up: Box<[GpioButton]>,
+    pub record: Box<[GpioButton]>,
+    pub screenshot: Box<[GpioButton]>,
+}
+
+/// Bindings set by `InputGpioButtons::configure`, read once when the pins are claimed
+static CONFIGURED_GPIO: OnceLock<InputGpioButtons> = OnceLock::new();
+
+impl Default for InputGpioButtons {
+    fn default() -> Self { CONFIGURED_GPIO.get().cloned().unwrap_or_else(Self::builtin) }
+}
+
+impl InputGpioButtons {
+    /// Use `buttons` for the GPIO input, it has to be called at startup before any `InputManager` is built
+    pub fn configure(buttons: Self) {
+        if CONFIGURED_GPIO.set(buttons).is_err() {
+            eprintln!("gpio bindings were already configured");
+        }
+    }
+
+    /// X escapes, Y records and the shoulder buttons speed up. Turbo has no buttons until some are bound
+    pub fn builtin() -> Self {
+        Self {
+            up: [GpioButton::Up].into(),
+            down: [GpioButton::Down].into(),
+            left: [GpioButton::Left].into(),
+            right: [GpioButton::Right].into(),
+            a: [GpioButton::A].into(),
+            b: [GpioButton::B].into(),
+            start: [GpioButton::Start].into(),
+            select: [GpioButton::Select].into(),
+            turbo_a: [].into(),
+            turbo_b: [].into(),
+            escape: [GpioButton::X].into(),
+            speed_up: [GpioButton::L, GpioButton::R].into(),
+            record: [GpioButton::Y].into(),
+            screenshot: [].into(),
+        }
+    }
+}
+
 #[cfg(feature = "gamepi13")]
 mod gpio {
-    use super::{InputState, ToInputState};
+    use super::{GpioButton, InputGpioButtons, InputState, ToInputState};
     use raylib::prelude::RaylibHandle;
     use rpi_pal::gpio::{Gpio, InputPin};
     use std::sync::{Arc, OnceLock};
@@ -192,9 +447,29 @@ mod gpio {
         select: InputPin,
     }
 
+    impl GpioPins {
+        fn pin(&self, button: GpioButton) -> &InputPin {
+            match button {
+                GpioButton::Up => &self.up,
+                GpioButton::Down => &self.down,
+                GpioButton::Left => &self.left,
+                GpioButton::Right => &self.right,
+                GpioButton::A => &self.a,
+                GpioButton::B => &self.b,
+                GpioButton::X => &self.x,
+                GpioButton::Y => &self.y,
+                GpioButton::L => &self.l,
+                GpioButton::R => &self.r,
+                GpioButton::Start => &self.start,
+                GpioButton::Select => &self.select,
+            }
+        }
+    }
+
     #[derive(Debug, Clone)]
     pub struct InputGpioTriggers {
         pins: Arc<GpioPins>,
+        buttons: InputGpioButtons,
     }
 
     impl InputGpioTriggers {
@@ -218,6 +493,7 @@ mod gpio {
                     start: pin(PIN_START)?,
                     select: pin(PIN_SELECT)?,
                 }),
+                buttons: InputGpioButtons::default(),
             })
         }
 
@@ -237,18 +513,24 @@ mod gpio {
 
     impl ToInputState for InputGpioTriggers {
         fn to_input(&self, _rl: &RaylibHandle) -> InputState {
-            let pins = &self.pins;
+            let down = |buttons: &[GpioButton]| buttons.iter().any(|button| self.pins.pin(*button).is_low());
+            let buttons = &self.buttons;
+
             InputState {
-                up: pins.up.is_low(),
-                down: pins.down.is_low(),
-                left: pins.left.is_low(),
-                right: pins.right.is_low(),
-                a: pins.a.is_low(),
-                b: pins.b.is_low(),
-                start: pins.start.is_low(),
-                select: pins.select.is_low(),
-                escape: pins.x.is_low() || pins.y.is_low(),
-                speed_up: pins.l.is_low() || pins.r.is_low(),
+                up: down(&buttons.up),
+                down: down(&buttons.down),
+                left: down(&buttons.left),
+                right: down(&buttons.right),
+                a: down(&buttons.a),
+                b: down(&buttons.b),
+                start: down(&buttons.start),
+                select: down(&buttons.select),
+                turbo_a: down(&buttons.turbo_a),
+                turbo_b: down(&buttons.turbo_b),
+                escape: down(&buttons.escape),
+                speed_up: down(&buttons.speed_up),
+                record: down(&buttons.record),
+                screenshot: down(&buttons.screenshot),
             }
         }
     }
@@ -284,25 +566,53 @@ impl InputState {
             acc.b |= to_state.b;
             acc.start |= to_state.start;
             acc.select |= to_state.select;
+            acc.turbo_a |= to_state.turbo_a;
+            acc.turbo_b |= to_state.turbo_b;
             acc.escape |= to_state.escape;
             acc.speed_up |= to_state.speed_up;
+            acc.record |= to_state.record;
+            acc.screenshot |= to_state.screenshot;
 
             acc
         })
     }
 
-    pub fn apply(self, joypad: &mut Joypad) {
+    /// Press the held buttons, turbo buttons pulse A and B with `turbo`
+    pub fn apply(self, joypad: &mut Joypad, turbo: &Turbo) {
+        let pulse = turbo.is_down();
+
         joypad.button_down(JoypadButton::Up, self.up);
         joypad.button_down(JoypadButton::Down, self.down);
         joypad.button_down(JoypadButton::Left, self.left);
         joypad.button_down(JoypadButton::Right, self.right);
-        joypad.button_down(JoypadButton::A, self.a);
-        joypad.button_down(JoypadButton::B, self.b);
+        joypad.button_down(JoypadButton::A, self.a || (self.turbo_a && pulse));
+        joypad.button_down(JoypadButton::B, self.b || (self.turbo_b && pulse));
         joypad.button_down(JoypadButton::Start, self.start);
         joypad.button_down(JoypadButton::Select, self.select);
     }
 }
 
+/// Autofire phase counted in emulated frames, so the rate holds while sped up or slowed down
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Turbo {
+    pub rate: TurboRate,
+    frame: u8,
+}
+
+impl Turbo {
+    /// Whether turbo buttons are down in the current frame
+    #[inline]
+    pub fn is_down(&self) -> bool { self.frame < self.rate.frames_held() }
+
+    /// Move on to the next frame, meant to be called on every VBlank so turbo keeps in step with
+    /// the emulation however it's run
+    #[inline]
+    pub fn next_frame(&mut self) {
+        let held = self.rate.frames_held();
+        self.frame = (self.frame + 1) % (held * 2);
+    }
+}
+
 macro_rules! impl_input_methods {
     ($($name:ident),*) => {
         $(
@@ -317,6 +627,11 @@ macro_rules! impl_input_methods {
                     self.current.$name && !self.previous.$name
                 }
 
+                #[inline(always)]
+                pub fn [<is_released_ $name>](&self) -> bool {
+                    !self.current.$name && self.previous.$name
+                }
+
                 #[inline(always)]
                 pub fn [<is_repeated_ $name>](&self, dt: f32) -> bool {
                     self.[<is_pressed_ $name>]() || (self.current.$name && self.check_repeat(dt))
@@ -330,7 +645,9 @@ macro_rules! impl_input_methods {
 pub struct InputManager {
     pub key_triggers: InputKeyTriggers,
     pub mouse_triggers: Option<InputMouseTriggers>,
+    pub gamepad_triggers: Option<InputGamepadTriggers>,
     pub gpio_triggers: Option<InputGpioTriggers>,
+    pub evdev_triggers: Option<InputEvdevTriggers>,
     pub current: InputState,
     pub previous: InputState,
     pub repeat_timer: f32,
@@ -338,7 +655,16 @@ pub struct InputManager {
 }
 
 impl Default for InputManager {
-    fn default() -> Self { Self::new(0.08, None, None, InputGpioTriggers::shared()) }
+    fn default() -> Self {
+        Self::new(
+            0.08,
+            None,
+            None,
+            Some(InputGamepadTriggers::default()),
+            InputGpioTriggers::shared(),
+            InputEvdevTriggers::shared(),
+        )
+    }
 }
 
 impl InputManager {
@@ -346,12 +672,16 @@ impl InputManager {
         debounce: f32,
         key_triggers: Option<InputKeyTriggers>,
         mouse_triggers: Option<InputMouseTriggers>,
+        gamepad_triggers: Option<InputGamepadTriggers>,
         gpio_triggers: Option<InputGpioTriggers>,
+        evdev_triggers: Option<InputEvdevTriggers>,
     ) -> Self {
         Self {
             key_triggers: key_triggers.unwrap_or_default(),
             mouse_triggers,
+            gamepad_triggers,
             gpio_triggers,
+            evdev_triggers,
             current: InputState::default(),
             previous: InputState::default(),
             repeat_timer: 0.0,
@@ -391,15 +721,23 @@ impl InputManager {
         if let Some(mouse) = &self.mouse_triggers {
             sources.push(mouse);
         }
+        if let Some(gamepad) = &self.gamepad_triggers {
+            sources.push(gamepad);
+        }
         if let Some(gpio) = &self.gpio_triggers {
             sources.push(gpio);
         }
+        if let Some(evdev) = &self.evdev_triggers {
+            sources.push(evdev);
+        }
         InputState::merge(rl, &sources)
     }
 
     pub fn state(&self) -> InputState { self.current }
 
-    impl_input_methods!(up, down, left, right, a, b, start, select, escape, speed_up);
+    impl_input_methods!(
+        up, down, left, right, a, b, start, select, turbo_a, turbo_b, escape, speed_up, record, screenshot
+    );
 
     fn check_repeat(&self, dt: f32) -> bool {
         const REPEAT_DELAY: f32 = 0.3;
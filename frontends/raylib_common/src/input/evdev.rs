@@ -0,0 +1,576 @@
+use super::{InputState, ToInputState};
+use raylib::prelude::RaylibHandle;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+
+macro_rules! evdev_codes {
+    ($($name:ident = $code:expr),+ $(,)?) => {
+        $(pub const $name: u16 = $code;)+
+
+        /// Codes by the names `evtest` prints for them
+        pub(crate) const EVDEV_NAMES: &[(u16, &str)] = &[$(($name, stringify!($name))),+];
+    };
+}
+
+evdev_codes!(
+    KEY_ESC = 1,
+    KEY_1 = 2,
+    KEY_2 = 3,
+    KEY_3 = 4,
+    KEY_4 = 5,
+    KEY_5 = 6,
+    KEY_6 = 7,
+    KEY_7 = 8,
+    KEY_8 = 9,
+    KEY_9 = 10,
+    KEY_0 = 11,
+    KEY_MINUS = 12,
+    KEY_EQUAL = 13,
+    KEY_BACKSPACE = 14,
+    KEY_TAB = 15,
+    KEY_Q = 16,
+    KEY_W = 17,
+    KEY_E = 18,
+    KEY_R = 19,
+    KEY_T = 20,
+    KEY_Y = 21,
+    KEY_U = 22,
+    KEY_I = 23,
+    KEY_O = 24,
+    KEY_P = 25,
+    KEY_LEFTBRACE = 26,
+    KEY_RIGHTBRACE = 27,
+    KEY_ENTER = 28,
+    KEY_LEFTCTRL = 29,
+    KEY_A = 30,
+    KEY_S = 31,
+    KEY_D = 32,
+    KEY_F = 33,
+    KEY_G = 34,
+    KEY_H = 35,
+    KEY_J = 36,
+    KEY_K = 37,
+    KEY_L = 38,
+    KEY_SEMICOLON = 39,
+    KEY_APOSTROPHE = 40,
+    KEY_GRAVE = 41,
+    KEY_LEFTSHIFT = 42,
+    KEY_BACKSLASH = 43,
+    KEY_Z = 44,
+    KEY_X = 45,
+    KEY_C = 46,
+    KEY_V = 47,
+    KEY_B = 48,
+    KEY_N = 49,
+    KEY_M = 50,
+    KEY_COMMA = 51,
+    KEY_DOT = 52,
+    KEY_SLASH = 53,
+    KEY_RIGHTSHIFT = 54,
+    KEY_LEFTALT = 56,
+    KEY_SPACE = 57,
+    KEY_F1 = 59,
+    KEY_F2 = 60,
+    KEY_F3 = 61,
+    KEY_F4 = 62,
+    KEY_F5 = 63,
+    KEY_F6 = 64,
+    KEY_F7 = 65,
+    KEY_F8 = 66,
+    KEY_F9 = 67,
+    KEY_F10 = 68,
+    KEY_F11 = 87,
+    KEY_F12 = 88,
+    KEY_RIGHTCTRL = 97,
+    KEY_RIGHTALT = 100,
+    KEY_HOME = 102,
+    KEY_UP = 103,
+    KEY_PAGEUP = 104,
+    KEY_LEFT = 105,
+    KEY_RIGHT = 106,
+    KEY_END = 107,
+    KEY_DOWN = 108,
+    KEY_PAGEDOWN = 109,
+    KEY_INSERT = 110,
+    KEY_DELETE = 111,
+    KEY_VOLUMEDOWN = 114,
+    KEY_VOLUMEUP = 115,
+    KEY_POWER = 116,
+    KEY_MENU = 139,
+    KEY_BACK = 158,
+    KEY_OK = 0x160,
+    KEY_SELECT = 0x161,
+    BTN_SOUTH = 0x130,
+    BTN_EAST = 0x131,
+    BTN_C = 0x132,
+    BTN_NORTH = 0x133,
+    BTN_WEST = 0x134,
+    BTN_Z = 0x135,
+    BTN_TL = 0x136,
+    BTN_TR = 0x137,
+    BTN_TL2 = 0x138,
+    BTN_TR2 = 0x139,
+    BTN_SELECT = 0x13A,
+    BTN_START = 0x13B,
+    BTN_MODE = 0x13C,
+    BTN_THUMBL = 0x13D,
+    BTN_THUMBR = 0x13E,
+    BTN_DPAD_UP = 0x220,
+    BTN_DPAD_DOWN = 0x221,
+    BTN_DPAD_LEFT = 0x222,
+    BTN_DPAD_RIGHT = 0x223,
+);
+
+/// Stick travel, out of 1, needed before it counts as a d-pad direction
+const DEFAULT_DEADZONE: f32 = 0.5;
+/// Highest key code tracked, `KEY_MAX` in the kernel headers
+const KEY_MAX: usize = 0x2FF;
+
+/// Bindings set by `InputEvdevTriggers::configure`
+static CONFIGURED_EVDEV: OnceLock<InputEvdevTriggers> = OnceLock::new();
+/// Devices being read in the background, shared by every `InputManager`
+static DEVICES: OnceLock<Arc<[Mutex<DeviceState>]>> = OnceLock::new();
+
+/// # Evdev triggers
+/// Key codes and absolute axes read straight from `/dev/input/event*` nodes, for kiosk setups where raylib's
+/// own input handling isn't wanted or doesn't see the device, like GPIO buttons wired through a `gpio-keys`
+/// device tree overlay. Every device is read by a thread of its own that keeps its state up to date and
+/// reopens it whenever it comes back after being unplugged. The left stick and the d-pad hat of gamepads
+/// work as the d-pad, the stick past `deadzone`
+#[derive(Debug, Clone)]
+pub struct InputEvdevTriggers {
+    /// Event device nodes, `/dev/input/by-id` or `by-path` links keep their names across reboots
+    pub devices: Vec<PathBuf>,
+    pub deadzone: f32,
+    pub up: Box<[u16]>,
+    pub down: Box<[u16]>,
+    pub left: Box<[u16]>,
+    pub right: Box<[u16]>,
+    pub a: Box<[u16]>,
+    pub b: Box<[u16]>,
+    pub start: Box<[u16]>,
+    pub select: Box<[u16]>,
+    pub turbo_a: Box<[u16]>,
+    pub turbo_b: Box<[u16]>,
+    pub escape: Box<[u16]>,
+    pub speed_up: Box<[u16]>,
+    pub record: Box<[u16]>,
+    pub screenshot: Box<[u16]>,
+}
+
+impl InputEvdevTriggers {
+    /// Use `triggers` for the evdev source of every `InputManager` built from now on
+    pub fn configure(triggers: Self) {
+        if CONFIGURED_EVDEV.set(triggers).is_err() {
+            eprintln!("evdev bindings were already configured");
+        }
+    }
+
+    /// Configured triggers, with the readers of their devices started on the first call.
+    /// There are none until `configure` sets some devices
+    pub fn shared() -> Option<Self> {
+        let triggers = CONFIGURED_EVDEV
+            .get()
+            .filter(|triggers| !triggers.devices.is_empty())?;
+        DEVICES.get_or_init(|| reader::start(&triggers.devices));
+        Some(triggers.clone())
+    }
+
+    /// Gamepad buttons like `InputGamepadTriggers::builtin`, plus the keys the Adafruit retrogame
+    /// and most `gpio-keys` overlays for handhelds send. No devices are read by default
+    pub fn builtin() -> Self {
+        Self {
+            devices: Vec::new(),
+            deadzone: DEFAULT_DEADZONE,
+            up: [KEY_UP, BTN_DPAD_UP].into(),
+            down: [KEY_DOWN, BTN_DPAD_DOWN].into(),
+            left: [KEY_LEFT, BTN_DPAD_LEFT].into(),
+            right: [KEY_RIGHT, BTN_DPAD_RIGHT].into(),
+            a: [KEY_LEFTCTRL, BTN_EAST].into(),
+            b: [KEY_LEFTALT, BTN_SOUTH].into(),
+            start: [KEY_ENTER, BTN_START].into(),
+            select: [KEY_SPACE, BTN_SELECT].into(),
+            turbo_a: [BTN_NORTH].into(),
+            turbo_b: [BTN_WEST].into(),
+            escape: [KEY_ESC, BTN_MODE, BTN_TL].into(),
+            speed_up: [BTN_TR, BTN_TR2].into(),
+            record: [BTN_THUMBL].into(),
+            screenshot: [BTN_THUMBR].into(),
+        }
+    }
+
+    /// Input from every device read so far, without going through raylib
+    pub fn state(&self) -> InputState {
+        let mut state = InputState::default();
+        let Some(devices) = DEVICES.get() else {
+            return state;
+        };
+
+        for device in devices.iter() {
+            let device = device.lock().unwrap_or_else(|e| e.into_inner());
+            let input = self.input_from(&device);
+
+            state.up |= input.up;
+            state.down |= input.down;
+            state.left |= input.left;
+            state.right |= input.right;
+            state.a |= input.a;
+            state.b |= input.b;
+            state.start |= input.start;
+            state.select |= input.select;
+            state.turbo_a |= input.turbo_a;
+            state.turbo_b |= input.turbo_b;
+            state.escape |= input.escape;
+            state.speed_up |= input.speed_up;
+            state.record |= input.record;
+            state.screenshot |= input.screenshot;
+        }
+
+        state
+    }
+
+    /// Actions a single device triggers
+    fn input_from(&self, device: &DeviceState) -> InputState {
+        let down = |codes: &[u16]| codes.iter().any(|code| device.is_down(*code));
+        let [x, y, hat_x, hat_y] = device.axes;
+
+        InputState {
+            up: down(&self.up) || y < -self.deadzone || hat_y < 0.0,
+            down: down(&self.down) || y > self.deadzone || hat_y > 0.0,
+            left: down(&self.left) || x < -self.deadzone || hat_x < 0.0,
+            right: down(&self.right) || x > self.deadzone || hat_x > 0.0,
+            a: down(&self.a),
+            b: down(&self.b),
+            start: down(&self.start),
+            select: down(&self.select),
+            turbo_a: down(&self.turbo_a),
+            turbo_b: down(&self.turbo_b),
+            escape: down(&self.escape),
+            speed_up: down(&self.speed_up),
+            record: down(&self.record),
+            screenshot: down(&self.screenshot),
+        }
+    }
+}
+
+impl ToInputState for InputEvdevTriggers {
+    fn to_input(&self, _rl: &RaylibHandle) -> InputState { self.state() }
+}
+
+/// What a device reports right now, all released while it is unplugged
+#[derive(Debug, Clone)]
+struct DeviceState {
+    /// Bit per key code
+    keys: [u64; KEY_MAX / 64 + 1],
+    /// Left stick and d-pad hat, from -1 to 1
+    axes: [f32; 4],
+}
+
+impl Default for DeviceState {
+    fn default() -> Self {
+        Self {
+            keys: [0; KEY_MAX / 64 + 1],
+            axes: [0.0; 4],
+        }
+    }
+}
+
+impl DeviceState {
+    #[inline(always)]
+    fn is_down(&self, code: u16) -> bool {
+        let code = code as usize;
+        code <= KEY_MAX && self.keys[code / 64] & (1 << (code % 64)) != 0
+    }
+
+    #[inline(always)]
+    fn set_key(&mut self, code: u16, down: bool) {
+        let code = code as usize;
+        if code <= KEY_MAX {
+            match down {
+                true => self.keys[code / 64] |= 1 << (code % 64),
+                false => self.keys[code / 64] &= !(1 << (code % 64)),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod reader {
+    use super::{DeviceState, KEY_MAX};
+    use std::ffi::{c_long, c_ulong};
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::AsRawFd;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    pub(super) const EV_SYN: u16 = 0x00;
+    pub(super) const EV_KEY: u16 = 0x01;
+    pub(super) const EV_ABS: u16 = 0x03;
+    const SYN_REPORT: u16 = 0;
+    const SYN_DROPPED: u16 = 3;
+
+    /// Absolute axes kept, in the order of `DeviceState::axes`
+    const AXES: [u16; 4] = [ABS_X, ABS_Y, ABS_HAT0X, ABS_HAT0Y];
+    pub(super) const ABS_X: u16 = 0x00;
+    const ABS_Y: u16 = 0x01;
+    pub(super) const ABS_HAT0X: u16 = 0x10;
+    const ABS_HAT0Y: u16 = 0x11;
+
+    /// `struct input_event`, a timestamp of two longs followed by type, code and value
+    pub(super) const EVENT_SIZE: usize = 2 * size_of::<c_long>() + 8;
+
+    /// How often a missing device is looked for again
+    const REOPEN_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub(super) fn start(paths: &[PathBuf]) -> Arc<[Mutex<DeviceState>]> {
+        let devices: Arc<[Mutex<DeviceState>]> = paths.iter().map(|_| Mutex::default()).collect();
+
+        for (index, path) in paths.iter().enumerate() {
+            let (devices, watched) = (devices.clone(), path.clone());
+            let spawned = thread::Builder::new()
+                .name(format!("evdev {}", path.display()))
+                .spawn(move || watch(&watched, &devices[index]));
+
+            if let Err(e) = spawned {
+                eprintln!("evdev: failed to start the reader of {}: {e}", path.display());
+            }
+        }
+
+        devices
+    }
+
+    /// Read the device for as long as the process lives, waiting for it whenever it goes away
+    fn watch(path: &Path, state: &Mutex<DeviceState>) {
+        let mut missing_reported = false;
+
+        loop {
+            match File::open(path) {
+                Ok(file) => {
+                    missing_reported = false;
+
+                    let e = read(file, state);
+                    *state.lock().unwrap_or_else(|e| e.into_inner()) = DeviceState::default();
+                    eprintln!("evdev: lost {}: {e}", path.display());
+                }
+                Err(e) if !missing_reported => {
+                    eprintln!("evdev: can't open {}, waiting for it: {e}", path.display());
+                    missing_reported = true;
+                }
+                Err(_) => {}
+            }
+
+            thread::sleep(REOPEN_INTERVAL);
+        }
+    }
+
+    /// Apply events until reading fails, which is how an unplugged device shows up
+    fn read(mut file: File, state: &Mutex<DeviceState>) -> io::Error {
+        let ranges = AXES.map(|axis| abs_info(&file, axis).map(|(_, min, max)| (min, max)));
+        let mut event = [0; EVENT_SIZE];
+        // events up to the next report are incomplete after the kernel drops some
+        let mut dropped = false;
+
+        // keys already held when the device is opened count too
+        *state.lock().unwrap_or_else(|e| e.into_inner()) = query(&file, &ranges);
+
+        loop {
+            if let Err(e) = file.read_exact(&mut event) {
+                return e;
+            }
+
+            let (kind, code, value) = parse(&event);
+            match (kind, code) {
+                (EV_SYN, SYN_DROPPED) => dropped = true,
+                (EV_SYN, SYN_REPORT) if dropped => {
+                    dropped = false;
+                    *state.lock().unwrap_or_else(|e| e.into_inner()) = query(&file, &ranges);
+                }
+                _ if dropped => {}
+                _ => apply(
+                    &mut state.lock().unwrap_or_else(|e| e.into_inner()),
+                    kind,
+                    code,
+                    value,
+                    &ranges,
+                ),
+            }
+        }
+    }
+
+    /// Type, code and value of a `struct input_event`
+    pub(super) fn parse(event: &[u8; EVENT_SIZE]) -> (u16, u16, i32) {
+        let tail = &event[EVENT_SIZE - 8..];
+        let kind = u16::from_ne_bytes([tail[0], tail[1]]);
+        let code = u16::from_ne_bytes([tail[2], tail[3]]);
+        let value = i32::from_ne_bytes([tail[4], tail[5], tail[6], tail[7]]);
+        (kind, code, value)
+    }
+
+    /// Update `state` with a key or axis event, `ranges` are the ones of `AXES`
+    pub(super) fn apply(
+        state: &mut DeviceState,
+        kind: u16,
+        code: u16,
+        value: i32,
+        ranges: &[Option<(i32, i32)>; 4],
+    ) {
+        match kind {
+            // autorepeat comes as 2, still down
+            EV_KEY => state.set_key(code, value != 0),
+            EV_ABS => {
+                if let Some(axis) = AXES.iter().position(|axis| *axis == code) {
+                    state.axes[axis] = normalize(value, ranges[axis]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// What the device reports right now, through `EVIOCGKEY` and `EVIOCGABS`
+    fn query(file: &File, ranges: &[Option<(i32, i32)>; 4]) -> DeviceState {
+        const BITS: usize = c_ulong::BITS as usize;
+        let mut state = DeviceState::default();
+
+        // the kernel fills a bitmap of unsigned longs
+        let mut keys: [c_ulong; (KEY_MAX + 1).div_ceil(BITS)] = [0; (KEY_MAX + 1).div_ceil(BITS)];
+        let request = ioc_read(0x18, size_of_val(&keys));
+        if unsafe { libc::ioctl(file.as_raw_fd(), request as _, keys.as_mut_ptr()) } >= 0 {
+            for code in 0..=KEY_MAX {
+                state.set_key(code as u16, keys[code / BITS] & (1 << (code % BITS)) != 0);
+            }
+        }
+
+        for (axis, code) in AXES.iter().enumerate() {
+            if let Some((value, _, _)) = abs_info(file, *code) {
+                state.axes[axis] = normalize(value, ranges[axis]);
+            }
+        }
+
+        state
+    }
+
+    /// Value, minimum and maximum of an absolute axis through `EVIOCGABS`, if the device has it
+    fn abs_info(file: &File, axis: u16) -> Option<(i32, i32, i32)> {
+        // struct input_absinfo: value, minimum, maximum, fuzz, flat and resolution
+        let mut info = [0i32; 6];
+        let request = ioc_read(0x40 + axis as u32, size_of_val(&info));
+
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, info.as_mut_ptr()) };
+        (result >= 0).then_some((info[0], info[1], info[2]))
+    }
+
+    /// `_IOR('E', nr, size)`
+    fn ioc_read(nr: u32, size: usize) -> u32 { (2 << 30) | ((size as u32) << 16) | ((b'E' as u32) << 8) | nr }
+
+    /// Scale an axis value to -1..1, values of axes without a range are only clamped
+    /// and axes with an empty one stay centred
+    #[inline(always)]
+    pub(super) fn normalize(value: i32, range: Option<(i32, i32)>) -> f32 {
+        match range {
+            Some((min, max)) if max > min => {
+                let (value, min, max) = (value as i64, min as i64, max as i64);
+                (2.0 * (value - min) as f32 / (max - min) as f32 - 1.0).clamp(-1.0, 1.0)
+            }
+            Some(_) => 0.0,
+            None => value.clamp(-1, 1) as f32,
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod reader {
+    use super::DeviceState;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    pub(super) fn start(_paths: &[PathBuf]) -> Arc<[Mutex<DeviceState>]> {
+        eprintln!("evdev: input devices can only be read on linux");
+        Arc::new([])
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::reader::*;
+    use super::*;
+
+    const RANGES: [Option<(i32, i32)>; 4] = [Some((0, 255)), Some((0, 255)), Some((-1, 1)), Some((-1, 1))];
+
+    fn event(kind: u16, code: u16, value: i32) -> [u8; EVENT_SIZE] {
+        let mut event = [0xAA; EVENT_SIZE];
+        event[EVENT_SIZE - 8..EVENT_SIZE - 6].copy_from_slice(&kind.to_ne_bytes());
+        event[EVENT_SIZE - 6..EVENT_SIZE - 4].copy_from_slice(&code.to_ne_bytes());
+        event[EVENT_SIZE - 4..].copy_from_slice(&value.to_ne_bytes());
+        event
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(0, Some((0, 255))), -1.0);
+        assert_eq!(normalize(255, Some((0, 255))), 1.0);
+        assert!(normalize(0, Some((-32768, 32767))).abs() < 0.001);
+        assert_eq!(normalize(-1, Some((-1, 1))), -1.0);
+
+        // values outside of the reported range are clamped
+        assert_eq!(normalize(300, Some((0, 255))), 1.0);
+
+        // the subtractions overflow in i32
+        assert_eq!(normalize(i32::MAX, Some((i32::MIN, i32::MAX))), 1.0);
+        assert_eq!(normalize(i32::MIN, Some((i32::MIN, i32::MAX))), -1.0);
+
+        assert_eq!(normalize(5, Some((5, 5))), 0.0);
+        assert_eq!(normalize(5, Some((10, 0))), 0.0);
+        assert_eq!(normalize(-7, None), -1.0);
+        assert_eq!(normalize(0, None), 0.0);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(&event(EV_KEY, BTN_SOUTH, 1)), (EV_KEY, BTN_SOUTH, 1));
+        assert_eq!(parse(&event(EV_ABS, ABS_X, -300)), (EV_ABS, ABS_X, -300));
+    }
+
+    #[test]
+    fn test_events_to_state() {
+        let mut state = DeviceState::default();
+
+        apply(&mut state, EV_KEY, BTN_SOUTH, 1, &RANGES);
+        assert!(state.is_down(BTN_SOUTH));
+        // autorepeat keeps the key down
+        apply(&mut state, EV_KEY, BTN_SOUTH, 2, &RANGES);
+        assert!(state.is_down(BTN_SOUTH));
+        apply(&mut state, EV_KEY, BTN_SOUTH, 0, &RANGES);
+        assert!(!state.is_down(BTN_SOUTH));
+
+        // codes past KEY_MAX are ignored instead of indexing out of the bitmap
+        apply(&mut state, EV_KEY, u16::MAX, 1, &RANGES);
+        assert!(!state.is_down(u16::MAX));
+
+        apply(&mut state, EV_ABS, ABS_X, 255, &RANGES);
+        apply(&mut state, EV_ABS, ABS_HAT0X, -1, &RANGES);
+        assert_eq!(state.axes, [1.0, 0.0, -1.0, 0.0]);
+        // axes other than the stick and the hat aren't kept
+        apply(&mut state, EV_ABS, 0x02, 255, &RANGES);
+        assert_eq!(state.axes, [1.0, 0.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_state_to_input() {
+        let triggers = InputEvdevTriggers::builtin();
+        let mut state = DeviceState::default();
+        apply(&mut state, EV_KEY, KEY_ENTER, 1, &RANGES);
+        apply(&mut state, EV_KEY, BTN_NORTH, 1, &RANGES);
+        apply(&mut state, EV_ABS, ABS_X, 0, &RANGES);
+
+        let input = triggers.input_from(&state);
+        assert!(input.start && input.turbo_a && input.left);
+        assert!(!input.a && !input.right && !input.up && !input.down);
+
+        // the stick has to go past the deadzone
+        apply(&mut state, EV_ABS, ABS_X, 100, &RANGES);
+        assert!(!triggers.input_from(&state).left);
+    }
+}
@@ -1,5 +1,15 @@
+pub mod archive;
+pub mod audio;
 pub mod color;
+pub mod config;
+pub mod filter;
+pub mod identify;
 pub mod input;
+pub mod library;
+pub mod patch;
+pub mod recording;
+pub mod saves;
+pub mod screenshot;
 pub mod settings;
 mod texture;
 mod utils;
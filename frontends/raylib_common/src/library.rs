@@ -0,0 +1,159 @@
+use crate::config::Config;
+use crate::saves::write_save;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LIBRARY_FILE: &str = "library.toml";
+const MAX_RECENTS: usize = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recent {
+    pub path: PathBuf,
+    /// Unix time in seconds
+    pub played: u64,
+}
+
+/// # Library
+/// Favourite and recently played ROMs. They change every time a game is played, so they are kept
+/// in their own file next to the config instead of in it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Library {
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub favourites: BTreeSet<PathBuf>,
+    /// Most recently played first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub recents: Vec<Recent>,
+}
+
+impl Library {
+    pub fn path() -> Option<PathBuf> { Config::dir().map(|dir| dir.join(LIBRARY_FILE)) }
+
+    /// Read the library file, there being none yet is an empty library
+    pub fn load() -> io::Result<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display()))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "neither XDG_CONFIG_HOME nor HOME are set",
+            ));
+        };
+
+        write_save(&path, toml::to_string(self).map_err(io::Error::other)?.as_bytes())
+    }
+
+    #[inline(always)]
+    pub fn is_favourite(&self, path: &Path) -> bool { self.favourites.contains(path) }
+
+    pub fn toggle_favourite(&mut self, path: &Path) {
+        if !self.favourites.remove(path) {
+            self.favourites.insert(path.to_path_buf());
+        }
+    }
+
+    /// Move `path` to the front of the recents as played right now
+    pub fn played(&mut self, path: &Path) {
+        self.recents.retain(|recent| recent.path != path);
+        self.recents.insert(
+            0,
+            Recent {
+                path: path.to_path_buf(),
+                played: now(),
+            },
+        );
+        self.recents.truncate(MAX_RECENTS);
+    }
+}
+
+#[inline(always)]
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// How long ago a unix time in seconds was, as short as `5m`, `3h` or `2d`
+pub fn elapsed_since(time: u64) -> String {
+    let seconds = now().saturating_sub(time);
+    match seconds {
+        0..60 => "now".to_string(),
+        60..3600 => format!("{}m", seconds / 60),
+        3600..86400 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recents() {
+        let mut library = Library::default();
+        for i in 0..MAX_RECENTS + 5 {
+            library.played(Path::new(&format!("roms/{i}.gb")));
+        }
+        assert_eq!(library.recents.len(), MAX_RECENTS);
+        assert_eq!(library.recents[0].path, Path::new("roms/34.gb"));
+        assert_eq!(library.recents[MAX_RECENTS - 1].path, Path::new("roms/5.gb"));
+
+        // played again, it moves to the front instead of being listed twice
+        library.played(Path::new("roms/20.gb"));
+        assert_eq!(library.recents.len(), MAX_RECENTS);
+        assert_eq!(library.recents[0].path, Path::new("roms/20.gb"));
+        assert_eq!(library.recents[1].path, Path::new("roms/34.gb"));
+        assert_eq!(
+            library
+                .recents
+                .iter()
+                .filter(|recent| recent.path == Path::new("roms/20.gb"))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_toggle_favourite() {
+        let mut library = Library::default();
+        let path = Path::new("roms/tetris.gb");
+
+        library.toggle_favourite(path);
+        assert!(library.is_favourite(path));
+        assert!(!library.is_favourite(Path::new("roms/zelda.gb")));
+
+        library.toggle_favourite(path);
+        assert!(!library.is_favourite(path));
+        assert!(library.favourites.is_empty());
+    }
+
+    #[test]
+    fn test_elapsed_since() {
+        let now = now();
+        assert_eq!(elapsed_since(now), "now");
+        assert_eq!(elapsed_since(now - 30), "now");
+        assert_eq!(elapsed_since(now - 90), "1m");
+        assert_eq!(elapsed_since(now - 3500), "58m");
+        assert_eq!(elapsed_since(now - 2 * 3600), "2h");
+        assert_eq!(elapsed_since(now - 86400), "1d");
+        assert_eq!(elapsed_since(now - 45 * 86400), "45d");
+        // clocks set back still read as just played
+        assert_eq!(elapsed_since(now + 100), "now");
+    }
+}
@@ -0,0 +1,62 @@
+use crate::archive;
+use gbeed_core::{PatchFormat, apply_patch};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[inline(always)]
+fn is_patch(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+        PatchFormat::ALL
+            .iter()
+            .any(|format| ext.eq_ignore_ascii_case(format.extension()))
+    })
+}
+
+/// Every patch in the directory of the ROM at `path`, or of its archive, the ones named after
+/// the ROM first so romhacks distributed under their own name can still be picked
+pub fn find_patches(path: &Path) -> Vec<PathBuf> {
+    let unpacked = archive::unpacked_path(path);
+    let stem = unpacked.file_stem();
+    let dir = unpacked.parent().unwrap_or(Path::new("."));
+
+    let mut patches: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file() && is_patch(path))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    patches.sort_by_key(|patch| (patch.file_stem() != stem, patch.clone()));
+    patches
+}
+
+/// The patch named after the ROM at `path`, `game.ips`, `game.ups` or `game.bps` next to `game.gb`
+pub fn default_patch(path: &Path) -> Option<PathBuf> {
+    let unpacked = archive::unpacked_path(path);
+
+    PatchFormat::ALL
+        .iter()
+        .map(|format| unpacked.with_extension(format.extension()))
+        .find(|patch| patch.is_file())
+}
+
+/// # Soft patching
+/// Read the ROM at `path` like `archive::read_rom` and apply `patch` to it in memory,
+/// the files on disk are never modified
+pub fn read_patched_rom(path: &Path, patch: Option<&Path>) -> io::Result<Vec<u8>> {
+    let rom = archive::read_rom(path)?;
+    let Some(patch) = patch else {
+        return Ok(rom);
+    };
+
+    apply_patch(&rom, &fs::read(patch)?).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to apply {}: {e}", patch.display()),
+        )
+    })
+}
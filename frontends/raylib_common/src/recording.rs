@@ -0,0 +1,461 @@
+use crate::utils::timestamped_path;
+use gbeed_core::prelude::{DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// The screen refreshes every 70224 clocks of the 4 MiHz clock, a bit under 60 frames per second
+const FRAME_CLOCKS: u64 = 70_224;
+const CLOCK_RATE: u64 = 4_194_304;
+
+const FRAME_BYTES: usize = DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT * 3;
+
+/// GIF delays are in hundredths of a second and most viewers slow anything below 2 down to 10,
+/// so frames closer than this are dropped instead
+const GIF_MIN_DELAY: u64 = 2;
+
+/// # Recorder
+/// Gameplay capture toggled from a hotkey. While recording, every sample goes to a WAV file and
+/// every frame to both an uncompressed Y4M stream and an animated GIF, all of them named after the game
+/// and the moment the recording started. A failed write stops the recording instead of the emulation
+#[derive(Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+}
+
+impl Recorder {
+    #[inline(always)]
+    pub fn is_recording(&self) -> bool { self.recording.is_some() }
+
+    pub fn toggle(&mut self, dir: &Path, name: &str, sample_rate: u32) {
+        if self.is_recording() {
+            self.stop();
+            return;
+        }
+
+        match Recording::start(dir, name, sample_rate) {
+            Ok(recording) => {
+                println!("Recording to {}.{{wav,y4m,gif}}", recording.path.display());
+                self.recording = Some(recording);
+            }
+            Err(e) => eprintln!("Failed to start recording: {e}"),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        let Some(mut recording) = self.recording.take() else {
+            return;
+        };
+
+        match recording.finish() {
+            Ok(_) => println!("Saved recording to {}.{{wav,y4m,gif}}", recording.path.display()),
+            Err(e) => eprintln!("Failed to save recording: {e}"),
+        }
+    }
+
+    #[inline]
+    pub fn push_sample(&mut self, left: i16, right: i16) {
+        if let Some(recording) = &mut self.recording
+            && let Err(e) = recording.push_sample(left, right)
+        {
+            eprintln!("Recording stopped: {e}");
+            self.stop();
+        }
+    }
+
+    /// Record a frame from a 160x144 RGB8 framebuffer
+    pub fn push_frame(&mut self, pixels: &[u8]) {
+        if let Some(recording) = &mut self.recording
+            && let Err(e) = recording.push_frame(pixels)
+        {
+            eprintln!("Recording stopped: {e}");
+            self.stop();
+        }
+    }
+}
+
+struct Recording {
+    /// Shared path of the three files, without extension
+    path: PathBuf,
+    wav: WavWriter,
+    y4m: Y4mWriter,
+    gif: GifWriter,
+
+    sample_rate: u64,
+    samples: u64,
+    frames: u64,
+    last_frame: Vec<u8>,
+    finished: bool,
+}
+
+impl Recording {
+    fn start(dir: &Path, name: &str, sample_rate: u32) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let path = timestamped_path(dir, name, "wav");
+
+        Ok(Self {
+            wav: WavWriter::create(&path.with_extension("wav"), sample_rate)?,
+            y4m: Y4mWriter::create(&path.with_extension("y4m"))?,
+            gif: GifWriter::create(&path.with_extension("gif"))?,
+            path,
+            sample_rate: sample_rate as u64,
+            samples: 0,
+            frames: 0,
+            last_frame: vec![0; FRAME_BYTES],
+            finished: false,
+        })
+    }
+
+    fn push_sample(&mut self, left: i16, right: i16) -> io::Result<()> {
+        self.samples += 1;
+        self.wav.push(left, right)
+    }
+
+    fn push_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        // no frames come while the lcd is off, repeat the last one so video keeps up with the audio
+        let audio_frames = self.samples * CLOCK_RATE / (FRAME_CLOCKS * self.sample_rate);
+        while self.frames > 0 && self.frames + 1 < audio_frames {
+            let last_frame = std::mem::take(&mut self.last_frame);
+            self.write_frame(&last_frame)?;
+            self.last_frame = last_frame;
+        }
+
+        self.write_frame(pixels)?;
+        self.last_frame.copy_from_slice(pixels);
+        Ok(())
+    }
+
+    fn write_frame(&mut self, pixels: &[u8]) -> io::Result<()> {
+        self.y4m.push(pixels)?;
+        self.gif.push(pixels, self.frames)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        self.wav.finish()?;
+        self.y4m.finish()?;
+        self.gif.finish(self.frames)
+    }
+}
+
+impl Drop for Recording {
+    /// Closing the emulator while recording still leaves playable files
+    fn drop(&mut self) { let _ = self.finish(); }
+}
+
+/// Hundredths of a second from the start of the recording to the start of a frame
+#[inline(always)]
+fn frame_centiseconds(frame: u64) -> u64 { frame * FRAME_CLOCKS * 100 / CLOCK_RATE }
+
+/// 16 bit stereo PCM, its sizes are only known once recording stops
+#[derive(Debug)]
+struct WavWriter {
+    file: BufWriter<File>,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    const HEADER_BYTES: u32 = 44;
+
+    fn create(path: &Path, sample_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let (channels, bits) = (2u16, 16u16);
+        let block_align = channels * bits / 8;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&(Self::HEADER_BYTES - 8).to_le_bytes())?;
+        file.write_all(b"WAVEfmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        // integer pcm
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&bits.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { file, data_bytes: 0 })
+    }
+
+    #[inline]
+    fn push(&mut self, left: i16, right: i16) -> io::Result<()> {
+        if self.data_bytes > u32::MAX - Self::HEADER_BYTES - 4 {
+            return Err(io::Error::other("wav file reached its 4 GiB limit"));
+        }
+
+        self.file.write_all(&left.to_le_bytes())?;
+        self.file.write_all(&right.to_le_bytes())?;
+        self.data_bytes += 4;
+        Ok(())
+    }
+
+    /// Patch the chunk sizes left empty in the header
+    fn finish(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(Self::HEADER_BYTES - 8 + self.data_bytes).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(Self::HEADER_BYTES as u64 - 4))?;
+        self.file.write_all(&self.data_bytes.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+/// Uncompressed YUV 4:4:4 frames, full chroma keeps the pixel edges sharp
+#[derive(Debug)]
+struct Y4mWriter {
+    file: BufWriter<File>,
+    planes: Vec<u8>,
+}
+
+impl Y4mWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            "YUV4MPEG2 W{DMG_SCREEN_WIDTH} H{DMG_SCREEN_HEIGHT} F{CLOCK_RATE}:{FRAME_CLOCKS} Ip A1:1 C444"
+        )?;
+
+        Ok(Self {
+            file,
+            planes: vec![0; FRAME_BYTES],
+        })
+    }
+
+    fn push(&mut self, pixels: &[u8]) -> io::Result<()> {
+        let area = DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT;
+        let (y, chroma) = self.planes.split_at_mut(area);
+        let (u, v) = chroma.split_at_mut(area);
+
+        // bt.601 studio range, what players assume when the header doesn't say otherwise
+        for (i, rgb) in pixels.chunks_exact(3).enumerate() {
+            let (r, g, b) = (rgb[0] as i32, rgb[1] as i32, rgb[2] as i32);
+            y[i] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            u[i] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+            v[i] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+        }
+
+        self.file.write_all(b"FRAME\n")?;
+        self.file.write_all(&self.planes)
+    }
+
+    fn finish(&mut self) -> io::Result<()> { self.file.flush() }
+}
+
+/// Looping GIF at native resolution. A frame is only written once the next different one shows up,
+/// since its delay is the time until then, so repeated frames just make the previous one last longer
+struct GifWriter {
+    encoder: gif::Encoder<BufWriter<File>>,
+    pending: Vec<u8>,
+    /// Frame number the pending frame started being shown at
+    pending_start: Option<u64>,
+}
+
+impl GifWriter {
+    fn create(path: &Path) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, DMG_SCREEN_WIDTH as u16, DMG_SCREEN_HEIGHT as u16, &[])
+            .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        Ok(Self {
+            encoder,
+            pending: vec![0; FRAME_BYTES],
+            pending_start: None,
+        })
+    }
+
+    fn push(&mut self, pixels: &[u8], frame: u64) -> io::Result<()> {
+        let Some(start) = self.pending_start else {
+            self.pending.copy_from_slice(pixels);
+            self.pending_start = Some(frame);
+            return Ok(());
+        };
+
+        if self.pending == pixels {
+            return Ok(());
+        }
+
+        // too soon to be shown, the newer frame takes the place of the pending one
+        if frame_centiseconds(frame) - frame_centiseconds(start) < GIF_MIN_DELAY {
+            self.pending.copy_from_slice(pixels);
+            return Ok(());
+        }
+
+        self.write_pending(frame)?;
+        self.pending.copy_from_slice(pixels);
+        self.pending_start = Some(frame);
+        Ok(())
+    }
+
+    fn write_pending(&mut self, end: u64) -> io::Result<()> {
+        let Some(start) = self.pending_start else {
+            return Ok(());
+        };
+
+        let mut frame = palette_frame(&self.pending).unwrap_or_else(|| {
+            gif::Frame::from_rgb_speed(
+                DMG_SCREEN_WIDTH as u16,
+                DMG_SCREEN_HEIGHT as u16,
+                &self.pending,
+                10,
+            )
+        });
+        let delay = frame_centiseconds(end) - frame_centiseconds(start);
+        frame.delay = delay.clamp(GIF_MIN_DELAY, u16::MAX as u64) as u16;
+
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+
+    fn finish(&mut self, frames: u64) -> io::Result<()> {
+        self.write_pending(frames)?;
+        self.pending_start = None;
+        self.encoder.get_mut().flush()
+    }
+}
+
+/// Frame indexing the colors it uses, a palette of a few shades doesn't need any quantization.
+/// `None` when the frame has more than 256 colors
+fn palette_frame(pixels: &[u8]) -> Option<gif::Frame<'static>> {
+    let mut palette: Vec<[u8; 3]> = Vec::with_capacity(4);
+    let mut indices = Vec::with_capacity(pixels.len() / 3);
+
+    for rgb in pixels.chunks_exact(3) {
+        let color = [rgb[0], rgb[1], rgb[2]];
+        let index = match palette.iter().position(|&c| c == color) {
+            Some(index) => index,
+            None if palette.len() < 256 => {
+                palette.push(color);
+                palette.len() - 1
+            }
+            None => return None,
+        };
+        indices.push(index as u8);
+    }
+
+    Some(gif::Frame::from_palette_pixels(
+        DMG_SCREEN_WIDTH as u16,
+        DMG_SCREEN_HEIGHT as u16,
+        indices,
+        palette.concat(),
+        None,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gbeed-recording-{}-{name}", std::process::id()))
+    }
+
+    fn frame(rgb: [u8; 3]) -> Vec<u8> { rgb.repeat(FRAME_BYTES / 3) }
+
+    #[test]
+    fn test_wav_sizes() {
+        let path = temporary("sizes.wav");
+        let mut wav = WavWriter::create(&path, 48000).unwrap();
+        for sample in 0..3 {
+            wav.push(sample, -sample).unwrap();
+        }
+        wav.finish().unwrap();
+        drop(wav);
+
+        let data = fs::read(&path).unwrap();
+        let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        assert_eq!(data.len(), 44 + 12);
+        assert_eq!(&data[..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + 12);
+        assert_eq!(&data[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(24), 48000);
+        assert_eq!(&data[36..40], b"data");
+        assert_eq!(u32_at(40), 12);
+        assert_eq!(&data[48..52], [1, 0, 0xFF, 0xFF]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_y4m_frames() {
+        let path = temporary("frames.y4m");
+        let mut y4m = Y4mWriter::create(&path).unwrap();
+        y4m.push(&frame([255, 255, 255])).unwrap();
+        y4m.push(&frame([0, 0, 0])).unwrap();
+        y4m.finish().unwrap();
+        drop(y4m);
+
+        let data = fs::read(&path).unwrap();
+        let header = b"YUV4MPEG2 W160 H144 F4194304:70224 Ip A1:1 C444\n";
+        assert_eq!(&data[..header.len()], header);
+
+        let frames = &data[header.len()..];
+        let frame_size = b"FRAME\n".len() + FRAME_BYTES;
+        assert_eq!(frames.len(), frame_size * 2);
+        assert!(
+            frames
+                .chunks_exact(frame_size)
+                .all(|frame| frame.starts_with(b"FRAME\n"))
+        );
+
+        // white and black in bt.601 studio range, with neutral chroma
+        let area = DMG_SCREEN_WIDTH * DMG_SCREEN_HEIGHT;
+        let white = &frames[6..frame_size];
+        assert_eq!((white[0], white[area], white[area * 2]), (235, 128, 128));
+        let black = &frames[frame_size + 6..];
+        assert_eq!((black[0], black[area], black[area * 2]), (16, 128, 128));
+        fs::remove_file(path).unwrap();
+    }
+
+    /// Delay and first pixel of every frame in a GIF
+    fn gif_frames(path: &Path) -> Vec<(u16, [u8; 3])> {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(path).unwrap()).unwrap();
+
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, [frame.buffer[0], frame.buffer[1], frame.buffer[2]]));
+        }
+        frames
+    }
+
+    #[test]
+    fn test_gif_merges_repeated_frames() {
+        let path = temporary("repeated.gif");
+        let mut gif = GifWriter::create(&path).unwrap();
+        for n in 0..10 {
+            gif.push(&frame([255, 0, 0]), n).unwrap();
+        }
+        gif.push(&frame([0, 0, 255]), 10).unwrap();
+        gif.finish(11).unwrap();
+        drop(gif);
+
+        // ten frames last 16.7 hundredths, the last one is only shown for the 2 cs minimum
+        assert_eq!(gif_frames(&path), [(16, [255, 0, 0]), (2, [0, 0, 255])]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_gif_drops_frames_under_minimum_delay() {
+        let path = temporary("fast.gif");
+        let mut gif = GifWriter::create(&path).unwrap();
+        gif.push(&frame([255, 0, 0]), 0).unwrap();
+        gif.push(&frame([0, 255, 0]), 1).unwrap();
+        gif.finish(2).unwrap();
+        drop(gif);
+
+        // a frame only 1 cs after the first replaces it
+        assert_eq!(gif_frames(&path), [(3, [0, 255, 0])]);
+        fs::remove_file(path).unwrap();
+    }
+}
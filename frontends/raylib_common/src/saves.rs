@@ -0,0 +1,261 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BACKUPS_DIR: &str = "backups";
+const BACKUP_NAME: &str = "save";
+const SAVE_EXTENSION: &str = "sav";
+
+/// # Crash-safe saves
+/// Write `data` to a temporary file next to `path` and rename it over the save once it is on disk,
+/// so losing power halfway leaves either the old save or the new one but never a mix of both
+pub fn write_save(path: &Path, data: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+
+    let temporary = path.with_added_extension("tmp");
+    let mut file = File::create(&temporary)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temporary, path)?;
+
+    // the rename itself is only kept once the directory entry is on disk too
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// Unix time in seconds
+    pub time: u64,
+    /// Tells apart backups made in the same second
+    count: u32,
+}
+
+/// `backups/<save name>` next to the save at `save_path`
+pub fn backups_dir(save_path: &Path) -> PathBuf {
+    let stem = save_path.file_stem().unwrap_or(save_path.as_os_str());
+    let dir = save_path.parent().unwrap_or(Path::new("."));
+
+    dir.join(BACKUPS_DIR).join(stem)
+}
+
+/// Backups of the save at `save_path`, newest first
+pub fn list_backups(save_path: &Path) -> Vec<Backup> {
+    let mut backups: Vec<Backup> = fs::read_dir(backups_dir(save_path))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|entry| {
+                    let path = entry.path();
+                    if path.extension().is_none_or(|ext| ext != SAVE_EXTENSION) {
+                        return None;
+                    }
+
+                    // `save-<time>` or `save-<time>-<count>` when several were made in the same second
+                    let stem = path
+                        .file_stem()?
+                        .to_str()?
+                        .strip_prefix(BACKUP_NAME)?
+                        .strip_prefix('-')?;
+                    let (time, count) = stem.split_once('-').unwrap_or((stem, "0"));
+                    let (time, count) = (time.parse().ok()?, count.parse().ok()?);
+                    Some(Backup { path, time, count })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    backups.sort_by_key(|backup| std::cmp::Reverse((backup.time, backup.count)));
+    backups
+}
+
+/// Copy the save at `save_path` to its backups unless the newest one has the same data, removing
+/// the oldest ones so no more than `keep` are left. Returns the new backup, if one was made
+pub fn backup_save(save_path: &Path, keep: usize) -> io::Result<Option<PathBuf>> {
+    if keep == 0 {
+        return Ok(None);
+    }
+
+    let data = match fs::read(save_path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let backups = list_backups(save_path);
+    if backups
+        .first()
+        .is_some_and(|newest| fs::read(&newest.path).is_ok_and(|newest| newest == data))
+    {
+        return Ok(None);
+    }
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let name = match backups.first() {
+        Some(newest) if newest.time >= time => format!("{BACKUP_NAME}-{}-{}", newest.time, newest.count + 1),
+        _ => format!("{BACKUP_NAME}-{time}"),
+    };
+    let path = backups_dir(save_path).join(name).with_extension(SAVE_EXTENSION);
+    write_save(&path, &data)?;
+
+    for old in backups.iter().skip(keep - 1) {
+        fs::remove_file(&old.path)?;
+    }
+    Ok(Some(path))
+}
+
+/// Replace the save at `save_path` with `backup`, backing up the replaced one first so restoring
+/// can be undone by restoring it back
+pub fn restore_backup(save_path: &Path, backup: &Path, keep: usize) -> io::Result<()> {
+    // read before backing up, which may remove `backup` if it is the oldest
+    let data = fs::read(backup)?;
+    backup_save(save_path, keep.max(1))?;
+    write_save(save_path, &data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// An empty directory of its own for each test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gbeed-saves-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn now() -> u64 { SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() }
+
+    fn backup_data(save_path: &Path) -> Vec<Vec<u8>> {
+        list_backups(save_path)
+            .iter()
+            .map(|backup| fs::read(&backup.path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_write_save() {
+        let dir = temp_dir("write");
+        let path = dir.join("nested").join("tetris.sav");
+
+        write_save(&path, b"first").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        write_save(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+
+        let files: Vec<PathBuf> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files, [path]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backup_rotation() {
+        let dir = temp_dir("rotation");
+        let path = dir.join("tetris.sav");
+
+        for i in 0..5u8 {
+            write_save(&path, &[i]).unwrap();
+            assert!(backup_save(&path, 3).unwrap().is_some());
+        }
+        assert_eq!(backup_data(&path), [[4], [3], [2]]);
+
+        // the same data as the newest backup isn't backed up again
+        assert_eq!(backup_save(&path, 3).unwrap(), None);
+        assert_eq!(list_backups(&path).len(), 3);
+
+        // nor is a missing save, or anything when no backups are kept
+        assert_eq!(backup_save(&dir.join("zelda.sav"), 3).unwrap(), None);
+        write_save(&path, &[5]).unwrap();
+        assert_eq!(backup_save(&path, 0).unwrap(), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_backups_in_the_same_second() {
+        let dir = temp_dir("same-second");
+        let path = dir.join("tetris.sav");
+
+        // a backup from a clock ahead of this one, newer than anything made now
+        let time = now() + 100;
+        let ahead = backups_dir(&path).join(format!("save-{time}.sav"));
+        write_save(&ahead, b"ahead").unwrap();
+
+        write_save(&path, b"first").unwrap();
+        let first = backup_save(&path, 5).unwrap().unwrap();
+        write_save(&path, b"second").unwrap();
+        let second = backup_save(&path, 5).unwrap().unwrap();
+
+        assert_eq!(first, backups_dir(&path).join(format!("save-{time}-1.sav")));
+        assert_eq!(second, backups_dir(&path).join(format!("save-{time}-2.sav")));
+        assert_eq!(backup_data(&path), [&b"second"[..], b"first", b"ahead"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_list_backups() {
+        let dir = temp_dir("list");
+        let path = dir.join("tetris.sav");
+        let backups = backups_dir(&path);
+
+        for name in [
+            "save-100.sav",
+            "save-300.sav",
+            "save-200-1.sav",
+            "save-200.sav",
+            "notes.txt",
+            "other-400.sav",
+        ] {
+            write_save(&backups.join(name), name.as_bytes()).unwrap();
+        }
+
+        let listed: Vec<(u64, u32)> = list_backups(&path)
+            .iter()
+            .map(|backup| (backup.time, backup.count))
+            .collect();
+        assert_eq!(listed, [(300, 0), (200, 1), (200, 0), (100, 0)]);
+        assert_eq!(list_backups(&dir.join("zelda.sav")), []);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_backup() {
+        let dir = temp_dir("restore");
+        let path = dir.join("tetris.sav");
+
+        write_save(&path, b"old").unwrap();
+        let old = backup_save(&path, 5).unwrap().unwrap();
+        write_save(&path, b"new").unwrap();
+
+        restore_backup(&path, &old, 5).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        // the replaced save was backed up, so restoring can be undone
+        assert_eq!(backup_data(&path), [&b"new"[..], b"old"]);
+
+        // the oldest backup is read before backing up removes it
+        write_save(&path, b"newer").unwrap();
+        let oldest = list_backups(&path).last().unwrap().path.clone();
+        restore_backup(&path, &oldest, 1).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(backup_data(&path), [b"newer"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
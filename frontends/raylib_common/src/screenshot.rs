@@ -0,0 +1,32 @@
+use crate::utils::timestamped_path;
+use gbeed_core::prelude::{DMG_SCREEN_HEIGHT, DMG_SCREEN_WIDTH};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Write 8 bit RGB or RGBA pixels as a PNG, told apart by how many bytes there are per pixel
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> io::Result<()> {
+    let color = match pixels.len() / (width * height) as usize {
+        3 => png::ColorType::Rgb,
+        4 => png::ColorType::Rgba,
+        _ => return Err(io::Error::other("pixels are neither rgb nor rgba")),
+    };
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Save a 160x144 RGB8 frame as `<dir>/<name>-<timestamp>.png`, returning where it went
+pub fn save_screenshot(dir: &Path, name: &str, pixels: &[u8]) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+
+    let path = timestamped_path(dir, name, "png").with_extension("png");
+    write_png(&path, DMG_SCREEN_WIDTH as u32, DMG_SCREEN_HEIGHT as u32, pixels)?;
+    Ok(path)
+}
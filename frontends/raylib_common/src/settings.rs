@@ -1,6 +1,9 @@
 use crate::impl_cyclic_enum;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+/// Stored as `"toggle"` or `"hold"`, a toggle always starts off
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
 pub enum SpeedUpMode {
     Toggle(bool),
     Hold,
@@ -12,11 +15,35 @@ impl Default for SpeedUpMode {
     fn default() -> Self { Toggle(false) }
 }
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+impl From<SpeedUpMode> for String {
+    fn from(mode: SpeedUpMode) -> Self {
+        match mode {
+            Toggle(_) => "toggle".into(),
+            Hold => "hold".into(),
+        }
+    }
+}
+
+impl TryFrom<String> for SpeedUpMode {
+    type Error = String;
+
+    fn try_from(mode: String) -> Result<Self, Self::Error> {
+        match mode.as_str() {
+            "toggle" => Ok(Toggle(false)),
+            "hold" => Ok(Hold),
+            _ => Err(format!("unknown speed up mode `{mode}`")),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum SpeedUpMultiplier {
     #[default]
+    #[serde(rename = "1.5x")]
     OneAndHalf,
+    #[serde(rename = "2x")]
     Double,
+    #[serde(rename = "4x")]
     Cuadruple,
 }
 
@@ -32,8 +59,10 @@ impl SpeedUpMultiplier {
     }
 }
 
+/// Stored as the frame rate, 0 being unlimited
 #[repr(u8)]
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "u32", try_from = "u32")]
 pub enum TargetedFps {
     Target30 = 30,
     #[default]
@@ -43,3 +72,169 @@ pub enum TargetedFps {
 
 use TargetedFps::*;
 impl_cyclic_enum!(TargetedFps, [Target30, Target60, Unlimited]);
+
+impl From<TargetedFps> for u32 {
+    fn from(fps: TargetedFps) -> Self { fps as u32 }
+}
+
+impl TryFrom<u32> for TargetedFps {
+    type Error = String;
+
+    fn try_from(fps: u32) -> Result<Self, Self::Error> {
+        Self::ALL
+            .into_iter()
+            .find(|target| *target as u32 == fps)
+            .ok_or_else(|| format!("unsupported target fps {fps}"))
+    }
+}
+
+/// Audio output rate, lower rates are cheaper for the Pi's PWM audio while HDMI usually expects 48000
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "u32", try_from = "u32")]
+pub enum SampleRate {
+    Rate22050,
+    Rate32000,
+    #[default]
+    Rate44100,
+    Rate48000,
+}
+
+use SampleRate::*;
+impl_cyclic_enum!(SampleRate, [Rate22050, Rate32000, Rate44100, Rate48000]);
+impl SampleRate {
+    pub fn hz(&self) -> u32 {
+        match self {
+            Rate22050 => 22050,
+            Rate32000 => 32000,
+            Rate44100 => 44100,
+            Rate48000 => 48000,
+        }
+    }
+
+    pub fn from_hz(hz: u32) -> Option<Self> { Self::ALL.into_iter().find(|rate| rate.hz() == hz) }
+}
+
+impl From<SampleRate> for u32 {
+    fn from(rate: SampleRate) -> Self { rate.hz() }
+}
+
+impl TryFrom<u32> for SampleRate {
+    type Error = String;
+
+    fn try_from(hz: u32) -> Result<Self, Self::Error> {
+        Self::from_hz(hz).ok_or_else(|| format!("unsupported sample rate {hz}"))
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioOutput {
+    #[default]
+    Stereo,
+    /// Both sides averaged, for single speaker devices
+    Mono,
+}
+
+use AudioOutput::*;
+impl_cyclic_enum!(AudioOutput, [Stereo, Mono]);
+impl AudioOutput {
+    pub fn channels(&self) -> u32 {
+        match self {
+            Stereo => 2,
+            Mono => 1,
+        }
+    }
+}
+
+/// What paces the emulation
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    /// Frames follow the display, the audio is resampled slightly to keep up
+    #[default]
+    Video,
+    /// Frames run whenever the audio queue needs samples, never resampling
+    Audio,
+}
+
+use SyncMode::*;
+impl_cyclic_enum!(SyncMode, [Video, Audio]);
+
+/// Post-processing applied to the screen on the CPU, so it also works on devices without a GPU
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenFilter {
+    #[default]
+    Off,
+    /// Darkened gaps between pixels like the DMG matrix display
+    LcdGrid,
+    Scale2x,
+    Scale3x,
+}
+
+use ScreenFilter::*;
+impl_cyclic_enum!(ScreenFilter, [Off, LcdGrid, Scale2x, Scale3x]);
+impl ScreenFilter {
+    /// Size of the filtered frame relative to the native one
+    pub fn scale(&self) -> usize {
+        match self {
+            Off => 1,
+            Scale2x => 2,
+            LcdGrid | Scale3x => 3,
+        }
+    }
+}
+
+/// How the screen fills the window
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenScaling {
+    #[default]
+    Stretch,
+    /// Largest whole multiple of the native size that fits, letterboxed
+    Integer,
+}
+
+use ScreenScaling::*;
+impl_cyclic_enum!(ScreenScaling, [Stretch, Integer]);
+
+/// How often turbo buttons press, counted in emulated frames. Stored as presses per second
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "f32", try_from = "f32")]
+pub enum TurboRate {
+    Turbo7_5,
+    Turbo10,
+    #[default]
+    Turbo15,
+    Turbo30,
+}
+
+use TurboRate::*;
+impl_cyclic_enum!(TurboRate, [Turbo7_5, Turbo10, Turbo15, Turbo30]);
+impl TurboRate {
+    /// Frames the button stays down, and then up, in every press
+    pub fn frames_held(&self) -> u8 {
+        match self {
+            Turbo7_5 => 4,
+            Turbo10 => 3,
+            Turbo15 => 2,
+            Turbo30 => 1,
+        }
+    }
+
+    pub fn hz(&self) -> f32 { 60.0 / (self.frames_held() * 2) as f32 }
+
+    pub fn from_hz(hz: f32) -> Option<Self> { Self::ALL.into_iter().find(|rate| rate.hz() == hz) }
+}
+
+impl From<TurboRate> for f32 {
+    fn from(rate: TurboRate) -> Self { rate.hz() }
+}
+
+impl TryFrom<f32> for TurboRate {
+    type Error = String;
+
+    fn try_from(hz: f32) -> Result<Self, Self::Error> {
+        Self::from_hz(hz).ok_or_else(|| format!("unsupported turbo rate {hz}"))
+    }
+}
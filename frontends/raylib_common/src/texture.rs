@@ -19,6 +19,13 @@ impl Texture {
             eprintln!("texture update failed: {e:?}");
         }
     }
+
+    /// RGB8 pixels as they will be uploaded on the next `update`
+    #[inline(always)]
+    pub fn pixels(&self) -> &[u8] { &self.framebuffer }
+
+    #[inline(always)]
+    pub fn pixels_mut(&mut self) -> &mut [u8] { &mut self.framebuffer }
 }
 
 impl std::ops::Index<usize> for Texture {
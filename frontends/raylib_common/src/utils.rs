@@ -1,3 +1,6 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[macro_export]
 macro_rules! impl_cyclic_enum {
     ($name:ident, [$($variant:expr),+ $(,)?]) => {
@@ -27,3 +30,24 @@ macro_rules! impl_cyclic_enum {
 
     (@replace $variant:expr) => { () };
 }
+
+/// `<dir>/<name>-<unix seconds>` without extension, with a counter added if a file with `extension` is already there.
+/// Anything but ascii letters and digits in the name is replaced, so titles can be used as they are
+pub(crate) fn timestamped_path(dir: &Path, name: &str, extension: &str) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    let mut path = dir.join(format!("{name}-{timestamp}"));
+    let mut count = 1;
+    while path.with_extension(extension).exists() {
+        path = dir.join(format!("{name}-{timestamp}-{count}"));
+        count += 1;
+    }
+    path
+}
@@ -13,6 +13,7 @@
       latest.clippy
       latest.rustfmt
       targets.wasm32-unknown-emscripten.latest.rust-std
+      targets.thumbv7em-none-eabihf.latest.rust-std
     ];
 
   commonPackages = with pkgs; [